@@ -2,14 +2,17 @@ mod persist;
 mod story;
 
 pub use persist::{
-    BackerUpper, BackupResult, IntoStoryQuery, MemIndex, PersistError, PersistLocation,
-    ScrapePersistResult, ScrapePersistResultSummarizer, ScrapePersistResultSummary, SearchSummary,
-    Shard, Storage, StorageFetch, StorageSummary, StorageWriter, StoryIndex, StoryQuery,
-    StoryScrapePayload,
+    BackerUpper, BackupResult, DomainSearchConfig, EmptyTitlePolicy, FuzzySearchConfig,
+    HostBlocklistConfig, IntoStoryQuery, MemIndex, PersistError, PersistLocation, PurgeSummary,
+    ScoreSnapshot, ScoreSnapshotConfig, ScrapePersistResult, ScrapePersistResultSummarizer,
+    ScrapePersistResultSummary, SearchRankingConfig, SearchSummary, Shard, ShardOrder,
+    ShardScanLimits, Storage, StorageFetch, StorageForEach, StorageSummary, StorageWriter,
+    StoryCursor, StoryDocDetail, StoryIndex, StoryQuery, StoryScrapePayload, UrlChangePolicy,
 };
 pub use story::{
-    Story, StoryEvaluator, StoryIdentifier, StoryRender, StoryScore, StoryScoreConfig, TagSet,
-    TaggerConfig,
+    ScrapeScoreInput, Story, StoryEvaluator, StoryIdentifier, StoryRender, StoryScore,
+    StoryScoreConfig, StoryScoreConfigError, StoryScoreInput, TagCollation, TagSet, TagSource,
+    TaggerConfig, TieBreak, TieBreakConfig, SCORE_EPSILON,
 };
 
 macro_rules! timer_start {
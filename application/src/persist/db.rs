@@ -202,6 +202,12 @@ impl DB {
         }
     }
 
+    /// Delete the row with the given id, returning whether it existed.
+    pub fn delete<T: Serialize>(&self, id: String) -> Result<bool, PersistError> {
+        let sql = format!("delete from {} where id = ?", Self::table_for::<T>());
+        Ok(self.connection.read().execute(&sql, [id])? > 0)
+    }
+
     pub fn execute_raw(&self, sql: &str) -> Result<(), PersistError> {
         self.connection.read().execute_batch(sql)?;
         Ok(())
@@ -219,6 +225,20 @@ impl DB {
         Ok(v)
     }
 
+    pub fn query_params<T: Serialize + DeserializeOwned, P: rusqlite::Params>(
+        &self,
+        sql: &str,
+        params: P,
+    ) -> Result<Vec<T>, PersistError> {
+        let db = self.connection.read();
+        let mut stmt = db.prepare(sql)?;
+        let mut v = vec![];
+        for row in stmt.query_and_then(params, serde_rusqlite::from_row::<T>)? {
+            v.push(row?);
+        }
+        Ok(v)
+    }
+
     pub fn query_raw_callback<
         T: Serialize + DeserializeOwned,
         F: FnMut(T) -> Result<(), PersistError>,
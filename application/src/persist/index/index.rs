@@ -1,37 +1,51 @@
 use itertools::Itertools;
 use keepcalm::SharedMut;
 
-use tantivy::collector::TopDocs;
+use tantivy::collector::{Count, DocSetCollector, FilterCollector, TopDocs};
 use tantivy::query::{
-    AllQuery, BooleanQuery, BoostQuery, Occur, PhraseQuery, Query, QueryParser, TermQuery,
+    AllQuery, BooleanQuery, BoostQuery, FuzzyTermQuery, Occur, PhraseQuery, Query, QueryParser,
+    RegexQuery, TermQuery, TermSetQuery,
 };
 use tantivy::tokenizer::{SimpleTokenizer, Tokenizer, TokenizerManager};
 use tantivy::{schema::*, DocAddress, IndexWriter, Searcher, SegmentReader};
 
-use progscrape_scrapers::{ScrapeCollection, StoryDate, StoryUrl, TypedScrape};
+use progscrape_scrapers::{
+    ExtractedScrapeCollection, ScrapeCollection, ScrapeId, StoryDate, StoryDuration, StoryUrl,
+    TypedScrape, TypedScrapeMap, UrlNormalizationConfig,
+};
 
 use std::borrow::Cow;
 use std::collections::{HashMap, HashSet};
+use std::ops::{Bound, RangeBounds};
 use std::panic::catch_unwind;
 use std::time::Duration;
 
-use crate::persist::index::indexshard::{StoryIndexShard, StoryLookup, StoryLookupId};
+use crate::persist::index::indexshard::{StoryFetch, StoryIndexShard, StoryLookup, StoryLookupId};
 use crate::persist::scrapestore::ScrapeStore;
 use crate::persist::shard::{ShardOrder, ShardRange};
 use crate::persist::{
-    ScrapePersistResult, SearchSummary, Shard, ShardSummary, StorageFetch, StoryQuery,
+    DomainSearchConfig, EmptyTitlePolicy, FuzzySearchConfig, HostBlocklistConfig, PurgeSummary,
+    ScoreSnapshot, ScoreSnapshotConfig, ScrapePersistResult, SearchRankingConfig, SearchSummary,
+    Shard, ShardScanLimits, ShardSummary, StorageFetch, StorageForEach, StoryCursor,
+    StoryDocDetail, StoryQuery, UrlChangePolicy,
 };
-use crate::story::{StoryCollector, TagSet};
+use crate::story::{StoryCollector, StoryScoreInput, StoryScorer, TagSet, TagSource};
 use crate::{
-    timer_end, timer_start, MemIndex, PersistError, PersistLocation, Storage, StorageSummary,
-    StorageWriter, Story, StoryEvaluator, StoryIdentifier,
+    timer_end, timer_start, BackerUpper, MemIndex, PersistError, PersistLocation, Storage,
+    StorageSummary, StorageWriter, Story, StoryEvaluator, StoryIdentifier,
 };
 
 use super::indexshard::{tokenize_domain, StoryInsert};
-use super::schema::StorySchema;
+use super::schema::{StorySchema, SCHEMA_VERSION};
 
+/// Records the URL normalization an index was built with, relative to its persistence path.
+const URL_NORMALIZATION_FILE: &str = "url_normalization.json";
 const STORY_INDEXING_CHUNK_SIZE: usize = 10000;
 const SCRAPE_PROCESSING_CHUNK_SIZE: usize = 1000;
+/// The maximum number of shard writers we commit (and wait on merges for) at the same time.
+const COMMIT_PARALLELISM: usize = 4;
+/// The maximum number of matching stories sampled when computing related tags.
+const RELATED_TAGS_SAMPLE_SIZE: usize = 500;
 
 struct IndexCache {
     cache: HashMap<Shard, SharedMut<StoryIndexShard>>,
@@ -39,16 +53,35 @@ struct IndexCache {
     range: ShardRange,
     schema: StorySchema,
     most_recent_story: Option<StoryDate>,
+    earliest_story: Option<StoryDate>,
+    /// Shards that failed to open, and why. Opening is retried on the next access.
+    degraded: HashMap<Shard, String>,
+    /// Shards whose index was written with an older schema, which can't be opened until they're rebuilt by
+    /// `StoryIndex::migrate_shards`.
+    migrating: HashSet<Shard>,
 }
 
 impl IndexCache {
     fn get_shard(&mut self, shard: Shard) -> Result<SharedMut<StoryIndexShard>, PersistError> {
         if let Some(shard) = self.cache.get(&shard) {
             Ok(shard.clone())
+        } else if self.migrating.contains(&shard) {
+            Err(PersistError::ShardNeedsMigration(shard))
         } else {
-            tracing::info!("Creating shard {}", shard.to_string());
-            let new_shard =
-                StoryIndexShard::initialize(self.location.clone(), shard, self.schema.clone())?;
+            tracing::info!("Creating shard {shard}");
+            let new_shard = match StoryIndexShard::initialize(
+                self.location.clone(),
+                shard,
+                self.schema.clone(),
+            ) {
+                Ok(new_shard) => new_shard,
+                Err(e) => {
+                    tracing::error!("Failed to open shard {shard}: {e:?}");
+                    self.degraded.insert(shard, format!("{e:?}"));
+                    return Err(e);
+                }
+            };
+            self.degraded.remove(&shard);
             self.range.include(shard);
             Ok(self
                 .cache
@@ -59,18 +92,103 @@ impl IndexCache {
     }
 }
 
+/// Where a story is inserted into the index. If the `DocAddress` is present, the scrapes must be merged with the
+/// document at that address, and the flag is set if the story was found by its scrapes rather than its URL (ie: it
+/// was manually merged into a story with another URL), in which case that document keeps its identity.
+type InsertPosition = (ScrapeCollection, Shard, Option<DocAddress>, bool);
+
 pub struct StoryIndex {
     index_cache: SharedMut<IndexCache>,
     scrape_db: ScrapeStore,
     schema: StorySchema,
+    scan_limits: ShardScanLimits,
+    score_snapshots: Option<ScoreSnapshotConfig>,
+    empty_titles: EmptyTitlePolicy,
+    blocklist: HostBlocklistConfig,
+    exclude_penalized_hosts: bool,
+    exclude_self_posts: bool,
+    skip_degraded_shards: bool,
+    search_ranking: SearchRankingConfig,
+    domain_search: DomainSearchConfig,
+    fuzzy_search: FuzzySearchConfig,
+    url_changes: UrlChangePolicy,
+    date_outlier_days: Option<u32>,
+    future_date_tolerance_hours: Option<u32>,
+    query_parsers: StoryQueryParsers,
+}
+
+/// The query parsers for each type of search, which only depend on the schema and so are built once. Parsing takes
+/// `&self`, and `StoryIndex` must be `Sync` to be [`Storage`], so concurrent searches can share them.
+struct StoryQueryParsers {
+    /// Tags, matched against titles and hosts as well but boosted within tags.
+    tag: QueryParser,
+    /// Free text, matched against titles and tags.
+    text: QueryParser,
+    /// The title of a story we're finding related stories for.
+    related: QueryParser,
+}
+
+impl StoryQueryParsers {
+    fn new(schema: &StorySchema) -> Self {
+        let tokenizers = TokenizerManager::default();
+        let mut tag = QueryParser::new(
+            schema.schema.clone(),
+            vec![schema.title_field, schema.tags_field, schema.host_field],
+            tokenizers.clone(),
+        );
+        // Boost search within tags
+        tag.set_field_boost(schema.tags_field, 10.0);
+        let mut text = QueryParser::new(
+            schema.schema.clone(),
+            vec![schema.title_field, schema.tags_field],
+            tokenizers.clone(),
+        );
+        // Boost search within tags
+        text.set_field_boost(schema.tags_field, 3.0);
+        let mut related = QueryParser::new(
+            schema.schema.clone(),
+            vec![schema.title_field, schema.tags_field],
+            tokenizers,
+        );
+        related.set_field_boost(schema.title_field, 2.0);
+        Self { tag, text, related }
+    }
 }
 
 struct WriterProvider {
     writers: HashMap<Shard, IndexWriter>,
     index: SharedMut<IndexCache>,
+    /// Stories written in this batch, keyed by `(url_norm_hash, day)`. These are not yet visible to
+    /// lookups, so we track them here to avoid creating duplicate stories before the commit.
+    pending: HashMap<(i64, i64), PendingStory>,
+}
+
+/// A story written earlier in a writer batch.
+struct PendingStory {
+    shard: Shard,
+    story: ScrapeCollection,
+    /// The id of the document written for the story.
+    id: String,
+    /// The stored document the story was merged into, if any.
+    existing: Option<Document>,
+    /// Whether the story kept the identity of the `existing` document.
+    keep_identity: bool,
 }
 
 impl WriterProvider {
+    fn pending_key(story: &ScrapeCollection, date: StoryDate) -> (i64, i64) {
+        (
+            story.url().normalization().hash(),
+            date.timestamp().div_euclid(60 * 60 * 24),
+        )
+    }
+
+    /// The key of a document written in this batch, which may differ from that of the story it was written for
+    /// once merged or given the identity of an existing story.
+    fn pending_doc_key(doc: &StoryInsert) -> (i64, i64) {
+        (doc.url_norm_hash, doc.date.div_euclid(60 * 60 * 24))
+    }
+
     fn provide<T>(
         &mut self,
         shard: Shard,
@@ -100,20 +218,31 @@ impl StoryIndex {
         let scrape_db = ScrapeStore::new(location.clone())?;
         tracing::info!("Initialized StoryIndex at {:?}", location);
 
-        // Determine the min/max shard, if any
+        // Determine the min/max shard, if any, and which shards were indexed with an older schema
         let mut range = ShardRange::default();
+        let mut migrating = HashSet::new();
         if let PersistLocation::Path(path) = &location {
             for d in std::fs::read_dir(path)?.flatten() {
                 if let Some(s) = d.file_name().to_str() {
-                    if let Some(shard) = Shard::from_string(s) {
+                    if let Ok(shard) = s.parse::<Shard>() {
                         range.include(shard);
+                        if !Self::legacy_index_dirs(&d.path()).is_empty() {
+                            migrating.insert(shard);
+                        }
                     }
                 }
             }
         }
 
         tracing::info!("Found shards {:?}", range);
+        if !migrating.is_empty() {
+            tracing::warn!(
+                "Shards {:?} were indexed with an older schema and must be migrated",
+                migrating.iter().sorted().collect_vec()
+            );
+        }
         let schema = StorySchema::instantiate_global_schema();
+        let query_parsers = StoryQueryParsers::new(&schema);
         let new = Self {
             index_cache: SharedMut::new(IndexCache {
                 cache: HashMap::new(),
@@ -121,14 +250,769 @@ impl StoryIndex {
                 range,
                 schema: schema.clone(),
                 most_recent_story: None,
+                earliest_story: None,
+                degraded: HashMap::new(),
+                migrating,
             }),
             scrape_db,
             schema,
+            scan_limits: Default::default(),
+            score_snapshots: None,
+            empty_titles: Default::default(),
+            blocklist: Default::default(),
+            exclude_penalized_hosts: false,
+            exclude_self_posts: false,
+            skip_degraded_shards: false,
+            search_ranking: Default::default(),
+            domain_search: Default::default(),
+            fuzzy_search: Default::default(),
+            url_changes: Default::default(),
+            date_outlier_days: None,
+            future_date_tolerance_hours: None,
+            query_parsers,
         };
 
         Ok(new)
     }
 
+    /// Limit how many of the newest shards each type of query will scan.
+    pub fn set_scan_limits(&mut self, scan_limits: ShardScanLimits) {
+        self.scan_limits = scan_limits;
+    }
+
+    /// Record a snapshot of each story's score whenever it is re-inserted, or disable snapshots with `None`.
+    pub fn set_score_snapshots(&mut self, score_snapshots: Option<ScoreSnapshotConfig>) {
+        self.score_snapshots = score_snapshots;
+    }
+
+    /// Choose how scrapes with empty titles are handled at insert time.
+    pub fn set_empty_title_policy(&mut self, empty_titles: EmptyTitlePolicy) {
+        self.empty_titles = empty_titles;
+    }
+
+    /// Drop scrapes for these hosts at insert time.
+    pub fn set_blocklist(&mut self, blocklist: HostBlocklistConfig) {
+        self.blocklist = blocklist;
+    }
+
+    /// Exclude stories from penalized (ie: image) hosts from the front page entirely, rather than just penalizing
+    /// their score.
+    pub fn set_exclude_penalized_hosts(&mut self, exclude_penalized_hosts: bool) {
+        self.exclude_penalized_hosts = exclude_penalized_hosts;
+    }
+
+    /// Keep likely self-posts from any source off the front page. They remain available to other queries.
+    pub fn set_exclude_self_posts(&mut self, exclude_self_posts: bool) {
+        self.exclude_self_posts = exclude_self_posts;
+    }
+
+    /// Skip shards that fail to open in cross-shard queries, rather than failing the whole query.
+    pub fn set_skip_degraded_shards(&mut self, skip_degraded_shards: bool) {
+        self.skip_degraded_shards = skip_degraded_shards;
+    }
+
+    /// Configure the search recency factor.
+    pub fn set_search_ranking(&mut self, search_ranking: SearchRankingConfig) {
+        self.search_ranking = search_ranking;
+    }
+
+    /// Configure how domain searches are normalized.
+    pub fn set_domain_search(&mut self, domain_search: DomainSearchConfig) {
+        self.domain_search = domain_search;
+    }
+
+    /// Configure inexact matching for tag and domain searches that find nothing.
+    pub fn set_fuzzy_search(&mut self, fuzzy_search: FuzzySearchConfig) {
+        self.fuzzy_search = fuzzy_search;
+    }
+
+    /// Choose what happens when a source re-scrapes a story under a different URL.
+    pub fn set_url_change_policy(&mut self, url_changes: UrlChangePolicy) {
+        self.url_changes = url_changes;
+    }
+
+    /// Ignore scrapes dated more than this many days before the rest of a story's scrapes when dating the story,
+    /// so that a single scrape mis-dated by its source can't drag the story back to an old date (and shard).
+    pub fn set_date_outlier_days(&mut self, date_outlier_days: Option<u32>) {
+        self.date_outlier_days = date_outlier_days;
+    }
+
+    /// Clamp story dates more than this many hours in the future to the current time, so that a mis-dated story
+    /// can't land in a future shard and sit at the top of the front page without ever aging.
+    pub fn set_future_date_tolerance_hours(&mut self, future_date_tolerance_hours: Option<u32>) {
+        self.future_date_tolerance_hours = future_date_tolerance_hours;
+    }
+
+    /// The date we file this story under.
+    fn story_date(&self, story: &ScrapeCollection) -> StoryDate {
+        let ingested_at = story
+            .scrapes
+            .values()
+            .filter_map(|scrape| scrape.ingested_at)
+            .min();
+        self.clamp_future_date(story.story_date(self.date_outlier_days), ingested_at)
+    }
+
+    /// Clamps a date too far past the time we first stored it to that time. As that time is recorded with the
+    /// scrape, the clamped date is the same every time we derive it and the story ages normally. Scrapes that
+    /// haven't been stored yet fall back to the present.
+    fn clamp_future_date(&self, date: StoryDate, ingested_at: Option<StoryDate>) -> StoryDate {
+        let Some(tolerance) = self.future_date_tolerance_hours else {
+            return date;
+        };
+        let ingested_at = ingested_at.unwrap_or_else(StoryDate::now);
+        if date - ingested_at > StoryDuration::hours(tolerance as i64) {
+            tracing::warn!("Clamping future story date {date} to {ingested_at}");
+            ingested_at
+        } else {
+            date
+        }
+    }
+
+    /// The shards that failed to open, along with the error.
+    pub fn degraded_shards(&self) -> Vec<(Shard, String)> {
+        self.index_cache
+            .read()
+            .degraded
+            .iter()
+            .map(|(shard, error)| (*shard, error.clone()))
+            .sorted()
+            .collect_vec()
+    }
+
+    /// Converts a failure to open a degraded shard into `None` if we're skipping degraded shards, passing
+    /// through any other result.
+    fn skip_degraded<T>(
+        &self,
+        shard: Shard,
+        res: Result<T, PersistError>,
+    ) -> Result<Option<T>, PersistError> {
+        match res {
+            Err(e)
+                if self.skip_degraded_shards
+                    && self.index_cache.read().degraded.contains_key(&shard) =>
+            {
+                tracing::warn!("Skipping degraded shard {shard}: {e:?}");
+                Ok(None)
+            }
+            res => res.map(Some),
+        }
+    }
+
+    /// Should this front-page candidate be left off the front page entirely?
+    fn exclude_from_front_page(&self, doc: &Document) -> bool {
+        let Some(url) = doc
+            .get_first(self.schema.url_field)
+            .and_then(|url| url.as_text())
+            .and_then(StoryUrl::parse)
+        else {
+            return false;
+        };
+        if self.exclude_penalized_hosts && StoryScorer::is_penalized_host(url.host()) {
+            return true;
+        }
+        if self.exclude_self_posts {
+            return doc
+                .get_all(self.schema.scrape_field)
+                .filter_map(|id| id.as_text())
+                // Scrape ids are indexed as `shard:id`
+                .filter_map(|id| ScrapeId::from_string(id.split_once(':')?.1))
+                .any(|id| id.is_likely_self_post(&url));
+        }
+        false
+    }
+
+    /// Fetch the recorded score trajectory for a story, oldest first.
+    pub fn fetch_score_snapshots(
+        &self,
+        id: &StoryIdentifier,
+    ) -> Result<Vec<ScoreSnapshot>, PersistError> {
+        self.scrape_db
+            .fetch_score_snapshots(id.shard(), &id.to_base64())
+    }
+
+    /// The index directories for older schema versions within a shard's directory.
+    fn legacy_index_dirs(shard_path: &std::path::Path) -> Vec<std::path::PathBuf> {
+        (1..SCHEMA_VERSION)
+            .map(|version| shard_path.join(StorySchema::index_dir(version)))
+            .filter(|path| path.is_dir())
+            .collect()
+    }
+
+    /// Compare `config` to the URL normalization this index was built with, recording `config` if this index has
+    /// none recorded yet. Stories are filed under their normalized URL, so an index built with another
+    /// normalization won't merge new scrapes into the stories it already has until it's rebuilt. Returns false
+    /// (with a warning) if the normalization differs.
+    pub fn check_url_normalization(
+        &self,
+        config: &UrlNormalizationConfig,
+    ) -> Result<bool, PersistError> {
+        let PersistLocation::Path(path) = self.index_cache.read().location.clone() else {
+            return Ok(true);
+        };
+        let path = path.join(URL_NORMALIZATION_FILE);
+        if !path.exists() {
+            std::fs::write(&path, serde_json::to_string_pretty(config)?)?;
+            return Ok(true);
+        }
+        let recorded: UrlNormalizationConfig =
+            serde_json::from_str(&std::fs::read_to_string(&path)?)?;
+        if &recorded != config {
+            tracing::warn!(
+                "URL normalization differs from the one this index was built with, so new scrapes won't merge with \
+                existing stories until the index is rebuilt: {recorded:?} vs {config:?}"
+            );
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    /// Shards that were indexed with an older schema and haven't been migrated yet.
+    pub fn shards_needing_migration(&self) -> Vec<Shard> {
+        self.index_cache
+            .read()
+            .migrating
+            .iter()
+            .copied()
+            .sorted()
+            .collect()
+    }
+
+    /// Rebuilds every shard that was indexed with an older schema from the scrapes in the scrape store, oldest
+    /// first, returning the shards that were migrated. The old index directories are renamed with an `.old` suffix
+    /// once every shard has been rebuilt, so an interrupted migration is simply started again.
+    pub fn migrate_shards(&mut self, eval: &StoryEvaluator) -> Result<Vec<Shard>, PersistError> {
+        let shards = self.shards_needing_migration();
+        let PersistLocation::Path(path) = self.index_cache.read().location.clone() else {
+            return Ok(vec![]);
+        };
+        if shards.is_empty() {
+            return Ok(vec![]);
+        }
+
+        // Throw away anything left over from an interrupted migration
+        for shard in &shards {
+            let dir = path
+                .join(shard.to_string())
+                .join(StorySchema::index_dir(SCHEMA_VERSION));
+            if dir.exists() {
+                tracing::info!(
+                    "Removing partially-migrated index {}",
+                    dir.to_string_lossy()
+                );
+                std::fs::remove_dir_all(dir)?;
+            }
+        }
+        self.index_cache.write().migrating.clear();
+
+        for shard in &shards {
+            let scrapes = self.fetch_shard_scrapes(*shard)?;
+            tracing::info!("Migrating shard {shard} from {} scrape(s)", scrapes.len());
+            for chunk in &scrapes.into_iter().chunks(STORY_INDEXING_CHUNK_SIZE) {
+                self.insert_scrape_batch(
+                    eval,
+                    chunk.collect_vec(),
+                    &HashMap::new(),
+                    &HashSet::new(),
+                )?;
+            }
+            // Make sure the new index exists, even if the shard's stories all moved to an earlier shard
+            self.get_shard(*shard)?;
+        }
+
+        for shard in &shards {
+            for dir in Self::legacy_index_dirs(&path.join(shard.to_string())) {
+                let mut old = dir.clone().into_os_string();
+                old.push(".old");
+                std::fs::rename(dir, old)?;
+            }
+        }
+        tracing::info!(
+            "Migrated {} shard(s) to schema version {SCHEMA_VERSION}",
+            shards.len()
+        );
+        Ok(shards)
+    }
+
+    /// Re-file every scrape in this index into `target` (ie: a new, empty index), re-parsing each scrape's URL so that
+    /// it's normalized with the current configuration. As with a migration, activity and comment counts that were
+    /// only recorded in the index are rebuilt from the scrapes. Returns the number of scrapes reindexed.
+    pub fn reindex_into(
+        &self,
+        eval: &StoryEvaluator,
+        target: &mut StoryIndex,
+    ) -> Result<usize, PersistError> {
+        let mut total = 0;
+        for shard in self.shards().iterate(ShardOrder::OldestFirst) {
+            let mut scrapes = self.fetch_shard_scrapes(shard)?;
+            for scrape in &mut scrapes {
+                if let Some(url) = StoryUrl::parse(scrape.url.raw()) {
+                    scrape.url = url;
+                }
+            }
+            tracing::info!("Reindexing shard {shard} from {} scrape(s)", scrapes.len());
+            total += scrapes.len();
+            for chunk in &scrapes.into_iter().chunks(STORY_INDEXING_CHUNK_SIZE) {
+                target.insert_scrapes(eval, chunk)?;
+            }
+        }
+        Ok(total)
+    }
+
+    /// Fetch every scrape stored in a shard, oldest first. Scrapes that fail to load are logged and skipped.
+    pub fn fetch_shard_scrapes(&self, shard: Shard) -> Result<Vec<TypedScrape>, PersistError> {
+        let mut scrapes = vec![];
+        self.scrape_db.fetch_all(
+            shard,
+            |scrape| {
+                scrapes.push(scrape);
+                Ok(())
+            },
+            |error| tracing::error!("Error fetching scrape: {:?}", error),
+        )?;
+        Ok(scrapes)
+    }
+
+    /// Fetch up to `max` stories matching a query, newest first, resuming after `cursor` if one is given. The
+    /// front page pages through every eligible story by date rather than the scored hot set.
+    pub fn fetch_after(
+        &self,
+        query: &StoryQuery,
+        cursor: Option<&StoryCursor>,
+        max: usize,
+    ) -> Result<Vec<Story<Shard>>, PersistError> {
+        let (search, front_page): (Box<dyn Query>, bool) = match self.try_parse_query(query)? {
+            Ok(search) => (search, false),
+            Err(_) if matches!(query, StoryQuery::FrontPage) => (Box::new(AllQuery), true),
+            Err(_) => {
+                return Err(PersistError::UnexpectedError(format!(
+                    "Cursor paging is not supported for {query:?}"
+                )))
+            }
+        };
+        let after = cursor.map(StoryCursor::key);
+        let first_shard =
+            after.and_then(|(date, _)| StoryDate::from_seconds(date).map(Shard::from_date_time));
+
+        let mut docs = vec![];
+        for shard in self.shards().iterate(ShardOrder::NewestFirst) {
+            if docs.len() >= max {
+                break;
+            }
+            if first_shard.is_some_and(|first| shard > first) {
+                continue;
+            }
+            let res = self.with_searcher(shard, |shard, searcher, schema| {
+                self.fetch_after_in_shard(
+                    shard,
+                    searcher,
+                    schema,
+                    search.as_ref(),
+                    front_page,
+                    after,
+                    max - docs.len(),
+                )
+            });
+            if let Some(res) = self.skip_degraded(shard, res)? {
+                docs.extend(res);
+            }
+        }
+
+        docs.into_iter()
+            .take(max)
+            .map(|(shard, doc)| self.story_with_shard(shard, doc))
+            .collect()
+    }
+
+    /// Fetch at least `max` stories from one shard (if there are that many), newest first, that sort strictly
+    /// before `after`. All stories sharing the date of the oldest story returned are included so that the
+    /// caller can truncate the result without skipping any of them on the next page.
+    #[allow(clippy::too_many_arguments)]
+    fn fetch_after_in_shard(
+        &self,
+        shard: Shard,
+        searcher: &Searcher,
+        schema: &StorySchema,
+        search: &dyn Query,
+        front_page: bool,
+        after: Option<(i64, i64)>,
+        max: usize,
+    ) -> Result<Vec<(Shard, DocAddress)>, PersistError> {
+        // The date field isn't indexed, so filter on its fast field instead of using a range query
+        let date_field = schema.date_field;
+        let bounded = |range: (Bound<i64>, Bound<i64>)| move |date: i64| range.contains(&date);
+
+        let mut v = vec![];
+        let mut upper = after.map_or(Bound::Unbounded, |(date, _)| Bound::Included(date));
+        while v.len() < max {
+            let limit = max - v.len();
+            let top = FilterCollector::new(
+                date_field,
+                bounded((Bound::Unbounded, upper)),
+                TopDocs::with_limit(limit).order_by_fast_field::<i64>(date_field),
+            );
+            let mut docs = searcher.search(search, &top)?;
+            let truncated = docs.len() == limit;
+            if let (true, Some(&(oldest, _))) = (truncated, docs.last()) {
+                // The oldest date may have been cut off part way through, so fetch every story with that date
+                docs.retain(|(date, _)| *date != oldest);
+                let ties = FilterCollector::new(
+                    date_field,
+                    bounded((Bound::Included(oldest), Bound::Included(oldest))),
+                    DocSetCollector,
+                );
+                docs.extend(
+                    searcher
+                        .search(search, &ties)?
+                        .into_iter()
+                        .map(|doc| (oldest, doc)),
+                );
+                upper = Bound::Excluded(oldest);
+            }
+
+            let mut page = vec![];
+            for (date, doc) in docs {
+                let hash = searcher
+                    .segment_reader(doc.segment_ord)
+                    .fast_fields()
+                    .i64(schema.url_norm_hash_field)?
+                    .get_val(doc.doc_id);
+                if after.is_some_and(|after| (date, hash) >= after) {
+                    continue;
+                }
+                if front_page
+                    && (self.exclude_penalized_hosts || self.exclude_self_posts)
+                    && self.exclude_from_front_page(&searcher.doc(doc)?)
+                {
+                    continue;
+                }
+                page.push(((date, hash), doc));
+            }
+            page.sort_by_key(|(key, _)| std::cmp::Reverse(*key));
+            v.extend(page.into_iter().map(|(_, doc)| (shard, doc)));
+
+            if !truncated {
+                break;
+            }
+        }
+        Ok(v)
+    }
+
+    /// Count the tags that co-occur on stories matching a query, most frequent first, returning at most `max` of
+    /// them. Only the top `RELATED_TAGS_SAMPLE_SIZE` matching stories are sampled, so counts for broad queries
+    /// are approximate. The searched tag of a tag search is excluded from its own results.
+    pub fn related_tags(
+        &self,
+        query: &StoryQuery,
+        max: usize,
+    ) -> Result<Vec<(String, usize)>, PersistError> {
+        let exclude = match query {
+            StoryQuery::TagSearch(tag, alt) => [Some(tag.as_str()), alt.as_deref()],
+            _ => [None, None],
+        };
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for (shard, doc) in self.fetch_doc_addresses(query, RELATED_TAGS_SAMPLE_SIZE)? {
+            let tags = self.with_index(shard, |_, index| Ok(index.lookup_story(doc)?.tags))?;
+            for tag in tags.into_iter().unique() {
+                if !exclude.contains(&Some(tag.as_str())) {
+                    *counts.entry(tag).or_default() += 1;
+                }
+            }
+        }
+        Ok(counts
+            .into_iter()
+            .sorted_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)))
+            .take(max)
+            .collect_vec())
+    }
+
+    /// Count the stories in the whole index that each source contributed at least one scrape to.
+    pub fn source_counts(&self) -> Result<TypedScrapeMap<usize>, PersistError> {
+        let mut counts = TypedScrapeMap::new();
+        for shard in self.shards().iterate(ShardOrder::OldestFirst) {
+            let shard_counts = self.with_searcher(shard, |_, searcher, schema| {
+                let mut counts: TypedScrapeMap<usize> = TypedScrapeMap::new();
+                for (idx, segment_reader) in searcher.segment_readers().iter().enumerate() {
+                    for doc_id in segment_reader.doc_ids_alive() {
+                        let doc = searcher.doc(DocAddress::new(idx as u32, doc_id))?;
+                        // Scrape ids are indexed as `shard:id`
+                        let sources: HashSet<_> = doc
+                            .get_all(schema.scrape_field)
+                            .filter_map(|id| id.as_text())
+                            .filter_map(|id| ScrapeId::from_string(id.split_once(':')?.1))
+                            .map(|id| id.source)
+                            .collect();
+                        for source in sources {
+                            counts.set(source, counts.get(source) + 1);
+                        }
+                    }
+                }
+                Ok(counts)
+            });
+            let Some(shard_counts) = self.skip_degraded(shard, shard_counts)? else {
+                continue;
+            };
+            for (source, count) in shard_counts.iter() {
+                counts.set(source, counts.get(source) + count);
+            }
+        }
+        Ok(counts)
+    }
+
+    /// The total number of stories in the index, from each shard's document count. Unlike
+    /// [`Storage::story_count`] this doesn't count the scrape store, so it's cheap enough for health checks.
+    pub fn total_story_count(&self) -> Result<usize, PersistError> {
+        let mut total = 0;
+        for shard in self.shards().iterate(ShardOrder::OldestFirst) {
+            if let Some(index) = self.skip_degraded(shard, self.get_shard(shard))? {
+                total += index.read().total_docs()?;
+            }
+        }
+        Ok(total)
+    }
+
+    /// Eagerly open the newest `n` shards and their searchers, so that the first queries after startup don't
+    /// pay that cost. Returns the shards that were warmed.
+    pub fn warm(&self, n: usize) -> Result<Vec<Shard>, PersistError> {
+        let shards = self
+            .shards()
+            .iterate(ShardOrder::NewestFirst)
+            .take(n)
+            .collect_vec();
+        for shard in &shards {
+            self.skip_degraded(*shard, self.with_searcher(*shard, |_, _, _| Ok(())))?;
+        }
+        tracing::info!("Warmed shards {shards:?}");
+        Ok(shards)
+    }
+
+    /// Archive every shard older than the one containing `date`, returning the archived shards. Each shard is
+    /// backed up first if a `BackerUpper` is provided, then closed and its directory removed. A failed backup
+    /// stops the archive before that shard is touched.
+    pub fn archive_shards_before(
+        &mut self,
+        date: StoryDate,
+        backup: Option<&BackerUpper>,
+    ) -> Result<Vec<Shard>, PersistError> {
+        let cutoff = Shard::from_date_time(date);
+        let shards = self
+            .shards()
+            .iterate(ShardOrder::OldestFirst)
+            .take_while(|shard| *shard < cutoff)
+            .collect_vec();
+        let mut archived = vec![];
+        for shard in shards {
+            if let Some(backup) = backup {
+                let res = backup.backup(&shard.to_string(), shard, &self.scrape_db)?;
+                tracing::info!("Backed up shard {shard} before archiving: {res:?}");
+            }
+
+            self.drop_shard(shard)?;
+            tracing::info!("Archived shard {shard}");
+            archived.push(shard);
+        }
+        Ok(archived)
+    }
+
+    /// Close the oldest shard and remove its stories and scrapes.
+    fn drop_shard(&self, shard: Shard) -> Result<(), PersistError> {
+        let mut index_cache = self.index_cache.write();
+        index_cache.cache.remove(&shard);
+        self.scrape_db.close_shard(shard);
+        if let PersistLocation::Path(path) = index_cache.location.join(shard.to_string()) {
+            if path.exists() {
+                std::fs::remove_dir_all(&path)?;
+            }
+        }
+        index_cache.range.exclude_before(shard + 1);
+        index_cache.most_recent_story = None;
+        index_cache.earliest_story = None;
+        Ok(())
+    }
+
+    /// Delete every story dated strictly before `date`. Shards that lie entirely before the cutoff are dropped
+    /// wholesale along with the scrapes stored in them, while the shard containing the cutoff has its older
+    /// stories and their scrapes deleted one at a time.
+    fn delete_before(&mut self, date: StoryDate) -> Result<PurgeSummary, PersistError> {
+        let cutoff = Shard::from_date_time(date);
+        let mut summary = PurgeSummary::default();
+        let shards = self.shards().iterate(ShardOrder::OldestFirst).collect_vec();
+        for shard in shards.iter().take_while(|shard| **shard < cutoff) {
+            if let Some(index) = self.skip_degraded(*shard, self.get_shard(*shard))? {
+                summary.stories += index.read().total_docs()?;
+            }
+            summary.scrapes += self.scrape_db.stats(*shard)?.count;
+            self.drop_shard(*shard)?;
+            tracing::info!("Deleted shard {shard}");
+        }
+
+        if shards.contains(&cutoff) {
+            let stories =
+                self.with_index(cutoff, |_, index| index.stories_before(date.timestamp()))?;
+            if !stories.is_empty() {
+                self.with_writers(|provider| {
+                    provider.provide(cutoff, |_, index, writer| {
+                        for (id, _) in &stories {
+                            index.delete_story_document(writer, id);
+                        }
+                        Ok(())
+                    })
+                })?;
+                summary.stories += stories.len();
+                summary.scrapes += self
+                    .scrape_db
+                    .delete_scrape_batch(stories.into_iter().flat_map(|(_, ids)| ids))?;
+            }
+        }
+
+        tracing::info!("Deleted stories before {date}: {summary:?}");
+        Ok(summary)
+    }
+
+    /// Manually merge the story `merge` into the story `keep`, for duplicates that automatic de-duplication missed.
+    /// The scrapes of both stories are combined under `keep`'s id and URL, and `merge` is removed from the index.
+    /// The raw scrapes themselves are left untouched in the scrape store.
+    pub fn merge_stories(
+        &mut self,
+        eval: &StoryEvaluator,
+        keep: &StoryIdentifier,
+        merge: &StoryIdentifier,
+    ) -> Result<ScrapePersistResult, PersistError> {
+        if keep == merge {
+            return Err(PersistError::UnexpectedError(
+                "Cannot merge a story into itself".into(),
+            ));
+        }
+        let Some((keep_shard, keep_doc)) = self
+            .with_searcher(keep.shard(), self.fetch_by_id(keep))?
+            .into_iter()
+            .next()
+        else {
+            return Ok(ScrapePersistResult::NotFound);
+        };
+        let Some((merge_shard, merge_doc)) = self
+            .with_searcher(merge.shard(), self.fetch_by_id(merge))?
+            .into_iter()
+            .next()
+        else {
+            return Ok(ScrapePersistResult::NotFound);
+        };
+
+        self.with_writers(|provider| {
+            let (merge_story, merge_last_activity, merge_comments) =
+                provider.provide(merge_shard, |_, index, writer| {
+                    let doc = index.with_searcher(|searcher, _| Ok(searcher.doc(merge_doc)?))?;
+                    let ids = index.extract_scrape_ids_from_doc(&doc);
+                    let scrapes = self.scrape_db.fetch_scrape_batch(ids)?;
+                    index.delete_story_document(writer, &merge.to_base64());
+                    Ok((
+                        ScrapeCollection::new_from_iter(scrapes.into_values().flatten()),
+                        index.extract_last_activity_from_doc(&doc),
+                        index.extract_comments_from_doc(&doc),
+                    ))
+                })?;
+            provider.provide(keep_shard, |_, index, writer| {
+                let doc = index.with_searcher(|searcher, _| Ok(searcher.doc(keep_doc)?))?;
+                let ids = index.extract_scrape_ids_from_doc(&doc);
+                let last_activity = index.extract_last_activity_from_doc(&doc);
+                let comments = index.extract_comments_from_doc(&doc);
+                let scrapes = self.scrape_db.fetch_scrape_batch(ids)?;
+                let mut story = ScrapeCollection::new_from_iter(scrapes.into_values().flatten());
+                // The merged story keeps the identity of `keep`, regardless of which scrapes are earlier
+                let identity = self.create_story_insert(eval, &story);
+                story.merge_all(merge_story);
+                let mut doc = self.create_story_insert(eval, &story);
+                doc.id = identity.id;
+                doc.host = identity.host;
+                doc.url = identity.url;
+                doc.url_norm = identity.url_norm;
+                doc.url_norm_hash = identity.url_norm_hash;
+                doc.date = identity.date;
+                doc.last_activity = doc
+                    .last_activity
+                    .max(last_activity)
+                    .max(merge_last_activity);
+                doc.merge_comments(&comments);
+                doc.merge_comments(&merge_comments);
+                index.reinsert_story_document(writer, doc)
+            })
+        })
+    }
+
+    /// Drop scrapes for blocked hosts and scrapes with empty titles, returning the remaining scrapes along with the
+    /// number dropped for an empty title and the number blocked.
+    fn filter_scrapes<I: IntoIterator<Item = TypedScrape>>(
+        &self,
+        scrapes: I,
+    ) -> (Vec<TypedScrape>, usize, usize) {
+        let mut dropped = 0;
+        let mut blocked = 0;
+        let v = scrapes
+            .into_iter()
+            .filter_map(|scrape| {
+                let id = scrape.id.clone();
+                if self.blocklist.is_blocked(scrape.url.host()) {
+                    tracing::info!(
+                        "Dropping scrape {id} for blocked host {}",
+                        scrape.url.host()
+                    );
+                    blocked += 1;
+                    return None;
+                }
+                let scrape = self.empty_titles.apply(scrape);
+                if scrape.is_none() {
+                    tracing::info!("Dropping scrape {id} with empty title");
+                    dropped += 1;
+                }
+                scrape
+            })
+            .collect_vec();
+        (v, dropped, blocked)
+    }
+
+    /// Predict the results that [`StorageWriter::insert_scrapes`] would return for these scrapes, without storing
+    /// the scrapes or opening any writers. Scrapes that would be coalesced with another story from the same batch
+    /// count as merged, as they do when inserted.
+    pub fn evaluate_scrapes<I: IntoIterator<Item = TypedScrape>>(
+        &self,
+        scrapes: I,
+    ) -> Result<Vec<ScrapePersistResult>, PersistError> {
+        let (v, dropped, blocked) = self.filter_scrapes(scrapes);
+
+        let mut moved = HashMap::new();
+        let mut stored = HashSet::new();
+        for scrape in &v {
+            let shard = Shard::from_date_time(scrape.date);
+            if let Some(existing) = self.scrape_db.fetch_scrape(shard, &scrape.id)? {
+                if !existing.url.same_story(&scrape.url) {
+                    if self.url_changes == UrlChangePolicy::Update {
+                        moved.insert(scrape.id.clone(), existing.url.clone());
+                    }
+                } else {
+                    stored.insert(scrape.id.clone());
+                }
+            }
+        }
+
+        let mut memindex = MemIndex::default();
+        memindex.insert_scrapes(v)?;
+        let positions = self.find_insert_position(memindex.get_all_stories(), &moved, &stored)?;
+
+        let mut pending = HashSet::new();
+        let mut res = vec![];
+        for (story, _, doc_address, _) in positions {
+            let key = WriterProvider::pending_key(&story, self.story_date(&story));
+            if pending.insert(key) && doc_address.is_none() {
+                res.push(ScrapePersistResult::NewStory);
+            } else {
+                res.push(ScrapePersistResult::MergedWithExistingStory);
+            }
+        }
+        res.extend((0..dropped).map(|_| ScrapePersistResult::DroppedEmptyTitle));
+        res.extend((0..blocked).map(|_| ScrapePersistResult::Blocked));
+        Ok(res)
+    }
+
     pub fn shards(&self) -> ShardRange {
         self.index_cache.read().range
     }
@@ -180,6 +1064,7 @@ impl StoryIndex {
         let mut provider = WriterProvider {
             writers: Default::default(),
             index: self.index_cache.clone(),
+            pending: Default::default(),
         };
         let res = f(&mut provider);
         let WriterProvider { writers, .. } = provider;
@@ -188,14 +1073,15 @@ impl StoryIndex {
         if res.is_ok() {
             tracing::info!("Commiting {} writer(s)", writer_count);
             let commit_start = timer_start!();
+            let mut pending = vec![];
             for (shard, writer) in writers.into_iter().sorted_by_key(|(shard, _)| *shard) {
-                tracing::info!("Committing shard {:?}...", shard);
-                let shard = self.get_shard(shard)?;
-                let mut shard = shard.write();
-                shard.commit_writer(writer)?;
+                pending.push((shard, self.get_shard(shard)?, writer));
             }
+            Self::commit_writers(pending)?;
             timer_end!(commit_start, "Committed {} writer(s).", writer_count);
-            self.index_cache.write().most_recent_story = None;
+            let mut index_cache = self.index_cache.write();
+            index_cache.most_recent_story = None;
+            index_cache.earliest_story = None;
         } else {
             // We'll just have to do our best here...
             for mut writer in writers.into_values() {
@@ -207,18 +1093,54 @@ impl StoryIndex {
         res
     }
 
-    fn create_scrape_id_from_scrape(scrape: &TypedScrape) -> String {
-        format!(
-            "{}:{}",
-            Shard::from_date_time(scrape.date).to_string(),
-            scrape.id
-        )
-    }
+    /// Commit each shard's writer, running up to `COMMIT_PARALLELISM` commits at once so that the segment merges
+    /// of a bulk insert across many shards overlap rather than running one shard at a time. Like the sequential
+    /// commit, the first failure stops any further batches from being committed.
+    fn commit_writers(
+        writers: Vec<(Shard, SharedMut<StoryIndexShard>, IndexWriter)>,
+    ) -> Result<(), PersistError> {
+        for batch in &writers.into_iter().chunks(COMMIT_PARALLELISM) {
+            let batch = batch.collect_vec();
+            let results = std::thread::scope(|scope| {
+                let handles = batch
+                    .into_iter()
+                    .map(|(shard, index, writer)| {
+                        scope.spawn(move || {
+                            tracing::info!("Committing shard {:?}...", shard);
+                            index.write().commit_writer(writer)
+                        })
+                    })
+                    .collect_vec();
+                handles
+                    .into_iter()
+                    .map(|handle| {
+                        handle.join().unwrap_or_else(|_| {
+                            Err(PersistError::UnexpectedError(
+                                "Shard commit panicked".into(),
+                            ))
+                        })
+                    })
+                    .collect_vec()
+            });
+            for res in results {
+                res?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The scrape's `ScrapeId` includes its source, so this is unique across sources.
+    fn create_scrape_id_from_scrape(scrape: &TypedScrape) -> String {
+        format!("{}:{}", Shard::from_date_time(scrape.date), scrape.id)
+    }
 
-    fn create_story_insert(eval: &StoryEvaluator, story: &ScrapeCollection) -> StoryInsert {
+    fn create_story_insert(&self, eval: &StoryEvaluator, story: &ScrapeCollection) -> StoryInsert {
         // TODO: We could be creating the doc directly here instead of allocating
         let extracted = story.extract(&eval.extractor);
-        let score = eval.scorer.score(&extracted);
+        let score_input = StoryScoreInput::from(&extracted);
+        // Any trending bonus is as of when the story is indexed, and fades as the story is rescored (ie: by the
+        // periodic hot set rescore)
+        let score = eval.scorer.score_input(&score_input, StoryDate::now()) as f64;
         let scrape_ids = extracted
             .scrapes
             .values()
@@ -228,76 +1150,196 @@ impl StoryIndex {
         let title = extracted.title().to_owned();
         let mut tags = TagSet::new();
         eval.tagger.tag(&title, &mut tags);
-        for tag in extracted.tags() {
-            if let Some(tag) = eval.tagger.check_tag_search(&tag) {
-                tags.add(tag);
-            } else {
+        let mut tag_sources = tags.dump().map(|tag| (tag, TagSource::Title)).collect_vec();
+        let mut host_tags = TagSet::new();
+        eval.tagger.tag_host(extracted.url().host(), &mut host_tags);
+        for tag in host_tags.dump() {
+            tag_sources.push((tag.clone(), TagSource::Host));
+            tags.add(tag);
+        }
+        let mut url_tags = TagSet::new();
+        eval.tagger.tag_url(extracted.url(), &mut url_tags);
+        for tag in url_tags.dump() {
+            tag_sources.push((tag.clone(), TagSource::Url));
+            tags.add(tag);
+        }
+        for (core, _) in extracted.scrapes.values() {
+            for tag in &core.tags {
+                let tag = eval
+                    .tagger
+                    .check_tag_search(tag)
+                    .unwrap_or(tag.as_ref())
+                    .to_ascii_lowercase();
+                tag_sources.push((tag.clone(), TagSource::Scrape(core.source.source)));
                 tags.add(tag);
             }
         }
+        tag_sources.sort();
+        tag_sources.dedup();
         let url = extracted.url();
-        let id = StoryIdentifier::new(story.earliest, extracted.url().normalization()).to_base64();
+        let date = self.story_date(story);
+        let id = StoryIdentifier::new(date, extracted.url().normalization()).to_base64();
+        let last_activity = story
+            .scrapes
+            .values()
+            .map(|scrape| self.clamp_future_date(scrape.date, scrape.ingested_at))
+            .max()
+            .unwrap_or(date);
+        let ingested_at = story
+            .scrapes
+            .values()
+            .filter_map(|scrape| scrape.ingested_at)
+            .min()
+            .map(|date| date.timestamp());
+        let mut comments: TypedScrapeMap<Option<u32>> = TypedScrapeMap::new();
+        for (core, _) in extracted.scrapes.values() {
+            let source = core.source.source;
+            let count = (*comments.get(source)).max(core.comments);
+            comments.set(source, count);
+        }
+        let authors = Self::story_authors(&extracted);
         let doc = StoryInsert {
             id,
             host: url.host().to_owned(),
             url: url.raw().to_owned(),
             url_norm: url.normalization().string().to_owned(),
             url_norm_hash: url.normalization().hash(),
-            score: score as f64,
-            date: story.earliest.timestamp(),
+            score,
+            date: date.timestamp(),
             title,
             scrape_ids,
             tags,
+            tag_sources,
+            last_activity: last_activity.timestamp(),
+            ingested_at,
+            comments,
+            authors,
+            score_inputs: score_input.scrapes,
         };
         doc
     }
 
-    /// Given a stream of `ScrapeCollection`s, returns the insert position in the index for each. If
-    /// the `DocAddress` is present, the scrapes must be merged with the document at that address. We want to check
-    /// one shard back if we don't find a document in the current shard, so that we can merge scrapes across a month
-    /// boundary, for example.
+    /// The lowercased usernames of everyone that submitted this story.
+    fn story_authors(extracted: &ExtractedScrapeCollection) -> Vec<String> {
+        extracted
+            .scrapes
+            .values()
+            .filter_map(|(core, _)| core.author.as_ref())
+            .map(|author| author.to_lowercase())
+            .sorted()
+            .dedup()
+            .collect_vec()
+    }
+
+    /// Look up the story with this URL from around `date`, checking one shard back if we don't find it in the
+    /// current shard so that we can merge scrapes across a month boundary, for example.
+    fn find_story(
+        &self,
+        url: &StoryUrl,
+        date: StoryDate,
+    ) -> Result<Option<(Shard, DocAddress)>, PersistError> {
+        let one_month = Duration::from_secs(60 * 60 * 24 * 30).as_secs() as i64;
+        let current_shard = Shard::from_date_time(date);
+        for shard in [current_shard, current_shard.sub_months(1)] {
+            let doc_address = self.with_index(shard, |_, index| {
+                let lookup = StoryLookupId {
+                    url_norm_hash: url.normalization().hash(),
+                    date: date.timestamp(),
+                };
+                let lookup = HashSet::from_iter([lookup]);
+                let result = index.lookup_stories(lookup, (-one_month)..one_month)?;
+                Ok(match result.into_iter().next() {
+                    Some(StoryLookup::Found(_, doc)) => Some(doc),
+                    _ => None,
+                })
+            })?;
+            if let Some(doc_address) = doc_address {
+                return Ok(Some((shard, doc_address)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Find the story holding any of the scrapes `ids`, searching the shards around `date`. This finds stories that
+    /// can't be found by URL, like those that a scrape was manually merged into.
+    fn find_story_by_scrape<'a>(
+        &self,
+        ids: impl IntoIterator<Item = &'a ScrapeId>,
+        date: StoryDate,
+    ) -> Result<Option<(Shard, DocAddress)>, PersistError> {
+        let ids = ids.into_iter().collect_vec();
+        let current_shard = Shard::from_date_time(date);
+        let range = self.shards();
+        for shard in [
+            current_shard,
+            current_shard.sub_months(1),
+            current_shard.plus_months(1),
+        ] {
+            if !range.contains(shard) {
+                continue;
+            }
+            for id in &ids {
+                if let Some(doc_address) =
+                    self.with_index(shard, |_, index| index.lookup_scrape(id))?
+                {
+                    return Ok(Some((shard, doc_address)));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Given a stream of `ScrapeCollection`s, returns the [`InsertPosition`] in the index for each. Stories whose
+    /// scrapes were previously stored under a different URL (`moved`) are matched against that URL as well, and
+    /// stories with scrapes that were stored before (`stored`) are matched against the story holding those scrapes.
     fn find_insert_position<'a, I: IntoIterator<Item = ScrapeCollection> + 'a>(
         &self,
         scrapes: I,
-    ) -> Result<Vec<(ScrapeCollection, Shard, Option<DocAddress>)>, PersistError> {
-        let one_month = Duration::from_secs(60 * 60 * 24 * 30).as_secs() as i64;
+        moved: &HashMap<ScrapeId, StoryUrl>,
+        stored: &HashSet<ScrapeId>,
+    ) -> Result<Vec<InsertPosition>, PersistError> {
         let mut res = vec![];
 
         // TODO: We could easily be batching the lookups here, though managing that batching
         // could be somewhat complicated.
         for story in scrapes {
-            let current_shard = Shard::from_date_time(story.earliest);
-            let mut shard = current_shard;
-            let mut i = 0;
-            let (shard, doc_address) = loop {
-                // Look this document up in the current shard.
-                let doc_address = self.with_index(shard, |_, index| {
-                    let lookup = StoryLookupId {
-                        url_norm_hash: story.url().normalization().hash(),
-                        date: story.earliest.timestamp(),
-                    };
-                    let lookup = HashSet::from_iter([lookup]);
-                    let result = index.lookup_stories(lookup, (-one_month)..one_month)?;
-                    Ok(match result.into_iter().next() {
-                        Some(StoryLookup::Found(_, doc)) => Some(doc),
-                        _ => None,
-                    })
-                })?;
-
-                // We break out of the loop if we find a document, _or_ if we're not searching shard w/offset zero
-                break if doc_address.is_some() {
-                    (shard, doc_address)
-                } else if i == 0 {
-                    // Not found, and we're looking at the current shard. Let's go back one month and look there instead.
-                    i -= 1;
-                    shard = shard.sub_months(1);
-                    continue;
-                } else {
-                    // Not found one shard back either, so insert in current shard.
-                    (current_shard, None)
-                };
-            };
-            res.push((story, shard, doc_address));
+            let date = self.story_date(&story);
+            let mut found = self.find_story(story.url(), date)?;
+            if found.is_none()
+                && story.earliest < date
+                && self
+                    .shards()
+                    .contains(Shard::from_date_time(story.earliest))
+            {
+                // Before trusting that the earliest scrape was mis-dated, check for a story filed under its date
+                found = self.find_story(story.url(), story.earliest)?;
+            }
+            if found.is_none() {
+                if let Some(old_url) = story.scrapes.keys().find_map(|id| moved.get(id)) {
+                    found = self.find_story(old_url, date)?;
+                    if found.is_some() {
+                        tracing::info!("Moving story from {old_url} to {}", story.url());
+                    }
+                }
+            }
+            let mut keep_identity = false;
+            if found.is_none() {
+                let ids = story.scrapes.keys().filter(|id| stored.contains(id));
+                found = self.find_story_by_scrape(ids, date)?;
+                if found.is_some() {
+                    tracing::info!("Found story for {} by its scrapes", story.url());
+                    keep_identity = true;
+                }
+            }
+            match found {
+                Some((shard, doc_address)) => {
+                    res.push((story, shard, Some(doc_address), keep_identity))
+                }
+                None => {
+                    let shard = Shard::from_date_time(date);
+                    res.push((story, shard, None, false))
+                }
+            }
         }
         Ok(res)
     }
@@ -306,47 +1348,170 @@ impl StoryIndex {
         &mut self,
         eval: &StoryEvaluator,
         scrapes: I,
+        moved: &HashMap<ScrapeId, StoryUrl>,
+        stored: &HashSet<ScrapeId>,
     ) -> Result<Vec<ScrapePersistResult>, PersistError> {
         let mut memindex = MemIndex::default();
         memindex.insert_scrapes(scrapes)?;
-        let positions = self.find_insert_position(memindex.get_all_stories())?;
+        let positions = self.find_insert_position(memindex.get_all_stories(), moved, stored)?;
 
         self.with_writers(|provider| {
             let mut res = vec![];
-            for (story, shard, doc_address) in positions {
-                res.push(provider.provide(shard, |_, index, writer| {
-                    if let Some(doc) = doc_address {
-                        let doc = index.with_searcher(|searcher, _| Ok(searcher.doc(doc)?))?;
-                        let ids = index.extract_scrape_ids_from_doc(&doc);
-                        let scrapes = self.scrape_db.fetch_scrape_batch(ids)?;
-                        let mut orig_story =
-                            ScrapeCollection::new_from_iter(scrapes.into_values().flatten());
-                        orig_story.merge_all(story);
-                        let doc = Self::create_story_insert(eval, &orig_story);
-                        index.reinsert_story_document(writer, doc)
-                    } else {
-                        let doc = Self::create_story_insert(eval, &story);
-                        index.insert_story_document(writer, doc)
-                    }
-                })?);
+            for (story, shard, doc_address, keep_identity) in positions {
+                res.push(self.insert_story_batched(
+                    eval,
+                    provider,
+                    story,
+                    shard,
+                    doc_address,
+                    keep_identity,
+                )?);
             }
             Ok(res)
         })
     }
 
+    /// Write a single story into the open writer batch, merging it with the document at `doc_address` if
+    /// provided (and keeping that document's id, URL and date if `keep_identity` is set). If a story with the same
+    /// normalized URL and day was already written in this batch, the two are coalesced into a single story.
+    fn insert_story_batched(
+        &self,
+        eval: &StoryEvaluator,
+        provider: &mut WriterProvider,
+        mut story: ScrapeCollection,
+        shard: Shard,
+        doc_address: Option<DocAddress>,
+        keep_identity: bool,
+    ) -> Result<ScrapePersistResult, PersistError> {
+        let key = WriterProvider::pending_key(&story, self.story_date(&story));
+        let (story, doc, existing, keep_identity, res) =
+            if let Some(pending) = provider.pending.remove(&key) {
+                tracing::info!(
+                    "Coalescing story with one already written in this batch: {:?}",
+                    story.url()
+                );
+                story.merge_all(pending.story);
+                provider.provide(pending.shard, |_, index, writer| {
+                    let mut doc = self.create_story_insert(eval, &story);
+                    if let Some(existing) = &pending.existing {
+                        if pending.keep_identity {
+                            index.keep_identity_from_doc(existing, &mut doc);
+                        }
+                        doc.last_activity = doc.last_activity.max(StoryDate::now().timestamp());
+                        doc.merge_comments(&index.extract_comments_from_doc(existing));
+                    }
+                    // The merge may have changed the story's URL or date, and with it the id of the pending document
+                    if pending.id != doc.id {
+                        index.delete_story_document(writer, &pending.id);
+                    }
+                    Ok((
+                        story,
+                        doc,
+                        pending.existing,
+                        pending.keep_identity,
+                        ScrapePersistResult::MergedWithExistingStory,
+                    ))
+                })?
+            } else if let Some(doc) = doc_address {
+                provider.provide(shard, |_, index, writer| {
+                    let doc_fields = index.with_searcher(|searcher, _| Ok(searcher.doc(doc)?))?;
+                    let ids = index.extract_scrape_ids_from_doc(&doc_fields);
+                    let scrapes = self.scrape_db.fetch_scrape_batch(ids)?;
+                    let mut orig_story =
+                        ScrapeCollection::new_from_iter(scrapes.into_values().flatten());
+                    orig_story.merge_all(story);
+                    let mut doc = self.create_story_insert(eval, &orig_story);
+                    if keep_identity {
+                        index.keep_identity_from_doc(&doc_fields, &mut doc);
+                    }
+                    // Seeing the story again counts as activity
+                    doc.last_activity = doc.last_activity.max(StoryDate::now().timestamp());
+                    doc.merge_comments(&index.extract_comments_from_doc(&doc_fields));
+                    // If the story's URL or date changed, so did its id
+                    let old_id = index.extract_id_from_doc(&doc_fields);
+                    if old_id != doc.id {
+                        index.delete_story_document(writer, &old_id);
+                    }
+                    Ok((
+                        orig_story,
+                        doc,
+                        Some(doc_fields),
+                        keep_identity,
+                        ScrapePersistResult::MergedWithExistingStory,
+                    ))
+                })?
+            } else {
+                let doc = self.create_story_insert(eval, &story);
+                (story, doc, None, false, ScrapePersistResult::NewStory)
+            };
+
+        // A new date may file the story under another shard (ie: once an outlier date is outvoted)
+        let doc_shard = StoryDate::from_seconds(doc.date)
+            .map(Shard::from_date_time)
+            .unwrap_or(shard);
+        if doc_shard != shard {
+            tracing::info!("Moving story {} from {shard} to {doc_shard}", doc.id);
+        }
+        // Key the story by the document we actually write, as merging may have moved its URL or date
+        let key = WriterProvider::pending_doc_key(&doc);
+        let id = doc.id.clone();
+        provider.provide(doc_shard, |_, index, writer| match res {
+            ScrapePersistResult::NewStory => index.insert_story_document(writer, doc),
+            _ => index.reinsert_story_document(writer, doc),
+        })?;
+        provider.pending.insert(
+            key,
+            PendingStory {
+                shard: doc_shard,
+                story,
+                id,
+                existing,
+                keep_identity,
+            },
+        );
+        Ok(res)
+    }
+
     /// Insert a list of scrapes into the index.
     fn insert_scrapes<I: IntoIterator<Item = TypedScrape>>(
         &mut self,
         eval: &StoryEvaluator,
         scrapes: I,
     ) -> Result<Vec<ScrapePersistResult>, PersistError> {
-        let v = scrapes.into_iter().collect_vec();
+        let (mut v, dropped, blocked) = self.filter_scrapes(scrapes);
+
+        // Find any scrapes we've seen before under a different URL before we overwrite them, and stamp each scrape
+        // with the time we first stored it. Re-scrapes keep the time from the copy we already have. Scrapes are
+        // stored by source and id, so the copy we find is always from the same source.
+        let now = StoryDate::now();
+        let mut moved = HashMap::new();
+        let mut stored = HashSet::new();
+        for scrape in &mut v {
+            let shard = Shard::from_date_time(scrape.date);
+            let existing = self.scrape_db.fetch_scrape(shard, &scrape.id)?;
+            if let Some(existing) = &existing {
+                if !existing.url.same_story(&scrape.url) {
+                    if self.url_changes == UrlChangePolicy::Update {
+                        moved.insert(scrape.id.clone(), existing.url.clone());
+                    }
+                } else {
+                    stored.insert(scrape.id.clone());
+                }
+            }
+            scrape.ingested_at = match existing {
+                Some(existing) => existing.ingested_at.or(scrape.ingested_at),
+                None => scrape.ingested_at.or(Some(now)),
+            };
+        }
 
         tracing::info!("Storing raw scrapes...");
         self.scrape_db.insert_scrape_batch(v.iter())?;
 
         tracing::info!("Indexing scrapes...");
-        self.insert_scrape_batch(eval, v)
+        let mut res = self.insert_scrape_batch(eval, v, &moved, &stored)?;
+        res.extend((0..dropped).map(|_| ScrapePersistResult::DroppedEmptyTitle));
+        res.extend((0..blocked).map(|_| ScrapePersistResult::Blocked));
+        Ok(res)
     }
 
     fn insert_scrape_collections<I: IntoIterator<Item = ScrapeCollection>>(
@@ -370,16 +1535,14 @@ impl StoryIndex {
                 for story in scrape_collections {
                     count += 1;
                     res.push(ScrapePersistResult::NewStory);
-                    let doc = Self::create_story_insert(eval, &story);
+                    let doc = self.create_story_insert(eval, &story);
+                    let shard = Shard::from_date_time(self.story_date(&story));
                     let scrapes = story.scrapes.into_values();
                     scrapes_batch.extend(scrapes);
-                    provider.provide(
-                        Shard::from_date_time(story.earliest),
-                        move |_, index, writer| {
-                            index.insert_story_document(writer, doc)?;
-                            Ok(())
-                        },
-                    )?;
+                    provider.provide(shard, move |_, index, writer| {
+                        index.insert_story_document(writer, doc)?;
+                        Ok(())
+                    })?;
 
                     if scrapes_batch.len() > SCRAPE_PROCESSING_CHUNK_SIZE {
                         self.scrape_db.insert_scrape_batch(scrapes_batch.iter())?;
@@ -401,29 +1564,97 @@ impl StoryIndex {
         eval: &StoryEvaluator,
         stories: I,
     ) -> Result<Vec<ScrapePersistResult>, PersistError> {
-        self.with_writers(|provider| {
+        let now = StoryDate::now();
+        let mut snapshots: HashMap<Shard, Vec<(String, f32)>> = HashMap::new();
+        let res = self.with_writers(|provider| {
             let mut res = vec![];
             for id in stories {
                 let searcher = self.fetch_by_id(&id);
                 let docs = self.with_searcher(id.shard(), searcher)?;
                 if let Some((shard, doc)) = docs.first() {
-                    provider.provide(*shard, |_, index, writer| {
+                    let score = provider.provide(*shard, |_, index, writer| {
                         let doc = index.with_searcher(|searcher, _| Ok(searcher.doc(*doc)?))?;
                         let ids = index.extract_scrape_ids_from_doc(&doc);
                         let scrapes = self.scrape_db.fetch_scrape_batch(ids)?;
                         let orig_story =
                             ScrapeCollection::new_from_iter(scrapes.into_values().flatten());
-                        let doc = Self::create_story_insert(eval, &orig_story);
+                        let last_activity = index.extract_last_activity_from_doc(&doc);
+                        let comments = index.extract_comments_from_doc(&doc);
+                        let mut doc = self.create_story_insert(eval, &orig_story);
+                        doc.last_activity = doc.last_activity.max(last_activity);
+                        doc.merge_comments(&comments);
+                        let score = doc.score as f32;
                         index.reinsert_story_document(writer, doc)?;
-                        Ok(())
+                        Ok(score)
                     })?;
+                    snapshots
+                        .entry(id.shard())
+                        .or_default()
+                        .push((id.to_base64(), score));
                     res.push(ScrapePersistResult::MergedWithExistingStory);
                 } else {
                     res.push(ScrapePersistResult::NotFound)
                 }
             }
             Ok(res)
-        })
+        })?;
+
+        self.record_score_snapshots(now, snapshots)?;
+        Ok(res)
+    }
+
+    /// Recompute the scores of existing stories, rewriting only the score of each stored document rather than
+    /// re-tagging and rebuilding it. Each document stores its score inputs, so the scrape store isn't read.
+    fn rescore_stories<I: IntoIterator<Item = StoryIdentifier>>(
+        &mut self,
+        eval: &StoryEvaluator,
+        stories: I,
+    ) -> Result<Vec<ScrapePersistResult>, PersistError> {
+        let now = StoryDate::now();
+        let mut snapshots: HashMap<Shard, Vec<(String, f32)>> = HashMap::new();
+        let res = self.with_writers(|provider| {
+            let mut res = vec![];
+            for id in stories {
+                let searcher = self.fetch_by_id(&id);
+                let docs = self.with_searcher(id.shard(), searcher)?;
+                if let Some((shard, doc)) = docs.first() {
+                    let score = provider.provide(*shard, |_, index, writer| {
+                        let doc = index.with_searcher(|searcher, _| Ok(searcher.doc(*doc)?))?;
+                        let input = index.extract_score_input_from_doc(&doc)?;
+                        let score = eval.scorer.score_input(&input, now) as f64;
+                        index.rescore_story_document(writer, &doc, score)?;
+                        Ok(score as f32)
+                    })?;
+                    snapshots
+                        .entry(id.shard())
+                        .or_default()
+                        .push((id.to_base64(), score));
+                    res.push(ScrapePersistResult::MergedWithExistingStory);
+                } else {
+                    res.push(ScrapePersistResult::NotFound)
+                }
+            }
+            Ok(res)
+        })?;
+
+        self.record_score_snapshots(now, snapshots)?;
+        Ok(res)
+    }
+
+    /// Record the given scores as of `now`, if score snapshots are enabled.
+    fn record_score_snapshots(
+        &self,
+        now: StoryDate,
+        snapshots: HashMap<Shard, Vec<(String, f32)>>,
+    ) -> Result<(), PersistError> {
+        if let Some(config) = &self.score_snapshots {
+            let retain_after = now.checked_sub_days(config.retention_days).unwrap_or(now);
+            for (shard, snapshots) in snapshots {
+                self.scrape_db
+                    .insert_score_snapshots(shard, now, snapshots, retain_after)?;
+            }
+        }
+        Ok(())
     }
 
     fn fetch_by_segment(
@@ -463,6 +1694,37 @@ impl StoryIndex {
         }
     }
 
+    /// Look up a set of stories by id, running a single search in each shard that the ids fall into. The result is
+    /// grouped by shard, and ids that aren't found are left out.
+    fn fetch_doc_addresses_by_ids(
+        &self,
+        ids: &[StoryIdentifier],
+    ) -> Result<HashMap<Shard, Vec<(StoryIdentifier, DocAddress)>>, PersistError> {
+        let mut res = HashMap::new();
+        for (shard, ids) in ids.iter().into_group_map_by(|id| id.shard()) {
+            let ids: HashMap<String, &StoryIdentifier> =
+                ids.into_iter().map(|id| (id.to_base64(), id)).collect();
+            let docs = self.with_index(shard, |_, index| {
+                index.with_searcher(|searcher, schema| {
+                    let query = TermSetQuery::new(
+                        ids.keys()
+                            .map(|id| Term::from_field_text(schema.id_field, id)),
+                    );
+                    let mut docs = vec![];
+                    for doc_address in searcher.search(&query, &DocSetCollector)? {
+                        let doc = searcher.doc(doc_address)?;
+                        if let Some(id) = ids.get(&index.extract_id_from_doc(&doc)) {
+                            docs.push(((*id).clone(), doc_address));
+                        }
+                    }
+                    Ok(docs)
+                })
+            })?;
+            res.insert(shard, docs);
+        }
+        Ok(res)
+    }
+
     /// Incrementally fetch a query from multiple shards, up to max items
     fn fetch_search_query<Q: Query>(
         &self,
@@ -473,10 +1735,18 @@ impl StoryIndex {
         let mut vec = vec![];
         let mut remaining = max;
         let now = self.most_recent_story()?.timestamp();
-        for shard in self.shards().iterate(ShardOrder::NewestFirst) {
+        let age_factor = self.search_ranking.age_factor;
+        let limit = self.scan_limits.search.unwrap_or(usize::MAX);
+        for (i, shard) in self.shards().iterate(ShardOrder::NewestFirst).enumerate() {
             if remaining == 0 {
                 break;
             }
+            if i >= limit {
+                tracing::info!(
+                    "Stopped search after scanning {limit} shard(s), results may be incomplete"
+                );
+                break;
+            }
             let docs = self.with_searcher(shard, |shard, searcher, schema| {
                 let schema = schema.clone();
                 // We're going to tweak the score using the internal score
@@ -497,13 +1767,16 @@ impl StoryIndex {
                                 let doc_score = score_field.get_val(doc);
                                 let doc_date = date_field.get_val(doc);
                                 let age = now - doc_date;
-                                score + doc_score as f32 + (age as f32) * -0.00001
+                                score + doc_score as f32 - (age as f32) * age_factor
                             }
                         }
                     });
                 let docs = searcher.search(&query, &docs)?;
                 Ok(docs.into_iter().map(move |x| (shard, x.1)))
-            })?;
+            });
+            let Some(docs) = self.skip_degraded(shard, docs)? else {
+                continue;
+            };
             vec.extend(docs);
             remaining = max.saturating_sub(vec.len());
         }
@@ -517,14 +1790,38 @@ impl StoryIndex {
     ) -> Result<Result<Box<dyn Query>, ()>, PersistError> {
         match query {
             StoryQuery::DomainSearch(domain) => Ok(Ok(self.parse_domain_search(&domain)?)),
+            StoryQuery::AuthorSearch(author) => Ok(Ok(self.parse_author_search(author))),
             StoryQuery::TagSearch(tag, alt) => Ok(Ok(self.parse_tag_search(&tag, alt.as_deref())?)),
             StoryQuery::RelatedSearch(title, tags) => {
                 Ok(Ok(self.parse_related_search(&title, tags.as_slice())?))
             }
             StoryQuery::TextSearch(search) => Ok(Ok(self.parse_text_search(&search)?)),
+            StoryQuery::AllTermsSearch(search) => Ok(Ok(self.parse_all_terms_search(search)?)),
             StoryQuery::UrlSearch(url) => Ok(Ok(self.parse_url_search(&url)?)),
-            StoryQuery::ById(..) | StoryQuery::ByShard(..) | StoryQuery::FrontPage => Ok(Err(())),
+            StoryQuery::Multi(queries) => Ok(Ok(self.parse_multi_search(queries)?)),
+            StoryQuery::ById(..)
+            | StoryQuery::ByShard(..)
+            | StoryQuery::FrontPage
+            | StoryQuery::DateRange(..) => Ok(Err(())),
+        }
+    }
+
+    /// Requires every one of the queries to match.
+    fn parse_multi_search(&self, queries: &[StoryQuery]) -> Result<Box<dyn Query>, PersistError> {
+        let mut clauses = vec![];
+        for query in queries {
+            match self.try_parse_query(query)? {
+                Ok(search) => clauses.push((Occur::Must, search)),
+                Err(_) => {
+                    return Err(PersistError::UnexpectedError(format!(
+                        "Query cannot be combined: {query:?}"
+                    )))
+                }
+            }
         }
+        let query = BooleanQuery::new(clauses);
+        tracing::debug!("Multi query = {:?}", query);
+        Ok(Box::new(query))
     }
 
     fn parse_tag_search(
@@ -543,14 +1840,23 @@ impl StoryIndex {
         //     IndexRecordOption::Basic,
         // );
 
-        // Note that a tag is ASCII, so this is kind of overkill but works
-        let mut query_parser = QueryParser::new(
-            self.schema.schema.clone(),
-            vec![self.schema.title_field, self.schema.tags_field],
-            TokenizerManager::default(),
-        );
-        // Boost search within tags
-        query_parser.set_field_boost(self.schema.tags_field, 10.0);
+        if let Some(prefix) = tag
+            .strip_suffix('*')
+            .filter(|prefix| prefix.chars().all(char::is_alphanumeric))
+        {
+            let query = self.tag_fields_query(|field| {
+                Ok(Box::new(RegexQuery::from_pattern(
+                    &format!("{prefix}.*"),
+                    field,
+                )?))
+            })?;
+            tracing::debug!("Tag prefix query = {:?}", query);
+            return Ok(query);
+        }
+
+        // Note that a tag is ASCII, so this is kind of overkill but works. We also match the host's tokens
+        // so that searches like "youtube" find stories from youtube.com.
+        let query_parser = &self.query_parsers.tag;
         let query = if let Some(alt) = alt {
             query_parser.parse_query(&format!("{tag} OR {alt}"))?
         } else {
@@ -560,8 +1866,102 @@ impl StoryIndex {
         Ok(Box::new(query))
     }
 
+    /// Builds a query matching any of the fields that a tag search looks at, boosting matches within tags.
+    fn tag_fields_query(
+        &self,
+        query: impl Fn(Field) -> Result<Box<dyn Query>, PersistError>,
+    ) -> Result<Box<dyn Query>, PersistError> {
+        Ok(Box::new(BooleanQuery::new(vec![
+            (Occur::Should, query(self.schema.title_field)?),
+            (
+                Occur::Should,
+                Box::new(BoostQuery::new(query(self.schema.tags_field)?, 10.0)),
+            ),
+            (Occur::Should, query(self.schema.host_field)?),
+        ])))
+    }
+
+    /// If fuzzy matching is enabled, builds an inexact version of a tag or domain search to retry with when the
+    /// exact search finds nothing.
+    fn try_parse_fuzzy_query(
+        &self,
+        query: &StoryQuery,
+    ) -> Result<Option<Box<dyn Query>>, PersistError> {
+        let distance = self.fuzzy_search.max_edit_distance.min(2);
+        if distance == 0 {
+            return Ok(None);
+        }
+        let fuzzy = |field, text: &str| -> Box<dyn Query> {
+            Box::new(FuzzyTermQuery::new(
+                Term::from_field_text(field, text),
+                distance,
+                true,
+            ))
+        };
+        match query {
+            StoryQuery::TagSearch(tag, alt) if !tag.ends_with('*') => {
+                let terms = std::iter::once(tag).chain(alt).collect_vec();
+                Ok(Some(self.tag_fields_query(|field| {
+                    Ok(Box::new(BooleanQuery::new(
+                        terms
+                            .iter()
+                            .map(|term| (Occur::Should, fuzzy(field, term)))
+                            .collect(),
+                    )))
+                })?))
+            }
+            StoryQuery::DomainSearch(domain) if !domain.ends_with('*') => {
+                let domain = self.domain_search.trim_host(domain);
+                let terms = tokenize_domain(domain)
+                    .into_iter()
+                    .map(|token| (Occur::Must, fuzzy(self.schema.host_field, &token.text)))
+                    .collect_vec();
+                Ok((!terms.is_empty())
+                    .then(|| Box::new(BooleanQuery::new(terms)) as Box<dyn Query>))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn parse_author_search(&self, author: &str) -> Box<dyn Query> {
+        // Authors are indexed lowercase as usernames are case-insensitive on the sources that report them
+        Box::new(TermQuery::new(
+            Term::from_field_text(self.schema.author_field, &author.to_lowercase()),
+            IndexRecordOption::Basic,
+        ))
+    }
+
     fn parse_domain_search(&self, domain: &str) -> Result<Box<dyn Query>, PersistError> {
         let host_field = self.schema.host_field;
+        if let Some(prefix) = domain.strip_suffix('*') {
+            // Every token must match, but the last one only by prefix
+            let prefix = self.domain_search.trim_host(prefix);
+            let mut tokens = tokenize_domain(prefix);
+            let Some(last) = tokens.pop() else {
+                return Err(PersistError::UnexpectedError("Empty domain".to_string()));
+            };
+            let mut terms: Vec<(Occur, Box<dyn Query>)> = tokens
+                .into_iter()
+                .map(|token| {
+                    let term = Term::from_field_text(host_field, &token.text);
+                    (
+                        Occur::Must,
+                        Box::new(TermQuery::new(term, IndexRecordOption::Basic)) as Box<dyn Query>,
+                    )
+                })
+                .collect();
+            terms.push((
+                Occur::Must,
+                Box::new(RegexQuery::from_pattern(
+                    &format!("{}.*", last.text),
+                    host_field,
+                )?),
+            ));
+            let query = BooleanQuery::new(terms);
+            tracing::debug!("Domain prefix query = {:?}", query);
+            return Ok(Box::new(query));
+        }
+        let domain = self.domain_search.trim_host(domain);
         let phrase = tokenize_domain(domain)
             .into_iter()
             .map(|s| Term::from_field_text(host_field, &s.text))
@@ -597,14 +1997,23 @@ impl StoryIndex {
         Ok(Box::new(query))
     }
 
+    /// Requires each whitespace-separated term of the search to match the title or tags.
+    fn parse_all_terms_search(&self, search: &str) -> Result<Box<dyn Query>, PersistError> {
+        let mut terms = vec![];
+        // Terms without any alphanumerics can't match anything, so they'd make the whole query fail
+        for term in search
+            .split_whitespace()
+            .filter(|term| term.contains(char::is_alphanumeric))
+        {
+            terms.push((Occur::Must, self.parse_text_search(term)?));
+        }
+        let query = BooleanQuery::new(terms);
+        tracing::debug!("All terms query = {:?}", query);
+        Ok(Box::new(query))
+    }
+
     fn parse_text_search(&self, search: &str) -> Result<Box<dyn Query>, PersistError> {
-        let mut query_parser = QueryParser::new(
-            self.schema.schema.clone(),
-            vec![self.schema.title_field, self.schema.tags_field],
-            TokenizerManager::default(),
-        );
-        // Boost search within tags
-        query_parser.set_field_boost(self.schema.tags_field, 3.0);
+        let query_parser = &self.query_parsers.text;
 
         // "Escape" http: and https: because they look like field searches
         let search = if search.contains("http:") {
@@ -618,7 +2027,15 @@ impl StoryIndex {
             search
         };
 
-        let query = query_parser.parse_query(&search)?;
+        let query = match query_parser.parse_query(&search) {
+            Ok(query) => query,
+            Err(e) => {
+                // Malformed query syntax (ie: unbalanced quotes) falls back to a literal search of the words
+                let literal = search.replace(|c: char| !c.is_alphanumeric(), " ");
+                tracing::info!("Falling back to literal search {literal:?} for {search:?} ({e:?})");
+                query_parser.parse_query(&literal)?
+            }
+        };
         tracing::debug!("Term query = {:?}", query);
         Ok(query)
     }
@@ -628,12 +2045,7 @@ impl StoryIndex {
         title: &str,
         tags: &[String],
     ) -> Result<Box<dyn Query>, PersistError> {
-        let mut query_parser = QueryParser::new(
-            self.schema.schema.clone(),
-            vec![self.schema.title_field, self.schema.tags_field],
-            TokenizerManager::default(),
-        );
-        query_parser.set_field_boost(self.schema.title_field, 2.0);
+        let query_parser = &self.query_parsers.related;
 
         // Parse the alphanumeric bits of a title with some manual stop-word removal
         // TODO: we need to index everything with stemming and stop-word removal!
@@ -670,20 +2082,47 @@ impl StoryIndex {
         Ok(Box::new(query))
     }
 
-    fn fetch_front_page(&self, max_count: usize) -> Result<Vec<(Shard, DocAddress)>, PersistError> {
+    /// Fetch the front page, leaving off stories whose score, aged relative to the most recent story, is below the
+    /// scorer's front page minimum. The scorer is passed in on each fetch so that a reloaded configuration takes
+    /// effect without reopening the index.
+    pub fn fetch_front_page_with_floor(
+        &self,
+        scorer: &StoryScorer,
+        max: usize,
+    ) -> Result<Vec<Story<Shard>>, PersistError> {
+        self.fetch_front_page(max, Some(scorer))?
+            .into_iter()
+            .map(|(shard, doc)| self.story_with_shard(shard, doc))
+            .collect()
+    }
+
+    fn fetch_front_page(
+        &self,
+        max_count: usize,
+        floor_scorer: Option<&StoryScorer>,
+    ) -> Result<Vec<(Shard, DocAddress)>, PersistError> {
         let mut story_collector: StoryCollector<(Shard, DocAddress)> =
             StoryCollector::new(max_count);
         let mut processed = 0;
         let processing_target = max_count * 2;
+        let floor =
+            match floor_scorer.and_then(|scorer| Some((scorer.front_page_min_score()?, scorer))) {
+                Some((min_score, scorer)) => Some((min_score, scorer, self.most_recent_story()?)),
+                None => None,
+            };
 
         // Limit how far back we go since the front page _should_ only be one or two shards unless our index is empty.
-        for shard in self.shards().iterate(ShardOrder::NewestFirst).take(3) {
+        for shard in self
+            .shards()
+            .iterate(ShardOrder::NewestFirst)
+            .take(self.scan_limits.front_page)
+        {
             // Process at least twice as many stories as requested
             if processed >= processing_target {
                 break;
             }
 
-            self.with_searcher(shard, |shard, searcher, _schema| {
+            let res = self.with_searcher(shard, |shard, searcher, _schema| {
                 let top = TopDocs::with_limit(processing_target - processed)
                     .order_by_fast_field::<i64>(self.schema.date_field);
                 let docs = searcher.search(&AllQuery {}, &top)?;
@@ -691,18 +2130,34 @@ impl StoryIndex {
 
                 for (_, doc_address) in docs {
                     processed += 1;
-                    let score = searcher
+                    let fast_fields = searcher
                         .segment_reader(doc_address.segment_ord)
-                        .fast_fields()
+                        .fast_fields();
+                    let score = fast_fields
                         .f64(self.schema.score_field)?
                         .get_val(doc_address.doc_id) as f32;
+                    if let Some((min_score, scorer, now)) = floor {
+                        let date = fast_fields
+                            .i64(self.schema.date_field)?
+                            .get_val(doc_address.doc_id);
+                        let date = StoryDate::from_seconds(date).unwrap_or(now);
+                        if score + scorer.score_age(now - date) < min_score {
+                            continue;
+                        }
+                    }
                     if story_collector.would_accept(score) {
+                        if (self.exclude_penalized_hosts || self.exclude_self_posts)
+                            && self.exclude_from_front_page(&searcher.doc(doc_address)?)
+                        {
+                            continue;
+                        }
                         story_collector.accept(score, (shard, doc_address));
                     }
                 }
 
                 Ok(())
-            })?;
+            });
+            self.skip_degraded(shard, res)?;
         }
         tracing::info!(
             "Got {}/{} docs for front page (processed {})",
@@ -713,6 +2168,84 @@ impl StoryIndex {
         Ok(story_collector.to_sorted())
     }
 
+    /// Fetch a page of `count` stories dated within `[start, end)`, newest first, skipping the first `offset`. Unlike
+    /// other queries, which are capped at a maximum number of results, every story in the range can be paged through.
+    pub fn fetch_date_range_page(
+        &self,
+        start: StoryDate,
+        end: StoryDate,
+        offset: usize,
+        count: usize,
+    ) -> Result<Vec<Story<Shard>>, PersistError> {
+        self.fetch_date_range(start, end, offset, count)?
+            .into_iter()
+            .map(|(shard, doc)| self.story_with_shard(shard, doc))
+            .collect()
+    }
+
+    /// Fetch up to `max` stories dated within `[start, end)`, newest first, after skipping the first `offset`. Only
+    /// the shards overlapping the range are searched, and shards that fall entirely within the offset are only
+    /// counted.
+    fn fetch_date_range(
+        &self,
+        start: StoryDate,
+        end: StoryDate,
+        mut offset: usize,
+        max: usize,
+    ) -> Result<Vec<(Shard, DocAddress)>, PersistError> {
+        let mut v = vec![];
+        let Some(last) = StoryDate::from_seconds(end.timestamp() - 1) else {
+            return Ok(v);
+        };
+        if last < start {
+            return Ok(v);
+        }
+        let range = (start.timestamp(), end.timestamp());
+        let shards = Shard::from_date_time(start)..=Shard::from_date_time(last);
+        for shard in self.shards().iterate(ShardOrder::NewestFirst) {
+            if v.len() >= max {
+                break;
+            }
+            if !shards.contains(&shard) {
+                continue;
+            }
+            let limit = max - v.len();
+            let res = self.with_searcher(shard, |shard, searcher, schema| {
+                // The date field isn't indexed, so filter on its fast field instead of using a range query
+                let in_range = move |date: i64| (range.0..range.1).contains(&date);
+                if offset > 0 {
+                    let matching = searcher.search(
+                        &AllQuery,
+                        &FilterCollector::new(schema.date_field, in_range, Count),
+                    )?;
+                    if matching <= offset {
+                        return Ok((vec![], matching));
+                    }
+                }
+                let top = FilterCollector::new(
+                    schema.date_field,
+                    in_range,
+                    TopDocs::with_limit(limit)
+                        .and_offset(offset)
+                        .order_by_fast_field::<i64>(schema.date_field),
+                );
+                Ok((
+                    searcher
+                        .search(&AllQuery, &top)?
+                        .into_iter()
+                        .map(|(_, doc)| (shard, doc))
+                        .collect_vec(),
+                    offset,
+                ))
+            });
+            if let Some((res, skipped)) = self.skip_degraded(shard, res)? {
+                offset -= skipped;
+                v.extend(res);
+            }
+        }
+        Ok(v)
+    }
+
     fn fetch_doc_addresses(
         &self,
         query: &StoryQuery,
@@ -724,11 +2257,21 @@ impl StoryIndex {
             ScoreAlgo::Default
         };
         catch_unwind(|| match self.try_parse_query(query)? {
-            Ok(query) => self.fetch_search_query(query, max, algo),
+            Ok(search) => {
+                let docs = self.fetch_search_query(search, max, algo)?;
+                if docs.is_empty() {
+                    if let Some(fuzzy) = self.try_parse_fuzzy_query(query)? {
+                        tracing::debug!("No exact matches, retrying with fuzzy query {:?}", fuzzy);
+                        return self.fetch_search_query(fuzzy, max, algo);
+                    }
+                }
+                Ok(docs)
+            }
             Err(_) => match query {
                 StoryQuery::ById(id) => self.with_searcher(id.shard(), self.fetch_by_id(&id)),
                 StoryQuery::ByShard(shard) => self.with_searcher(*shard, self.fetch_by_segment()),
-                StoryQuery::FrontPage => self.fetch_front_page(max),
+                StoryQuery::FrontPage => self.fetch_front_page(max, None),
+                StoryQuery::DateRange(start, end) => self.fetch_date_range(*start, *end, 0, max),
                 _ => Err(PersistError::UnexpectedError(format!(
                     "Unexpected try_parse_query result"
                 ))),
@@ -775,6 +2318,18 @@ impl StorageWriter for StoryIndex {
     ) -> Result<Vec<ScrapePersistResult>, PersistError> {
         self.reinsert_stories(eval, stories)
     }
+
+    fn rescore_stories<I: IntoIterator<Item = StoryIdentifier>>(
+        &mut self,
+        eval: &StoryEvaluator,
+        stories: I,
+    ) -> Result<Vec<ScrapePersistResult>, PersistError> {
+        self.rescore_stories(eval, stories)
+    }
+
+    fn delete_before(&mut self, date: StoryDate) -> Result<PurgeSummary, PersistError> {
+        self.delete_before(date)
+    }
 }
 
 impl StorageFetch<Shard> for StoryIndex {
@@ -785,25 +2340,23 @@ impl StorageFetch<Shard> for StoryIndex {
     ) -> Result<Vec<Story<Shard>>, PersistError> {
         let mut v = vec![];
         for (shard, doc) in self.fetch_doc_addresses(query, max)? {
-            let doc = self.with_index(shard, |_, index| {
-                let story = index.lookup_story(doc)?;
-                let url = StoryUrl::parse(story.url).expect("Failed to parse URL");
-                let date = StoryDate::from_seconds(story.date).expect("Failed to re-parse date");
-                let score = story.score as f32;
-                Ok(Story::new_from_parts(
-                    story.title,
-                    url,
-                    date,
-                    score,
-                    story.tags,
-                    story.scrape_ids,
-                ))
-            })?;
-
-            v.push(doc);
+            v.push(self.story_with_shard(shard, doc)?);
         }
         Ok(v)
     }
+
+    fn fetch_type_by_ids(
+        &self,
+        ids: &[StoryIdentifier],
+    ) -> Result<HashMap<StoryIdentifier, Story<Shard>>, PersistError> {
+        let mut map = HashMap::new();
+        for (shard, docs) in self.fetch_doc_addresses_by_ids(ids)? {
+            for (id, doc) in docs {
+                map.insert(id, self.story_with_shard(shard, doc)?);
+            }
+        }
+        Ok(map)
+    }
 }
 
 impl StorageFetch<TypedScrape> for StoryIndex {
@@ -814,32 +2367,161 @@ impl StorageFetch<TypedScrape> for StoryIndex {
     ) -> Result<Vec<Story<TypedScrape>>, PersistError> {
         let mut v = vec![];
         for (shard, doc) in self.fetch_doc_addresses(query, max)? {
-            let doc = self.with_index(shard, |_, index| {
-                let story = index.lookup_story(doc)?;
-                let url = StoryUrl::parse(story.url).expect("Failed to parse URL");
-                let date = StoryDate::from_seconds(story.date).expect("Failed to re-parse date");
-                let score = story.score as f32;
-
-                let scrapes = self
-                    .scrape_db
-                    .fetch_scrape_batch(story.scrape_ids.clone())?;
-                let story = Story::new_from_parts(
-                    story.title,
-                    url,
-                    date,
-                    score,
-                    story.tags,
-                    scrapes.into_values().flatten(),
-                );
-
-                Ok(story)
-            })?;
-
+            let doc = self.with_index(shard, |_, index| self.story_with_scrapes(index, doc))?;
             v.push(doc);
         }
         Ok(v)
     }
-}
+
+    fn fetch_type_by_ids(
+        &self,
+        ids: &[StoryIdentifier],
+    ) -> Result<HashMap<StoryIdentifier, Story<TypedScrape>>, PersistError> {
+        let mut map = HashMap::new();
+        for (shard, docs) in self.fetch_doc_addresses_by_ids(ids)? {
+            self.with_index(shard, |_, index| {
+                for (id, doc) in &docs {
+                    map.insert(id.clone(), self.story_with_scrapes(index, *doc)?);
+                }
+                Ok(())
+            })?;
+        }
+        Ok(map)
+    }
+}
+
+impl StorageForEach<Shard> for StoryIndex {
+    fn for_each_story_in_shard(
+        &self,
+        shard: Shard,
+        mut f: impl FnMut(Story<Shard>) -> Result<(), PersistError>,
+    ) -> Result<usize, PersistError> {
+        self.for_each_doc_in_shard(shard, |mut story| {
+            let scrape_ids = std::mem::take(&mut story.scrape_ids);
+            f(Self::story_from_fetch(story, scrape_ids))
+        })
+    }
+}
+
+impl StorageForEach<TypedScrape> for StoryIndex {
+    fn for_each_story_in_shard(
+        &self,
+        shard: Shard,
+        mut f: impl FnMut(Story<TypedScrape>) -> Result<(), PersistError>,
+    ) -> Result<usize, PersistError> {
+        self.for_each_doc_in_shard(shard, |story| f(self.story_from_fetch_with_scrapes(story)?))
+    }
+}
+
+impl StoryIndex {
+    /// Walks the live documents of a shard, loading each one only as it is visited.
+    fn for_each_doc_in_shard(
+        &self,
+        shard: Shard,
+        mut f: impl FnMut(StoryFetch) -> Result<(), PersistError>,
+    ) -> Result<usize, PersistError> {
+        let now = timer_start!();
+        let count = self.with_index(shard, |_, index| {
+            index.with_searcher(|searcher, _| {
+                let mut count = 0;
+                for (idx, segment_reader) in searcher.segment_readers().iter().enumerate() {
+                    for doc_id in segment_reader.doc_ids_alive() {
+                        let doc = searcher.doc(DocAddress::new(idx as u32, doc_id))?;
+                        f(index.story_from_doc(&doc))?;
+                        count += 1;
+                    }
+                }
+                Ok(count)
+            })
+        })?;
+        timer_end!(now, "Walked {} stories in shard {:?}", count, shard);
+        Ok(count)
+    }
+
+    fn story_from_fetch<S, I: Into<(ScrapeId, S)>>(
+        story: StoryFetch,
+        scrapes: impl IntoIterator<Item = I>,
+    ) -> Story<S> {
+        let url = StoryUrl::parse(story.url).expect("Failed to parse URL");
+        let date = StoryDate::from_seconds(story.date).expect("Failed to re-parse date");
+        let score = story.score as f32;
+        Story::new_from_parts(story.title, url, date, score, story.tags, scrapes)
+            .with_tag_sources(story.tag_sources)
+            .with_last_activity(StoryDate::from_seconds(story.last_activity).unwrap_or(date))
+            .with_ingested_at(story.ingested_at.and_then(StoryDate::from_seconds))
+            .with_comments(story.comments)
+    }
+
+    fn story_from_fetch_with_scrapes(
+        &self,
+        mut story: StoryFetch,
+    ) -> Result<Story<TypedScrape>, PersistError> {
+        let scrapes = self
+            .scrape_db
+            .fetch_scrape_batch(std::mem::take(&mut story.scrape_ids))?;
+        Ok(Self::story_from_fetch(
+            story,
+            scrapes.into_values().flatten(),
+        ))
+    }
+
+    fn story_with_shard(
+        &self,
+        shard: Shard,
+        doc: DocAddress,
+    ) -> Result<Story<Shard>, PersistError> {
+        self.with_index(shard, |_, index| {
+            let mut story = index.lookup_story(doc)?;
+            let scrape_ids = std::mem::take(&mut story.scrape_ids);
+            Ok(Self::story_from_fetch(story, scrape_ids))
+        })
+    }
+
+    fn story_with_scrapes(
+        &self,
+        index: &StoryIndexShard,
+        doc: DocAddress,
+    ) -> Result<Story<TypedScrape>, PersistError> {
+        self.story_from_fetch_with_scrapes(index.lookup_story(doc)?)
+    }
+
+    fn doc_detail(
+        index: &StoryIndexShard,
+        doc: DocAddress,
+    ) -> Result<StoryDocDetail, PersistError> {
+        let named_doc = index.doc_fields(doc)?;
+        let mut map = HashMap::new();
+        for (key, value) in named_doc.0 {
+            map.insert(
+                key,
+                value
+                    .into_iter()
+                    .map(|v| serde_json::to_string(&v).unwrap_or_else(|e| e.to_string()))
+                    .collect_vec(),
+            );
+        }
+        Ok(map)
+    }
+
+    /// Fetches a single story with its scrapes, along with the raw index fields for that same document (as
+    /// returned by `fetch_detail_one`), in a single lookup.
+    pub fn fetch_one_with_doc(
+        &self,
+        query: &StoryQuery,
+    ) -> Result<Option<(Story<TypedScrape>, StoryDocDetail)>, PersistError> {
+        if let Some((shard, doc)) = self.fetch_doc_addresses(query, 1)?.first() {
+            let res = self.with_index(*shard, |_, index| {
+                Ok((
+                    self.story_with_scrapes(index, *doc)?,
+                    Self::doc_detail(index, *doc)?,
+                ))
+            })?;
+            Ok(Some(res))
+        } else {
+            Ok(None)
+        }
+    }
+}
 
 impl Storage for StoryIndex {
     fn most_recent_story(&self) -> Result<StoryDate, PersistError> {
@@ -847,15 +2529,34 @@ impl Storage for StoryIndex {
             return Ok(most_recent_story);
         }
 
-        if let Some(max) = self.shards().iterate(ShardOrder::NewestFirst).next() {
-            let shard = self.get_shard(max)?;
+        for shard in self.shards().iterate(ShardOrder::NewestFirst) {
+            let Some(shard) = self.skip_degraded(shard, self.get_shard(shard))? else {
+                continue;
+            };
             let index = shard.read();
             let result = index.most_recent_story()?;
             self.index_cache.write().most_recent_story = Some(result);
-            Ok(result)
-        } else {
-            Ok(StoryDate::MIN)
+            return Ok(result);
+        }
+        Ok(StoryDate::MIN)
+    }
+
+    fn earliest_story(&self) -> Result<StoryDate, PersistError> {
+        if let Some(earliest_story) = self.index_cache.read().earliest_story {
+            return Ok(earliest_story);
+        }
+
+        for shard in self.shards().iterate(ShardOrder::OldestFirst) {
+            let Some(shard) = self.skip_degraded(shard, self.get_shard(shard))? else {
+                continue;
+            };
+            let index = shard.read();
+            if let Some(result) = index.earliest_story()? {
+                self.index_cache.write().earliest_story = Some(result);
+                return Ok(result);
+            }
         }
+        Ok(StoryDate::MIN)
     }
 
     fn shard_range(&self) -> Result<ShardRange, PersistError> {
@@ -865,7 +2566,9 @@ impl Storage for StoryIndex {
     fn story_count(&self) -> Result<StorageSummary, PersistError> {
         let mut summary = StorageSummary::default();
         for shard in self.shards().iterate(ShardOrder::OldestFirst) {
-            let index = self.get_shard(shard)?;
+            let Some(index) = self.skip_degraded(shard, self.get_shard(shard))? else {
+                continue;
+            };
             let subtotal = index.read().total_docs()?;
             let scrape_subtotal = self.scrape_db.stats(shard)?.count;
             summary.by_shard.push((
@@ -878,6 +2581,11 @@ impl Storage for StoryIndex {
             summary.total.story_count += subtotal;
             summary.total.scrape_count += scrape_subtotal;
         }
+        summary.degraded = self
+            .degraded_shards()
+            .into_iter()
+            .map(|(shard, error)| (shard.to_string(), error))
+            .collect();
         Ok(summary)
     }
 
@@ -885,7 +2593,10 @@ impl Storage for StoryIndex {
         let mut summary = SearchSummary::default();
         if let StoryQuery::FrontPage = query {
             for shard in self.shards().iterate(ShardOrder::OldestFirst) {
-                let docs = self.get_shard(shard)?.read().total_docs()?;
+                let Some(index) = self.skip_degraded(shard, self.get_shard(shard))? else {
+                    continue;
+                };
+                let docs = index.read().total_docs()?;
                 summary.by_shard.push((shard.to_string(), docs));
                 summary.total += docs;
             }
@@ -902,7 +2613,10 @@ impl Storage for StoryIndex {
             let docs = self.with_searcher(shard, |_, searcher, _| {
                 let docs = searcher.search(&query, &tantivy::collector::Count)?;
                 Ok(docs)
-            })?;
+            });
+            let Some(docs) = self.skip_degraded(shard, docs)? else {
+                continue;
+            };
             summary.by_shard.push((shard.to_string(), docs));
             summary.total += docs;
         }
@@ -913,25 +2627,9 @@ impl Storage for StoryIndex {
         Ok(self.fetch_doc_addresses(query, max)?.len())
     }
 
-    fn fetch_detail_one(
-        &self,
-        query: &StoryQuery,
-    ) -> Result<Option<HashMap<String, Vec<String>>>, PersistError> {
+    fn fetch_detail_one(&self, query: &StoryQuery) -> Result<Option<StoryDocDetail>, PersistError> {
         if let Some((shard, doc)) = self.fetch_doc_addresses(query, 1)?.first() {
-            let res = self.with_index(*shard, |_, index| {
-                let named_doc = index.doc_fields(*doc)?;
-                let mut map = HashMap::new();
-                for (key, value) in named_doc.0 {
-                    map.insert(
-                        key,
-                        value
-                            .into_iter()
-                            .map(|v| serde_json::to_string(&v).unwrap_or_else(|e| e.to_string()))
-                            .collect_vec(),
-                    );
-                }
-                Ok(map)
-            })?;
+            let res = self.with_index(*shard, |_, index| Self::doc_detail(index, *doc))?;
             Ok(Some(res))
         } else {
             Ok(None)
@@ -948,7 +2646,9 @@ mod test {
     };
     use tempfile::tempdir;
 
-    use crate::{story::TagSet, test::*, MemIndex};
+    use crate::{
+        story::TagSet, test::*, MemIndex, ScrapePersistResultSummarizer, StoryScoreConfig,
+    };
     use rstest::*;
 
     fn populate_shard(
@@ -1097,6 +2797,38 @@ mod test {
         Ok(())
     }
 
+    /// Each story counts once per source that scraped it, across every shard.
+    #[rstest]
+    fn test_source_counts(_enable_tracing: &bool) -> Result<(), Box<dyn std::error::Error>> {
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        let eval = StoryEvaluator::new_for_test();
+        index.insert_scrapes(
+            &eval,
+            [
+                rust_story_hn(),
+                rust_story_reddit(),
+                rust_story_lobsters(),
+                hn_story(
+                    "story2",
+                    StoryDate::year_month_day(2019, 6, 1).expect("Date failed"),
+                    "Another story",
+                    &StoryUrl::parse("http://example.com/other").expect("URL"),
+                ),
+            ],
+        )?;
+        assert_eq!(index.story_count()?.total.story_count, 2);
+        assert!(index.shards().iterate(ShardOrder::OldestFirst).count() > 1);
+
+        let counts = index.source_counts()?;
+        assert_eq!(counts.hacker_news, 2);
+        assert_eq!(counts.reddit, 1);
+        assert_eq!(counts.lobsters, 1);
+        assert_eq!(counts.slashdot, 0);
+        assert_eq!(counts.other, 0);
+
+        Ok(())
+    }
+
     #[rstest]
     fn test_index_scrapes_across_shard(
         _enable_tracing: &bool,
@@ -1165,111 +2897,2155 @@ mod test {
         Ok(())
     }
 
-    /// Does re-indexing a story work correctly?
-    #[test]
-    fn test_reindex_story() -> Result<(), Box<dyn std::error::Error>> {
-        // Load a story
-        let mut memindex = MemIndex::default();
+    /// A source that corrects a story's URL either moves the existing story or forks a new one, depending on policy.
+    #[rstest]
+    #[case(UrlChangePolicy::Fork, 2)]
+    #[case(UrlChangePolicy::Update, 1)]
+    fn test_url_change(
+        _enable_tracing: &bool,
+        #[case] policy: UrlChangePolicy,
+        #[case] expected: usize,
+    ) -> Result<(), Box<dyn std::error::Error>> {
         let eval = StoryEvaluator::new_for_test();
-        memindex.insert_scrapes([rust_story_hn(), rust_story_reddit()])?;
         let mut index = StoryIndex::new(PersistLocation::Memory)?;
-        index.insert_scrape_collections(&eval, memindex.get_all_stories())?;
+        index.set_url_change_policy(policy);
+        let date = StoryDate::year_month_day(2020, 1, 1).expect("Date failed");
+        let typo = StoryUrl::parse("https://example.com/strory").expect("URL");
+        let fixed = StoryUrl::parse("https://example.com/story").expect("URL");
+        index.insert_scrapes(&eval, [hn_story("1", date, "A story", &typo)])?;
+        index.insert_scrapes(&eval, [hn_story("1", date, "A story", &fixed)])?;
 
-        // Ask the index for this story
+        assert_eq!(index.story_count()?.total.story_count, expected);
         let story = index
-            .fetch_one::<Shard>(&StoryQuery::from_search(&eval.tagger, "rust"))?
+            .fetch_one::<TypedScrape>(&StoryQuery::ById(StoryIdentifier::new(
+                date,
+                fixed.normalization(),
+            )))?
             .expect("Missing story");
+        assert_eq!(story.url, fixed);
+        assert_eq!(story.scrapes.len(), 1);
+        let old = index.fetch_one::<Shard>(&StoryQuery::ById(StoryIdentifier::new(
+            date,
+            typo.normalization(),
+        )))?;
+        assert_eq!(old.is_some(), policy == UrlChangePolicy::Fork);
 
-        // Re-insert it and make sure it comes back with the right info
-        assert_eq!(
-            index.reinsert_stories(&eval, [story.id])?,
-            vec![ScrapePersistResult::MergedWithExistingStory]
-        );
+        Ok(())
+    }
+
+    /// We record when a scrape was first stored, regardless of when the source says it was posted.
+    #[rstest]
+    fn test_ingested_at(_enable_tracing: &bool) -> Result<(), Box<dyn std::error::Error>> {
+        let eval = StoryEvaluator::new_for_test();
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        let date = StoryDate::year_month_day(2020, 1, 1).expect("Date failed");
+        let url = StoryUrl::parse("https://example.com/story").expect("URL");
+        let before = StoryDate::now();
+        index.insert_scrapes(&eval, [hn_story("1", date, "A story", &url)])?;
+
+        let query = StoryQuery::ById(StoryIdentifier::new(date, url.normalization()));
         let story = index
-            .fetch_one::<Shard>(&StoryQuery::from_search(&eval.tagger, "rust"))?
+            .fetch_one::<TypedScrape>(&query)?
             .expect("Missing story");
-        assert_eq!(story.title, "I love Rust");
-
-        let counts = index.story_count()?;
-        assert_eq!(counts.total.story_count, 1);
+        let ingested_at = story.ingested_at.expect("Missing ingested_at");
+        assert!(ingested_at > date);
+        assert!(ingested_at.timestamp() >= before.timestamp());
+        let scrape = story.scrapes.values().next().expect("Missing scrape");
+        let first = scrape.ingested_at.expect("Missing scrape ingested_at");
+
+        // Seeing the same scrape again keeps the original time
+        index.insert_scrapes(&eval, [hn_story("1", date, "A story", &url)])?;
+        let story = index
+            .fetch_one::<TypedScrape>(&query)?
+            .expect("Missing story");
+        let scrape = story.scrapes.values().next().expect("Missing scrape");
+        assert_eq!(scrape.ingested_at, Some(first));
+        assert_eq!(story.ingested_at, Some(ingested_at));
 
         Ok(())
     }
 
+    /// Stories fetched by id in bulk are looked up with one search per shard.
     #[rstest]
-    fn test_insert_batch(_enable_tracing: &bool) -> Result<(), Box<dyn std::error::Error>> {
-        let mut batch = vec![];
-        let date = StoryDate::year_month_day(2020, 1, 1).expect("Date failed");
+    fn test_fetch_many_by_id(_enable_tracing: &bool) -> Result<(), Box<dyn std::error::Error>> {
+        let eval = StoryEvaluator::new_for_test();
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        let mut ids = vec![];
+        let mut scrapes = vec![];
+        for i in 0..10 {
+            let month = if i % 2 == 0 { 1 } else { 6 };
+            let date = StoryDate::year_month_day(2020, month, 1 + i).expect("Date failed");
+            let url = StoryUrl::parse(format!("https://example.com/story/{i}")).expect("URL");
+            ids.push(StoryIdentifier::new(date, url.normalization()));
+            scrapes.push(hn_story(&i.to_string(), date, &format!("Story {i}"), &url));
+        }
+        index.insert_scrapes(&eval, scrapes)?;
 
-        for i in 0..30 {
-            let url = StoryUrl::parse(format!("http://domain-{}.com/", i)).expect("URL");
-            batch.push(hn_story(
-                &format!("story-{}", i),
-                date,
-                &format!("Title {}", i),
-                &url,
-            ));
+        // A story that doesn't exist is left out of the results
+        let missing = StoryUrl::parse("https://example.com/missing").expect("URL");
+        let date = StoryDate::year_month_day(2020, 1, 1).expect("Date failed");
+        let mut lookup = ids.clone();
+        lookup.push(StoryIdentifier::new(date, missing.normalization()));
+
+        let by_shard = index.fetch_doc_addresses_by_ids(&lookup)?;
+        assert_eq!(by_shard.len(), 2);
+        assert_eq!(by_shard.values().map(Vec::len).sum::<usize>(), 10);
+
+        let stories = index.fetch_many_by_id::<TypedScrape>(&lookup)?;
+        assert_eq!(stories.len(), 10);
+        for (i, id) in ids.iter().enumerate() {
+            let story = stories.get(id).expect("Missing story");
+            assert_eq!(story.title, format!("Story {i}"));
+            assert_eq!(story.scrapes.len(), 1);
         }
+        let stories = index.fetch_many_by_id::<Shard>(&ids)?;
+        assert_eq!(stories.len(), 10);
+
+        Ok(())
+    }
 
+    /// A dry run predicts the results of an insert without changing the index.
+    #[rstest]
+    fn test_evaluate_scrapes(_enable_tracing: &bool) -> Result<(), Box<dyn std::error::Error>> {
+        let eval = StoryEvaluator::new_for_test();
         let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        let date = StoryDate::year_month_day(2020, 1, 1).expect("Date failed");
+        let existing = StoryUrl::parse("https://example.com/existing").expect("URL");
+        let new = StoryUrl::parse("https://example.com/new").expect("URL");
+        index.insert_scrapes(&eval, [hn_story("1", date, "Existing story", &existing)])?;
+
+        let batch = || {
+            [
+                hn_story("2", date, "Existing story", &existing),
+                hn_story("3", date, "New story", &new),
+                hn_story("4", date, "New story", &new),
+                hn_story("5", date, "", &new),
+            ]
+        };
+        let predicted = index.evaluate_scrapes(batch())?;
+        let query = StoryQuery::ById(StoryIdentifier::new(date, new.normalization()));
+        assert!(index.fetch_one::<TypedScrape>(&query)?.is_none());
+
+        let actual = index.insert_scrapes(&eval, batch())?;
+        assert_eq!(
+            predicted.iter().sorted().collect_vec(),
+            actual.iter().sorted().collect_vec()
+        );
+        assert_eq!(
+            predicted.summary().new,
+            1,
+            "Expected exactly one new story: {predicted:?}"
+        );
+        assert!(index.fetch_one::<TypedScrape>(&query)?.is_some());
+
+        Ok(())
+    }
+
+    /// A single mis-dated scrape doesn't drag the story back to its date unless we allow it.
+    #[rstest]
+    #[case(None, 2000)]
+    #[case(Some(30), 2022)]
+    fn test_date_outliers(
+        _enable_tracing: &bool,
+        #[case] outlier_days: Option<u32>,
+        #[case] expected_year: i32,
+    ) -> Result<(), Box<dyn std::error::Error>> {
         let eval = StoryEvaluator::new_for_test();
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        index.set_date_outlier_days(outlier_days);
+        let url = StoryUrl::parse("https://example.com/story").expect("URL");
+        let date = |year, month, day| StoryDate::year_month_day(year, month, day).expect("Date");
+        let story = ScrapeCollection::new_from_iter(
+            [
+                hn_story("1", date(2022, 12, 20), "A story", &url),
+                reddit_story("2", "rust", date(2022, 12, 21), "A story", &url),
+                reddit_story("3", "programming", date(2022, 12, 22), "A story", &url),
+                reddit_story("4", "science", date(2000, 1, 1), "A story", &url),
+            ]
+            .into_iter(),
+        );
+        index.insert_scrape_collections(&eval, [story])?;
 
-        index.insert_scrapes(&eval, batch.clone())?;
+        let story = index
+            .fetch_one::<Shard>(&StoryQuery::UrlSearch(url.clone()))?
+            .expect("Missing story");
+        assert_eq!(story.date.year(), expected_year);
+        assert_eq!(story.scrapes.len(), 4);
+        assert_eq!(
+            index
+                .shard_range()?
+                .iterate(ShardOrder::OldestFirst)
+                .collect_vec(),
+            vec![Shard::from_date_time(story.date)]
+        );
 
-        // Cause a delete
-        let url = StoryUrl::parse("http://domain-3.com/").expect("URL");
+        Ok(())
+    }
+
+    /// A story first filed under a mis-dated scrape is found again and moved to its real date once enough other
+    /// scrapes outvote it.
+    #[rstest]
+    fn test_date_outlier_outvoted(
+        _enable_tracing: &bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let eval = StoryEvaluator::new_for_test();
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        index.set_date_outlier_days(Some(30));
+        let url = StoryUrl::parse("https://example.com/story").expect("URL");
+        let date = |year, month, day| StoryDate::year_month_day(year, month, day).expect("Date");
+        // With only two scrapes, the mis-dated one can't be outvoted
+        let outlier = reddit_story("1", "science", date(2022, 10, 25), "A story", &url);
+        let first = hn_story("2", date(2022, 12, 20), "A story", &url);
+        index.insert_scrapes(&eval, [outlier.clone(), first.clone()])?;
+        let story = index
+            .fetch_one::<Shard>(&StoryQuery::UrlSearch(url.clone()))?
+            .expect("Missing story");
+        assert_eq!(story.date.month(), 10);
 
         index.insert_scrapes(
             &eval,
-            [reddit_story("story-3", "subreddit", date, "Title 3", &url)],
+            [
+                outlier,
+                first,
+                reddit_story("3", "rust", date(2022, 12, 21), "A story", &url),
+            ],
         )?;
 
-        index.insert_scrapes(&eval, batch.clone())?;
-
-        let front_page = index.fetch_count(&StoryQuery::FrontPage, 100)?;
-        assert_eq!(30, front_page);
+        assert_eq!(index.story_count()?.total.story_count, 1);
+        let story = index
+            .fetch_one::<Shard>(&StoryQuery::UrlSearch(url.clone()))?
+            .expect("Missing story");
+        assert_eq!(story.date.month(), 12);
+        assert_eq!(story.scrapes.len(), 3);
 
         Ok(())
     }
 
-    #[test]
-    fn test_findable_by_extracted_tag() -> Result<(), Box<dyn std::error::Error>> {
-        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+    /// A story dated a year in the future is clamped to the present so it ages like any other story, unless we
+    /// allow it.
+    #[rstest]
+    #[case(None, true)]
+    #[case(Some(12), false)]
+    fn test_future_dates(
+        _enable_tracing: &bool,
+        #[case] tolerance_hours: Option<u32>,
+        #[case] expect_future: bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
         let eval = StoryEvaluator::new_for_test();
-        let scrape = rust_story_lobsters();
-        let id = StoryIdentifier::new(scrape.date, scrape.url.normalization());
-        index.insert_scrapes(&eval, [scrape.clone()])?;
-
-        let counts = index.story_count()?;
-        assert_eq!(counts.total.story_count, 1);
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        index.set_future_date_tolerance_hours(tolerance_hours);
+        let now = StoryDate::now();
+        let future = now.checked_add_days(365).expect("Date");
+        let url = StoryUrl::parse("https://example.com/story").expect("URL");
+        index.insert_scrapes(&eval, [hn_story("1", future, "A story", &url)])?;
 
-        // Title is "Type inference in rust". Site tags are "plt"/"c++" from scrape.
         let story = index
-            .fetch_one::<Shard>(&StoryQuery::ById(id.clone()))?
-            .expect("Expected one story");
+            .fetch_one::<Shard>(&StoryQuery::UrlSearch(url.clone()))?
+            .expect("Missing story");
+        assert_eq!(story.date > now, expect_future);
+        if !expect_future {
+            // Clamped to when the story was stored, which doesn't move as the story is re-derived
+            assert_eq!(
+                story.date.timestamp(),
+                story.ingested_at.expect("Missing ingested_at").timestamp()
+            );
+        }
+        let expected_shard = Shard::from_date_time(if expect_future { future } else { now });
         assert_eq!(
-            story.tags.into_iter().sorted().collect_vec(),
-            vec!["cplusplus", "plt", "rust"]
+            index.shard_range()?.iterate(ShardOrder::NewestFirst).next(),
+            Some(expected_shard)
         );
 
-        for term in ["plt", "c++", "cplusplus", "type", "inference"] {
-            let search = index.fetch_count(&StoryQuery::from_search(&eval.tagger, term), 10)?;
-            let doc = index.fetch_detail_one(&StoryQuery::ById(id.clone()))?;
-            assert_eq!(
-                1, search,
-                "Expected one search result when querying '{}' for title={} url={} doc={:?}",
-                term, scrape.raw_title, scrape.url, doc
-            );
+        // A future story still hasn't aged by tomorrow, so it would keep its full score on the front page
+        let tomorrow = now.checked_add_days(1).expect("Date");
+        assert_eq!(
+            (tomorrow - story.date).num_milliseconds() > 0,
+            !expect_future
+        );
+
+        Ok(())
+    }
+
+    /// A bulk insert that spans more shards than we commit at once lands every story in the right shard.
+    #[rstest]
+    fn test_index_scrape_collections_many_shards(
+        _enable_tracing: &bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let eval = StoryEvaluator::new_for_test();
+        let months = COMMIT_PARALLELISM as u32 * 2 + 1;
+        let mut memindex = MemIndex::default();
+        let mut scrapes = vec![];
+        for month in 1..=months {
+            let date = StoryDate::year_month_day(2020, month, 1).expect("Date failed");
+            for i in 0..month {
+                let url = StoryUrl::parse(format!("https://example.com/{month}/{i}")).expect("URL");
+                scrapes.push(hn_story(&format!("{month}-{i}"), date, "A story", &url));
+            }
+        }
+        memindex.insert_scrapes(scrapes)?;
+
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        index.insert_scrape_collections(&eval, memindex.get_all_stories())?;
+
+        let counts = index.story_count()?;
+        assert_eq!(counts.by_shard.len(), months as usize);
+        for (month, (shard, summary)) in (1..=months).zip(&counts.by_shard) {
+            assert_eq!(*shard, format!("2020-{month:02}"));
+            assert_eq!(summary.story_count, month as usize);
         }
+        assert_eq!(
+            counts.total.story_count,
+            (months * (months + 1) / 2) as usize
+        );
+
+        // Every shard's searcher was reloaded after its commit
+        let stories = index.fetch::<Shard>(&StoryQuery::DomainSearch("example.com".into()), 100)?;
+        assert_eq!(stories.len(), counts.total.story_count);
+
         Ok(())
     }
 
-    /// Ensure that none of these cases crashes the query engine
+    /// Does re-indexing a story work correctly?
     #[test]
-    fn test_torture() -> Result<(), Box<dyn std::error::Error>> {
-        let mut index = StoryIndex::new(PersistLocation::Memory)?;
-        let eval = StoryEvaluator::new_for_test();
-        let url = StoryUrl::parse("http://example.com").expect("URL");
+    fn test_reindex_story() -> Result<(), Box<dyn std::error::Error>> {
+        // Load a story
+        let mut memindex = MemIndex::default();
+        let eval = StoryEvaluator::new_for_test();
+        memindex.insert_scrapes([rust_story_hn(), rust_story_reddit()])?;
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        index.insert_scrape_collections(&eval, memindex.get_all_stories())?;
+
+        // Ask the index for this story
+        let story = index
+            .fetch_one::<Shard>(&StoryQuery::from_search(&eval.tagger, "rust"))?
+            .expect("Missing story");
+
+        // Re-insert it and make sure it comes back with the right info
+        assert_eq!(
+            index.reinsert_stories(&eval, [story.id])?,
+            vec![ScrapePersistResult::MergedWithExistingStory]
+        );
+        let story = index
+            .fetch_one::<Shard>(&StoryQuery::from_search(&eval.tagger, "rust"))?
+            .expect("Missing story");
+        assert_eq!(story.title, "I love Rust");
+
+        let counts = index.story_count()?;
+        assert_eq!(counts.total.story_count, 1);
+
+        Ok(())
+    }
+
+    /// Are tags attributed to the title, or the scrape source that provided them?
+    #[rstest]
+    fn test_tags_with_source(_enable_tracing: &bool) -> Result<(), Box<dyn std::error::Error>> {
+        let eval = StoryEvaluator::new_for_test();
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        let url = StoryUrl::parse("http://example.com").expect("URL");
+        let date = StoryDate::year_month_day(2020, 1, 1).expect("Date failed");
+        index.insert_scrapes(
+            &eval,
+            [
+                hn_story("story1", date, "I love Rust", &url),
+                lobsters_story(
+                    "story1",
+                    date,
+                    "I love Rust",
+                    &url,
+                    vec!["compilers".into()],
+                ),
+            ],
+        )?;
+
+        let story = index
+            .fetch_one::<Shard>(&StoryQuery::from_search(&eval.tagger, "compilers"))?
+            .expect("Missing story");
+        let tags = story.tags_with_source();
+        assert_eq!(
+            tags,
+            vec![
+                (
+                    "compilers".to_owned(),
+                    vec![TagSource::Scrape(ScrapeSource::Lobsters)]
+                ),
+                ("rust".to_owned(), vec![TagSource::Title]),
+            ]
+        );
+
+        Ok(())
+    }
+
+    /// Stories from a configured host are tagged even if the title doesn't mention the tag.
+    #[rstest]
+    fn test_host_tags(_enable_tracing: &bool) -> Result<(), Box<dyn std::error::Error>> {
+        let eval = StoryEvaluator::new_for_test();
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        let url = StoryUrl::parse("https://arxiv.org/abs/1706.03762").expect("URL");
+        let date = StoryDate::year_month_day(2020, 1, 1).expect("Date failed");
+        index.insert_scrapes(
+            &eval,
+            [hn_story("story1", date, "Attention is all you need", &url)],
+        )?;
+
+        let story = index
+            .fetch_one::<Shard>(&StoryQuery::from_search(&eval.tagger, "paper"))?
+            .expect("Missing story");
+        assert_eq!(
+            story.tags_with_source(),
+            vec![("paper".to_owned(), vec![TagSource::Host])]
+        );
+
+        Ok(())
+    }
+
+    /// With URL tagging enabled, stories are tagged from words in their URL even if the title doesn't mention the tag.
+    #[rstest]
+    fn test_url_tags(_enable_tracing: &bool) -> Result<(), Box<dyn std::error::Error>> {
+        let config = serde_json::json!({"tags": {"testing": {"rust": {}}}, "url_tags": true});
+        let eval = StoryEvaluator::new(
+            &serde_json::from_value(config)?,
+            &Default::default(),
+            &Default::default(),
+        );
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        let url = StoryUrl::parse("https://github.com/rust-lang/rust").expect("URL");
+        let date = StoryDate::year_month_day(2020, 1, 1).expect("Date failed");
+        index.insert_scrapes(
+            &eval,
+            [hn_story("story1", date, "A new release is out", &url)],
+        )?;
+
+        let story = index
+            .fetch_one::<Shard>(&StoryQuery::from_search(&eval.tagger, "rust"))?
+            .expect("Missing story");
+        assert_eq!(
+            story.tags_with_source(),
+            vec![("rust".to_owned(), vec![TagSource::Url])]
+        );
+
+        Ok(())
+    }
+
+    /// Walking a shard visits each live story once, and never the documents replaced when a story is updated.
+    #[rstest]
+    fn test_for_each_story_in_shard(
+        _enable_tracing: &bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let eval = StoryEvaluator::new_for_test();
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        let date = StoryDate::year_month_day(2020, 1, 1).expect("Date failed");
+        let url = |n| StoryUrl::parse(&format!("https://example.com/{n}")).expect("URL");
+        index.insert_scrapes(
+            &eval,
+            (0..3).map(|n| hn_story(&n.to_string(), date, &format!("Story {n}"), &url(n))),
+        )?;
+        // Updating a story deletes its old document
+        index.insert_scrapes(&eval, [reddit_story("1", "rust", date, "Story 1", &url(1))])?;
+        let shard = Shard::from_date_time(date);
+
+        let mut titles = vec![];
+        let count = index.for_each_story_in_shard(shard, |story: Story<Shard>| {
+            titles.push(story.title);
+            Ok(())
+        })?;
+        assert_eq!(count, 3);
+        titles.sort();
+        assert_eq!(titles, vec!["Story 0", "Story 1", "Story 2"]);
+
+        let mut scrapes = vec![];
+        index.for_each_story_in_shard(shard, |story: Story<TypedScrape>| {
+            scrapes.push((story.title, story.scrapes.len()));
+            Ok(())
+        })?;
+        scrapes.sort();
+        assert_eq!(
+            scrapes,
+            vec![
+                ("Story 0".to_owned(), 1),
+                ("Story 1".to_owned(), 2),
+                ("Story 2".to_owned(), 1)
+            ]
+        );
+
+        // Errors stop the walk
+        let mut visited = 0;
+        let res = index.for_each_story_in_shard(shard, |_: Story<Shard>| {
+            visited += 1;
+            Err(PersistError::UnexpectedError("Stop".to_owned()))
+        });
+        assert!(res.is_err());
+        assert_eq!(visited, 1);
+
+        Ok(())
+    }
+
+    /// A date range spanning a shard boundary returns stories from both shards, newest first, and nothing outside
+    /// of the range.
+    #[rstest]
+    fn test_date_range(_enable_tracing: &bool) -> Result<(), Box<dyn std::error::Error>> {
+        let eval = StoryEvaluator::new_for_test();
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        let dates = [(1, 15), (1, 31), (2, 1), (2, 15), (3, 1)];
+        index.insert_scrapes(
+            &eval,
+            dates.map(|(month, day)| {
+                let date = StoryDate::year_month_day(2020, month, day).expect("Date failed");
+                let url =
+                    StoryUrl::parse(format!("https://example.com/{month}/{day}")).expect("URL");
+                hn_story(
+                    &format!("{month}-{day}"),
+                    date,
+                    &format!("Story {month}/{day}"),
+                    &url,
+                )
+            }),
+        )?;
+
+        let range = |start: (u32, u32), end: (u32, u32)| {
+            StoryQuery::DateRange(
+                StoryDate::year_month_day(2020, start.0, start.1).expect("Date failed"),
+                StoryDate::year_month_day(2020, end.0, end.1).expect("Date failed"),
+            )
+        };
+        let titles = |query: StoryQuery, max: usize| -> Result<Vec<String>, PersistError> {
+            Ok(index
+                .fetch::<Shard>(&query, max)?
+                .into_iter()
+                .map(|story| story.title)
+                .collect_vec())
+        };
+
+        assert_eq!(
+            titles(range((1, 20), (2, 20)), 10)?,
+            vec!["Story 2/15", "Story 2/1", "Story 1/31"]
+        );
+        assert_eq!(
+            titles(range((1, 20), (2, 20)), 2)?,
+            vec!["Story 2/15", "Story 2/1"]
+        );
+        // The end of the range is exclusive
+        assert_eq!(
+            titles(range((2, 1), (3, 1)), 10)?,
+            vec!["Story 2/15", "Story 2/1"]
+        );
+        assert!(titles(range((3, 1), (2, 1)), 10)?.is_empty());
+
+        // Pages can skip over whole shards
+        let page =
+            |query: StoryQuery, offset: usize, count: usize| -> Result<Vec<String>, PersistError> {
+                let StoryQuery::DateRange(start, end) = query else {
+                    unreachable!()
+                };
+                Ok(index
+                    .fetch_date_range_page(start, end, offset, count)?
+                    .into_iter()
+                    .map(|story| story.title)
+                    .collect_vec())
+            };
+        assert_eq!(
+            page(range((1, 1), (4, 1)), 1, 2)?,
+            vec!["Story 2/15", "Story 2/1"]
+        );
+        assert_eq!(
+            page(range((1, 1), (4, 1)), 3, 10)?,
+            vec!["Story 1/31", "Story 1/15"]
+        );
+        assert!(page(range((1, 1), (4, 1)), 5, 10)?.is_empty());
+
+        Ok(())
+    }
+
+    /// A month with more stories than a single query returns can be paged through to its end.
+    #[rstest]
+    fn test_date_range_paging(_enable_tracing: &bool) -> Result<(), Box<dyn std::error::Error>> {
+        let eval = StoryEvaluator::new_for_test();
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        let start = StoryDate::year_month_day(2020, 1, 1).expect("Date failed");
+        let end = StoryDate::year_month_day(2020, 2, 1).expect("Date failed");
+        index.insert_scrapes(
+            &eval,
+            (0..150).map(|i| {
+                let date = StoryDate::from_seconds(start.timestamp() + i * 60 * 60).expect("Date");
+                let url = StoryUrl::parse(format!("https://example.com/{i}")).expect("URL");
+                hn_story(&i.to_string(), date, &format!("Story {i}"), &url)
+            }),
+        )?;
+
+        let mut titles = vec![];
+        for offset in (0..).step_by(40) {
+            let page = index.fetch_date_range_page(start, end, offset, 40)?;
+            if page.is_empty() {
+                break;
+            }
+            titles.extend(page.into_iter().map(|story| story.title));
+        }
+        assert_eq!(titles.len(), 150);
+        assert_eq!(titles[0], "Story 149");
+        assert_eq!(titles[149], "Story 0");
+
+        Ok(())
+    }
+
+    /// A `+` prefix requires every term to match, while a plain search matches any of them.
+    #[rstest]
+    fn test_all_terms_search(_enable_tracing: &bool) -> Result<(), Box<dyn std::error::Error>> {
+        let eval = StoryEvaluator::new_for_test();
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        let date = StoryDate::year_month_day(2020, 1, 1).expect("Date failed");
+        let url = |s| StoryUrl::parse(s).expect("URL");
+        index.insert_scrapes(
+            &eval,
+            [
+                hn_story(
+                    "1",
+                    date,
+                    "An async runtime for Rust",
+                    &url("https://example.com/1"),
+                ),
+                hn_story(
+                    "2",
+                    date,
+                    "Async programming in Python",
+                    &url("https://example.com/2"),
+                ),
+            ],
+        )?;
+
+        let query = StoryQuery::from_search(&eval.tagger, "+rust async");
+        assert!(matches!(query, StoryQuery::AllTermsSearch(_)));
+        assert_eq!(query.query_text(), "+rust async");
+        let stories = index.fetch::<Shard>(&query, 10)?;
+        assert_eq!(stories.len(), 1);
+        assert_eq!(stories[0].title, "An async runtime for Rust");
+
+        let stories =
+            index.fetch::<Shard>(&StoryQuery::from_search(&eval.tagger, "rust async"), 10)?;
+        assert_eq!(stories.len(), 2);
+
+        Ok(())
+    }
+
+    /// Related tags are ranked by how many matching stories they co-occur on.
+    #[rstest]
+    fn test_related_tags(_enable_tracing: &bool) -> Result<(), Box<dyn std::error::Error>> {
+        let eval = StoryEvaluator::new_for_test();
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        let date = StoryDate::year_month_day(2020, 1, 1).expect("Date failed");
+        let stories = [
+            vec!["rust", "async"],
+            vec!["rust", "async"],
+            vec!["rust", "async", "networking"],
+            vec!["rust", "networking"],
+            vec!["rust", "compilers"],
+            vec!["python", "async"],
+            vec!["python", "async"],
+        ];
+        index.insert_scrapes(
+            &eval,
+            stories.iter().enumerate().map(|(n, tags)| {
+                let url = StoryUrl::parse(format!("https://example.com/{n}")).expect("URL");
+                lobsters_story(
+                    &n.to_string(),
+                    date,
+                    &format!("Story number {n}"),
+                    &url,
+                    tags.iter().map(|tag| tag.to_string()).collect(),
+                )
+            }),
+        )?;
+
+        let related = index.related_tags(&StoryQuery::TagSearch("rust".into(), None), 10)?;
+        assert_eq!(
+            related,
+            vec![
+                ("async".to_owned(), 3),
+                ("networking".to_owned(), 2),
+                ("compilers".to_owned(), 1)
+            ]
+        );
+        let related = index.related_tags(&StoryQuery::TagSearch("rust".into(), None), 1)?;
+        assert_eq!(related, vec![("async".to_owned(), 3)]);
+        let related = index.related_tags(&StoryQuery::TagSearch("async".into(), None), 10)?;
+        assert_eq!(
+            related,
+            vec![
+                ("rust".to_owned(), 3),
+                ("python".to_owned(), 2),
+                ("networking".to_owned(), 1)
+            ]
+        );
+
+        Ok(())
+    }
+
+    /// Paging with a cursor visits every story exactly once, newest first, even if a story arrives mid-paging.
+    #[rstest]
+    fn test_fetch_after_cursor(_enable_tracing: &bool) -> Result<(), Box<dyn std::error::Error>> {
+        let eval = StoryEvaluator::new_for_test();
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        let start = StoryDate::year_month_day(2020, 1, 25)
+            .expect("Date failed")
+            .timestamp();
+        // Spread the stories over two shards, with pairs of stories sharing a date
+        let story = |n: i64| {
+            let date = StoryDate::from_seconds(start + (n / 2) * 86400).expect("Date failed");
+            let url = StoryUrl::parse(format!("https://example.com/{n}")).expect("URL");
+            hn_story(&n.to_string(), date, &format!("Story {n}"), &url)
+        };
+        index.insert_scrapes(&eval, (0..20).map(story))?;
+
+        let mut seen = vec![];
+        let mut cursor = None;
+        loop {
+            let page = index.fetch_after(&StoryQuery::FrontPage, cursor.as_ref(), 3)?;
+            if seen.len() == 6 {
+                index.insert_scrapes(&eval, [story(20)])?;
+            }
+            let Some(last) = page.last() else {
+                break;
+            };
+            let next = StoryCursor::after(last);
+            assert_eq!(StoryCursor::from_base64(next.to_base64()), Some(next));
+            cursor = Some(next);
+            seen.extend(page.into_iter().map(|story| story.title));
+        }
+
+        assert_eq!(seen.len(), 20);
+        assert_eq!(seen.iter().collect::<HashSet<_>>().len(), 20);
+        assert!(!seen.contains(&"Story 20".to_owned()));
+        let days = seen
+            .iter()
+            .map(|title| title["Story ".len()..].parse::<i64>().expect("Number") / 2)
+            .collect_vec();
+        assert_eq!(days, (0..10).rev().flat_map(|day| [day, day]).collect_vec());
+
+        // Searches page the same way
+        let query = StoryQuery::from_search(&eval.tagger, "+story 1");
+        let page = index.fetch_after(&query, None, 100)?;
+        assert_eq!(page.len(), 1);
+        assert_eq!(
+            index
+                .fetch_after(&query, Some(&StoryCursor::after(&page[0])), 100)?
+                .len(),
+            0
+        );
+
+        Ok(())
+    }
+
+    /// URLs that only differ by tracking parameters are the same story.
+    #[rstest]
+    fn test_tracking_params_merge(
+        _enable_tracing: &bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let eval = StoryEvaluator::new_for_test();
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        let date = StoryDate::year_month_day(2020, 1, 1).expect("Date failed");
+        let url = |s| StoryUrl::parse(s).expect("URL");
+        index.insert_scrapes(
+            &eval,
+            [
+                hn_story(
+                    "story1",
+                    date,
+                    "Title",
+                    &url("https://example.com/post?utm_source=hn"),
+                ),
+                reddit_story(
+                    "story1",
+                    "rust",
+                    date,
+                    "Title",
+                    &url("https://example.com/post?utm_source=reddit&utm_campaign=launch"),
+                ),
+            ],
+        )?;
+
+        let stories = index.fetch::<Shard>(&StoryQuery::from_search(&eval.tagger, "title"), 10)?;
+        assert_eq!(stories.len(), 1);
+        assert_eq!(stories[0].scrapes.len(), 2);
+
+        Ok(())
+    }
+
+    /// Author searches only find the stories submitted by that author, regardless of case.
+    #[rstest]
+    fn test_author_search(_enable_tracing: &bool) -> Result<(), Box<dyn std::error::Error>> {
+        let eval = StoryEvaluator::new_for_test();
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        let date = StoryDate::year_month_day(2020, 1, 1).expect("Date failed");
+        let story = |id: &str, author: &str| {
+            let url = StoryUrl::parse(format!("https://example.com/{id}")).expect("URL");
+            let mut reddit =
+                RedditStory::new_subsource_with_defaults(id, "rust", date, "Title", url);
+            reddit.data.author = author.to_owned();
+            TypedScrape::from(reddit)
+        };
+        index.insert_scrapes(
+            &eval,
+            [
+                story("1", "alice"),
+                story("2", "Bob"),
+                story("3", "alice"),
+                story("4", ""),
+            ],
+        )?;
+
+        let search = |search: &str| -> Result<Vec<String>, PersistError> {
+            let query = StoryQuery::from_search(&eval.tagger, search);
+            assert!(matches!(query, StoryQuery::AuthorSearch(_)));
+            Ok(index
+                .fetch::<Shard>(&query, 10)?
+                .into_iter()
+                .map(|story| story.url.to_string())
+                .sorted()
+                .collect())
+        };
+        assert_eq!(
+            search("by:alice")?,
+            vec!["https://example.com/1", "https://example.com/3"]
+        );
+        assert_eq!(search("by:bob")?, vec!["https://example.com/2"]);
+        assert_eq!(search("by:ALICE")?.len(), 2);
+        assert!(search("by:carol")?.is_empty());
+
+        Ok(())
+    }
+
+    /// Combined filters only find the stories matching all of them.
+    #[rstest]
+    fn test_multi_search(_enable_tracing: &bool) -> Result<(), Box<dyn std::error::Error>> {
+        let eval = StoryEvaluator::new_for_test();
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        let date = StoryDate::year_month_day(2020, 1, 1).expect("Date failed");
+        let story = |id: &str, title: &str, url: &str| {
+            hn_story(id, date, title, &StoryUrl::parse(url).expect("URL"))
+        };
+        index.insert_scrapes(
+            &eval,
+            [
+                story("1", "I love Rust", "https://github.com/rust/a"),
+                story("2", "I love Rust", "https://example.com/rust/b"),
+                story("3", "Python tips", "https://github.com/python/c"),
+            ],
+        )?;
+
+        let query = StoryQuery::from_search(&eval.tagger, "tag:rust site:github.com");
+        assert_eq!(
+            query,
+            StoryQuery::Multi(vec![
+                StoryQuery::TagSearch("rust".into(), None),
+                StoryQuery::DomainSearch("github.com".into()),
+            ])
+        );
+        assert_eq!(query.query_type(), "multi");
+        assert_eq!(
+            StoryQuery::from_search(&eval.tagger, &query.query_text()),
+            query
+        );
+        let urls = index
+            .fetch::<Shard>(&query, 10)?
+            .into_iter()
+            .map(|story| story.url.to_string())
+            .collect_vec();
+        assert_eq!(urls, vec!["https://github.com/rust/a"]);
+        assert_eq!(index.fetch_count(&query, 10)?, 1);
+
+        // A single filter is just that search
+        assert_eq!(
+            StoryQuery::from_search(&eval.tagger, "site:github.com"),
+            StoryQuery::DomainSearch("github.com".into())
+        );
+
+        // Queries that don't filter stories can't be combined
+        let id = StoryIdentifier::new(
+            date,
+            StoryUrl::parse("https://github.com/rust/a")
+                .expect("URL")
+                .normalization(),
+        );
+        assert_eq!(
+            StoryQuery::multi(vec![StoryQuery::ById(id.clone()), StoryQuery::ById(id)]),
+            None
+        );
+        assert_eq!(
+            StoryQuery::multi(vec![
+                StoryQuery::FrontPage,
+                StoryQuery::TextSearch("rust".into())
+            ]),
+            None
+        );
+        assert_eq!(StoryQuery::multi(vec![query.clone(), query]), None);
+
+        Ok(())
+    }
+
+    /// The query parsers are shared between searches, so running the same searches again must give the same results.
+    #[rstest]
+    fn test_repeated_text_search(_enable_tracing: &bool) -> Result<(), Box<dyn std::error::Error>> {
+        let eval = StoryEvaluator::new_for_test();
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        let date = StoryDate::year_month_day(2020, 1, 1).expect("Date failed");
+        let story = |id: &str, title: &str, url: &str| {
+            hn_story(id, date, title, &StoryUrl::parse(url).expect("URL"))
+        };
+        index.insert_scrapes(
+            &eval,
+            [
+                story("1", "I love Rust", "https://github.com/rust/a"),
+                story("2", "Rust and Python", "https://example.com/rust/b"),
+                story("3", "Python tips", "https://github.com/python/c"),
+            ],
+        )?;
+
+        let searches = ["rust", "python tips", "love", "rust", "github.com"];
+        let run = |index: &StoryIndex| -> Result<Vec<Vec<String>>, PersistError> {
+            searches
+                .iter()
+                .map(|search| {
+                    let query = StoryQuery::from_search(&eval.tagger, search);
+                    Ok(index
+                        .fetch::<Shard>(&query, 10)?
+                        .into_iter()
+                        .map(|story| story.url.to_string())
+                        .collect_vec())
+                })
+                .collect()
+        };
+
+        let first = run(&index)?;
+        assert!(first.iter().all(|urls| !urls.is_empty()), "{first:?}");
+        for _ in 0..3 {
+            assert_eq!(run(&index)?, first);
+        }
+
+        // The cached parsers produce the same queries as freshly-built ones
+        let fresh = StoryQueryParsers::new(&index.schema);
+        for search in searches {
+            assert_eq!(
+                format!("{:?}", index.query_parsers.text.parse_query(search)?),
+                format!("{:?}", fresh.text.parse_query(search)?)
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Comment counts are tracked per source, and a re-scrape with fewer comments doesn't lower the count.
+    #[rstest]
+    fn test_comment_counts(_enable_tracing: &bool) -> Result<(), Box<dyn std::error::Error>> {
+        let eval = StoryEvaluator::new_for_test();
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        let url = StoryUrl::parse("https://example.com/story").expect("URL");
+        let date = StoryDate::year_month_day(2020, 1, 1).expect("Date failed");
+        let hn = |comments| {
+            let mut hn = HackerNewsStory::new_with_defaults("story1", date, "Title", url.clone());
+            hn.data.comments = comments;
+            TypedScrape::from(hn)
+        };
+        let mut reddit =
+            RedditStory::new_subsource_with_defaults("story1", "rust", date, "Title", url.clone());
+        reddit.data.num_comments = 20;
+
+        index.insert_scrapes(&eval, [hn(142), reddit.into()])?;
+        index.insert_scrapes(&eval, [hn(50)])?;
+
+        let story = index
+            .fetch_one::<Shard>(&StoryQuery::from_search(&eval.tagger, "title"))?
+            .expect("Missing story");
+        assert_eq!(story.comments.hacker_news, Some(142));
+        assert_eq!(story.comments.reddit, Some(20));
+        assert_eq!(story.comments.lobsters, None);
+        let render = story.render(&eval, 0);
+        assert_eq!(render.comments.hacker_news, Some(142));
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case(EmptyTitlePolicy::Keep, Some("  "))]
+    #[case(EmptyTitlePolicy::Drop, None)]
+    #[case(EmptyTitlePolicy::UrlFallback, Some("my great post"))]
+    fn test_empty_title(
+        _enable_tracing: &bool,
+        #[case] policy: EmptyTitlePolicy,
+        #[case] title: Option<&str>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let eval = StoryEvaluator::new_for_test();
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        index.set_empty_title_policy(policy);
+        let url = StoryUrl::parse("https://example.com/posts/my-great-post/").expect("URL");
+        let scrape = progscrape_scrapers::feed::FeedStory::new(
+            "1".to_owned(),
+            StoryDate::year_month_day(2020, 1, 1).expect("Date failed"),
+            "  ".to_owned(),
+            url,
+            vec![],
+        );
+
+        let res = index.insert_scrapes(&eval, [scrape.into()])?;
+        let stories =
+            index.fetch::<Shard>(&StoryQuery::ByShard(Shard::from_year_month(2020, 1)), 10)?;
+        if let Some(title) = title {
+            assert_eq!(res, vec![ScrapePersistResult::NewStory]);
+            assert_eq!(stories.len(), 1);
+            assert_eq!(stories[0].title, title);
+        } else {
+            assert_eq!(res, vec![ScrapePersistResult::DroppedEmptyTitle]);
+            assert_eq!(res.summary().dropped, 1);
+            assert!(stories.is_empty());
+        }
+
+        Ok(())
+    }
+
+    /// Are scrapes for blocked hosts (and their subdomains) dropped, while others are inserted?
+    #[rstest]
+    #[case("https://bit.ly/abc123", true)]
+    #[case("https://www.contentfarm.com/article", true)]
+    #[case("https://example.com/article", false)]
+    #[case("https://notcontentfarm.com/article", false)]
+    fn test_blocklist(
+        _enable_tracing: &bool,
+        #[case] url: &str,
+        #[case] blocked: bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let eval = StoryEvaluator::new_for_test();
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        index.set_blocklist(HostBlocklistConfig {
+            hosts: vec!["bit.ly".into(), "contentfarm.com".into()],
+        });
+        let date = StoryDate::year_month_day(2020, 1, 1).expect("Date failed");
+        let url = StoryUrl::parse(url).expect("URL");
+        let res = index.insert_scrapes(&eval, [hn_story("1", date, "A story", &url)])?;
+        let stories =
+            index.fetch::<Shard>(&StoryQuery::ByShard(Shard::from_year_month(2020, 1)), 10)?;
+        if blocked {
+            assert_eq!(res, vec![ScrapePersistResult::Blocked]);
+            assert_eq!(res.summary().blocked, 1);
+            assert!(stories.is_empty());
+        } else {
+            assert_eq!(res, vec![ScrapePersistResult::NewStory]);
+            assert_eq!(res.summary().blocked, 0);
+            assert_eq!(stories.len(), 1);
+        }
+
+        Ok(())
+    }
+
+    /// Does rescoring update a story's score while leaving the rest of its document alone?
+    #[rstest]
+    fn test_rescore_stories(_enable_tracing: &bool) -> Result<(), Box<dyn std::error::Error>> {
+        let mut eval = StoryEvaluator::new_for_test();
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        index.insert_scrapes(&eval, [rust_story_hn(), rust_story_reddit()])?;
+        let query = StoryQuery::from_search(&eval.tagger, "rust");
+        let before = index
+            .fetch_one::<TypedScrape>(&query)?
+            .expect("Missing story");
+
+        let config: StoryScoreConfig = serde_json::from_value(serde_json::json!({
+            "age_breakpoint_days": [1, 30],
+            "hour_scores": [-5.0, -3.0, -0.1],
+            "service_rank": {},
+            "service_boost": {"hacker_news": 100.0},
+            "multi_source": {"power": 1.0, "factor": 1.0}
+        }))?;
+        eval.scorer = StoryScorer::new(&config);
+        let res = index.rescore_stories(&eval, [before.id.clone()])?;
+        assert_eq!(res, vec![ScrapePersistResult::MergedWithExistingStory]);
+
+        let after = index
+            .fetch_one::<TypedScrape>(&query)?
+            .expect("Missing story");
+        assert_ne!(before.score, after.score);
+        assert_eq!(before.id, after.id);
+        assert_eq!(before.title, after.title);
+        assert_eq!(before.tags, after.tags);
+        assert_eq!(
+            before.scrapes.keys().sorted().collect_vec(),
+            after.scrapes.keys().sorted().collect_vec()
+        );
+
+        // The story can still be found by URL, which relies on the copied normalized URL fields
+        let by_url = index.fetch_one::<Shard>(&StoryQuery::UrlSearch(before.url.clone()))?;
+        assert_eq!(by_url.map(|story| story.id), Some(after.id.clone()));
+
+        // The stored score inputs score the same as the scrapes themselves
+        let story = ScrapeCollection::new_from_iter(after.scrapes.into_values());
+        let expected = eval
+            .scorer
+            .score(&story.extract(&eval.extractor), StoryDate::now());
+        assert_eq!(after.score, expected);
+
+        Ok(())
+    }
+
+    /// Does each re-index of a story record a score snapshot when snapshots are enabled?
+    #[rstest]
+    fn test_score_snapshots(_enable_tracing: &bool) -> Result<(), Box<dyn std::error::Error>> {
+        let eval = StoryEvaluator::new_for_test();
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        index.set_score_snapshots(Some(ScoreSnapshotConfig::default()));
+        index.insert_scrapes(&eval, [rust_story_hn()])?;
+
+        let story = index
+            .fetch_one::<Shard>(&StoryQuery::from_search(&eval.tagger, "rust"))?
+            .expect("Missing story");
+        assert!(index.fetch_score_snapshots(&story.id)?.is_empty());
+        index.reinsert_stories(&eval, [story.id.clone()])?;
+
+        // A second source changes the score
+        index.insert_scrapes(&eval, [rust_story_reddit()])?;
+        index.reinsert_stories(&eval, [story.id.clone()])?;
+
+        let snapshots = index.fetch_score_snapshots(&story.id)?;
+        assert_eq!(snapshots.len(), 2);
+        assert_ne!(snapshots[0].score, snapshots[1].score);
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_earliest_story(_enable_tracing: &bool) -> Result<(), Box<dyn std::error::Error>> {
+        let eval = StoryEvaluator::new_for_test();
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        assert_eq!(index.earliest_story()?, StoryDate::MIN);
+
+        let url = |s: &str| StoryUrl::parse(format!("https://example.com/{s}")).expect("URL");
+        let date = |y, m, d| StoryDate::year_month_day(y, m, d).expect("Date failed");
+        index.insert_scrapes(
+            &eval,
+            [
+                hn_story("1", date(2020, 3, 5), "Middle", &url("middle")),
+                hn_story("2", date(2020, 2, 10), "Earliest", &url("earliest")),
+                hn_story("3", date(2020, 2, 20), "Same shard", &url("same-shard")),
+                hn_story("4", date(2021, 1, 1), "Latest", &url("latest")),
+            ],
+        )?;
+        assert_eq!(index.earliest_story()?, date(2020, 2, 10));
+        assert_eq!(index.most_recent_story()?, date(2021, 1, 1));
+
+        // The cache is invalidated by new stories
+        index.insert_scrapes(
+            &eval,
+            [hn_story("5", date(2019, 12, 31), "Older", &url("older"))],
+        )?;
+        assert_eq!(index.earliest_story()?, date(2019, 12, 31));
+
+        Ok(())
+    }
+
+    /// Shards before the cutoff are dropped whole, while the shard containing it is pruned story by story.
+    #[rstest]
+    #[case((2020, 2, 1), 1, vec!["Later", "Latest", "Older", "Recent"])]
+    #[case((2020, 3, 25), 3, vec!["Later", "Latest"])]
+    fn test_delete_before(
+        _enable_tracing: &bool,
+        #[case] cutoff: (i32, u32, u32),
+        #[case] deleted: usize,
+        #[case] remaining: Vec<&str>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let eval = StoryEvaluator::new_for_test();
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+
+        let url = |s: &str| StoryUrl::parse(format!("https://example.com/{s}")).expect("URL");
+        let date = |y, m, d| StoryDate::year_month_day(y, m, d).expect("Date failed");
+        index.insert_scrapes(
+            &eval,
+            [
+                hn_story("1", date(2020, 1, 5), "Oldest", &url("oldest")),
+                hn_story("2", date(2020, 2, 10), "Older", &url("older")),
+                hn_story("3", date(2020, 3, 20), "Recent", &url("recent")),
+                hn_story("4", date(2020, 3, 28), "Later", &url("later")),
+                hn_story("5", date(2020, 4, 1), "Latest", &url("latest")),
+            ],
+        )?;
+
+        let (y, m, d) = cutoff;
+        let summary = index.delete_before(date(y, m, d))?;
+        assert_eq!(
+            summary,
+            PurgeSummary {
+                stories: deleted,
+                scrapes: deleted
+            }
+        );
+
+        let stories = index.fetch::<Shard>(&StoryQuery::FrontPage, 10)?;
+        assert_eq!(
+            stories
+                .iter()
+                .map(|story| story.title.as_str())
+                .sorted()
+                .collect_vec(),
+            remaining
+        );
+        let recent = index.with_scrapes(|scrapes| {
+            scrapes.fetch_scrape(
+                Shard::from_year_month(2020, 3),
+                &ScrapeSource::HackerNews.id("3"),
+            )
+        })?;
+        assert_eq!(recent.is_some(), remaining.contains(&"Recent"));
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_archive_shards(_enable_tracing: &bool) -> Result<(), Box<dyn std::error::Error>> {
+        let eval = StoryEvaluator::new_for_test();
+        let dir = tempfile::tempdir()?;
+        let backup_dir = tempfile::tempdir()?;
+        let mut index = StoryIndex::new(PersistLocation::Path(dir.path().to_owned()))?;
+
+        let url = |s: &str| StoryUrl::parse(format!("https://example.com/{s}")).expect("URL");
+        let date = |y, m, d| StoryDate::year_month_day(y, m, d).expect("Date failed");
+        index.insert_scrapes(
+            &eval,
+            [
+                hn_story("1", date(2020, 1, 5), "Oldest", &url("oldest")),
+                hn_story("2", date(2020, 2, 10), "Older", &url("older")),
+                hn_story("3", date(2020, 3, 20), "Recent", &url("recent")),
+                hn_story("4", date(2020, 4, 1), "Latest", &url("latest")),
+            ],
+        )?;
+        assert_eq!(index.earliest_story()?, date(2020, 1, 5));
+
+        let backup = BackerUpper::new(backup_dir.path());
+        let archived = index.archive_shards_before(date(2020, 3, 15), Some(&backup))?;
+        // Lookups may have opened neighbouring shards as well, so we just check the shards we know about
+        assert!(archived.contains(&Shard::from_year_month(2020, 1)));
+        assert!(archived.contains(&Shard::from_year_month(2020, 2)));
+        assert!(archived
+            .iter()
+            .all(|shard| *shard < Shard::from_year_month(2020, 3)));
+        assert_eq!(
+            index
+                .shards()
+                .iterate(ShardOrder::OldestFirst)
+                .collect_vec(),
+            vec![
+                Shard::from_year_month(2020, 3),
+                Shard::from_year_month(2020, 4)
+            ]
+        );
+        assert!(!dir.path().join("2020-01").exists());
+        assert!(!dir.path().join("2020-02").exists());
+        assert!(backup_dir.path().join("2020-01.json").exists());
+        assert!(backup_dir.path().join("2020-02.json").exists());
+
+        // The recent shards remain queryable
+        assert_eq!(index.earliest_story()?, date(2020, 3, 20));
+        let stories = index.fetch::<Shard>(&StoryQuery::FrontPage, 10)?;
+        assert_eq!(
+            stories
+                .iter()
+                .map(|story| story.title.as_str())
+                .sorted()
+                .collect_vec(),
+            vec!["Latest", "Recent"]
+        );
+
+        // Nothing left to archive, and a reopened index only sees the remaining shards
+        assert!(index
+            .archive_shards_before(date(2020, 3, 15), None)?
+            .is_empty());
+        drop(index);
+        let index = StoryIndex::new(PersistLocation::Path(dir.path().to_owned()))?;
+        assert_eq!(index.shards().iterate(ShardOrder::OldestFirst).count(), 2);
+
+        Ok(())
+    }
+
+    /// With penalized hosts excluded, an image-host story never reaches the front page, no matter how many
+    /// sources picked it up.
+    #[rstest]
+    #[case(false)]
+    #[case(true)]
+    fn test_exclude_penalized_hosts(
+        _enable_tracing: &bool,
+        #[case] exclude: bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let eval = StoryEvaluator::new_for_test();
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        index.set_exclude_penalized_hosts(exclude);
+        let date = StoryDate::year_month_day(2020, 1, 1).expect("Date failed");
+        let image = StoryUrl::parse("https://i.imgur.com/abcdef.jpg").expect("URL");
+        let mut scrapes = vec![
+            hn_story("1", date, "A picture", &image),
+            reddit_story("1", "rust", date, "A picture", &image),
+            reddit_story("2", "programming", date, "A picture", &image),
+        ];
+        for i in 0..5 {
+            let url = StoryUrl::parse(format!("https://example.com/{i}")).expect("URL");
+            scrapes.push(hn_story(&format!("story-{i}"), date, "A story", &url));
+        }
+        index.insert_scrapes(&eval, scrapes)?;
+
+        let stories = index.fetch::<Shard>(&StoryQuery::FrontPage, 10)?;
+        let has_image = stories.iter().any(|story| story.url == image);
+        assert_eq!(has_image, !exclude);
+        assert_eq!(stories.len(), if exclude { 5 } else { 6 });
+
+        Ok(())
+    }
+
+    /// The search age factor trades stored score against recency.
+    #[rstest]
+    #[case(0.0, "Old rust story")]
+    #[case(0.001, "New rust story")]
+    fn test_search_age_factor(
+        _enable_tracing: &bool,
+        #[case] age_factor: f32,
+        #[case] first: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let eval = StoryEvaluator::new_for_test();
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        index.set_search_ranking(SearchRankingConfig { age_factor });
+        let old = StoryDate::year_month_day(2020, 1, 19).expect("Date failed");
+        let new = StoryDate::year_month_day(2020, 1, 20).expect("Date failed");
+        let old_url = StoryUrl::parse("https://example.com/old").expect("URL");
+        let new_url = StoryUrl::parse("https://example.com/new").expect("URL");
+        index.insert_scrapes(
+            &eval,
+            [
+                // The old story is more popular
+                HackerNewsStory::new(
+                    "1",
+                    old,
+                    "Old rust story",
+                    old_url,
+                    500,
+                    300,
+                    1,
+                    String::new(),
+                )
+                .into(),
+                hn_story("4", new, "New rust story", &new_url),
+            ],
+        )?;
+
+        let stories = index.fetch::<Shard>(&StoryQuery::from_search(&eval.tagger, "story"), 10)?;
+        assert_eq!(stories.len(), 2);
+        // Stored scores are unaffected by the age factor, so only the recency tweak can reorder these
+        let score = |title: &str| {
+            stories
+                .iter()
+                .find(|story| story.title == title)
+                .expect("Missing story")
+                .score
+        };
+        assert!(score("Old rust story") > score("New rust story"));
+        assert_eq!(stories[0].title, first);
+
+        Ok(())
+    }
+
+    /// Common host prefixes are trimmed from domain searches to match how hosts are normalized at insert time.
+    #[rstest]
+    #[case("example.com", true, 2)]
+    #[case("www.example.com", true, 2)]
+    #[case("m.example.com", true, 2)]
+    #[case("example.com", false, 2)]
+    #[case("www.example.com", false, 0)]
+    fn test_domain_search_prefixes(
+        _enable_tracing: &bool,
+        #[case] domain: &str,
+        #[case] trim: bool,
+        #[case] expected: usize,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let eval = StoryEvaluator::new_for_test();
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        if !trim {
+            index.set_domain_search(DomainSearchConfig {
+                ignored_host_prefixes: vec![],
+            });
+        }
+        let date = StoryDate::year_month_day(2020, 1, 1).expect("Date failed");
+        let www = StoryUrl::parse("https://www.example.com/1").expect("URL");
+        let bare = StoryUrl::parse("https://example.com/2").expect("URL");
+        let other = StoryUrl::parse("https://example.org/3").expect("URL");
+        index.insert_scrapes(
+            &eval,
+            [
+                hn_story("1", date, "A story", &www),
+                hn_story("2", date, "Another story", &bare),
+                hn_story("3", date, "Unrelated story", &other),
+            ],
+        )?;
+
+        let stories = index.fetch::<Shard>(&StoryQuery::DomainSearch(domain.to_owned()), 10)?;
+        assert_eq!(stories.len(), expected, "{stories:?}");
+        assert!(stories.iter().all(|story| story.url != other));
+
+        Ok(())
+    }
+
+    /// A trailing `*` searches tags and domains by prefix, while plain searches remain exact.
+    #[rstest]
+    #[case("rust*", true, &["Rust 1.0 released", "Rustaceans gather"])]
+    #[case("rust", true, &["Rust 1.0 released"])]
+    #[case("example.co*", false, &["Rust 1.0 released", "Rustaceans gather"])]
+    #[case("www.example.co*", false, &["Rust 1.0 released", "Rustaceans gather"])]
+    #[case("example.co", false, &[])]
+    fn test_prefix_search(
+        _enable_tracing: &bool,
+        #[case] search: &str,
+        #[case] is_tag: bool,
+        #[case] expected: &[&str],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let eval = StoryEvaluator::new_for_test();
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        let date = StoryDate::year_month_day(2020, 1, 1).expect("Date failed");
+        let url = |s: &str| StoryUrl::parse(s).expect("URL");
+        index.insert_scrapes(
+            &eval,
+            [
+                hn_story(
+                    "1",
+                    date,
+                    "Rust 1.0 released",
+                    &url("https://example.com/1"),
+                ),
+                hn_story(
+                    "2",
+                    date,
+                    "Rustaceans gather",
+                    &url("https://example.com/2"),
+                ),
+                hn_story("3", date, "Python news", &url("https://example.org/3")),
+            ],
+        )?;
+
+        let query = StoryQuery::from_search(&eval.tagger, search);
+        if is_tag {
+            assert!(matches!(query, StoryQuery::TagSearch(..)), "{query:?}");
+        } else {
+            assert!(matches!(query, StoryQuery::DomainSearch(..)), "{query:?}");
+        }
+        let titles = index
+            .fetch::<Shard>(&query, 10)?
+            .into_iter()
+            .map(|story| story.title)
+            .sorted()
+            .collect_vec();
+        assert_eq!(titles, expected);
+
+        Ok(())
+    }
+
+    /// With fuzzy search enabled, a tag or domain search with a one-edit typo falls back to an inexact match.
+    #[rstest]
+    #[case("rsut", 0, 0)]
+    #[case("rsut", 1, 1)]
+    #[case("rus", 1, 1)]
+    #[case("exmaple.com", 0, 0)]
+    #[case("exmaple.com", 1, 1)]
+    #[case("pyhton", 1, 0)]
+    fn test_fuzzy_search(
+        _enable_tracing: &bool,
+        #[case] search: &str,
+        #[case] max_edit_distance: u8,
+        #[case] expected: usize,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let eval = StoryEvaluator::new_for_test();
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        index.set_fuzzy_search(FuzzySearchConfig { max_edit_distance });
+        let date = StoryDate::year_month_day(2020, 1, 1).expect("Date failed");
+        let url = StoryUrl::parse("https://example.com/1").expect("URL");
+        index.insert_scrapes(&eval, [hn_story("1", date, "Rust 1.0 released", &url)])?;
+
+        let query = StoryQuery::from_search(&eval.tagger, search);
+        assert_eq!(index.fetch::<Shard>(&query, 10)?.len(), expected);
+
+        Ok(())
+    }
+
+    /// A shard that can't be opened is skipped by cross-shard queries and reported as degraded.
+    #[rstest]
+    fn test_degraded_shard(_enable_tracing: &bool) -> Result<(), Box<dyn std::error::Error>> {
+        let eval = StoryEvaluator::new_for_test();
+        let dir = tempfile::tempdir()?;
+        let date = |y, m, d| StoryDate::year_month_day(y, m, d).expect("Date failed");
+        let url = |s: &str| StoryUrl::parse(format!("https://example.com/{s}")).expect("URL");
+        {
+            let mut index = StoryIndex::new(PersistLocation::Path(dir.path().to_owned()))?;
+            index.insert_scrapes(
+                &eval,
+                [
+                    hn_story("1", date(2020, 1, 5), "Broken", &url("broken")),
+                    hn_story("2", date(2020, 2, 10), "Healthy", &url("healthy")),
+                ],
+            )?;
+        }
+
+        // Corrupt the older shard's index metadata
+        let broken = Shard::from_year_month(2020, 1);
+        std::fs::write(
+            dir.path()
+                .join(broken.to_string())
+                .join(StorySchema::index_dir(SCHEMA_VERSION))
+                .join("meta.json"),
+            "garbage",
+        )?;
+
+        let mut index = StoryIndex::new(PersistLocation::Path(dir.path().to_owned()))?;
+        let query = StoryQuery::from_search(&eval.tagger, "example.com");
+        assert!(index.fetch::<Shard>(&query, 10).is_err());
+        assert_eq!(index.degraded_shards().len(), 1);
+        assert_eq!(index.degraded_shards()[0].0, broken);
+
+        index.set_skip_degraded_shards(true);
+        for query in [query, StoryQuery::FrontPage] {
+            let stories = index.fetch::<Shard>(&query, 10)?;
+            assert_eq!(
+                stories
+                    .iter()
+                    .map(|story| story.title.as_str())
+                    .collect_vec(),
+                vec!["Healthy"]
+            );
+        }
+        assert_eq!(index.earliest_story()?, date(2020, 2, 10));
+        let summary = index.story_count()?;
+        assert_eq!(summary.total.story_count, 1);
+        assert_eq!(summary.degraded.len(), 1);
+        assert_eq!(summary.degraded[0].0, broken.to_string());
+
+        // Writes to the degraded shard still fail
+        assert!(index
+            .insert_scrapes(&eval, [hn_story("3", date(2020, 1, 6), "New", &url("new"))])
+            .is_err());
+
+        Ok(())
+    }
+
+    /// The URL normalization is recorded the first time it's checked, and later checks flag any change.
+    #[rstest]
+    fn test_check_url_normalization(
+        _enable_tracing: &bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempfile::tempdir()?;
+        let config = UrlNormalizationConfig::default();
+        let changed = UrlNormalizationConfig {
+            tracking_params: vec!["ref".to_owned()],
+            ..Default::default()
+        };
+        {
+            let index = StoryIndex::new(PersistLocation::Path(dir.path().to_owned()))?;
+            assert!(index.check_url_normalization(&config)?);
+        }
+        let index = StoryIndex::new(PersistLocation::Path(dir.path().to_owned()))?;
+        assert!(index.check_url_normalization(&config)?);
+        assert!(!index.check_url_normalization(&changed)?);
+        // The original normalization is still the one recorded
+        assert!(index.check_url_normalization(&config)?);
+
+        Ok(())
+    }
+
+    /// Reindexing re-normalizes URLs, so stories split by an old normalization are merged again.
+    #[rstest]
+    fn test_reindex_into(_enable_tracing: &bool) -> Result<(), Box<dyn std::error::Error>> {
+        let eval = StoryEvaluator::new_for_test();
+        let date = StoryDate::year_month_day(2020, 1, 1).expect("Date failed");
+        // URLs normalized by a configuration that didn't know about `utm_source`
+        let stale = |raw: &str| -> StoryUrl {
+            serde_json::from_value(serde_json::json!([raw, "example.com", raw])).expect("URL")
+        };
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        index.insert_scrapes(
+            &eval,
+            [
+                hn_story(
+                    "1",
+                    date,
+                    "A story",
+                    &stale("https://example.com/post?utm_source=hn"),
+                ),
+                reddit_story(
+                    "2",
+                    "rust",
+                    date,
+                    "A story",
+                    &stale("https://example.com/post?utm_source=reddit"),
+                ),
+            ],
+        )?;
+        assert_eq!(index.story_count()?.total.story_count, 2);
+
+        let mut target = StoryIndex::new(PersistLocation::Memory)?;
+        assert_eq!(index.reindex_into(&eval, &mut target)?, 2);
+        assert_eq!(target.story_count()?.total.story_count, 1);
+        let story = target
+            .fetch_one::<Shard>(&StoryQuery::UrlSearch(
+                StoryUrl::parse("https://example.com/post").expect("URL"),
+            ))?
+            .expect("Missing story");
+        assert_eq!(story.scrapes.len(), 2);
+
+        Ok(())
+    }
+
+    /// Shards indexed with an older schema refuse to open (even when skipping degraded shards) until they're
+    /// rebuilt from the scrape store.
+    #[rstest]
+    fn test_migrate_shards(_enable_tracing: &bool) -> Result<(), Box<dyn std::error::Error>> {
+        let eval = StoryEvaluator::new_for_test();
+        let dir = tempfile::tempdir()?;
+        let date = |y, m, d| StoryDate::year_month_day(y, m, d).expect("Date failed");
+        let url = |s: &str| StoryUrl::parse(format!("https://example.com/{s}")).expect("URL");
+        let shards = [
+            Shard::from_year_month(2020, 1),
+            Shard::from_year_month(2020, 2),
+        ];
+        {
+            let mut index = StoryIndex::new(PersistLocation::Path(dir.path().to_owned()))?;
+            index.insert_scrapes(
+                &eval,
+                [
+                    hn_story("1", date(2020, 1, 5), "January", &url("january")),
+                    hn_story("2", date(2020, 2, 10), "February", &url("february")),
+                    reddit_story("3", "rust", date(2020, 2, 11), "February", &url("february")),
+                ],
+            )?;
+        }
+
+        // Pretend both shards were written by the first, unversioned schema
+        for shard in shards {
+            let shard_path = dir.path().join(shard.to_string());
+            std::fs::rename(
+                shard_path.join(StorySchema::index_dir(SCHEMA_VERSION)),
+                shard_path.join(StorySchema::index_dir(1)),
+            )?;
+        }
+
+        let mut index = StoryIndex::new(PersistLocation::Path(dir.path().to_owned()))?;
+        index.set_skip_degraded_shards(true);
+        assert_eq!(index.shards_needing_migration(), shards);
+        assert!(matches!(
+            index.fetch::<Shard>(&StoryQuery::FrontPage, 10),
+            Err(PersistError::ShardNeedsMigration(_))
+        ));
+
+        assert_eq!(index.migrate_shards(&eval)?, shards);
+        assert!(index.shards_needing_migration().is_empty());
+        let titles = |index: &StoryIndex| -> Result<Vec<(String, usize)>, PersistError> {
+            Ok(index
+                .fetch::<Shard>(&StoryQuery::FrontPage, 10)?
+                .into_iter()
+                .map(|story| (story.title, story.scrapes.len()))
+                .sorted()
+                .collect())
+        };
+        let expected = vec![("February".to_owned(), 2), ("January".to_owned(), 1)];
+        assert_eq!(titles(&index)?, expected);
+        drop(index);
+
+        // The old indexes are kept aside, and the migration isn't repeated
+        assert!(dir
+            .path()
+            .join(shards[0].to_string())
+            .join("index.old")
+            .is_dir());
+        let mut index = StoryIndex::new(PersistLocation::Path(dir.path().to_owned()))?;
+        assert!(index.shards_needing_migration().is_empty());
+        assert!(index.migrate_shards(&eval)?.is_empty());
+        assert_eq!(titles(&index)?, expected);
+
+        Ok(())
+    }
+
+    /// With self-posts excluded, self-posts from any source stay off the front page but can still be found.
+    #[rstest]
+    #[case(false)]
+    #[case(true)]
+    fn test_exclude_self_posts(
+        _enable_tracing: &bool,
+        #[case] exclude: bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let eval = StoryEvaluator::new_for_test();
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        index.set_exclude_self_posts(exclude);
+        let date = StoryDate::year_month_day(2020, 1, 1).expect("Date failed");
+        let hn_self = StoryUrl::parse("https://news.ycombinator.com/item?id=1000").expect("URL");
+        let reddit_self =
+            StoryUrl::parse("https://www.reddit.com/r/rust/comments/abc123/my_project/")
+                .expect("URL");
+        let link = StoryUrl::parse("https://example.com/article").expect("URL");
+        index.insert_scrapes(
+            &eval,
+            [
+                hn_story("1000", date, "Ask HN: Anything", &hn_self),
+                reddit_story("abc123", "rust", date, "My project", &reddit_self),
+                hn_story("1001", date, "An article", &link),
+            ],
+        )?;
+
+        let stories = index.fetch::<Shard>(&StoryQuery::FrontPage, 10)?;
+        let urls = stories.iter().map(|story| story.url.clone()).collect_vec();
+        assert!(urls.contains(&link));
+        assert_eq!(urls.contains(&hn_self), !exclude);
+        assert_eq!(urls.contains(&reddit_self), !exclude);
+
+        for url in [&hn_self, &reddit_self] {
+            let stories = index.fetch::<Shard>(&StoryQuery::UrlSearch(url.clone()), 10)?;
+            assert_eq!(stories.len(), 1);
+            assert!(stories[0].is_likely_self_post());
+        }
+
+        Ok(())
+    }
+
+    /// With a front page floor, only stories that still clear it once aged make the front page, even if that leaves
+    /// the front page short.
+    #[rstest]
+    fn test_front_page_floor(_enable_tracing: &bool) -> Result<(), Box<dyn std::error::Error>> {
+        let mut eval = StoryEvaluator::new_for_test();
+        let config: StoryScoreConfig = serde_json::from_value(serde_json::json!({
+            "age_breakpoint_days": [1, 30],
+            "hour_scores": [-5.0, -3.0, -0.1],
+            "service_rank": {},
+            "service_boost": {"hacker_news": 100.0},
+            "multi_source": {"power": 1.0, "factor": 1.0},
+            "front_page_min_score": 50.0
+        }))?;
+        config.validate()?;
+        eval.scorer = StoryScorer::new(&config);
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        let date = StoryDate::year_month_day(2020, 1, 30).expect("Date failed");
+        let old = StoryDate::year_month_day(2020, 1, 10).expect("Date failed");
+        let url = |s: &str| StoryUrl::parse(format!("https://example.com/{s}")).expect("URL");
+        index.insert_scrapes(
+            &eval,
+            [
+                hn_story("1", date, "Popular story", &url("hn1")),
+                hn_story("2", date, "Another popular story", &url("hn2")),
+                reddit_story("a", "rust", date, "Quiet story", &url("reddit1")),
+                reddit_story("b", "rust", date, "Another quiet story", &url("reddit2")),
+                hn_story("3", old, "Popular a while ago", &url("old")),
+            ],
+        )?;
+
+        // Without a floor, the front page has everything
+        assert_eq!(index.fetch::<Shard>(&StoryQuery::FrontPage, 10)?.len(), 5);
+
+        let urls = index
+            .fetch_front_page_with_floor(&eval.scorer, 10)?
+            .into_iter()
+            .map(|story| story.url)
+            .sorted()
+            .collect_vec();
+        assert_eq!(urls, vec![url("hn1"), url("hn2")]);
+
+        Ok(())
+    }
+
+    /// A story that is scraped again is marked as updated, while one that hasn't been seen for a week is not.
+    #[rstest]
+    fn test_updated(_enable_tracing: &bool) -> Result<(), Box<dyn std::error::Error>> {
+        let eval = StoryEvaluator::new_for_test();
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        let now = StoryDate::now();
+        let week_ago = now.checked_sub_days(7).expect("Date failed");
+        let active_url = StoryUrl::parse("https://example.com/active").expect("URL");
+        let stale_url = StoryUrl::parse("https://example.com/stale").expect("URL");
+        index.insert_scrapes(
+            &eval,
+            [
+                hn_story("1", week_ago, "Active story", &active_url),
+                hn_story("2", week_ago, "Stale story", &stale_url),
+            ],
+        )?;
+
+        // Re-scrape the active story today
+        index.insert_scrapes(
+            &eval,
+            [hn_story("1", week_ago, "Active story", &active_url)],
+        )?;
+        // Re-indexing doesn't count as activity, but shouldn't lose it either
+        let stories =
+            index.fetch::<Shard>(&StoryQuery::ByShard(Shard::from_date_time(week_ago)), 10)?;
+        index.reinsert_stories(&eval, stories.iter().map(|story| story.id.clone()))?;
+
+        let stories =
+            index.fetch::<Shard>(&StoryQuery::ByShard(Shard::from_date_time(week_ago)), 10)?;
+        assert_eq!(stories.len(), 2);
+        for story in stories {
+            let render = story.render(&eval, 0);
+            if story.url == active_url {
+                assert!(story.last_activity > week_ago);
+                assert!(render.updated);
+            } else {
+                assert_eq!(story.last_activity, story.date);
+                assert!(!render.updated);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_insert_batch(_enable_tracing: &bool) -> Result<(), Box<dyn std::error::Error>> {
+        let mut batch = vec![];
+        let date = StoryDate::year_month_day(2020, 1, 1).expect("Date failed");
+
+        for i in 0..30 {
+            let url = StoryUrl::parse(format!("http://domain-{}.com/", i)).expect("URL");
+            batch.push(hn_story(
+                &format!("story-{}", i),
+                date,
+                &format!("Title {}", i),
+                &url,
+            ));
+        }
+
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        let eval = StoryEvaluator::new_for_test();
+
+        index.insert_scrapes(&eval, batch.clone())?;
+
+        // Cause a delete
+        let url = StoryUrl::parse("http://domain-3.com/").expect("URL");
+
+        index.insert_scrapes(
+            &eval,
+            [reddit_story("story-3", "subreddit", date, "Title 3", &url)],
+        )?;
+
+        index.insert_scrapes(&eval, batch.clone())?;
+
+        let front_page = index.fetch_count(&StoryQuery::FrontPage, 100)?;
+        assert_eq!(30, front_page);
+
+        Ok(())
+    }
+
+    /// Two stories with the same URL written in the same writer batch are coalesced into one.
+    #[rstest]
+    fn test_insert_same_url_in_batch(
+        _enable_tracing: &bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        use ScrapeSource::*;
+
+        let index = StoryIndex::new(PersistLocation::Memory)?;
+        let eval = StoryEvaluator::new_for_test();
+        let (hn, reddit) = (rust_story_hn(), rust_story_reddit());
+        index
+            .scrape_db
+            .insert_scrape_batch([hn.clone(), reddit.clone()].iter())?;
+        let res = index.with_writers(|provider| {
+            let shard = Shard::from_date_time(hn.date);
+            Ok(vec![
+                index.insert_story_batched(
+                    &eval,
+                    provider,
+                    ScrapeCollection::new_from_one(hn),
+                    shard,
+                    None,
+                    false,
+                )?,
+                index.insert_story_batched(
+                    &eval,
+                    provider,
+                    ScrapeCollection::new_from_one(reddit),
+                    shard,
+                    None,
+                    false,
+                )?,
+            ])
+        })?;
+        assert_eq!(
+            res,
+            vec![
+                ScrapePersistResult::NewStory,
+                ScrapePersistResult::MergedWithExistingStory
+            ]
+        );
+
+        let counts = index.story_count()?;
+        assert_eq!(counts.total.story_count, 1);
+
+        let story = index
+            .fetch_one::<Shard>(&StoryQuery::from_search(&eval.tagger, "rust"))?
+            .expect("Missing story");
+        assert!(itertools::equal(
+            [
+                &HackerNews.id("story1"),
+                &Reddit.subsource_id("rust", "story1")
+            ],
+            story.scrapes.keys().sorted()
+        ));
+
+        Ok(())
+    }
+
+    /// A story merged in a batch is keyed by the merged story's date, so a later story from the same batch that
+    /// matches the merged date is coalesced with it rather than written as a duplicate.
+    #[rstest]
+    fn test_insert_batch_merge_moves_date(
+        _enable_tracing: &bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        let eval = StoryEvaluator::new_for_test();
+        let url = StoryUrl::parse("http://example.com").expect("URL");
+        let day = |day| StoryDate::year_month_day(2020, 1, day).expect("Date failed");
+        index.insert_scrapes(&eval, [hn_story("story1", day(10), "I love Rust", &url)])?;
+
+        // The existing story's earlier date wins, moving the merged story back a day
+        let reddit = reddit_story("story1", "rust", day(11), "I love Rust", &url);
+        let lobsters = lobsters_story("story1", day(10), "I love Rust", &url, vec![]);
+        index
+            .scrape_db
+            .insert_scrape_batch([reddit.clone(), lobsters.clone()].iter())?;
+        let (shard, doc_address) = index.find_story(&url, day(11))?.expect("Missing story");
+        let res = index.with_writers(|provider| {
+            Ok(vec![
+                index.insert_story_batched(
+                    &eval,
+                    provider,
+                    ScrapeCollection::new_from_one(reddit),
+                    shard,
+                    Some(doc_address),
+                    false,
+                )?,
+                index.insert_story_batched(
+                    &eval,
+                    provider,
+                    ScrapeCollection::new_from_one(lobsters),
+                    shard,
+                    None,
+                    false,
+                )?,
+            ])
+        })?;
+        assert_eq!(
+            res,
+            vec![
+                ScrapePersistResult::MergedWithExistingStory,
+                ScrapePersistResult::MergedWithExistingStory
+            ]
+        );
+
+        let counts = index.story_count()?;
+        assert_eq!(counts.total.story_count, 1);
+        let story = index
+            .fetch_one::<Shard>(&StoryQuery::ByShard(shard))?
+            .expect("Missing story");
+        assert_eq!(story.date, day(10));
+        assert_eq!(story.scrapes.len(), 3);
+
+        Ok(())
+    }
+
+    /// Searches stop after scanning the configured number of newest shards.
+    #[rstest]
+    fn test_search_scan_limit(_enable_tracing: &bool) -> Result<(), Box<dyn std::error::Error>> {
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        let eval = StoryEvaluator::new_for_test();
+        let mut batch = vec![];
+        for month in 1..=4 {
+            let url = StoryUrl::parse(format!("http://example.com/{month}")).expect("URL");
+            let date = StoryDate::year_month_day(2020, month, 1).expect("Date failed");
+            batch.push(hn_story(
+                &format!("story{month}"),
+                date,
+                "I love Rust",
+                &url,
+            ));
+        }
+        index.insert_scrapes(&eval, batch)?;
+
+        let query = StoryQuery::from_search(&eval.tagger, "rust");
+        assert_eq!(index.fetch_count(&query, 10)?, 4);
+
+        index.set_scan_limits(ShardScanLimits {
+            search: Some(2),
+            ..Default::default()
+        });
+        let stories = index.fetch::<Shard>(&query, 10)?;
+        assert_eq!(
+            stories
+                .iter()
+                .map(|s| s.date.month())
+                .sorted()
+                .collect_vec(),
+            vec![3, 4]
+        );
+
+        Ok(())
+    }
+
+    /// Malformed query syntax falls back to a literal search rather than failing.
+    #[rstest]
+    #[case("\"rust")]
+    #[case("love \"rust")]
+    #[case("(love rust")]
+    #[case("love rust)")]
+    #[case("love: rust")]
+    #[case("title:[love")]
+    #[case("love^ rust")]
+    fn test_malformed_text_search(
+        _enable_tracing: &bool,
+        #[case] search: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        let eval = StoryEvaluator::new_for_test();
+        index.insert_scrapes(&eval, [rust_story_hn()])?;
+
+        let query = StoryQuery::TextSearch(search.to_owned());
+        let stories = index.fetch::<Shard>(&query, 10)?;
+        assert_eq!(stories.len(), 1, "{search}");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_findable_by_extracted_tag() -> Result<(), Box<dyn std::error::Error>> {
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        let eval = StoryEvaluator::new_for_test();
+        let scrape = rust_story_lobsters();
+        let id = StoryIdentifier::new(scrape.date, scrape.url.normalization());
+        index.insert_scrapes(&eval, [scrape.clone()])?;
+
+        let counts = index.story_count()?;
+        assert_eq!(counts.total.story_count, 1);
+
+        // Title is "Type inference in rust". Site tags are "plt"/"c++" from scrape.
+        let story = index
+            .fetch_one::<Shard>(&StoryQuery::ById(id.clone()))?
+            .expect("Expected one story");
+        assert_eq!(
+            story.tags.into_iter().sorted().collect_vec(),
+            vec!["cplusplus", "plt", "rust"]
+        );
+
+        for term in ["plt", "c++", "cplusplus", "type", "inference"] {
+            let search = index.fetch_count(&StoryQuery::from_search(&eval.tagger, term), 10)?;
+            let doc = index.fetch_detail_one(&StoryQuery::ById(id.clone()))?;
+            assert_eq!(
+                1, search,
+                "Expected one search result when querying '{}' for title={} url={} doc={:?}",
+                term, scrape.raw_title, scrape.url, doc
+            );
+        }
+        Ok(())
+    }
+
+    /// Merging keeps the identity of the kept story, even if the merged story is older and in another shard.
+    #[rstest]
+    fn test_merge_stories(_enable_tracing: &bool) -> Result<(), Box<dyn std::error::Error>> {
+        let eval = StoryEvaluator::new_for_test();
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        let keep_date = StoryDate::year_month_day(2020, 2, 1).expect("Date failed");
+        let merge_date = StoryDate::year_month_day(2020, 1, 15).expect("Date failed");
+        let keep_url = StoryUrl::parse("https://example.com/story").expect("URL");
+        let merge_url = StoryUrl::parse("https://example.com/story-mirror").expect("URL");
+        index.insert_scrapes(
+            &eval,
+            [
+                hn_story("1", keep_date, "A story", &keep_url),
+                reddit_story("2", "rust", merge_date, "A story", &merge_url),
+            ],
+        )?;
+
+        let keep = StoryIdentifier::new(keep_date, keep_url.normalization());
+        let merge = StoryIdentifier::new(merge_date, merge_url.normalization());
+        assert_eq!(
+            index.merge_stories(&eval, &keep, &merge)?,
+            ScrapePersistResult::MergedWithExistingStory
+        );
+
+        let (story, _) = index
+            .fetch_one_with_doc(&StoryQuery::ById(keep.clone()))?
+            .expect("Expected kept story");
+        assert_eq!(story.id, keep);
+        assert_eq!(story.url, keep_url);
+        assert_eq!(story.scrapes.len(), 2);
+        assert!(index
+            .fetch_one_with_doc(&StoryQuery::ById(merge.clone()))?
+            .is_none());
+        assert_eq!(index.fetch::<Shard>(&StoryQuery::FrontPage, 10)?.len(), 1);
+
+        // The merged story is gone, so there's nothing left to merge
+        assert_eq!(
+            index.merge_stories(&eval, &keep, &merge)?,
+            ScrapePersistResult::NotFound
+        );
+
+        // Scraping the merged story again updates the story it was merged into, rather than bringing it back
+        assert_eq!(
+            index.insert_scrapes(
+                &eval,
+                [reddit_story("2", "rust", merge_date, "A story", &merge_url)]
+            )?,
+            vec![ScrapePersistResult::MergedWithExistingStory]
+        );
+        assert_eq!(index.fetch::<Shard>(&StoryQuery::FrontPage, 10)?.len(), 1);
+        let (story, _) = index
+            .fetch_one_with_doc(&StoryQuery::ById(keep.clone()))?
+            .expect("Expected kept story");
+        assert_eq!(story.url, keep_url);
+        assert_eq!(story.scrapes.len(), 2);
+
+        Ok(())
+    }
+
+    #[rstest]
+    fn test_fetch_one_with_doc(_enable_tracing: &bool) -> Result<(), Box<dyn std::error::Error>> {
+        let eval = StoryEvaluator::new_for_test();
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        let date = StoryDate::year_month_day(2020, 1, 1).expect("Date failed");
+        let url = StoryUrl::parse("https://example.com/story").expect("URL");
+        index.insert_scrapes(
+            &eval,
+            [
+                hn_story("1", date, "A story", &url),
+                reddit_story("2", "rust", date, "A story", &url),
+                hn_story(
+                    "3",
+                    date,
+                    "Another story",
+                    &StoryUrl::parse("https://example.com/other").expect("URL"),
+                ),
+            ],
+        )?;
+
+        let id = StoryIdentifier::new(date, url.normalization());
+        let query = StoryQuery::ById(id.clone());
+        let (story, doc) = index
+            .fetch_one_with_doc(&query)?
+            .expect("Expected one story");
+        assert_eq!(story.url, url);
+        assert_eq!(story.id, id);
+        assert_eq!(story.scrapes.len(), 2);
+
+        // The doc matches both the story and a separate detail fetch
+        assert_eq!(Some(&doc), index.fetch_detail_one(&query)?.as_ref());
+        assert_eq!(doc["id"], vec![serde_json::to_string(&id.to_base64())?]);
+        assert_eq!(doc["url"], vec![serde_json::to_string(url.raw())?]);
+        assert_eq!(doc["scrapes"].len(), story.scrapes.len());
+
+        let missing = StoryIdentifier::new(
+            date,
+            StoryUrl::parse("https://example.com/missing")
+                .expect("URL")
+                .normalization(),
+        );
+        assert!(index
+            .fetch_one_with_doc(&StoryQuery::ById(missing))?
+            .is_none());
+
+        Ok(())
+    }
+
+    /// Ensure that none of these cases crashes the query engine
+    #[test]
+    fn test_torture() -> Result<(), Box<dyn std::error::Error>> {
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        let eval = StoryEvaluator::new_for_test();
+        let url = StoryUrl::parse("http://example.com").expect("URL");
         let date = StoryDate::year_month_day(2020, 1, 1).expect("Date failed");
         index.insert_scrapes(&eval, [hn_story("story1", date, "title", &url)])?;
         for a in ["http://", "http:", " ", ""] {
@@ -1304,8 +5080,7 @@ mod test {
     // Special case: we allow for domain searches like this
     #[case("http://localhost", "About that special host", &["http://localhost", "localhost."])]
     #[case("http://www3.xyz.imperial.co.uk", "Why England is England", &["england", "www3.xyz.imperial.co.uk", "xyz.imperial.co.uk",  "co.uk"])]
-    // TODO: This case doesn't work yet
-    // #[case("http://youtube.com/?v=123", "A tutorial", &["video", "youtube", "tutorial"])]
+    #[case("http://youtube.com/?v=123", "A tutorial", &["video", "youtube", "tutorial"])]
     fn test_findable(
         #[case] url: &str,
         #[case] title: &str,
@@ -1339,6 +5114,68 @@ mod test {
         Ok(())
     }
 
+    #[rstest]
+    fn test_warm(_enable_tracing: &bool) -> Result<(), Box<dyn std::error::Error>> {
+        let path = tempdir()?.path().to_owned();
+        std::fs::create_dir_all(&path)?;
+        let eval = StoryEvaluator::new_for_test();
+        let mut index = StoryIndex::new(PersistLocation::Path(path.clone()))?;
+        for month in 1..=3 {
+            let url = StoryUrl::parse(format!("http://example.com/{month}")).expect("URL");
+            let date = StoryDate::year_month_day(2020, month, 1).expect("Date failed");
+            index.insert_scrapes(
+                &eval,
+                [hn_story(&format!("story{month}"), date, "Title", &url)],
+            )?;
+        }
+        drop(index);
+
+        // A freshly-opened index has no shards open until we warm it
+        let index = StoryIndex::new(PersistLocation::Path(path.clone()))?;
+        assert!(index.index_cache.read().cache.is_empty());
+        let newest = [
+            Shard::from_year_month(2020, 3),
+            Shard::from_year_month(2020, 2),
+        ];
+        assert_eq!(index.warm(2)?, newest);
+        assert_eq!(
+            index.index_cache.read().cache.keys().sorted().collect_vec(),
+            newest.iter().rev().collect_vec()
+        );
+
+        std::fs::remove_dir_all(&path)?;
+        Ok(())
+    }
+
+    /// Distinct YouTube videos are distinguished by their `v=` parameter, so they must not merge.
+    #[rstest]
+    fn test_youtube_distinct(_enable_tracing: &bool) -> Result<(), Box<dyn std::error::Error>> {
+        let mut index = StoryIndex::new(PersistLocation::Memory)?;
+        let eval = StoryEvaluator::new_for_test();
+        let date = StoryDate::year_month_day(2020, 1, 1).expect("Date failed");
+        let url1 = StoryUrl::parse("https://www.youtube.com/watch?v=123").expect("URL");
+        let url2 = StoryUrl::parse("https://www.youtube.com/watch?v=456").expect("URL");
+        let url3 = StoryUrl::parse("https://www.youtube.com/watch?v=123&t=30s").expect("URL");
+        index.insert_scrapes(
+            &eval,
+            [
+                hn_story("story1", date, "A Rust tutorial", &url1),
+                hn_story("story2", date, "A Rust talk", &url2),
+                reddit_story("story3", "rust", date, "A Rust tutorial", &url3),
+            ],
+        )?;
+
+        assert_eq!(index.story_count()?.total.story_count, 2);
+        for url in [&url1, &url2, &url3] {
+            let query = StoryQuery::UrlSearch(url.clone());
+            assert_eq!(index.fetch_count(&query, 10)?, 1, "Expected to find {url}");
+        }
+        let video = StoryQuery::from_search(&eval.tagger, "video");
+        assert_eq!(index.fetch_count(&video, 10)?, 2);
+
+        Ok(())
+    }
+
     #[rstest]
     fn test_index_lots(_enable_tracing: &bool) -> Result<(), Box<dyn std::error::Error>> {
         let path = tempdir()?.path().to_owned();
@@ -1,13 +1,15 @@
 use itertools::Itertools;
 
+use tantivy::collector::TopDocs;
 use tantivy::directory::{MmapDirectory, RamDirectory};
+use tantivy::query::{PhraseQuery, Query, TermQuery};
 use tantivy::tokenizer::{PreTokenizedString, SimpleTokenizer, Token, Tokenizer};
 use tantivy::{doc, Index, IndexReader};
 use tantivy::{
     schema::*, Directory, DocAddress, IndexSettings, IndexSortByField, IndexWriter, Searcher,
 };
 
-use progscrape_scrapers::{ScrapeId, StoryDate};
+use progscrape_scrapers::{ScrapeId, ScrapeSource, StoryDate, TypedScrapeMap};
 
 use std::collections::HashSet;
 use std::hash::Hash;
@@ -16,10 +18,10 @@ use std::sync::RwLock;
 use std::time::Instant;
 
 use crate::persist::{ScrapePersistResult, Shard};
-use crate::story::{StoryScrapeId, TagSet};
+use crate::story::{ScrapeScoreInput, StoryScoreInput, StoryScrapeId, TagSet, TagSource};
 use crate::{PersistError, PersistLocation};
 
-use super::schema::StorySchema;
+use super::schema::{StorySchema, SCHEMA_VERSION};
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
 pub struct StoryLookupId {
@@ -44,7 +46,28 @@ pub struct StoryInsert {
     pub date: i64,
     pub score: f64,
     pub tags: TagSet,
+    pub tag_sources: Vec<(String, TagSource)>,
     pub scrape_ids: Vec<String>,
+    /// The last time we saw activity for this story (seconds).
+    pub last_activity: i64,
+    /// When we first stored any of this story's scrapes (seconds), if known.
+    pub ingested_at: Option<i64>,
+    /// The highest comment count we've seen for each source.
+    pub comments: TypedScrapeMap<Option<u32>>,
+    /// The lowercased usernames of everyone that submitted this story.
+    pub authors: Vec<String>,
+    /// What each scrape contributes to the story's score, so that it can be rescored without its scrapes.
+    pub score_inputs: Vec<ScrapeScoreInput>,
+}
+
+impl StoryInsert {
+    /// Keep the highest comment count for each source, as older scrapes may have reported more comments.
+    pub fn merge_comments(&mut self, comments: &TypedScrapeMap<Option<u32>>) {
+        for (source, count) in comments.iter() {
+            let current = *self.comments.get(source);
+            self.comments.set(source, current.max(*count));
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -54,7 +77,11 @@ pub struct StoryFetch {
     pub date: i64,
     pub score: f64,
     pub tags: Vec<String>,
+    pub tag_sources: Vec<(String, TagSource)>,
     pub scrape_ids: Vec<StoryScrapeId>,
+    pub last_activity: i64,
+    pub ingested_at: Option<i64>,
+    pub comments: TypedScrapeMap<Option<u32>>,
 }
 
 /// For performance, we shard stories by time period to allow for more efficient lookup of normalized URLs.
@@ -84,7 +111,9 @@ impl StoryIndexShard {
         let (directory, exists): (Box<dyn Directory>, bool) = match location {
             PersistLocation::Memory => (Box::new(RamDirectory::create()), false),
             PersistLocation::Path(path) => {
-                let path = path.join(format!("{}/index", shard.to_string()));
+                let path = path
+                    .join(shard.to_string())
+                    .join(StorySchema::index_dir(SCHEMA_VERSION));
                 tracing::info!("Opening index at {}", path.to_string_lossy());
                 std::fs::create_dir_all(&path)?;
                 let dir = MmapDirectory::open(path)?;
@@ -184,6 +213,20 @@ impl StoryIndexShard {
         Ok(StoryDate::from_seconds(recent).unwrap_or_default())
     }
 
+    /// The oldest story date in this shard, or `None` if the shard is empty.
+    pub fn earliest_story(&self) -> Result<Option<StoryDate>, PersistError> {
+        let searcher = self.index.reader()?.searcher();
+        let mut earliest = None;
+        for segment_reader in searcher.segment_readers() {
+            if segment_reader.num_docs() == 0 {
+                continue;
+            }
+            let date = segment_reader.fast_fields().i64(self.schema.date_field)?;
+            earliest = Some(earliest.unwrap_or(i64::MAX).min(date.min_value()));
+        }
+        Ok(earliest.and_then(StoryDate::from_seconds))
+    }
+
     pub fn total_docs(&self) -> Result<usize, PersistError> {
         let meta = self.index.load_metas()?;
         Ok(meta.segments.iter().fold(0, |a, b| a + b.num_docs()) as usize)
@@ -200,6 +243,31 @@ impl StoryIndexShard {
         Ok(ScrapePersistResult::MergedWithExistingStory)
     }
 
+    /// Re-insert a stored story document with a new score, leaving its other stored fields as they are.
+    pub fn rescore_story_document(
+        &self,
+        writer: &mut IndexWriter,
+        doc: &Document,
+        score: f64,
+    ) -> Result<(), PersistError> {
+        let mut new_doc = Document::new();
+        for value in doc.field_values() {
+            if value.field() != self.schema.score_field {
+                new_doc.add_field_value(value.field(), value.value().clone());
+            }
+        }
+        new_doc.add_f64(self.schema.score_field, score);
+        let id = self.extract_id_from_doc(doc);
+        writer.delete_term(Term::from_field_text(self.schema.id_field, &id));
+        writer.add_document(new_doc)?;
+        Ok(())
+    }
+
+    /// Delete a story document by id.
+    pub fn delete_story_document(&self, writer: &mut IndexWriter, id: &str) {
+        writer.delete_term(Term::from_field_text(self.schema.id_field, id));
+    }
+
     /// Insert a brand-new story document.
     pub fn insert_story_document(
         &self,
@@ -214,13 +282,37 @@ impl StoryIndexShard {
             self.schema.title_field => doc.title,
             self.schema.date_field => doc.date,
             self.schema.score_field => doc.score,
+            self.schema.last_activity_field => doc.last_activity,
         };
+        if let Some(ingested_at) = doc.ingested_at {
+            new_doc.add_i64(self.schema.ingested_at_field, ingested_at);
+        }
         for id in doc.scrape_ids {
             new_doc.add_text(self.schema.scrape_field, id);
         }
         for tag in doc.tags {
             new_doc.add_text(self.schema.tags_field, tag);
         }
+        for (tag, source) in doc.tag_sources {
+            new_doc.add_text(self.schema.tag_sources_field, format!("{tag}:{source}"));
+        }
+        for author in doc.authors {
+            new_doc.add_text(self.schema.author_field, author);
+        }
+        for input in doc.score_inputs {
+            new_doc.add_text(
+                self.schema.score_inputs_field,
+                serde_json::to_string(&input)?,
+            );
+        }
+        for (source, count) in doc.comments.iter() {
+            if let Some(count) = count {
+                new_doc.add_text(
+                    self.schema.comments_field,
+                    format!("{}:{count}", source.into_str()),
+                );
+            }
+        }
 
         let tokens = tokenize_domain(&doc.host);
         new_doc.add_pre_tokenized_text(
@@ -285,22 +377,132 @@ impl StoryIndexShard {
             .collect_vec()
     }
 
+    /// The story id stored in a document.
+    pub fn extract_id_from_doc(&self, doc: &Document) -> String {
+        self.text_value(doc, self.schema.id_field)
+    }
+
+    /// Give `insert` the identity (ie: id, URL and date) of the story stored in a document.
+    pub fn keep_identity_from_doc(&self, doc: &Document, insert: &mut StoryInsert) {
+        insert.id = self.extract_id_from_doc(doc);
+        insert.host = self.text_value(doc, self.schema.host_field);
+        insert.url = self.text_value(doc, self.schema.url_field);
+        insert.url_norm = self.text_value(doc, self.schema.url_norm_field);
+        insert.url_norm_hash = self.i64_value(doc, self.schema.url_norm_hash_field);
+        insert.date = self.i64_value(doc, self.schema.date_field);
+    }
+
+    /// The last activity for a story, falling back to the story's date for documents indexed before this was
+    /// recorded.
+    pub fn extract_last_activity_from_doc(&self, doc: &Document) -> i64 {
+        if doc.get_first(self.schema.last_activity_field).is_some() {
+            self.i64_value(doc, self.schema.last_activity_field)
+        } else {
+            self.i64_value(doc, self.schema.date_field)
+        }
+    }
+
+    /// The per-source comment counts for a story, stored as `source:count`.
+    pub fn extract_comments_from_doc(&self, doc: &Document) -> TypedScrapeMap<Option<u32>> {
+        let mut comments = TypedScrapeMap::new();
+        for s in self.text_values(doc, self.schema.comments_field) {
+            if let Some((source, count)) = s.split_once(':') {
+                if let (Some(source), Ok(count)) =
+                    (ScrapeSource::try_from_str(source), count.parse())
+                {
+                    comments.set(source, Some(count));
+                }
+            }
+        }
+        comments
+    }
+
+    /// Everything needed to rescore a story, from its stored title, host, normalized URL hash and score inputs.
+    pub fn extract_score_input_from_doc(
+        &self,
+        doc: &Document,
+    ) -> Result<StoryScoreInput, PersistError> {
+        let scrapes = self
+            .text_values(doc, self.schema.score_inputs_field)
+            .iter()
+            .map(|input| serde_json::from_str(input))
+            .collect::<Result<_, _>>()?;
+        Ok(StoryScoreInput {
+            title_chars: self
+                .text_value(doc, self.schema.title_field)
+                .chars()
+                .count(),
+            host: self.text_value(doc, self.schema.host_field),
+            url_norm_hash: self.i64_value(doc, self.schema.url_norm_hash_field),
+            scrapes,
+        })
+    }
+
+    /// The id and scrape ids of every story in this shard dated strictly before `date` (seconds).
+    pub fn stories_before(
+        &self,
+        date: i64,
+    ) -> Result<Vec<(String, Vec<StoryScrapeId>)>, PersistError> {
+        self.with_searcher(|searcher, _| {
+            let mut result = vec![];
+            for (segment_ord, segment_reader) in searcher.segment_readers().iter().enumerate() {
+                let dates = segment_reader.fast_fields().i64(self.schema.date_field)?;
+                if dates.min_value() >= date {
+                    continue;
+                }
+                for i in segment_reader.doc_ids_alive() {
+                    if dates.get_val(i) < date {
+                        let doc = searcher.doc(DocAddress::new(segment_ord as u32, i))?;
+                        result.push((
+                            self.text_value(&doc, self.schema.id_field),
+                            self.extract_scrape_ids_from_doc(&doc),
+                        ));
+                    }
+                }
+            }
+            Ok(result)
+        })
+    }
+
     pub fn lookup_story(&self, doc_address: DocAddress) -> Result<StoryFetch, PersistError> {
         let doc = self.doc(doc_address)?;
-        let url = self.text_value(&doc, self.schema.url_field);
-        let title = self.text_value(&doc, self.schema.title_field);
-        let date = self.i64_value(&doc, self.schema.date_field);
-        let score = self.f64_value(&doc, self.schema.score_field);
-        let scrape_ids = self.extract_scrape_ids_from_doc(&doc);
-        let tags = self.text_values(&doc, self.schema.tags_field);
-        Ok(StoryFetch {
+        Ok(self.story_from_doc(&doc))
+    }
+
+    /// Extracts the stored story fields from a document that has already been loaded.
+    pub fn story_from_doc(&self, doc: &Document) -> StoryFetch {
+        let url = self.text_value(doc, self.schema.url_field);
+        let title = self.text_value(doc, self.schema.title_field);
+        let date = self.i64_value(doc, self.schema.date_field);
+        let score = self.f64_value(doc, self.schema.score_field);
+        let scrape_ids = self.extract_scrape_ids_from_doc(doc);
+        let tags = self.text_values(doc, self.schema.tags_field);
+        let tag_sources = self
+            .text_values(doc, self.schema.tag_sources_field)
+            .into_iter()
+            .filter_map(|s| {
+                let (tag, source) = s.rsplit_once(':')?;
+                Some((tag.to_owned(), source.parse().ok()?))
+            })
+            .collect_vec();
+        let last_activity = self.extract_last_activity_from_doc(doc);
+        // Documents indexed before this was recorded won't have it
+        let ingested_at = doc
+            .get_first(self.schema.ingested_at_field)
+            .and_then(|value| value.as_i64());
+        let comments = self.extract_comments_from_doc(doc);
+        StoryFetch {
             url,
             title,
             date,
             score,
             scrape_ids,
             tags,
-        })
+            tag_sources,
+            last_activity,
+            ingested_at,
+            comments,
+        }
     }
 
     pub fn doc(&self, doc_address: DocAddress) -> Result<Document, PersistError> {
@@ -313,6 +515,41 @@ impl StoryIndexShard {
         Ok(named_doc)
     }
 
+    /// Finds the story in this shard holding the scrape `id`, if there is one.
+    pub fn lookup_scrape(&self, id: &ScrapeId) -> Result<Option<DocAddress>, PersistError> {
+        let field = self.schema.scrape_field;
+        let id_string = id.to_string();
+        let mut terms = vec![];
+        let mut token_stream = self
+            .index
+            .tokenizer_for_field(field)?
+            .token_stream(&id_string);
+        while token_stream.advance() {
+            let token = token_stream.token();
+            terms.push((token.position, Term::from_field_text(field, &token.text)));
+        }
+        // The PhraseQuery asserts if only a single term is passed, so convert those into term queries
+        let query: Box<dyn Query> = match terms.len() {
+            0 => return Ok(None),
+            1 => Box::new(TermQuery::new(terms.remove(0).1, IndexRecordOption::Basic)),
+            _ => Box::new(PhraseQuery::new_with_offset(terms)),
+        };
+        self.with_searcher(|searcher, _| {
+            // Tokenizing loses punctuation and case, so confirm the match against the ids themselves
+            for (_, doc_address) in searcher.search(&query, &TopDocs::with_limit(10))? {
+                let doc = searcher.doc(doc_address)?;
+                if self
+                    .extract_scrape_ids_from_doc(&doc)
+                    .iter()
+                    .any(|scrape| &scrape.id == id)
+                {
+                    return Ok(Some(doc_address));
+                }
+            }
+            Ok(None)
+        })
+    }
+
     /// Given a set of `StoryLookupId`s, computes the documents that match them.
     pub fn lookup_stories(
         &self,
@@ -1,5 +1,14 @@
 use tantivy::schema::{Field, Schema, FAST, INDEXED, STORED, STRING, TEXT};
 
+/// The version of [`StorySchema`], which must be bumped whenever a field is added, removed or changed. Tantivy won't
+/// open an index written with a different schema, so each version lives in its own directory within a shard and
+/// shards with an older version are rebuilt from the scrape store by `StoryIndex::migrate_shards`.
+///
+/// Version 2 added `tag_sources`, `last_activity`, `comments`, `author` and `ingested_at`. Version 3 added
+/// `score_inputs` and stores `url_norm`, `url_norm_hash` and `author`, so that a story can be rescored from its
+/// document alone.
+pub const SCHEMA_VERSION: u32 = 3;
+
 #[derive(Clone)]
 pub struct StorySchema {
     pub schema: Schema,
@@ -13,21 +22,44 @@ pub struct StorySchema {
     pub date_field: Field,
     pub scrape_field: Field,
     pub tags_field: Field,
+    pub tag_sources_field: Field,
+    pub last_activity_field: Field,
+    pub ingested_at_field: Field,
+    pub comments_field: Field,
+    pub author_field: Field,
+    pub score_inputs_field: Field,
 }
 
 impl StorySchema {
+    /// The name of the directory (within a shard) holding the index for a schema version. The first version
+    /// predates versioning, so it has the unversioned name.
+    pub fn index_dir(version: u32) -> String {
+        if version <= 1 {
+            "index".to_owned()
+        } else {
+            format!("index-v{version}")
+        }
+    }
+
     pub fn instantiate_global_schema() -> Self {
         let mut schema_builder = Schema::builder();
         let date_field = schema_builder.add_i64_field("date", FAST | STORED);
         let id_field = schema_builder.add_text_field("id", STRING | STORED);
         let url_field = schema_builder.add_text_field("url", STRING | STORED);
-        let url_norm_field = schema_builder.add_text_field("url_norm", FAST | STRING);
-        let url_norm_hash_field = schema_builder.add_i64_field("url_norm_hash", FAST | INDEXED);
+        let url_norm_field = schema_builder.add_text_field("url_norm", FAST | STRING | STORED);
+        let url_norm_hash_field =
+            schema_builder.add_i64_field("url_norm_hash", FAST | INDEXED | STORED);
         let host_field = schema_builder.add_text_field("host", TEXT | STORED);
         let title_field = schema_builder.add_text_field("title", TEXT | STORED);
         let scrape_field = schema_builder.add_text_field("scrapes", TEXT | STORED);
         let score_field = schema_builder.add_f64_field("score", FAST | STORED);
         let tags_field = schema_builder.add_text_field("tags", TEXT | STORED);
+        let tag_sources_field = schema_builder.add_text_field("tag_sources", STORED);
+        let last_activity_field = schema_builder.add_i64_field("last_activity", STORED);
+        let ingested_at_field = schema_builder.add_i64_field("ingested_at", STORED);
+        let comments_field = schema_builder.add_text_field("comments", STORED);
+        let author_field = schema_builder.add_text_field("author", STRING | STORED);
+        let score_inputs_field = schema_builder.add_text_field("score_inputs", STORED);
         let schema = schema_builder.build();
 
         Self {
@@ -42,6 +74,12 @@ impl StorySchema {
             date_field,
             scrape_field,
             tags_field,
+            tag_sources_field,
+            last_activity_field,
+            ingested_at_field,
+            comments_field,
+            author_field,
+            score_inputs_field,
         }
     }
 }
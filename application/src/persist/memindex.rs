@@ -5,6 +5,7 @@ use itertools::Itertools;
 use progscrape_scrapers::{ScrapeCollection, StoryUrlNorm};
 
 use super::{shard::Shard, *};
+use crate::story::StoryIdentifier;
 
 /// Builds an index of stories in memory, useful for pre-aggregation of scrapes into normalized URL collections.
 #[derive(Default, Serialize, Deserialize)]
@@ -24,6 +25,24 @@ impl MemIndex {
         out.into_iter()
     }
 
+    /// The number of stories in the index.
+    pub fn len(&self) -> usize {
+        self.stories.values().map(HashMap::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stories.values().all(HashMap::is_empty)
+    }
+
+    /// Iterate over the stories in the index, oldest shard first.
+    pub fn iter(&self) -> impl Iterator<Item = (StoryIdentifier, &ScrapeCollection)> {
+        self.stories
+            .iter()
+            .sorted_by_key(|(shard, _)| **shard)
+            .flat_map(|(_, stories)| stories.iter())
+            .map(|(norm, story)| (StoryIdentifier::new(story.earliest, norm), story))
+    }
+
     fn map_mut(&mut self, shard: Shard) -> &mut HashMap<StoryUrlNorm, ScrapeCollection> {
         self.stories.entry(shard).or_default()
     }
@@ -79,4 +98,40 @@ mod test {
             .insert_scrapes(stories)
             .expect("Failed to insert scrapes");
     }
+
+    #[test]
+    fn test_len_and_iter() {
+        use progscrape_scrapers::{hacker_news::HackerNewsStory, reddit::RedditStory, StoryUrl};
+
+        let mut index = MemIndex::default();
+        assert!(index.is_empty());
+        assert_eq!(index.len(), 0);
+
+        let date = StoryDate::year_month_day(2020, 1, 1).expect("Date failed");
+        let later = StoryDate::year_month_day(2020, 3, 1).expect("Date failed");
+        let url = |s: &str| StoryUrl::parse(format!("https://example.com/{s}")).expect("URL");
+        index
+            .insert_scrapes([
+                HackerNewsStory::new_with_defaults("1", date, "A story", url("a")).into(),
+                RedditStory::new_subsource_with_defaults("2", "rust", date, "A story", url("a"))
+                    .into(),
+                HackerNewsStory::new_with_defaults("3", later, "Another story", url("b")).into(),
+            ])
+            .expect("Failed to insert scrapes");
+        assert!(!index.is_empty());
+        assert_eq!(index.len(), 2);
+
+        let stories = index.iter().collect_vec();
+        assert_eq!(stories.len(), index.len());
+        assert_eq!(
+            stories[0].0,
+            StoryIdentifier::new(date, url("a").normalization())
+        );
+        assert_eq!(stories[0].1.scrapes.len(), 2);
+        assert_eq!(
+            stories[1].0,
+            StoryIdentifier::new(later, url("b").normalization())
+        );
+        assert_eq!(stories[1].1.url(), &url("b"));
+    }
 }
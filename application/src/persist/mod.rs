@@ -15,10 +15,13 @@ mod shard;
 pub use backerupper::{BackerUpper, BackupResult};
 pub use index::StoryIndex;
 pub use memindex::MemIndex;
-pub use shard::Shard;
+pub use shard::{Shard, ShardOrder};
 
 use self::shard::ShardRange;
 
+/// The raw index fields of a single story's document, keyed by field name.
+pub type StoryDocDetail = HashMap<String, Vec<String>>;
+
 #[derive(Error, Debug)]
 pub enum PersistError {
     #[error("SQLite error")]
@@ -37,6 +40,8 @@ pub enum PersistError {
     IOError(#[from] std::io::Error),
     #[error("Unexpected error")]
     UnexpectedError(String),
+    #[error("Shard {0} was indexed with an older schema and must be migrated")]
+    ShardNeedsMigration(Shard),
 }
 
 #[derive(Debug, Default, Serialize, Deserialize)]
@@ -49,15 +54,263 @@ pub struct ShardSummary {
 pub struct StorageSummary {
     pub by_shard: Vec<(String, ShardSummary)>,
     pub total: ShardSummary,
+    /// Shards that failed to open, along with the error
+    #[serde(default)]
+    pub degraded: Vec<(String, String)>,
+}
+
+/// Limits on how many of the newest shards each type of query will scan before stopping.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ShardScanLimits {
+    /// The front page should only span one or two shards unless the index is nearly empty.
+    #[serde(default = "ShardScanLimits::default_front_page")]
+    pub front_page: usize,
+    /// Searches (text, tag, domain, URL and related), or unlimited if not specified.
+    #[serde(default)]
+    pub search: Option<usize>,
+}
+
+impl ShardScanLimits {
+    fn default_front_page() -> usize {
+        3
+    }
+}
+
+impl Default for ShardScanLimits {
+    fn default() -> Self {
+        Self {
+            front_page: Self::default_front_page(),
+            search: None,
+        }
+    }
+}
+
+/// How search results are ranked.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SearchRankingConfig {
+    /// Search results lose this much score per second of age, relative to the most recent story.
+    #[serde(default = "SearchRankingConfig::default_age_factor")]
+    pub age_factor: f32,
+}
+
+impl SearchRankingConfig {
+    fn default_age_factor() -> f32 {
+        0.00001
+    }
+}
+
+impl Default for SearchRankingConfig {
+    fn default() -> Self {
+        Self {
+            age_factor: Self::default_age_factor(),
+        }
+    }
+}
+
+/// Configuration for inexact tag and domain searches.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct FuzzySearchConfig {
+    /// If an exact tag or domain search finds nothing, retry it allowing up to this many edits per term (at
+    /// most 2). Zero disables fuzzy matching.
+    #[serde(default)]
+    pub max_edit_distance: u8,
+}
+
+/// Configuration for domain searches.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DomainSearchConfig {
+    /// Host prefixes that are trimmed from a searched domain before matching. Stored hosts have already had
+    /// these trimmed by URL normalization, so a search for `www.example.com` would otherwise never match.
+    #[serde(default = "DomainSearchConfig::default_ignored_host_prefixes")]
+    pub ignored_host_prefixes: Vec<String>,
+}
+
+impl DomainSearchConfig {
+    fn default_ignored_host_prefixes() -> Vec<String> {
+        vec!["www.".to_owned(), "m.".to_owned(), "mobile.".to_owned()]
+    }
+
+    /// Trim any ignored prefixes (repeatedly) from the start of a domain.
+    pub fn trim_host<'a>(&self, mut domain: &'a str) -> &'a str {
+        while let Some(trimmed) = self
+            .ignored_host_prefixes
+            .iter()
+            .filter(|prefix| !prefix.is_empty())
+            .find_map(|prefix| domain.strip_prefix(prefix.as_str()))
+        {
+            domain = trimmed;
+        }
+        domain
+    }
+}
+
+impl Default for DomainSearchConfig {
+    fn default() -> Self {
+        Self {
+            ignored_host_prefixes: Self::default_ignored_host_prefixes(),
+        }
+    }
+}
+
+/// Configuration for recording a snapshot of each hot story's score every time the hot set is re-indexed.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ScoreSnapshotConfig {
+    /// Snapshots older than this are discarded.
+    #[serde(default = "ScoreSnapshotConfig::default_retention_days")]
+    pub retention_days: u64,
+}
+
+impl ScoreSnapshotConfig {
+    fn default_retention_days() -> u64 {
+        30
+    }
+}
+
+impl Default for ScoreSnapshotConfig {
+    fn default() -> Self {
+        Self {
+            retention_days: Self::default_retention_days(),
+        }
+    }
+}
+
+/// What to do when a source re-scrapes a story we already have under a different URL (ie: the submitter corrected
+/// a typo in the link).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UrlChangePolicy {
+    /// Index the scrape as a new story under its new URL, leaving the old story as-is.
+    #[default]
+    Fork,
+    /// Move the existing story to the new URL.
+    Update,
+}
+
+/// What to do with scrapes that arrive with an empty (or whitespace-only) title.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EmptyTitlePolicy {
+    /// Store the scrape with its empty title, as we always have.
+    #[default]
+    Keep,
+    /// Drop the scrape without storing it.
+    Drop,
+    /// Use the last path segment of the URL (or the host, if there is no path) as the title.
+    UrlFallback,
+}
+
+impl EmptyTitlePolicy {
+    /// Apply this policy to a scrape, returning `None` if the scrape should be dropped.
+    pub fn apply(&self, mut scrape: TypedScrape) -> Option<TypedScrape> {
+        if !scrape.raw_title.trim().is_empty() {
+            return Some(scrape);
+        }
+        match self {
+            EmptyTitlePolicy::Keep => Some(scrape),
+            EmptyTitlePolicy::Drop => None,
+            EmptyTitlePolicy::UrlFallback => {
+                let raw = scrape.url.raw();
+                let path = raw.split(['?', '#']).next().unwrap_or(raw);
+                let segment = path
+                    .trim_end_matches('/')
+                    .rsplit('/')
+                    .next()
+                    .unwrap_or_default();
+                let title = segment.replace(['-', '_'], " ").trim().to_owned();
+                if title.is_empty() {
+                    None
+                } else {
+                    scrape.raw_title = title;
+                    Some(scrape)
+                }
+            }
+        }
+    }
+}
+
+/// Hosts whose scrapes are dropped at insert time, ie: content farms and link shorteners (whose URLs are useless for
+/// deduplicating stories).
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct HostBlocklistConfig {
+    /// Blocked hosts. Subdomains of these hosts are blocked as well.
+    #[serde(default)]
+    pub hosts: Vec<String>,
+}
+
+impl HostBlocklistConfig {
+    /// Is this host, or any of its parent domains, blocked?
+    pub fn is_blocked(&self, host: &str) -> bool {
+        let host = host.to_lowercase();
+        let mut suffix = host.as_str();
+        loop {
+            if self.hosts.iter().any(|blocked| blocked == suffix) {
+                return true;
+            }
+            match suffix.split_once('.') {
+                Some((_, rest)) => suffix = rest,
+                None => return false,
+            }
+        }
+    }
+}
+
+/// A story's score at a point in time.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ScoreSnapshot {
+    pub date: StoryDate,
+    pub score: f32,
 }
 
 #[derive(Debug, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "json_schema", derive(schemars::JsonSchema))]
 pub struct SearchSummary {
+    /// The number of matching stories in each monthly shard (`YYYY-MM`), oldest first.
     pub by_shard: Vec<(String, usize)>,
     pub total: usize,
 }
 
-#[derive(Debug, Clone)]
+/// A position in the newest-first list of stories matching a query, identified by the date and URL of the last
+/// story on a page. Paging with a cursor is stable as new stories arrive, unlike paging with an offset.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StoryCursor {
+    date: i64,
+    url_norm_hash: i64,
+}
+
+impl StoryCursor {
+    const BASE64_CONFIG: base64::engine::GeneralPurpose =
+        base64::engine::general_purpose::URL_SAFE_NO_PAD;
+
+    /// A cursor that resumes after the given story.
+    pub fn after<S>(story: &Story<S>) -> Self {
+        Self {
+            date: story.date.timestamp(),
+            url_norm_hash: story.url.normalization().hash(),
+        }
+    }
+
+    /// The sort key for this cursor. Stories are paged in descending order of this key.
+    pub(crate) fn key(&self) -> (i64, i64) {
+        (self.date, self.url_norm_hash)
+    }
+
+    pub fn to_base64(&self) -> String {
+        use base64::Engine;
+        Self::BASE64_CONFIG.encode(format!("{}:{}", self.date, self.url_norm_hash))
+    }
+
+    pub fn from_base64<T: AsRef<[u8]>>(s: T) -> Option<Self> {
+        use base64::Engine;
+        let s = String::from_utf8(Self::BASE64_CONFIG.decode(s).ok()?).ok()?;
+        let (date, url_norm_hash) = s.split_once(':')?;
+        Some(Self {
+            date: date.parse().ok()?,
+            url_norm_hash: url_norm_hash.parse().ok()?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 /// The type of story fetch to perform.
 pub enum StoryQuery {
     /// A single story.
@@ -70,12 +323,21 @@ pub enum StoryQuery {
     TagSearch(String, Option<String>),
     /// Stories matching a domain query.
     DomainSearch(String),
+    /// Stories submitted by a given author.
+    AuthorSearch(String),
     /// Stories matching a specific URL.
     UrlSearch(StoryUrl),
     /// Stories matching a text search.
     TextSearch(String),
+    /// Stories matching every term of a text search (ie: `+rust async`).
+    AllTermsSearch(String),
     /// Related stories (title, tags)
     RelatedSearch(String, Vec<String>),
+    /// Stories dated from the first date (inclusive) up to the second (exclusive).
+    DateRange(StoryDate, StoryDate),
+    /// Stories matching every one of these searches (ie: a tag and a domain). Build this with
+    /// [`StoryQuery::multi`], which rejects queries that can't be combined.
+    Multi(Vec<StoryQuery>),
 }
 
 /// A string that may be turned into a [`StoryQuery`].
@@ -129,11 +391,24 @@ impl StoryQuery {
             Self::ById(id) => format!("id={id}").into(),
             Self::ByShard(shard) => format!("shard={shard:?}").into(),
             Self::DomainSearch(domain) => domain.into(),
+            Self::AuthorSearch(author) => format!("by:{author}").into(),
             Self::UrlSearch(url) => url.to_string().into(),
             Self::TagSearch(tag, _) => tag.into(),
             Self::TextSearch(text) => text.into(),
+            Self::AllTermsSearch(text) => format!("+{text}").into(),
             // TODO: This probably won't work
             Self::RelatedSearch(title, tags) => format!("title:{title:?} tags:{tags:?}").into(),
+            Self::DateRange(start, end) => format!("date={start}..{end}").into(),
+            Self::Multi(queries) => queries
+                .iter()
+                .map(|query| match query {
+                    Self::TagSearch(tag, _) => format!("tag:{tag}"),
+                    Self::DomainSearch(domain) => format!("site:{domain}"),
+                    query => query.query_text().into_owned(),
+                })
+                .collect::<Vec<_>>()
+                .join(" ")
+                .into(),
         }
     }
 
@@ -143,10 +418,38 @@ impl StoryQuery {
             Self::ById(id) => "id",
             Self::ByShard(shard) => "shard",
             Self::DomainSearch(domain) => "domain",
+            Self::AuthorSearch(_) => "author",
             Self::UrlSearch(url) => "url",
             Self::TagSearch(tag, _) => "tag",
             Self::TextSearch(text) => "text",
+            Self::AllTermsSearch(_) => "text",
             Self::RelatedSearch(title, tags) => "related",
+            Self::DateRange(..) => "archive",
+            Self::Multi(_) => "multi",
+        }
+    }
+
+    /// Combine searches so that stories must match all of them. Only tag, domain, author, URL and text searches
+    /// may be combined, and a single search is returned as-is.
+    pub fn multi(queries: Vec<StoryQuery>) -> Option<Self> {
+        let combinable = queries.iter().all(|query| {
+            matches!(
+                query,
+                Self::TagSearch(..)
+                    | Self::DomainSearch(..)
+                    | Self::AuthorSearch(..)
+                    | Self::UrlSearch(..)
+                    | Self::TextSearch(..)
+                    | Self::AllTermsSearch(..)
+            )
+        });
+        if !combinable {
+            return None;
+        }
+        match <[StoryQuery; 1]>::try_from(queries) {
+            Ok([query]) => Some(query),
+            Err(queries) if queries.is_empty() => None,
+            Err(queries) => Some(Self::Multi(queries)),
         }
     }
 
@@ -159,14 +462,40 @@ impl StoryQuery {
             return Self::FrontPage;
         }
 
+        // `by:username` searches for stories submitted by that user
+        if let Some(author) = search.strip_prefix("by:") {
+            let author = author.trim();
+            if !author.is_empty() && !author.contains(char::is_whitespace) {
+                return StoryQuery::AuthorSearch(author.to_owned());
+            }
+        }
+
+        // `tag:rust site:github.com` requires every filter to match
+        if let Some(query) = Self::try_filters(tagger, search) {
+            return query;
+        }
+
+        // A leading `+` requires every term to match, rather than any of them
+        if let Some(terms) = search.strip_prefix('+') {
+            let terms = terms.trim();
+            if terms.contains(char::is_whitespace) {
+                return StoryQuery::AllTermsSearch(terms.to_owned());
+            }
+        }
+
+        // A trailing `*` searches for tags or domains by prefix (ie: `rust*` or `example.co*`)
+        if let Some(prefix) = search.strip_suffix('*').map(str::trim_end) {
+            if !prefix.is_empty() && prefix.chars().all(char::is_alphanumeric) {
+                return StoryQuery::TagSearch(format!("{}*", prefix.to_lowercase()), None);
+            }
+            if let Some(StoryQuery::DomainSearch(domain)) = Self::try_domain_or_url(prefix) {
+                return StoryQuery::DomainSearch(format!("{domain}*"));
+            }
+        }
+
         // This isn't terribly smart, buuuuut it allows us to search either a tag or site
-        if let Some(tag) = tagger.check_tag_search(search) {
-            let alt = if tag.eq_ignore_ascii_case(search) {
-                None
-            } else {
-                Some(search.to_ascii_lowercase())
-            };
-            StoryQuery::TagSearch(tag.to_string(), alt)
+        if let Some(tag) = Self::try_tag(tagger, search) {
+            tag
         } else if let Some(domain_or_url) = Self::try_domain_or_url(search) {
             domain_or_url
         } else if !search.contains(|c: char| !c.is_alphanumeric()) {
@@ -177,6 +506,41 @@ impl StoryQuery {
         }
     }
 
+    fn try_tag(tagger: &StoryTagger, search: &str) -> Option<StoryQuery> {
+        let tag = tagger.check_tag_search(search)?;
+        let alt = if tag.eq_ignore_ascii_case(search) {
+            None
+        } else {
+            Some(search.to_ascii_lowercase())
+        };
+        Some(StoryQuery::TagSearch(tag.to_string(), alt))
+    }
+
+    /// Parses a search made up entirely of `tag:`, `site:` and `by:` filters.
+    fn try_filters(tagger: &StoryTagger, search: &str) -> Option<StoryQuery> {
+        let queries = search
+            .split_whitespace()
+            .map(|filter| {
+                let (kind, value) = filter.split_once(':')?;
+                if value.is_empty() {
+                    return None;
+                }
+                match kind {
+                    "tag" => Self::try_tag(tagger, value).or_else(|| {
+                        value
+                            .chars()
+                            .all(char::is_alphanumeric)
+                            .then(|| StoryQuery::TagSearch(value.to_lowercase(), None))
+                    }),
+                    "site" => Self::try_domain_or_url(value),
+                    "by" => Some(StoryQuery::AuthorSearch(value.to_owned())),
+                    _ => None,
+                }
+            })
+            .collect::<Option<Vec<_>>>()?;
+        Self::multi(queries)
+    }
+
     fn try_domain_or_url(search: &str) -> Option<StoryQuery> {
         // Only test a domain search if the search contains a domain-like char
         if search.contains('.') || search.contains(':') {
@@ -214,6 +578,20 @@ impl StoryScrapePayload for TypedScrape {}
 
 pub trait StorageFetch<S: StoryScrapePayload> {
     fn fetch_type(&self, query: &StoryQuery, max: usize) -> Result<Vec<Story<S>>, PersistError>;
+    fn fetch_type_by_ids(
+        &self,
+        ids: &[StoryIdentifier],
+    ) -> Result<HashMap<StoryIdentifier, Story<S>>, PersistError>;
+}
+
+/// Walks the stories in a shard one at a time, without collecting the whole shard into memory.
+pub trait StorageForEach<S: StoryScrapePayload> {
+    /// Calls `f` for each live story in `shard`, stopping at the first error. Returns the number of stories visited.
+    fn for_each_story_in_shard(
+        &self,
+        shard: Shard,
+        f: impl FnMut(Story<S>) -> Result<(), PersistError>,
+    ) -> Result<usize, PersistError>;
 }
 
 /// The underlying storage engine.
@@ -221,6 +599,9 @@ pub trait Storage: Send + Sync {
     /// Returns the most recent story date.
     fn most_recent_story(&self) -> Result<StoryDate, PersistError>;
 
+    /// Returns the oldest story date.
+    fn earliest_story(&self) -> Result<StoryDate, PersistError>;
+
     /// Returns the range of shards for this index.
     fn shard_range(&self) -> Result<ShardRange, PersistError>;
 
@@ -234,10 +615,7 @@ pub trait Storage: Send + Sync {
     fn fetch_count_by_shard(&self, query: &StoryQuery) -> Result<SearchSummary, PersistError>;
 
     /// Fetches the index-specific story details for a single story.
-    fn fetch_detail_one(
-        &self,
-        query: &StoryQuery,
-    ) -> Result<Option<HashMap<String, Vec<String>>>, PersistError>;
+    fn fetch_detail_one(&self, query: &StoryQuery) -> Result<Option<StoryDocDetail>, PersistError>;
 
     /// Fetch a list of stories with the specified payload type.
     #[inline(always)]
@@ -265,6 +643,19 @@ pub trait Storage: Send + Sync {
             .into_iter()
             .next())
     }
+
+    /// Fetch many stories by id with the specified payload type, searching each shard once rather than once per
+    /// story. Stories that aren't found are missing from the result.
+    #[inline(always)]
+    fn fetch_many_by_id<S: StoryScrapePayload>(
+        &self,
+        ids: &[StoryIdentifier],
+    ) -> Result<HashMap<StoryIdentifier, Story<S>>, PersistError>
+    where
+        Self: StorageFetch<S>,
+    {
+        <Self as StorageFetch<S>>::fetch_type_by_ids(self, ids)
+    }
 }
 
 pub trait StorageWriter: Storage {
@@ -288,6 +679,17 @@ pub trait StorageWriter: Storage {
         eval: &StoryEvaluator,
         stories: I,
     ) -> Result<Vec<ScrapePersistResult>, PersistError>;
+
+    /// Given a set of existing stories, updates their scores without re-tagging them. This is cheaper than
+    /// [`StorageWriter::reinsert_stories`] when only the score configuration has changed.
+    fn rescore_stories<I: IntoIterator<Item = StoryIdentifier>>(
+        &mut self,
+        eval: &StoryEvaluator,
+        stories: I,
+    ) -> Result<Vec<ScrapePersistResult>, PersistError>;
+
+    /// Delete every story dated strictly before `date`, along with its scrapes.
+    fn delete_before(&mut self, date: StoryDate) -> Result<PurgeSummary, PersistError>;
 }
 
 #[derive(Debug, Serialize, PartialEq, Eq, Ord, PartialOrd, Hash)]
@@ -300,6 +702,10 @@ pub enum ScrapePersistResult {
     NewStory,
     /// The story was not found whilst we tried to re-insert it.
     NotFound,
+    /// The scrape had an empty title and was dropped.
+    DroppedEmptyTitle,
+    /// The scrape was for a blocked host and was dropped.
+    Blocked,
 }
 
 #[derive(Default, Debug, Serialize)]
@@ -308,6 +714,8 @@ pub struct ScrapePersistResultSummary {
     pub existing: usize,
     pub new: usize,
     pub not_found: usize,
+    pub dropped: usize,
+    pub blocked: usize,
 }
 
 impl AddAssign for ScrapePersistResultSummary {
@@ -316,9 +724,18 @@ impl AddAssign for ScrapePersistResultSummary {
         self.existing += rhs.existing;
         self.new += rhs.new;
         self.not_found += rhs.not_found;
+        self.dropped += rhs.dropped;
+        self.blocked += rhs.blocked;
     }
 }
 
+/// How much was removed by [`StorageWriter::delete_before`].
+#[derive(Default, Debug, Serialize, PartialEq, Eq)]
+pub struct PurgeSummary {
+    pub stories: usize,
+    pub scrapes: usize,
+}
+
 pub trait ScrapePersistResultSummarizer {
     fn summary(&self) -> ScrapePersistResultSummary;
 }
@@ -332,6 +749,8 @@ impl ScrapePersistResultSummarizer for Vec<ScrapePersistResult> {
                 &ScrapePersistResult::AlreadyPartOfExistingStory => summary.existing += 1,
                 &ScrapePersistResult::NewStory => summary.new += 1,
                 &ScrapePersistResult::NotFound => summary.not_found += 1,
+                &ScrapePersistResult::DroppedEmptyTitle => summary.dropped += 1,
+                &ScrapePersistResult::Blocked => summary.blocked += 1,
             }
         }
         summary
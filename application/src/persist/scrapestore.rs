@@ -6,7 +6,7 @@ use std::{
 use progscrape_scrapers::{ScrapeId, StoryDate, TypedScrape};
 use serde::{Deserialize, Serialize};
 
-use crate::{story::StoryScrapeId, PersistError};
+use crate::{story::StoryScrapeId, PersistError, ScoreSnapshot};
 
 use super::{db::DB, shard::Shard, PersistLocation};
 
@@ -37,6 +37,14 @@ struct ScrapeCacheEntry {
     json: String,
 }
 
+/// An append-only record of a story's score at a given time.
+#[derive(Default, Serialize, Deserialize)]
+struct ScoreSnapshotEntry {
+    id: String,
+    date: StoryDate,
+    score: f64,
+}
+
 impl ScrapeStore {
     pub fn new(location: PersistLocation) -> Result<Self, PersistError> {
         tracing::info!("Initialized ScrapeStore at {:?}", location);
@@ -67,6 +75,11 @@ impl ScrapeStore {
         };
         db.create_table::<ScrapeCacheEntry>()?;
         db.create_unique_index::<ScrapeCacheEntry>("idx_id", &["id"])?;
+        db.create_table::<ScoreSnapshotEntry>()?;
+        db.execute_raw(&format!(
+            "create index if not exists idx_snapshot_id on {}(id)",
+            DB::table_for::<ScoreSnapshotEntry>()
+        ))?;
         Ok(db.clone())
     }
 
@@ -87,6 +100,8 @@ impl ScrapeStore {
             let db = self.open_shard(shard)?;
             let mut batch = vec![];
             for item in stories {
+                // The id includes the scrape's source (ie: `hacker_news-123`), so the same id from two different
+                // sources will never share a row
                 let json = serde_json::to_string(item)?;
                 batch.push(ScrapeCacheEntry {
                     date: item.date,
@@ -132,6 +147,21 @@ impl ScrapeStore {
         Ok(map)
     }
 
+    /// Delete the given scrapes, returning how many of them were stored.
+    pub fn delete_scrape_batch<I: IntoIterator<Item = StoryScrapeId>>(
+        &self,
+        iter: I,
+    ) -> Result<usize, PersistError> {
+        let mut count = 0;
+        for id in iter {
+            let db = self.open_shard(id.shard)?;
+            if db.delete::<ScrapeCacheEntry>(id.id.to_string())? {
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
     /// Fetch all the scrapes, passing them to a given callback (or the error to an error callback).
     pub fn fetch_all<F: FnMut(TypedScrape) -> Result<(), PersistError>, FE: FnMut(PersistError)>(
         &self,
@@ -154,6 +184,59 @@ impl ScrapeStore {
         Ok(())
     }
 
+    /// Append a score snapshot at `date` for each of the given `(story_id, score)` pairs, discarding
+    /// any snapshots in this shard older than `retain_after`.
+    pub fn insert_score_snapshots(
+        &self,
+        shard: Shard,
+        date: StoryDate,
+        snapshots: Vec<(String, f32)>,
+        retain_after: StoryDate,
+    ) -> Result<(), PersistError> {
+        let db = self.open_shard(shard)?;
+        db.execute_raw(&format!(
+            "delete from {} where date < {}",
+            DB::table_for::<ScoreSnapshotEntry>(),
+            retain_after.timestamp()
+        ))?;
+        db.store_batch(
+            snapshots
+                .into_iter()
+                .map(|(id, score)| ScoreSnapshotEntry {
+                    id,
+                    date,
+                    score: score as f64,
+                })
+                .collect(),
+        )
+    }
+
+    /// Fetch the score snapshots for the given story, oldest first.
+    pub fn fetch_score_snapshots(
+        &self,
+        shard: Shard,
+        id: &str,
+    ) -> Result<Vec<ScoreSnapshot>, PersistError> {
+        let db = self.open_shard(shard)?;
+        let sql = format!(
+            "select * from {} where id = ? order by date, rowid",
+            DB::table_for::<ScoreSnapshotEntry>()
+        );
+        Ok(db
+            .query_params::<ScoreSnapshotEntry, _>(&sql, [id])?
+            .into_iter()
+            .map(|entry| ScoreSnapshot {
+                date: entry.date,
+                score: entry.score as f32,
+            })
+            .collect())
+    }
+
+    /// Close the database for a given shard, if it is open. The shard will be reopened on next use.
+    pub(crate) fn close_shard(&self, shard: Shard) {
+        self.shards.write().expect("Poisoned").remove(&shard);
+    }
+
     /// Get the stats for a given shard.
     pub fn stats(&self, shard: Shard) -> Result<ScrapeStoreStats, PersistError> {
         let db = self.open_shard(shard)?;
@@ -175,7 +258,9 @@ impl ScrapeStore {
 
 #[cfg(test)]
 mod test {
-    use progscrape_scrapers::ScrapeConfig;
+    use progscrape_scrapers::{
+        hacker_news::HackerNewsStory, lobsters::LobstersStory, ScrapeConfig, ScrapeSource, StoryUrl,
+    };
     use rstest::rstest;
 
     use crate::test::enable_tracing;
@@ -209,4 +294,45 @@ mod test {
 
         Ok(())
     }
+
+    /// Identical ids from different sources are stored distinctly.
+    #[rstest]
+    fn test_same_id_across_sources(
+        _enable_tracing: &bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let store = ScrapeStore::new(PersistLocation::Memory)?;
+        let date = StoryDate::year_month_day(2020, 1, 1).expect("Date failed");
+        let shard = Shard::from_date_time(date);
+
+        let hn: TypedScrape = HackerNewsStory::new_with_defaults(
+            "123",
+            date,
+            "Hacker News story",
+            StoryUrl::parse("https://example.com/hn").expect("url"),
+        )
+        .into();
+        let lobsters: TypedScrape = LobstersStory::new_with_defaults(
+            "123",
+            date,
+            "Lobsters story",
+            StoryUrl::parse("https://example.com/lobsters").expect("url"),
+        )
+        .into();
+        assert_eq!(hn.id.id, lobsters.id.id);
+        store.insert_scrape_batch([&hn, &lobsters])?;
+
+        assert_eq!(store.stats(shard)?.count, 2);
+        let loaded_hn = store
+            .fetch_scrape(shard, &hn.id)?
+            .expect("Missing HN scrape");
+        let loaded_lobsters = store
+            .fetch_scrape(shard, &lobsters.id)?
+            .expect("Missing Lobsters scrape");
+        assert_eq!(loaded_hn.id.source, ScrapeSource::HackerNews);
+        assert_eq!(loaded_hn.url, hn.url);
+        assert_eq!(loaded_lobsters.id.source, ScrapeSource::Lobsters);
+        assert_eq!(loaded_lobsters.url, lobsters.url);
+
+        Ok(())
+    }
 }
@@ -1,20 +1,46 @@
-use std::{fmt::Debug, ops::RangeInclusive};
+use std::{
+    fmt::{Debug, Display},
+    ops::RangeInclusive,
+    str::FromStr,
+};
 
 use progscrape_scrapers::StoryDate;
 use serde::{Deserialize, Serialize};
+use thiserror::Error;
 
+/// A shard holds one month of stories. The canonical string format of a shard is `YYYY-MM`, which is
+/// used for on-disk paths, summary keys, and admin URLs.
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub struct Shard(u16);
 
-impl ToString for Shard {
-    fn to_string(&self) -> String {
-        format!("{:04}-{:02}", self.0 / 12, self.0 % 12 + 1)
+#[derive(Debug, Error, PartialEq, Eq)]
+#[error("Invalid shard '{0}' (expected YYYY-MM)")]
+pub struct ShardParseError(String);
+
+impl Display for Shard {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("{:04}-{:02}", self.0 / 12, self.0 % 12 + 1))
     }
 }
 
 impl Debug for Shard {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_fmt(format_args!("{:04}-{:02}", self.0 / 12, self.0 % 12 + 1))
+        Display::fmt(self, f)
+    }
+}
+
+impl FromStr for Shard {
+    type Err = ShardParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || ShardParseError(s.to_owned());
+        let (year, month) = s.split_once('-').ok_or_else(err)?;
+        let year: u16 = year.parse().map_err(|_| err())?;
+        let month: u8 = month.parse().map_err(|_| err())?;
+        if !(1..=12).contains(&month) || year > u16::MAX / 12 - 1 {
+            return Err(err());
+        }
+        Ok(Self::from_year_month(year, month))
     }
 }
 
@@ -78,6 +104,12 @@ impl ShardRange {
         })
     }
 
+    pub fn contains(&self, shard: Shard) -> bool {
+        self.range
+            .map(|(start, end)| start <= shard && shard <= end)
+            .unwrap_or_default()
+    }
+
     pub fn include(&mut self, shard: Shard) {
         if let Some(range) = self.range {
             self.range = Some((range.0.min(shard), range.1.max(shard)))
@@ -85,6 +117,17 @@ impl ShardRange {
             self.range = Some((shard, shard))
         }
     }
+
+    /// Drop every shard older than `shard` from this range, leaving it empty if nothing remains.
+    pub fn exclude_before(&mut self, shard: Shard) {
+        if let Some((start, end)) = self.range {
+            if shard > end {
+                self.range = None
+            } else {
+                self.range = Some((start.max(shard), end))
+            }
+        }
+    }
 }
 
 impl Shard {
@@ -97,12 +140,7 @@ impl Shard {
     }
 
     pub fn from_string(s: &str) -> Option<Self> {
-        if let Some((a, b)) = s.split_once('-') {
-            if let (Ok(a), Ok(b)) = (str::parse(a), str::parse(b)) {
-                return Some(Self::from_year_month(a, b));
-            }
-        }
-        None
+        s.parse().ok()
     }
 
     pub fn from_date_time(date: StoryDate) -> Self {
@@ -123,6 +161,7 @@ impl Shard {
 mod test {
     use super::*;
     use itertools::Itertools;
+    use rstest::*;
 
     #[test]
     fn test_year_month() {
@@ -138,6 +177,32 @@ mod test {
         );
     }
 
+    #[rstest]
+    #[case(2000, 1, "2000-01")]
+    #[case(2000, 9, "2000-09")]
+    #[case(2000, 10, "2000-10")]
+    #[case(2023, 12, "2023-12")]
+    #[case(1999, 12, "1999-12")]
+    #[case(0, 1, "0000-01")]
+    fn test_round_trip(#[case] year: u16, #[case] month: u8, #[case] s: &str) {
+        let shard = Shard::from_year_month(year, month);
+        assert_eq!(shard.to_string(), s);
+        assert_eq!(format!("{shard:?}"), s);
+        assert_eq!(s.parse(), Ok(shard));
+        assert_eq!(Shard::from_string(s), Some(shard));
+    }
+
+    #[rstest]
+    #[case("2000")]
+    #[case("2000-00")]
+    #[case("2000-13")]
+    #[case("2000-1a")]
+    #[case("abcd-01")]
+    #[case("")]
+    fn test_parse_invalid(#[case] s: &str) {
+        assert!(s.parse::<Shard>().is_err());
+    }
+
     #[test]
     fn test_shard_iterator() {
         let range = ShardRange::new_from(
@@ -152,4 +217,21 @@ mod test {
 
         assert_eq!(in_order, rev_order);
     }
+
+    #[test]
+    fn test_exclude_before() {
+        let mut range = ShardRange::new_from(
+            Shard::from_year_month(2000, 1)..=Shard::from_year_month(2000, 12),
+        );
+        range.exclude_before(Shard::from_year_month(1999, 6));
+        assert_eq!(range.iterate(ShardOrder::OldestFirst).count(), 12);
+        range.exclude_before(Shard::from_year_month(2000, 7));
+        assert_eq!(
+            range.iterate(ShardOrder::OldestFirst).next(),
+            Some(Shard::from_year_month(2000, 7))
+        );
+        assert_eq!(range.iterate(ShardOrder::OldestFirst).count(), 6);
+        range.exclude_before(Shard::from_year_month(2001, 1));
+        assert_eq!(range, ShardRange::default());
+    }
 }
@@ -1,12 +1,15 @@
 use serde::{Deserialize, Serialize};
 
-use std::fmt::Display;
+use std::{cmp::Ordering, fmt::Display};
 
 use progscrape_scrapers::{StoryDate, StoryUrlNorm};
 
 use crate::Shard;
 
 /// Uniquely identifies a story within the index.
+///
+/// The derived `Ord` compares the URL normalization first and the date second, which is only useful as a
+/// stable total order (ie: for sorted sets). Use [`StoryIdentifier::cmp_chronological`] for date-ordered listings.
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
 pub struct StoryIdentifier {
     pub norm: StoryUrlNorm,
@@ -45,6 +48,13 @@ impl StoryIdentifier {
             == (date.year() as u16, date.month() as u8, date.day() as u8)
     }
 
+    /// Compares two identifiers by date, then by URL normalization so that the order remains stable.
+    pub fn cmp_chronological(&self, other: &Self) -> Ordering {
+        self.date
+            .cmp(&other.date)
+            .then_with(|| self.norm.cmp(&other.norm))
+    }
+
     pub fn to_base64(&self) -> String {
         use base64::Engine;
         Self::BASE64_CONFIG.encode(self.to_string().as_bytes())
@@ -107,4 +117,30 @@ mod test {
             StoryIdentifier::from_base64(base64).expect("Failed to decode ID")
         );
     }
+
+    #[test]
+    fn test_cmp_chronological() {
+        let early_url = StoryUrl::parse("https://zzz.com/").expect("Failed to parse URL");
+        let late_url = StoryUrl::parse("https://aaa.com/").expect("Failed to parse URL");
+        let early = StoryIdentifier::new(
+            StoryDate::year_month_day(2020, 1, 1).expect("Invalid date"),
+            early_url.normalization(),
+        );
+        let late = StoryIdentifier::new(
+            StoryDate::year_month_day(2020, 1, 2).expect("Invalid date"),
+            late_url.normalization(),
+        );
+
+        // The derived order sorts by URL first
+        assert_eq!(early.cmp(&late), Ordering::Greater);
+        assert_eq!(early.cmp_chronological(&late), Ordering::Less);
+        assert_eq!(late.cmp_chronological(&early), Ordering::Greater);
+        assert_eq!(early.cmp_chronological(&early), Ordering::Equal);
+
+        let same_day = StoryIdentifier::new(
+            StoryDate::year_month_day(2020, 1, 1).expect("Invalid date"),
+            late_url.normalization(),
+        );
+        assert_eq!(same_day.cmp_chronological(&early), Ordering::Less);
+    }
 }
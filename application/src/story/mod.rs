@@ -4,9 +4,14 @@ use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 
 use progscrape_scrapers::{
-    ScrapeConfig, ScrapeExtractor, ScrapeId, StoryDate, StoryUrl, TypedScrapeMap,
+    ScrapeConfig, ScrapeExtractor, ScrapeId, ScrapeSource, StoryDate, StoryDuration, StoryUrl,
+    TypedScrapeMap,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    str::FromStr,
 };
-use std::collections::{HashMap, HashSet};
 
 mod collector;
 mod id;
@@ -20,8 +25,11 @@ pub use self::{
     collector::StoryCollector,
     id::StoryIdentifier,
     render::StoryRender,
-    scorer::{StoryScore, StoryScoreConfig, StoryScorer},
-    tagger::{StoryTagger, TaggerConfig},
+    scorer::{
+        ScrapeScoreInput, StoryScore, StoryScoreConfig, StoryScoreConfigError, StoryScoreInput,
+        StoryScorer, TieBreak, TieBreakConfig, SCORE_EPSILON,
+    },
+    tagger::{StoryTagger, TagCollation, TaggerConfig},
 };
 
 /// Required services to evaulate a story.
@@ -62,6 +70,46 @@ impl From<StoryScrapeId> for (ScrapeId, Shard) {
     }
 }
 
+/// Where a story's tag came from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TagSource {
+    /// The tag was derived from the story's title.
+    Title,
+    /// The tag was derived from the story's host.
+    Host,
+    /// The tag was derived from the words in the story's URL.
+    Url,
+    /// The tag was provided by the given scrape source.
+    Scrape(ScrapeSource),
+}
+
+impl Display for TagSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TagSource::Title => f.write_str("title"),
+            TagSource::Host => f.write_str("host"),
+            TagSource::Url => f.write_str("url"),
+            TagSource::Scrape(source) => f.write_str(source.into_str()),
+        }
+    }
+}
+
+impl FromStr for TagSource {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "title" => Ok(TagSource::Title),
+            "host" => Ok(TagSource::Host),
+            "url" => Ok(TagSource::Url),
+            s => ScrapeSource::try_from_str(s)
+                .map(TagSource::Scrape)
+                .ok_or(()),
+        }
+    }
+}
+
 /// Story scrape w/information from underlying sources.
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Story<S> {
@@ -72,6 +120,18 @@ pub struct Story<S> {
     pub title: String,
     pub tags: TagSet,
     pub scrapes: HashMap<ScrapeId, S>,
+    /// The provenance of each tag, if it was recorded when the story was indexed.
+    #[serde(default)]
+    pub tag_sources: Vec<(String, TagSource)>,
+    /// The last time we saw activity for this story (ie: a re-scrape), which is at least its date.
+    #[serde(default)]
+    pub last_activity: StoryDate,
+    /// The highest comment count reported by each source, if the source reports them.
+    #[serde(default)]
+    pub comments: TypedScrapeMap<Option<u32>>,
+    /// When we first stored any of this story's scrapes, which may be well after it was posted.
+    #[serde(default)]
+    pub ingested_at: Option<StoryDate>,
 }
 
 impl<S> Story<S> {
@@ -91,13 +151,83 @@ impl<S> Story<S> {
             date,
             score,
             scrapes: HashMap::from_iter(scrapes.into_iter().map(|x| x.into())),
+            tag_sources: vec![],
+            last_activity: date,
+            comments: TypedScrapeMap::new(),
+            ingested_at: None,
         }
     }
 
-    /// Compares two stories, ordering by score.
-    pub fn compare_score(&self, other: &Self) -> std::cmp::Ordering {
+    pub fn with_tag_sources(mut self, tag_sources: Vec<(String, TagSource)>) -> Self {
+        self.tag_sources = tag_sources;
+        self
+    }
+
+    /// The number of distinct sources that have scraped this story.
+    pub fn source_count(&self) -> usize {
+        self.scrapes.keys().map(|id| id.source).unique().count()
+    }
+
+    /// The distinct subsources (ie: subreddits) that this story was scraped from for the given source, in order.
+    pub fn subsources(&self, source: ScrapeSource) -> Vec<String> {
+        self.scrapes
+            .keys()
+            .filter(|id| id.source == source)
+            .filter_map(|id| id.subsource.clone())
+            .sorted()
+            .dedup()
+            .collect()
+    }
+
+    pub fn with_last_activity(mut self, last_activity: StoryDate) -> Self {
+        self.last_activity = last_activity;
+        self
+    }
+
+    pub fn with_ingested_at(mut self, ingested_at: Option<StoryDate>) -> Self {
+        self.ingested_at = ingested_at;
+        self
+    }
+
+    pub fn with_comments(mut self, comments: TypedScrapeMap<Option<u32>>) -> Self {
+        self.comments = comments;
+        self
+    }
+
+    /// Has this story seen activity since it was first seen, and was that activity recent (relative to `now`)?
+    pub fn is_updated(&self, now: StoryDate) -> bool {
+        self.last_activity - self.date >= StoryDuration::hours(1)
+            && now - self.last_activity <= StoryDuration::days(1)
+    }
+
+    /// Each of this story's tags, along with the sources that provided it. A tag may come from more than one
+    /// source, and tags without recorded provenance have no sources.
+    pub fn tags_with_source(&self) -> Vec<(String, Vec<TagSource>)> {
+        self.tags
+            .dump()
+            .map(|tag| {
+                let sources = self
+                    .tag_sources
+                    .iter()
+                    .filter(|(t, _)| *t == tag)
+                    .map(|(_, source)| *source)
+                    .sorted()
+                    .dedup()
+                    .collect();
+                (tag, sources)
+            })
+            .collect()
+    }
+
+    /// Compares two stories, ordering by score. Scores are compared in steps of the tie-break epsilon so that
+    /// scores differing only by rounding noise are equal while the comparison remains a total order. Equal scores
+    /// are broken by `tie_break`, with the story it would list first ordering greater.
+    pub fn compare_score(&self, other: &Self, tie_break: &TieBreakConfig) -> std::cmp::Ordering {
+        let step = |score: f32| (score / tie_break.epsilon).round();
         // Sort by score, but fall back to date if score is somehow a NaN (it shouldn't be, but we'll just be robust here)
-        f32::partial_cmp(&self.score, &other.score).unwrap_or_else(|| self.date.cmp(&other.date))
+        f32::partial_cmp(&step(self.score), &step(other.score))
+            .unwrap_or_else(|| self.date.cmp(&other.date))
+            .then_with(|| tie_break.compare_ties(other, self))
     }
 
     /// Compares two stories, ordering by date.
@@ -144,11 +274,17 @@ impl<S> Story<S> {
             score: self.score,
             url: self.url.to_string(),
             domain: self.url.host().to_owned(),
-            title: self.title.to_owned(),
+            title: render::truncate_title(&self.title),
             date: self.date,
             tags: self.render_tags(&eval.tagger),
             html: Default::default(),
             sources,
+            subreddits: self.subsources(ScrapeSource::Reddit),
+            last_activity: self.last_activity,
+            ingested_at: self.ingested_at,
+            comments: self.comments.clone(),
+            updated: self.is_updated(StoryDate::now()),
+            self_post: self.is_likely_self_post(),
         }
     }
 }
@@ -219,3 +355,75 @@ impl TagAcceptor for TagSet {
 pub trait TagAcceptor {
     fn tag(&mut self, s: &str);
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Stories with equal scores are ordered by the tie-breakers (here, the same date, so by their best source and
+    /// then by URL) regardless of input order.
+    #[test]
+    fn test_compare_score_tie_break() {
+        let date = StoryDate::year_month_day(2020, 1, 1).expect("Date failed");
+        let story = |url: &str, source: ScrapeSource, score: f32| {
+            let url = StoryUrl::parse(url).expect("URL");
+            let id = ScrapeId::new(source, None, url.to_string());
+            Story::new_from_parts("Title".into(), url, date, score, [], [(id, ())])
+        };
+        let stories = [
+            story("https://example.com/reddit", ScrapeSource::Reddit, 1.0),
+            story("https://example.com/lobsters", ScrapeSource::Lobsters, 1.0),
+            story("https://example.com/hn", ScrapeSource::HackerNews, 1.0),
+            story("https://example.com/slashdot", ScrapeSource::Slashdot, 1.0),
+            story("https://example.com/best", ScrapeSource::Reddit, 2.0),
+            // Within epsilon of the others, so still a tie
+            story("https://example.com/hn2", ScrapeSource::HackerNews, 1.0002),
+        ];
+        let order = |mut stories: Vec<&Story<()>>| {
+            let tie_break = TieBreakConfig::default();
+            stories.sort_by(|a, b| b.compare_score(a, &tie_break));
+            stories
+                .into_iter()
+                .map(|story| story.url.path_segments().join("/"))
+                .collect_vec()
+        };
+        let forward = order(stories.iter().collect());
+        let reverse = order(stories.iter().rev().collect());
+        assert_eq!(forward, reverse);
+        assert_eq!(forward[0], "best");
+        assert_eq!(
+            forward[3..],
+            ["lobsters".to_owned(), "slashdot".into(), "reddit".into()]
+        );
+        assert_eq!(forward[1..3].iter().sorted().collect_vec(), ["hn", "hn2"]);
+    }
+
+    /// Self-posts are marked when rendered, so the UI can tell them apart from external links.
+    #[test]
+    fn test_render_self_post() {
+        let eval = StoryEvaluator::new_for_test();
+        let date = StoryDate::year_month_day(2020, 1, 1).expect("Date failed");
+        let story = |url: &str| {
+            let url = StoryUrl::parse(url).expect("URL");
+            let id = ScrapeId::new(ScrapeSource::Reddit, Some("rust".into()), "abc123".into());
+            Story::new_from_parts("Title".into(), url, date, 1.0, [], [(id, ())])
+        };
+        let render = story("https://www.reddit.com/r/rust/comments/abc123/title/").render(&eval, 0);
+        assert!(render.self_post);
+        assert_eq!(
+            render.url,
+            "https://www.reddit.com/r/rust/comments/abc123/title/"
+        );
+        assert!(
+            !story("https://example.com/title")
+                .render(&eval, 0)
+                .self_post
+        );
+        // A link to some other reddit discussion isn't this story's self-post
+        assert!(
+            !story("https://www.reddit.com/r/rust/comments/def456/other/")
+                .render(&eval, 0)
+                .self_post
+        );
+    }
+}
@@ -3,6 +3,9 @@ use std::collections::HashMap;
 use progscrape_scrapers::{ScrapeId, StoryDate, TypedScrape, TypedScrapeMap};
 use serde::{Deserialize, Serialize};
 
+/// Titles longer than this many characters are cut short when rendered.
+const MAX_RENDER_TITLE_CHARS: usize = 250;
+
 /// Rendered story with all properties hydrated from the underlying scrapes. Extraneous data is removed at this point.
 #[derive(Clone, Default, Debug, Deserialize, Serialize)]
 pub struct StoryRender {
@@ -19,4 +22,54 @@ pub struct StoryRender {
     /// Only for our blog posts
     pub html: String,
     pub sources: TypedScrapeMap<Option<ScrapeId>>,
+    /// Every subreddit this story was posted to, in order.
+    #[serde(default)]
+    pub subreddits: Vec<String>,
+    /// The last time we saw activity for this story.
+    #[serde(default)]
+    pub last_activity: StoryDate,
+    /// When we first indexed this story, if known.
+    #[serde(default)]
+    pub ingested_at: Option<StoryDate>,
+    /// The comment count for each source that reports one.
+    #[serde(default)]
+    pub comments: TypedScrapeMap<Option<u32>>,
+    /// Has this story been seen again recently (ie: it's still being discussed)?
+    #[serde(default)]
+    pub updated: bool,
+    /// Is this a self-post, whose URL is its own discussion rather than an external link?
+    #[serde(default)]
+    pub self_post: bool,
+}
+
+/// Shorten a title to at most `MAX_RENDER_TITLE_CHARS` characters (not bytes), marking the cut with an ellipsis.
+pub(crate) fn truncate_title(title: &str) -> String {
+    match title.char_indices().nth(MAX_RENDER_TITLE_CHARS) {
+        Some(_) => {
+            let (end, _) = title
+                .char_indices()
+                .nth(MAX_RENDER_TITLE_CHARS - 1)
+                .expect("Title has enough characters");
+            format!("{}…", &title[..end])
+        }
+        None => title.to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_truncate_title() {
+        assert_eq!(truncate_title("A short title"), "A short title");
+
+        // Multibyte titles are measured in characters, and cut on a character boundary
+        let cjk = "漢".repeat(MAX_RENDER_TITLE_CHARS);
+        assert_eq!(truncate_title(&cjk), cjk);
+        let emoji = "🦀".repeat(MAX_RENDER_TITLE_CHARS + 10);
+        let truncated = truncate_title(&emoji);
+        assert_eq!(truncated.chars().count(), MAX_RENDER_TITLE_CHARS);
+        assert!(truncated.ends_with("🦀…"));
+    }
 }
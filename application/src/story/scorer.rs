@@ -1,4 +1,7 @@
+use itertools::Itertools;
 use serde::{Deserialize, Serialize};
+use std::{cmp::Ordering, collections::HashSet};
+use thiserror::Error;
 
 use progscrape_scrapers::{
     ExtractedScrapeCollection, ScrapeCore, ScrapeSource, StoryDate, StoryDuration, TypedScrape,
@@ -7,10 +10,171 @@ use progscrape_scrapers::{
 
 use super::Story;
 
-#[derive(Clone, Default, Serialize, Deserialize)]
+/// The default distance within which story scores are treated as ties when ordering stories. See
+/// [`TieBreakConfig`] for how ties are found and broken.
+pub const SCORE_EPSILON: f32 = 0.001;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TieBreak {
+    /// Newest story first
+    Date,
+    /// Story with the highest-priority source first
+    SourcePriority,
+    /// Normalized URL hash, which is arbitrary but stable across restarts
+    UrlHash,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct TieBreakConfig {
+    /// Scores within this distance of the highest score in a run of stories are considered equal, so no two stories
+    /// in a tie are further apart than this
+    pub epsilon: f32,
+    /// The tie-breakers to apply, in order
+    pub order: Vec<TieBreak>,
+    /// Sources in priority order, highest first (unlisted sources sort last). Defaults to the order in which we
+    /// trust each source's titles, [`ScrapeSource::title_priority`].
+    pub source_priority: Vec<ScrapeSource>,
+}
+
+impl Default for TieBreakConfig {
+    fn default() -> Self {
+        Self {
+            epsilon: SCORE_EPSILON,
+            order: vec![TieBreak::Date, TieBreak::SourcePriority, TieBreak::UrlHash],
+            source_priority: ScrapeSource::all()
+                .iter()
+                .copied()
+                .sorted_by_key(ScrapeSource::title_priority)
+                .collect(),
+        }
+    }
+}
+
+impl TieBreakConfig {
+    /// Sort stories by descending `score`. Stories are first ordered by score alone, and then each run of stories
+    /// where every score is within `epsilon` of the run's first (highest) score is re-ordered by the tie-breakers.
+    pub fn sort<S>(
+        &self,
+        stories: Vec<Story<S>>,
+        score: impl Fn(&Story<S>) -> f32,
+    ) -> Vec<Story<S>> {
+        let mut scored = stories
+            .into_iter()
+            .map(|story| (score(&story), story))
+            .collect_vec();
+        scored.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+
+        let mut sorted = Vec::with_capacity(scored.len());
+        let mut run: Vec<(f32, Story<S>)> = vec![];
+        for (score, story) in scored {
+            if run
+                .first()
+                .is_some_and(|(first, _)| first - score > self.epsilon)
+            {
+                sorted.extend(self.break_ties(std::mem::take(&mut run)));
+            }
+            run.push((score, story));
+        }
+        sorted.extend(self.break_ties(run));
+        sorted
+    }
+
+    /// Compare two stories with effectively equal scores by the tie-breakers, where the story listed first orders
+    /// lower.
+    pub fn compare_ties<S>(&self, a: &Story<S>, b: &Story<S>) -> Ordering {
+        self.tie_break_key(a).cmp(&self.tie_break_key(b))
+    }
+
+    fn break_ties<S>(&self, run: Vec<(f32, Story<S>)>) -> std::vec::IntoIter<Story<S>> {
+        run.into_iter()
+            .map(|(_, story)| story)
+            .sorted_by_cached_key(|story| self.tie_break_key(story))
+    }
+
+    /// Compute a key for ordering tied stories, where lower keys sort first.
+    fn tie_break_key<S>(&self, story: &Story<S>) -> Vec<i64> {
+        self.order
+            .iter()
+            .map(|tie_break| match tie_break {
+                TieBreak::Date => -story.date.timestamp(),
+                TieBreak::SourcePriority => story
+                    .scrapes
+                    .keys()
+                    .filter_map(|id| self.source_priority.iter().position(|s| *s == id.source))
+                    .min()
+                    .unwrap_or(self.source_priority.len())
+                    as i64,
+                TieBreak::UrlHash => story.url.normalization().hash(),
+            })
+            .collect_vec()
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct StoryScoreMultiSourceConfig {
     power: f32,
     factor: f32,
+    /// Only the best Reddit scrape is scored, so stories earn this much for each subreddit beyond the first that
+    /// they were posted to (before the Reddit weight is applied).
+    #[serde(default = "StoryScoreMultiSourceConfig::default_subreddit_factor")]
+    subreddit_factor: f32,
+    /// The most that posts to other subreddits can add to a story's score (before the Reddit weight is applied).
+    #[serde(default = "StoryScoreMultiSourceConfig::default_subreddit_max")]
+    subreddit_max: f32,
+}
+
+impl Default for StoryScoreMultiSourceConfig {
+    fn default() -> Self {
+        Self {
+            power: 0.0,
+            factor: 0.0,
+            subreddit_factor: Self::default_subreddit_factor(),
+            subreddit_max: Self::default_subreddit_max(),
+        }
+    }
+}
+
+impl StoryScoreMultiSourceConfig {
+    fn default_subreddit_factor() -> f32 {
+        5.0
+    }
+
+    fn default_subreddit_max() -> f32 {
+        15.0
+    }
+}
+
+/// How a source's rank turns into score: `base - slope * rank`, but never less than `floor`. Ranks are zero-based.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StoryScoreRankConfig {
+    base: f32,
+    slope: f32,
+    floor: f32,
+}
+
+impl Default for StoryScoreRankConfig {
+    fn default() -> Self {
+        Self {
+            base: 30.0,
+            slope: 1.0,
+            floor: 0.0,
+        }
+    }
+}
+
+/// Rewards stories that several sources picked up at around the same time, recently. The bonus is part of the score
+/// stored when a story is indexed, so it only fades as the story is rescored: the `rescore_hot_set` cron job does this
+/// for the front page, and must run well within `window_hours` for the bonus to expire on time.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct StoryScoreTrendingConfig {
+    /// Scrapes ingested further apart than this many hours earn no trending bonus, and the bonus expires this many
+    /// hours after the latest ingestion.
+    window_hours: f32,
+    /// The bonus for each scrape beyond the first when they all arrive at once.
+    factor: f32,
 }
 
 #[derive(Clone, Default, Serialize, Deserialize)]
@@ -18,8 +182,189 @@ pub struct StoryScoreConfig {
     age_breakpoint_days: [u32; 2],
     hour_scores: [f32; 3],
     service_rank: TypedScrapeMap<f32>,
+    /// The score for each rank, before it is multiplied by `service_rank`.
+    #[serde(default)]
+    rank: StoryScoreRankConfig,
+    /// A flat boost for stories seen on each source. Sources without a rank (ie: feeds, including the progscrape
+    /// blog) otherwise get no source-specific score, so this is how we keep them from being drowned out.
     service_boost: TypedScrapeMap<f32>,
+    /// Multiplies everything a source contributes to a story's score. Sources without a weight use 1.0.
+    #[serde(default)]
+    service_weight: TypedScrapeMap<Option<f32>>,
     multi_source: StoryScoreMultiSourceConfig,
+    /// A bonus of this much per `ln(points)` for sources that report points (ie: Hacker News). Zero disables it.
+    #[serde(default)]
+    points_bonus: f32,
+    /// The trending bonus is disabled unless configured.
+    #[serde(default)]
+    trending: Option<StoryScoreTrendingConfig>,
+    /// Stories scoring below this once aged are left off the front page. The front page is never padded with
+    /// stories below the floor, so it may come up short.
+    #[serde(default)]
+    front_page_min_score: Option<f32>,
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum StoryScoreConfigError {
+    #[error("Age breakpoints must be non-zero and increasing (got {0:?})")]
+    AgeBreakpoints([u32; 2]),
+    #[error("Hourly age scores must be finite and negative (got {0:?})")]
+    HourScores([f32; 3]),
+    #[error("Multi-source power and factor must be finite")]
+    MultiSource,
+    #[error("Subreddit factor and maximum must be finite and non-negative")]
+    MultiSubreddit,
+    #[error("Source weights must be finite and non-negative (got {1} for {0:?})")]
+    ServiceWeight(ScrapeSource, f32),
+    #[error("Points bonus must be finite and non-negative (got {0})")]
+    PointsBonus(f32),
+    #[error("Trending window must be positive and factor finite")]
+    Trending,
+    #[error("Rank base, slope and floor must be finite, and the slope non-negative")]
+    Rank,
+    #[error("Front page minimum score must be finite (got {0})")]
+    FrontPageMinScore(f32),
+}
+
+impl StoryScoreConfig {
+    /// Reject configurations that would make age scoring degenerate (the all-zero default, for example), where
+    /// stories would never age or the breakpoint segments would be empty.
+    pub fn validate(&self) -> Result<(), StoryScoreConfigError> {
+        let [breakpoint1, breakpoint2] = self.age_breakpoint_days;
+        if breakpoint1 == 0 || breakpoint2 <= breakpoint1 {
+            return Err(StoryScoreConfigError::AgeBreakpoints(
+                self.age_breakpoint_days,
+            ));
+        }
+        if !self
+            .hour_scores
+            .iter()
+            .all(|score| score.is_finite() && *score < 0.0)
+        {
+            return Err(StoryScoreConfigError::HourScores(self.hour_scores));
+        }
+        let multi_source = &self.multi_source;
+        if !multi_source.power.is_finite() || !multi_source.factor.is_finite() {
+            return Err(StoryScoreConfigError::MultiSource);
+        }
+        if !multi_source.subreddit_factor.is_finite()
+            || multi_source.subreddit_factor < 0.0
+            || !multi_source.subreddit_max.is_finite()
+            || multi_source.subreddit_max < 0.0
+        {
+            return Err(StoryScoreConfigError::MultiSubreddit);
+        }
+        let rank = &self.rank;
+        if !rank.base.is_finite()
+            || !rank.slope.is_finite()
+            || !rank.floor.is_finite()
+            || rank.slope < 0.0
+        {
+            return Err(StoryScoreConfigError::Rank);
+        }
+        for (source, weight) in self.service_weight.iter() {
+            if let Some(weight) = *weight {
+                if !weight.is_finite() || weight < 0.0 {
+                    return Err(StoryScoreConfigError::ServiceWeight(source, weight));
+                }
+            }
+        }
+        if !self.points_bonus.is_finite() || self.points_bonus < 0.0 {
+            return Err(StoryScoreConfigError::PointsBonus(self.points_bonus));
+        }
+        if let Some(trending) = &self.trending {
+            if !trending.window_hours.is_finite()
+                || trending.window_hours <= 0.0
+                || !trending.factor.is_finite()
+            {
+                return Err(StoryScoreConfigError::Trending);
+            }
+        }
+        if let Some(min_score) = self.front_page_min_score {
+            if !min_score.is_finite() {
+                return Err(StoryScoreConfigError::FrontPageMinScore(min_score));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The parts of a single scrape that a story's score depends on. These are stored with each story, so that it can
+/// be rescored without loading its scrapes.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ScrapeScoreInput {
+    pub source: ScrapeSource,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subsource: Option<String>,
+    /// The host of this scrape's URL.
+    pub host: String,
+    /// The length of this scrape's title, in characters.
+    pub title_chars: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rank: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub points: Option<u32>,
+    /// The comment count we score, which for some sources isn't reported as the scrape's comment count.
+    #[serde(default)]
+    pub comments: u32,
+    /// Reddit's upvotes and upvote ratio.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub upvotes: Option<(u32, f32)>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ingested_at: Option<StoryDate>,
+}
+
+impl ScrapeScoreInput {
+    fn new(core: &ScrapeCore, scrape: &TypedScrape) -> Self {
+        let (comments, upvotes) = match scrape {
+            TypedScrape::HackerNews(hn) => (hn.data.comments, None),
+            TypedScrape::Reddit(reddit) => (
+                reddit.data.num_comments,
+                Some((reddit.data.upvotes, reddit.data.upvote_ratio)),
+            ),
+            TypedScrape::Lobsters(lobsters) => (lobsters.data.num_comments, None),
+            TypedScrape::Slashdot(slashdot) => (slashdot.data.num_comments, None),
+            TypedScrape::Feed(_) => (0, None),
+            TypedScrape::Mastodon(mastodon) => (mastodon.data.replies, None),
+        };
+        Self {
+            source: scrape.id.source,
+            subsource: scrape.id.subsource.clone(),
+            host: core.url.host().to_owned(),
+            title_chars: core.title.chars().count(),
+            rank: core.rank,
+            points: core.points,
+            comments,
+            upvotes,
+            ingested_at: scrape.ingested_at,
+        }
+    }
+}
+
+/// Everything a story's score depends on.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StoryScoreInput {
+    /// The length of the story's title, in characters.
+    pub title_chars: usize,
+    pub host: String,
+    pub url_norm_hash: i64,
+    pub scrapes: Vec<ScrapeScoreInput>,
+}
+
+impl From<&ExtractedScrapeCollection<'_>> for StoryScoreInput {
+    fn from(scrapes: &ExtractedScrapeCollection<'_>) -> Self {
+        let url = scrapes.url();
+        Self {
+            title_chars: scrapes.title().chars().count(),
+            host: url.host().to_owned(),
+            url_norm_hash: url.normalization().hash(),
+            scrapes: scrapes
+                .scrapes
+                .values()
+                .map(|(core, scrape)| ScrapeScoreInput::new(core, scrape))
+                .collect(),
+        }
+    }
 }
 
 pub enum StoryScoreType {
@@ -39,9 +384,17 @@ pub enum StoryScore {
     PoorUpvoteRatio,
     UpvoteCount,
     CommentCount,
+    /// A bonus that grows with the log of the points reported by the source.
+    Points,
     MetaStory,
+    /// A bonus for stories posted to more than one subreddit.
+    MultiSubreddit,
+    /// A bonus for stories whose scrapes were ingested close together.
+    Trending,
     Position(ScrapeSource),
     Source(ScrapeSource),
+    /// The multiplier applied to a source's contribution. This is informational and not part of the score.
+    Weight(ScrapeSource),
 }
 
 impl Serialize for StoryScore {
@@ -53,6 +406,7 @@ impl Serialize for StoryScore {
     }
 }
 
+#[derive(Clone)]
 pub struct StoryScorer {
     config: StoryScoreConfig,
 }
@@ -76,6 +430,11 @@ impl StoryScorer {
         stories.sort_by_cached_key(|story| (new_score(story) * -100000.0) as i64);
     }
 
+    /// The aged score that a story needs to make the front page, if any.
+    pub fn front_page_min_score(&self) -> Option<f32> {
+        self.config.front_page_min_score
+    }
+
     #[inline(always)]
     pub fn score_age(&self, age: StoryDuration) -> f32 {
         let breakpoint1 = StoryDuration::days(self.config.age_breakpoint_days[0] as i64);
@@ -102,34 +461,68 @@ impl StoryScorer {
         }
     }
 
+    /// The score for a zero-based rank, before the per-source multiplier.
+    fn score_rank(&self, rank: usize) -> f32 {
+        let rank_config = &self.config.rank;
+        (rank_config.base - rank_config.slope * rank as f32).max(rank_config.floor)
+    }
+
+    /// The multiplier for everything this source contributes to a story's score.
+    pub fn service_weight(&self, source: ScrapeSource) -> f32 {
+        self.config.service_weight.get(source).unwrap_or(1.0)
+    }
+
+    /// The trending bonus for a story as of `now`: each scrape beyond the first earns the configured factor, scaled
+    /// down linearly as the spread of the scrapes' ingestion times approaches the window, and again as the time since
+    /// the latest ingestion does. Scrapes without an ingestion time are ignored.
+    fn score_trending(
+        config: &StoryScoreTrendingConfig,
+        input: &StoryScoreInput,
+        now: StoryDate,
+    ) -> f32 {
+        let ingested = input
+            .scrapes
+            .iter()
+            .filter_map(|scrape| scrape.ingested_at)
+            .collect::<Vec<_>>();
+        let (Some(first), Some(last)) = (ingested.iter().min(), ingested.iter().max()) else {
+            return 0.0;
+        };
+        let hours =
+            |duration: StoryDuration| duration.num_milliseconds() as f32 / (60.0 * 60.0 * 1000.0);
+        let closeness = (1.0 - hours(*last - *first) / config.window_hours).max(0.0);
+        let recency = (1.0 - hours(now - *last).max(0.0) / config.window_hours).max(0.0);
+        (ingested.len() - 1) as f32 * config.factor * closeness * recency
+    }
+
+    /// Is this an image host whose links we penalize?
+    pub fn is_penalized_host(host: &str) -> bool {
+        host.contains("gfycat") || host.contains("imgur") || host.contains("i.reddit.com")
+    }
+
     /// Score a single scrape so that we can evaluate which of multiple stories we want to
     /// choose.
     #[inline(always)]
-    fn score_single<T: FnMut(StoryScore, f32)>(
-        &self,
-        scrape: &TypedScrape,
-        core: &ScrapeCore,
-        mut accum: T,
-    ) {
+    fn score_single<T: FnMut(StoryScore, f32)>(&self, scrape: &ScrapeScoreInput, mut accum: T) {
         use StoryScore::*;
 
-        let url = core.url;
-
-        let source = scrape.id.source;
-        if let Some(rank) = core.rank {
+        let source = scrape.source;
+        if let Some(rank) = scrape.rank {
             accum(
                 Position(source),
-                (30.0 - rank.clamp(0, 30) as f32) * self.config.service_rank.get(source),
+                self.score_rank(rank) * self.config.service_rank.get(source),
             );
         }
         let boost = *self.config.service_boost.get(source);
         if boost > f32::EPSILON {
             accum(Source(source), boost);
         }
-        if url.host().contains("gfycat")
-            || url.host().contains("imgur")
-            || url.host().contains("i.reddit.com")
-        {
+        if let Some(points) = scrape.points {
+            if self.config.points_bonus > f32::EPSILON && points > 1 {
+                accum(Points, (points as f32).ln() * self.config.points_bonus);
+            }
+        }
+        if Self::is_penalized_host(&scrape.host) {
             if source == ScrapeSource::HackerNews {
                 accum(ImageLink, -5.0);
             } else {
@@ -137,117 +530,138 @@ impl StoryScorer {
             }
         }
 
-        match scrape {
-            TypedScrape::HackerNews(hn) => {
-                if hn.data.comments > 100 {
-                    accum(CommentCount, 5.0);
-                }
-            }
-            TypedScrape::Reddit(reddit) => {
+        match source {
+            ScrapeSource::Reddit => {
                 // Penalize Reddit self links
-                if url.host().contains("reddit.com") {
+                if scrape.host.contains("reddit.com") {
                     accum(SelfLink, -20.0);
                 }
 
-                // Penalize a long title if reddit is a source
-                if core.title.len() > 130 {
+                // Penalize a long title if reddit is a source. Titles are measured in characters so that non-ASCII
+                // titles aren't penalized for their encoding.
+                if scrape.title_chars > 130 {
                     accum(LongRedditTitle, -5.0);
                 }
 
-                if reddit.data.upvote_ratio < 0.6 {
+                let (upvotes, upvote_ratio) = scrape.upvotes.unwrap_or_default();
+                if upvote_ratio < 0.6 {
                     accum(PoorUpvoteRatio, -20.0);
                 }
-                if reddit.data.upvotes < 10 {
+                if upvotes < 10 {
                     accum(UpvoteCount, -20.0);
-                } else if reddit.data.upvotes > 10 {
+                } else if upvotes > 10 {
                     accum(UpvoteCount, 10.0);
-                } else if reddit.data.upvotes > 100 {
+                } else if upvotes > 100 {
                     accum(UpvoteCount, 15.0);
                 }
-                if reddit.data.num_comments < 10 {
+                if scrape.comments < 10 {
                     accum(CommentCount, -5.0);
-                } else if reddit.data.num_comments > 10 {
-                    accum(CommentCount, 5.0);
-                }
-            }
-            TypedScrape::Lobsters(lobsters) => {
-                // This won't get triggered until we start scraping lobsters comment counts
-                if lobsters.data.num_comments > 100 {
+                } else if scrape.comments > 10 {
                     accum(CommentCount, 5.0);
                 }
             }
-            TypedScrape::Slashdot(slashdot) => {
-                if slashdot.data.num_comments > 100 {
+            ScrapeSource::Feed => {}
+            // This won't get triggered for lobsters until we start scraping lobsters comment counts
+            _ => {
+                if scrape.comments > 100 {
                     accum(CommentCount, 5.0);
                 }
             }
-            TypedScrape::Feed(_feed) => {}
         }
     }
 
     #[inline(always)]
     fn score_impl<T: FnMut(StoryScore, f32)>(
         &self,
-        scrapes: &ExtractedScrapeCollection,
-        best: TypedScrapeMap<Option<(&TypedScrape, &ScrapeCore, f32)>>,
+        input: &StoryScoreInput,
+        best: TypedScrapeMap<Option<(&ScrapeScoreInput, f32)>>,
+        now: StoryDate,
         mut accum: T,
     ) {
         use StoryScore::*;
 
-        let title = scrapes.title();
-        let url = scrapes.url();
-
         // Small random shuffle for stories to mix up the front page a bit
-        accum(
-            Random,
-            (url.normalization().hash() % 6000000) as f32 / 1000000.0,
-        );
+        accum(Random, (input.url_norm_hash % 6000000) as f32 / 1000000.0);
 
         accum(
             SourceCount,
-            (scrapes.scrapes.len() as f32).powf(self.config.multi_source.power)
+            (input.scrapes.len() as f32).powf(self.config.multi_source.power)
                 * self.config.multi_source.factor,
         );
 
-        for (scrape, core, _) in best.values().flatten() {
-            self.score_single(scrape, core, &mut accum);
+        if let Some(trending) = &self.config.trending {
+            let score = Self::score_trending(trending, input, now);
+            if score != 0.0 {
+                accum(Trending, score);
+            }
+        }
+
+        // Only the best reddit scrape is scored, so give a small (capped) bonus for each additional subreddit
+        let subreddits = input
+            .scrapes
+            .iter()
+            .filter(|scrape| scrape.source == ScrapeSource::Reddit)
+            .filter_map(|scrape| scrape.subsource.as_deref())
+            .collect::<HashSet<_>>()
+            .len();
+        if subreddits > 1 {
+            let multi_source = &self.config.multi_source;
+            let bonus = ((subreddits - 1) as f32 * multi_source.subreddit_factor)
+                .min(multi_source.subreddit_max);
+            accum(
+                MultiSubreddit,
+                bonus * self.service_weight(ScrapeSource::Reddit),
+            );
+        }
+
+        for (scrape, _) in best.values().flatten() {
+            let weight = self.service_weight(scrape.source);
+            self.score_single(scrape, |score_type, score| {
+                accum(score_type, score * weight)
+            });
         }
 
         // Boost our own stories, once only
-        if url.host().contains("progscrape") {
+        if input.host.contains("progscrape") {
             accum(MetaStory, 50.0);
         }
 
         // Penalize a really long title regardless of source
-        if title.len() > 250 {
+        if input.title_chars > 250 {
             accum(LongTitle, -15.0);
         }
     }
 
-    fn calculate_best_scrapes<'a, 'b>(
+    fn calculate_best_scrapes<'a>(
         &self,
-        scrapes: &'a ExtractedScrapeCollection<'b>,
-    ) -> TypedScrapeMap<Option<(&'a TypedScrape, &'a ScrapeCore<'b>, f32)>> {
+        input: &'a StoryScoreInput,
+    ) -> TypedScrapeMap<Option<(&'a ScrapeScoreInput, f32)>> {
         let mut service_scrapes = TypedScrapeMap::new();
-        for (id, (core, scrape)) in &scrapes.scrapes {
+        for scrape in &input.scrapes {
             let mut score_total = 0_f32;
             let accum = |_, score| score_total += score;
-            self.score_single(scrape, core, accum);
-            if let Some((_, _, existing_score)) = service_scrapes.get(id.source) {
+            self.score_single(scrape, accum);
+            if let Some((_, existing_score)) = service_scrapes.get(scrape.source) {
                 if *existing_score > score_total {
                     continue;
                 }
             }
-            service_scrapes.set(id.source, Some((*scrape, core, score_total)));
+            service_scrapes.set(scrape.source, Some((scrape, score_total)));
         }
         service_scrapes
     }
 
-    pub fn score(&self, scrapes: &ExtractedScrapeCollection) -> f32 {
-        let best = self.calculate_best_scrapes(scrapes);
+    /// Score a story as of `now`, which only matters to the trending bonus. The age penalty is applied separately.
+    pub fn score(&self, scrapes: &ExtractedScrapeCollection, now: StoryDate) -> f32 {
+        self.score_input(&scrapes.into(), now)
+    }
+
+    /// Score a story from its stored score inputs, without its scrapes.
+    pub fn score_input(&self, input: &StoryScoreInput, now: StoryDate) -> f32 {
+        let best = self.calculate_best_scrapes(input);
         let mut score_total = 0_f32;
         let accum = |_, score| score_total += score;
-        self.score_impl(scrapes, best, accum);
+        self.score_impl(input, best, now, accum);
         score_total
     }
 
@@ -256,11 +670,17 @@ impl StoryScorer {
         scrapes: &ExtractedScrapeCollection,
         now: StoryDate,
     ) -> Vec<(StoryScore, f32)> {
-        let best = self.calculate_best_scrapes(scrapes);
+        let input = scrapes.into();
+        let best = self.calculate_best_scrapes(&input);
         let mut score_bits = vec![];
         let mut accum = |score_type, score| score_bits.push((score_type, score));
         accum(StoryScore::Age, self.score_age(now - scrapes.earliest));
-        self.score_impl(scrapes, best, accum);
+        for (source, scrape) in best.iter() {
+            if scrape.is_some() && self.config.service_weight.get(source).is_some() {
+                accum(StoryScore::Weight(source), self.service_weight(source));
+            }
+        }
+        self.score_impl(&input, best, now, accum);
         score_bits
     }
 }
@@ -268,20 +688,35 @@ impl StoryScorer {
 #[cfg(test)]
 mod test {
     use super::*;
+    use progscrape_scrapers::{
+        feed::FeedStory, hacker_news::HackerNewsStory, lobsters::LobstersStory,
+        reddit::RedditStory, ScrapeCollection, ScrapeConfig, ScrapeExtractor, StoryUrl,
+    };
 
-    /// Make sure that the scores are decreasing.
-    #[test]
-    fn test_age_score() {
-        let config = StoryScoreConfig {
+    fn score_config() -> StoryScoreConfig {
+        StoryScoreConfig {
             age_breakpoint_days: [1, 30],
             hour_scores: [-5.0, -3.0, -0.1],
             service_rank: TypedScrapeMap::new_with_all(1.0),
+            rank: Default::default(),
             service_boost: TypedScrapeMap::new_with_all(1.0),
+            service_weight: TypedScrapeMap::new(),
             multi_source: StoryScoreMultiSourceConfig {
                 power: 2.0,
                 factor: 10.0,
+                ..Default::default()
             },
-        };
+            points_bonus: 0.0,
+            trending: None,
+            front_page_min_score: None,
+        }
+    }
+
+    /// Make sure that the scores are decreasing.
+    #[test]
+    fn test_age_score() {
+        let config = score_config();
+        assert_eq!(config.validate(), Ok(()));
         let mut last_score = f32::MAX;
         let scorer = StoryScorer::new(&config);
         for i in 0..StoryDuration::days(60).num_hours() {
@@ -290,4 +725,409 @@ mod test {
             last_score = score;
         }
     }
+
+    #[test]
+    fn test_validate_config() {
+        assert_eq!(
+            StoryScoreConfig::default().validate(),
+            Err(StoryScoreConfigError::AgeBreakpoints([0, 0]))
+        );
+
+        let mut config = score_config();
+        config.age_breakpoint_days = [30, 1];
+        assert_eq!(
+            config.validate(),
+            Err(StoryScoreConfigError::AgeBreakpoints([30, 1]))
+        );
+
+        let mut config = score_config();
+        config.hour_scores = [-5.0, 0.0, -0.1];
+        assert_eq!(
+            config.validate(),
+            Err(StoryScoreConfigError::HourScores([-5.0, 0.0, -0.1]))
+        );
+
+        let mut config = score_config();
+        config.multi_source.power = f32::NAN;
+        assert_eq!(config.validate(), Err(StoryScoreConfigError::MultiSource));
+
+        let mut config = score_config();
+        config.multi_source.subreddit_factor = -1.0;
+        assert_eq!(
+            config.validate(),
+            Err(StoryScoreConfigError::MultiSubreddit)
+        );
+
+        let mut config = score_config();
+        config.multi_source.subreddit_max = f32::INFINITY;
+        assert_eq!(
+            config.validate(),
+            Err(StoryScoreConfigError::MultiSubreddit)
+        );
+
+        let mut config = score_config();
+        config.service_weight.reddit = Some(-1.0);
+        assert_eq!(
+            config.validate(),
+            Err(StoryScoreConfigError::ServiceWeight(
+                ScrapeSource::Reddit,
+                -1.0
+            ))
+        );
+
+        let mut config = score_config();
+        config.rank.slope = -1.0;
+        assert_eq!(config.validate(), Err(StoryScoreConfigError::Rank));
+
+        let mut config = score_config();
+        config.trending = Some(StoryScoreTrendingConfig {
+            window_hours: 0.0,
+            factor: 10.0,
+        });
+        assert_eq!(config.validate(), Err(StoryScoreConfigError::Trending));
+
+        let mut config = score_config();
+        config.points_bonus = -1.0;
+        assert_eq!(
+            config.validate(),
+            Err(StoryScoreConfigError::PointsBonus(-1.0))
+        );
+
+        let mut config = score_config();
+        config.front_page_min_score = Some(f32::INFINITY);
+        assert_eq!(
+            config.validate(),
+            Err(StoryScoreConfigError::FrontPageMinScore(f32::INFINITY))
+        );
+    }
+
+    #[test]
+    fn test_feed_boost() {
+        let extractor = ScrapeExtractor::new(&ScrapeConfig::default());
+        let date = StoryDate::year_month_day(2020, 1, 1).expect("Date failed");
+        let url = StoryUrl::parse("https://example.com/story").expect("URL");
+        // An unranked HN story without comments gets no source-specific score, just like a feed story
+        let feed = ScrapeCollection::new_from_one(
+            FeedStory::new("1", date, "A story", url.clone(), vec![]).into(),
+        );
+        let hn = ScrapeCollection::new_from_one(
+            HackerNewsStory::new("1", date, "A story", url, 0, 0, 0, String::new()).into(),
+        );
+
+        let mut config = score_config();
+        config.service_boost = TypedScrapeMap::new();
+        let scorer = StoryScorer::new(&config);
+        assert_eq!(
+            scorer.score(&feed.extract(&extractor), date),
+            scorer.score(&hn.extract(&extractor), date)
+        );
+
+        config.service_boost.feed = 10.0;
+        let scorer = StoryScorer::new(&config);
+        assert!(
+            scorer.score(&feed.extract(&extractor), date)
+                > scorer.score(&hn.extract(&extractor), date)
+        );
+    }
+
+    #[test]
+    fn test_multi_subreddit() {
+        let extractor = ScrapeExtractor::new(&ScrapeConfig::default());
+        let date = StoryDate::year_month_day(2020, 1, 1).expect("Date failed");
+        let url = StoryUrl::parse("https://example.com/story").expect("URL");
+        let reddit = |id, subreddit| {
+            RedditStory::new_subsource_with_defaults(id, subreddit, date, "A story", url.clone())
+                .into()
+        };
+        let one = ScrapeCollection::new_from_one(reddit("1", "rust"));
+        let mut two = ScrapeCollection::new_from_one(reddit("1", "rust"));
+        two.merge(reddit("2", "programming"));
+        // Cross-posts to the same subreddit don't count
+        let mut same = ScrapeCollection::new_from_one(reddit("1", "rust"));
+        same.merge(reddit("2", "rust"));
+        let mut many = ScrapeCollection::new_from_one(reddit("1", "rust"));
+        for (id, subreddit) in [("2", "programming"), ("3", "linux"), ("4", "golang")] {
+            many.merge(reddit(id, subreddit));
+        }
+
+        // Disable the multi-source score so that we only see the subreddit bonus
+        let mut config = score_config();
+        config.multi_source.factor = 0.0;
+        config.multi_source.subreddit_max = 12.0;
+        let scorer = StoryScorer::new(&config);
+        let one = scorer.score(&one.extract(&extractor), date);
+        assert_eq!(scorer.score(&same.extract(&extractor), date), one);
+        assert_eq!(scorer.score(&two.extract(&extractor), date), one + 5.0);
+        // Three more subreddits would earn 15.0, but the bonus is capped
+        assert_eq!(scorer.score(&many.extract(&extractor), date), one + 12.0);
+    }
+
+    #[test]
+    fn test_service_weight() {
+        let extractor = ScrapeExtractor::new(&ScrapeConfig::default());
+        let date = StoryDate::year_month_day(2020, 1, 1).expect("Date failed");
+        let url = StoryUrl::parse("https://example.com/story").expect("URL");
+        let lobsters = ScrapeCollection::new_from_one(
+            LobstersStory::new_with_defaults("1", date, "A story", url.clone()).into(),
+        );
+        // A reddit story that triggers none of reddit's upvote, ratio or comment scoring
+        let mut reddit = RedditStory::new_with_defaults("1", date, "A story", url);
+        reddit.data.upvotes = 10;
+        reddit.data.upvote_ratio = 1.0;
+        reddit.data.num_comments = 10;
+        let reddit = ScrapeCollection::new_from_one(reddit.into());
+        let lobsters = lobsters.extract(&extractor);
+        let reddit = reddit.extract(&extractor);
+
+        let mut config = score_config();
+        config.service_boost = TypedScrapeMap::new_with_all(10.0);
+        let scorer = StoryScorer::new(&config);
+        assert_eq!(scorer.score(&lobsters, date), scorer.score(&reddit, date));
+
+        config.service_weight.lobsters = Some(3.0);
+        let scorer = StoryScorer::new(&config);
+        assert!(scorer.score(&lobsters, date) > scorer.score(&reddit, date));
+
+        // Only configured weights for the story's sources are reported
+        let detail = scorer.score_detail(&lobsters, date);
+        let weights = detail
+            .iter()
+            .filter_map(|(score_type, score)| match score_type {
+                StoryScore::Weight(source) => Some((*source, *score)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(weights, vec![(ScrapeSource::Lobsters, 3.0)]);
+    }
+
+    #[test]
+    fn test_points_bonus() {
+        let extractor = ScrapeExtractor::new(&ScrapeConfig::default());
+        let date = StoryDate::year_month_day(2020, 1, 1).expect("Date failed");
+        let url = StoryUrl::parse("https://example.com/story").expect("URL");
+        let hn = |points| {
+            ScrapeCollection::new_from_one(
+                HackerNewsStory::new(
+                    "1",
+                    date,
+                    "A story",
+                    url.clone(),
+                    points,
+                    0,
+                    1,
+                    String::new(),
+                )
+                .into(),
+            )
+        };
+        let (low, high) = (hn(5), hn(500));
+
+        // Without the bonus, points don't matter
+        let mut config = score_config();
+        let scorer = StoryScorer::new(&config);
+        assert_eq!(
+            scorer.score(&low.extract(&extractor), date),
+            scorer.score(&high.extract(&extractor), date)
+        );
+
+        config.points_bonus = 2.0;
+        let scorer = StoryScorer::new(&config);
+        let (low, high) = (
+            scorer.score(&low.extract(&extractor), date),
+            scorer.score(&high.extract(&extractor), date),
+        );
+        assert!(high > low, "{high} > {low}");
+        assert!((high - low - 2.0 * 100_f32.ln()).abs() < 0.01);
+    }
+
+    /// Long-title penalties count characters rather than bytes.
+    #[test]
+    fn test_long_title_chars() {
+        let extractor = ScrapeExtractor::new(&ScrapeConfig::default());
+        let date = StoryDate::year_month_day(2020, 1, 1).expect("Date failed");
+        let url = StoryUrl::parse("https://example.com/story").expect("URL");
+        let scorer = StoryScorer::new(&score_config());
+        let penalties = |title: String| {
+            let story = ScrapeCollection::new_from_one(
+                RedditStory::new_subsource_with_defaults("1", "rust", date, &title, url.clone())
+                    .into(),
+            );
+            scorer
+                .score_detail(&story.extract(&extractor), date)
+                .into_iter()
+                .filter(|(score, _)| {
+                    matches!(score, StoryScore::LongRedditTitle | StoryScore::LongTitle)
+                })
+                .map(|(score, _)| format!("{score:?}"))
+                .collect::<Vec<_>>()
+        };
+
+        // Well under the limits visually, but several times longer in bytes
+        assert!(penalties("🦀".repeat(100)).is_empty());
+        assert!(penalties("漢字".repeat(60)).is_empty());
+        assert_eq!(penalties("🦀".repeat(131)), vec!["LongRedditTitle"]);
+        assert_eq!(
+            penalties("漢".repeat(251)),
+            vec!["LongRedditTitle", "LongTitle"]
+        );
+    }
+
+    /// A story picked up by several sources at once outscores one that trickled in over days, but only when the
+    /// trending bonus is enabled and only while the burst is recent.
+    #[test]
+    fn test_trending() {
+        let extractor = ScrapeExtractor::new(&ScrapeConfig::default());
+        let date = StoryDate::year_month_day(2020, 1, 1).expect("Date failed");
+        let url = StoryUrl::parse("https://example.com/story").expect("URL");
+        let story = |hours_apart: i64| {
+            let mut scrapes: Vec<TypedScrape> = vec![
+                HackerNewsStory::new_with_defaults("1", date, "A story", url.clone()).into(),
+                RedditStory::new_subsource_with_defaults("1", "rust", date, "A story", url.clone())
+                    .into(),
+                LobstersStory::new_with_defaults("1", date, "A story", url.clone()).into(),
+            ];
+            for (i, scrape) in scrapes.iter_mut().enumerate() {
+                scrape.ingested_at =
+                    StoryDate::from_seconds(date.timestamp() + i as i64 * hours_apart * 60 * 60);
+            }
+            ScrapeCollection::new_from_iter(scrapes.into_iter())
+        };
+        let (burst, trickle) = (story(0), story(36));
+
+        let mut config = score_config();
+        let scorer = StoryScorer::new(&config);
+        assert_eq!(
+            scorer.score(&burst.extract(&extractor), date),
+            scorer.score(&trickle.extract(&extractor), date)
+        );
+
+        config.trending = Some(StoryScoreTrendingConfig {
+            window_hours: 24.0,
+            factor: 10.0,
+        });
+        assert_eq!(config.validate(), Ok(()));
+        let scorer = StoryScorer::new(&config);
+        let (burst, trickle) = (burst.extract(&extractor), trickle.extract(&extractor));
+        let (burst_score, trickle_score) =
+            (scorer.score(&burst, date), scorer.score(&trickle, date));
+        assert!(
+            burst_score > trickle_score,
+            "{burst_score} > {trickle_score}"
+        );
+        assert_eq!(burst_score - trickle_score, 20.0);
+
+        // The bonus fades as the burst recedes into the past, and is gone a window after it
+        let later = |hours: i64| {
+            let now = StoryDate::from_seconds(date.timestamp() + hours * 60 * 60).expect("Date");
+            scorer.score(&burst, now)
+        };
+        assert_eq!(later(12), trickle_score + 10.0);
+        assert_eq!(later(24), trickle_score);
+        assert_eq!(later(48), trickle_score);
+    }
+
+    /// Rank scores fall off linearly and bottom out at the floor, even on later pages.
+    #[test]
+    fn test_rank_score() {
+        let mut config = score_config();
+        let scorer = StoryScorer::new(&config);
+        // Positions 1, 30 and 90 are zero-based ranks 0, 29 and 89
+        assert_eq!(scorer.score_rank(0), 30.0);
+        assert_eq!(scorer.score_rank(29), 1.0);
+        assert_eq!(scorer.score_rank(89), 0.0);
+
+        // A shallower slope spreads the score over three pages, and the floor keeps deep ranks from going negative
+        config.rank = StoryScoreRankConfig {
+            base: 30.0,
+            slope: 0.25,
+            floor: 1.0,
+        };
+        let scorer = StoryScorer::new(&config);
+        assert_eq!(scorer.score_rank(0), 30.0);
+        assert_eq!(scorer.score_rank(29), 22.75);
+        assert_eq!(scorer.score_rank(89), 7.75);
+        assert_eq!(scorer.score_rank(500), 1.0);
+    }
+
+    fn story(source: ScrapeSource, url: &str, day: u32) -> Story<()> {
+        Story::new_from_parts(
+            "title".into(),
+            StoryUrl::parse(url).expect("url"),
+            StoryDate::year_month_day(2020, 1, day).expect("date"),
+            10.0,
+            vec![],
+            [(source.id(url), ())],
+        )
+    }
+
+    #[test]
+    fn test_tie_break() {
+        let sort = |config: &TieBreakConfig, stories: Vec<Story<()>>| {
+            config
+                .sort(stories, |s| s.score)
+                .into_iter()
+                .map(|s| s.url.raw().to_owned())
+                .collect_vec()
+        };
+        let stories = || {
+            vec![
+                story(ScrapeSource::Reddit, "http://a.com/", 1),
+                story(ScrapeSource::HackerNews, "http://b.com/", 1),
+                story(ScrapeSource::Lobsters, "http://c.com/", 2),
+            ]
+        };
+
+        // Newest first, then by source priority
+        let config = TieBreakConfig {
+            source_priority: vec![ScrapeSource::HackerNews, ScrapeSource::Reddit],
+            ..Default::default()
+        };
+        assert_eq!(
+            sort(&config, stories()),
+            vec!["http://c.com/", "http://b.com/", "http://a.com/"]
+        );
+
+        // By default, sources are prioritized by how much we trust their titles
+        assert_eq!(
+            sort(&TieBreakConfig::default(), stories()),
+            vec!["http://c.com/", "http://b.com/", "http://a.com/"]
+        );
+
+        // Source priority first, with unlisted sources last
+        let config = TieBreakConfig {
+            order: vec![TieBreak::SourcePriority, TieBreak::Date],
+            source_priority: vec![ScrapeSource::Reddit, ScrapeSource::HackerNews],
+            ..Default::default()
+        };
+        assert_eq!(
+            sort(&config, stories()),
+            vec!["http://a.com/", "http://b.com/", "http://c.com/"]
+        );
+
+        // Scores within epsilon of each other are still a tie, but a larger difference is not
+        let mut close = stories();
+        close[1].score += 0.0001;
+        assert_eq!(sort(&config, close.clone())[0], "http://a.com/");
+        close[2].score += 1.0;
+        assert_eq!(sort(&config, close)[0], "http://c.com/");
+
+        // A run is bounded by epsilon from its highest score: each step here is within epsilon, but the lowest story
+        // is further than that from the highest, so it doesn't chain onto the tie
+        let epsilon = config.epsilon;
+        let mut chained = stories();
+        chained[1].score += 0.6 * epsilon;
+        chained[2].score += 1.2 * epsilon;
+        assert_eq!(
+            sort(&config, chained),
+            vec!["http://b.com/", "http://c.com/", "http://a.com/"]
+        );
+
+        // A single step of more than epsilon splits the run
+        let mut split = stories();
+        split[2].score += 1.5 * epsilon;
+        assert_eq!(
+            sort(&config, split),
+            vec!["http://c.com/", "http://a.com/", "http://b.com/"]
+        );
+    }
 }
@@ -1,7 +1,9 @@
 use std::collections::{HashMap, HashSet};
 
 use itertools::Itertools;
+use progscrape_scrapers::StoryUrl;
 use serde::{Deserialize, Serialize};
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
 
 use super::{TagAcceptor, TagSet};
 
@@ -28,6 +30,39 @@ pub struct TagConfig {
 #[derive(Default, Serialize, Deserialize)]
 pub struct TaggerConfig {
     tags: HashMap<String, HashMap<String, TagConfig>>,
+    #[serde(default)]
+    collation: TagCollation,
+    /// Also match tags against the words in a story's host and URL path (ie: `github.com/rust-lang/rust`).
+    #[serde(default)]
+    url_tags: bool,
+}
+
+/// How display tags are ordered. Indexed tags are always kept in byte order.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TagCollation {
+    /// Display tags keep the byte order of the underlying indexed tags.
+    #[default]
+    Bytes,
+    /// Display tags are ordered by their base letters, ignoring accents and case (so `éclair` sorts next to
+    /// `eclipse` rather than after `zebra`). Ties fall back to byte order.
+    Unicode,
+}
+
+impl TagCollation {
+    fn sort(&self, tags: &mut [String]) {
+        match self {
+            Self::Bytes => {}
+            Self::Unicode => tags.sort_by_cached_key(|tag| {
+                let base = tag
+                    .nfd()
+                    .filter(|c| !is_combining_mark(*c))
+                    .flat_map(char::to_lowercase)
+                    .collect::<String>();
+                (base, tag.clone())
+            }),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -77,6 +112,14 @@ pub struct StoryTagger {
     backward: HashMap<String, String>,
     ///
     symbols: HashMap<String, usize>,
+    /// Maps hosts to the tags applied to stories from that host (or its subdomains).
+    hosts: HashMap<String, usize>,
+    /// Any implication cycles found in the configuration (ie: `a` -> `b` -> `a`).
+    implication_cycles: Vec<Vec<String>>,
+    /// How display tags are ordered.
+    collation: TagCollation,
+    /// Whether we generate tags from story URLs.
+    url_tags: bool,
 }
 
 impl StoryTagger {
@@ -122,7 +165,12 @@ impl StoryTagger {
             records: vec![],
             symbols: HashMap::new(),
             exclusions: HashMap::new(),
+            hosts: HashMap::new(),
+            implication_cycles: vec![],
+            collation: config.collation,
+            url_tags: config.url_tags,
         };
+        let mut primaries = vec![];
         for tags in config.tags.values() {
             for (tag, tags) in tags {
                 let (primary, all_tags) = Self::compute_all_tags(tag, &tags.alt, &tags.alts);
@@ -146,6 +194,9 @@ impl StoryTagger {
                 if let Some(internal) = &tags.internal {
                     new.backward.insert(internal.clone(), tag.clone());
                 }
+                for host in tags.host.iter().chain(tags.hosts.iter()) {
+                    new.hosts.insert(host.to_lowercase(), new.records.len());
+                }
                 for tag in all_tags {
                     if tags.symbol {
                         new.backward.insert(record.output.clone(), tag.clone());
@@ -160,13 +211,74 @@ impl StoryTagger {
                     }
                 }
 
+                primaries.push(tag.clone());
                 new.records.push(record);
             }
         }
 
+        // Expand the implication chains so that each record directly implies all of its ancestors
+        let mut direct = HashMap::new();
+        for (primary, record) in primaries.iter().zip(&new.records) {
+            for tag in Self::compute_tag(primary) {
+                direct.insert(tag, record.implies.clone());
+            }
+        }
+        for (primary, record) in primaries.iter().zip(new.records.iter_mut()) {
+            let mut path = vec![Self::compute_tag(primary).swap_remove(0)];
+            let mut implies = vec![];
+            Self::expand_implications(
+                &direct,
+                &mut path,
+                &mut implies,
+                &mut new.implication_cycles,
+            );
+            record.implies = implies;
+        }
+        for cycle in &new.implication_cycles {
+            tracing::warn!("Tag implication cycle detected: {}", cycle.join(" -> "));
+        }
+
         new
     }
 
+    /// Depth-first expansion of the tag at the end of `path` into all the tags it transitively implies. Cycles
+    /// are recorded (once per set of tags) and not followed.
+    fn expand_implications(
+        direct: &HashMap<String, Vec<String>>,
+        path: &mut Vec<String>,
+        implies: &mut Vec<String>,
+        cycles: &mut Vec<Vec<String>>,
+    ) {
+        let tag = path.last().expect("Path must not be empty").clone();
+        for implied in direct.get(&tag).into_iter().flatten() {
+            if let Some(start) = path.iter().position(|t| t == implied) {
+                let mut cycle = path[start..].to_vec();
+                cycle.push(implied.clone());
+                let members = cycle.iter().sorted().dedup().collect_vec();
+                if !cycles
+                    .iter()
+                    .any(|c| c.iter().sorted().dedup().collect_vec() == members)
+                {
+                    cycles.push(cycle);
+                }
+                continue;
+            }
+            if implies.contains(implied) {
+                continue;
+            }
+            implies.push(implied.clone());
+            path.push(implied.clone());
+            Self::expand_implications(direct, path, implies, cycles);
+            path.pop();
+        }
+    }
+
+    /// Any implication cycles found when constructing this tagger. These are reported and broken rather than
+    /// followed forever.
+    pub fn implication_cycles(&self) -> &[Vec<String>] {
+        &self.implication_cycles
+    }
+
     pub fn tag<T: TagAcceptor>(&self, s: &str, tags: &mut T) {
         let s = s.to_lowercase();
 
@@ -239,6 +351,53 @@ impl StoryTagger {
         }
     }
 
+    /// Generate tags for a story's host, matching the configured hosts and any of their subdomains.
+    pub fn tag_host<T: TagAcceptor>(&self, host: &str, tags: &mut T) {
+        let host = host.to_lowercase();
+        let mut suffix = host.as_str();
+        loop {
+            if let Some(rec) = self.hosts.get(suffix) {
+                let rec = &self.records[*rec];
+                tags.tag(&rec.output);
+                for implies in &rec.implies {
+                    tags.tag(implies);
+                }
+            }
+            match suffix.split_once('.') {
+                Some((_, rest)) => suffix = rest,
+                None => break,
+            }
+        }
+    }
+
+    /// Generate tags from the words in a story's host and URL path, if enabled (ie: `github.com/rust-lang/rust` is
+    /// tagged `rust`). Single-character words, numbers and file extensions are skipped, since in a URL they are far
+    /// more likely to be noise than in a title.
+    pub fn tag_url<T: TagAcceptor>(&self, url: &StoryUrl, tags: &mut T) {
+        if !self.url_tags {
+            return;
+        }
+        // Skip the TLD
+        let host = url
+            .host()
+            .rsplit_once('.')
+            .map(|(host, _)| host)
+            .unwrap_or_default();
+        let mut segments = url.path_segments();
+        if let Some(last) = segments.last_mut() {
+            if let Some((name, _)) = last.rsplit_once('.') {
+                *last = name.to_owned();
+            }
+        }
+        let words = host
+            .split('.')
+            .chain(segments.iter().map(String::as_str))
+            .flat_map(|s| s.split(|c: char| c == '-' || c == '_' || c == '.' || c.is_whitespace()))
+            .filter(|word| word.chars().count() > 1 && !word.chars().all(|c| c.is_ascii_digit()))
+            .join(" ");
+        self.tag(&words, tags);
+    }
+
     /// Identify any tags in the search term and return the appropriate search term to use. If the search term is a symbol,
     /// we must use its internal version (ie: cplusplus -> c++, c -> clanguage).
     pub fn check_tag_search(&self, search: &str) -> Option<&str> {
@@ -271,7 +430,12 @@ impl StoryTagger {
         &'a self,
         iter: I,
     ) -> impl Iterator<Item = String> + 'a {
-        iter.into_iter().map(|s| self.make_display_tag(s))
+        let mut tags = iter
+            .into_iter()
+            .map(|s| self.make_display_tag(s))
+            .collect_vec();
+        self.collation.sort(&mut tags);
+        tags.into_iter()
     }
 
     pub fn tag_details() -> Vec<(String, TagSet)> {
@@ -291,6 +455,7 @@ impl StoryTagger {
 #[cfg(test)]
 pub(crate) mod test {
     use itertools::Itertools;
+    use progscrape_scrapers::StoryUrl;
     use rstest::*;
     use serde_json::json;
 
@@ -305,6 +470,10 @@ pub(crate) mod test {
             "tags": {
                 "testing": {
                     "video(s)": {"hosts": ["youtube.com", "vimeo.com"]},
+                    "paper(s)": {"hosts": ["arxiv.org"]},
+                    "Show HN": {"internal": "showhn", "alt": "show hn"},
+                    "Ask HN": {"internal": "askhn", "alt": "ask hn"},
+                    "Tell HN": {"internal": "tellhn", "alt": "tell hn"},
                     "rust": {},
                     "chrome": {"alt": "chromium"},
                     "neovim": {"implies": "vim"},
@@ -341,6 +510,52 @@ pub(crate) mod test {
                 .collect_vec(),
             vec!["at&t", "c++", "c", "rust"]
         );
+        // Hacker News classification tags are indexed as single tokens
+        assert_eq!(
+            tagger.make_display_tags(["showhn", "askhn"]).collect_vec(),
+            vec!["Show HN", "Ask HN"]
+        );
+        assert_eq!(tagger.check_tag_search("showhn"), Some("showhn"));
+    }
+
+    /// Unicode collation orders accented display tags by their base letters, without changing the byte order
+    /// that the index sees.
+    #[rstest]
+    #[case("bytes", &["apple", "eclipse", "zebra", "éclair"])]
+    #[case("unicode", &["apple", "éclair", "eclipse", "zebra"])]
+    fn test_display_tag_collation(#[case] collation: &str, #[case] expected: &[&str]) {
+        let tagger = StoryTagger::new(
+            &serde_json::from_value(json!({"tags": {}, "collation": collation}))
+                .expect("Failed to parse config"),
+        );
+        let tags = TagSet::from_iter(["zebra", "éclair", "eclipse", "apple"]);
+        assert_eq!(
+            tagger.make_display_tags(tags.dump()).collect_vec(),
+            expected
+        );
+        assert_eq!(tags.collect(), vec!["apple", "eclipse", "zebra", "éclair"]);
+    }
+
+    /// URL tagging picks up tags from the host and path words, but only when enabled.
+    #[rstest]
+    #[case(true, "https://github.com/rust-lang/rust", &["rust"])]
+    #[case(true, "https://go.dev/blog", &["golang"])]
+    #[case(true, "https://docs.google.com/document/d/1a2b3c/edit", &[])]
+    #[case(true, "https://example.com/learning-c%2B%2B.html", &["cplusplus"])]
+    #[case(false, "https://github.com/rust-lang/rust", &[])]
+    fn test_url_tags(
+        tagger_config: TaggerConfig,
+        #[case] enabled: bool,
+        #[case] url: &str,
+        #[case] expected: &[&str],
+    ) {
+        let mut config = serde_json::to_value(tagger_config).expect("Failed to serialize config");
+        config["url_tags"] = enabled.into();
+        let tagger =
+            StoryTagger::new(&serde_json::from_value(config).expect("Failed to parse config"));
+        let mut tags = TagSet::new();
+        tagger.tag_url(&StoryUrl::parse(url).expect("URL"), &mut tags);
+        assert_eq!(tags.collect(), expected, "{url}");
     }
 
     /// Esnure that we can detect when symbol-like tags are passed to a search function.
@@ -362,6 +577,59 @@ pub(crate) mod test {
         }
     }
 
+    /// Implications are followed transitively, and cycles are reported rather than followed forever.
+    #[test]
+    fn test_implication_chains() {
+        let config = serde_json::from_value(json!({
+            "tags": {
+                "testing": {
+                    "a": {"implies": "b"},
+                    "b": {"implies": "c"},
+                    "c": {},
+                    "x": {"implies": "y"},
+                    "y": {"implies": "x"},
+                }
+            }
+        }))
+        .expect("Failed to parse test config");
+        let tagger = StoryTagger::new(&config);
+
+        let mut tag_set = TagSet::new();
+        tagger.tag("a", &mut tag_set);
+        assert_eq!(tag_set.collect(), vec!["a", "b", "c"]);
+
+        let mut tag_set = TagSet::new();
+        tagger.tag("b", &mut tag_set);
+        assert_eq!(tag_set.collect(), vec!["b", "c"]);
+
+        let mut tag_set = TagSet::new();
+        tagger.tag("x", &mut tag_set);
+        assert_eq!(tag_set.collect(), vec!["x", "y"]);
+
+        assert_eq!(tagger.implication_cycles().len(), 1);
+        let cycle = &tagger.implication_cycles()[0];
+        assert_eq!(cycle.first(), cycle.last());
+        assert_eq!(cycle.iter().sorted().dedup().collect_vec(), vec!["x", "y"]);
+    }
+
+    #[rstest]
+    #[case("youtube.com", &["video"])]
+    #[case("m.youtube.com", &["video"])]
+    #[case("vimeo.com", &["video"])]
+    #[case("export.arxiv.org", &["paper"])]
+    #[case("notyoutube.com", &[])]
+    #[case("example.com", &[])]
+    fn test_host_tags(tagger: StoryTagger, #[case] host: &str, #[case] tags: &[&str]) {
+        let mut tag_set = TagSet::new();
+        tagger.tag_host(host, &mut tag_set);
+        assert_eq!(
+            tag_set.collect(),
+            tags.to_vec(),
+            "while checking tags for {}",
+            host
+        );
+    }
+
     #[rstest]
     #[case("I love rust!", &["rust"])]
     #[case("Good old video", &["video"])]
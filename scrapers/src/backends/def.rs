@@ -1,3 +1,5 @@
+use std::collections::HashSet;
+
 use crate::ScrapeId;
 
 use super::*;
@@ -8,6 +10,10 @@ pub trait ScrapeSourceDef {
     type Scrape: ScrapeStory;
     type Scraper: Scraper<Config = Self::Config, Output = Self::Scrape>;
 
+    /// Does this source qualify its ids with a subsource (ie: a subreddit)? Sources without subsources may use
+    /// hyphens in their ids, so we only split a subsource out of a serialized id for sources that have them.
+    const SUBSOURCE_IDS: bool = false;
+
     fn comments_url(id: &str, subsource: Option<&str>) -> String;
     fn id_from_comments_url(url: &str) -> Option<(&str, Option<&str>)>;
     fn is_comments_host(host: &str) -> bool;
@@ -19,16 +25,26 @@ pub trait ScrapeStory {
     fn merge(&mut self, other: Self);
 }
 
+/// The stories scraped from an input, along with a warning for each story that was skipped.
+pub type ScrapeResult<T> = Result<(Vec<GenericScrape<T>>, Vec<ScrapeWarning>), ScrapeError>;
+
 pub trait Scraper: Default {
     type Config: ScrapeConfigSource;
     type Output: ScrapeStory;
 
     /// Given input in the correct format, scrapes raw stories.
-    fn scrape(
+    fn scrape(&self, args: &Self::Config, input: &str) -> ScrapeResult<Self::Output>;
+
+    /// Scrapes input that was fetched from `url`. Most sources can tell which subsource a story came from by its
+    /// content, but those that can't (ie: feeds) use the URL.
+    fn scrape_url(
         &self,
         args: &Self::Config,
+        _url: &str,
         input: &str,
-    ) -> Result<(Vec<GenericScrape<Self::Output>>, Vec<String>), ScrapeError>;
+    ) -> ScrapeResult<Self::Output> {
+        self.scrape(args, input)
+    }
 
     /// Extract the core scrape elements from the raw scrape.
     fn extract_core<'a>(
@@ -62,17 +78,69 @@ pub struct ScrapeCore<'a> {
 
     /// If this story has a rank, lower is better.
     pub rank: Option<usize>,
+
+    /// The number of comments on this story at the scrape source, if the source reports it.
+    pub comments: Option<u32>,
+
+    /// The number of points (upvotes) this story has at the scrape source, if the source reports it.
+    pub points: Option<u32>,
+
+    /// The user that submitted this story, if the source reports it.
+    pub author: Option<Cow<'a, str>>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "json_schema", derive(schemars::JsonSchema))]
 pub struct ScrapeShared {
     pub id: ScrapeId,
     pub url: StoryUrl,
     pub raw_title: String,
     pub date: StoryDate,
+    /// When we first stored this scrape, as opposed to when the source says the story was posted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ingested_at: Option<StoryDate>,
+}
+
+/// A part of a scrape that changed when a re-scrape of the same story was merged into it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ScrapeMergeResult {
+    /// The re-scrape was dated earlier.
+    Date,
+    /// The re-scrape had a better title.
+    Title,
+    /// The re-scrape pointed at a different URL.
+    Url,
+}
+
+impl ScrapeShared {
+    /// Merge the shared fields of a re-scrape of this story, returning the fields that changed. The earliest date
+    /// wins, a differing URL replaces ours, and differing titles are chosen like titles from sources with the same
+    /// priority (ie: the shorter one).
+    pub fn merge(&mut self, other: ScrapeShared) -> HashSet<ScrapeMergeResult> {
+        let mut changed = HashSet::new();
+        if other.date < self.date {
+            self.date = other.date;
+            changed.insert(ScrapeMergeResult::Date);
+        }
+        let title = other.raw_title.trim();
+        if !title.is_empty() && title.len() < self.raw_title.trim().len() {
+            self.raw_title = other.raw_title;
+            changed.insert(ScrapeMergeResult::Title);
+        }
+        if other.url.normalization() != self.url.normalization() {
+            self.url = other.url;
+            changed.insert(ScrapeMergeResult::Url);
+        }
+        self.ingested_at = match (self.ingested_at, other.ingested_at) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        changed
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "json_schema", derive(schemars::JsonSchema))]
 pub struct GenericScrape<T: ScrapeStory> {
     #[serde(flatten)]
     pub shared: ScrapeShared,
@@ -94,14 +162,20 @@ impl<T: ScrapeStory> std::ops::DerefMut for GenericScrape<T> {
 }
 
 impl<T: ScrapeStory> GenericScrape<T> {
-    pub fn merge_generic(&mut self, _other: Self) {}
+    /// Merge a re-scrape of this story, letting the source decide how to combine its counts. Returns the shared
+    /// fields that changed.
+    pub fn merge_generic(&mut self, other: Self) -> HashSet<ScrapeMergeResult> {
+        self.data.merge(other.data);
+        self.shared.merge(other.shared)
+    }
 }
 
 macro_rules! scrape_story {
-    ( $name:ident { $( $id:ident : $type:ty ),* $(,)? } ) => {
+    ( $name:ident { $( $(#[$meta:meta])* $id:ident : $type:ty ),* $(,)? } ) => {
         #[derive(Serialize, Deserialize, Clone, Debug, Default)]
+        #[cfg_attr(feature = "json_schema", derive(schemars::JsonSchema))]
         pub struct $name {
-            $( pub $id : $type ),*
+            $( $(#[$meta])* pub $id : $type ),*
         }
 
         impl $name {
@@ -109,7 +183,7 @@ macro_rules! scrape_story {
             pub fn new<'a, S: Clone + Into<std::borrow::Cow<'a, str>>>(id: S, date: $crate::types::StoryDate, raw_title: S, url: $crate::types::StoryUrl, $( $id: $type ),*) -> GenericScrape<$name> {
                 GenericScrape {
                     shared: $crate::backends::ScrapeShared {
-                        id: $crate::backends::ScrapeId::new(<$name as ScrapeStory>::TYPE, None, id.into().into()), date, raw_title: raw_title.into().into(), url
+                        id: $crate::backends::ScrapeId::new(<$name as ScrapeStory>::TYPE, None, id.into().into()), date, raw_title: raw_title.into().into(), url, ingested_at: None
                     },
                     data: $name {
                         $($id),*
@@ -121,7 +195,7 @@ macro_rules! scrape_story {
             pub fn new_subsource<'a, S: Clone + Into<std::borrow::Cow<'a, str>>>(id: S, subsource: S, date: $crate::types::StoryDate, raw_title: S, url: $crate::types::StoryUrl, $( $id: $type ),*) -> GenericScrape<$name> {
                 GenericScrape {
                     shared: $crate::backends::ScrapeShared {
-                        id: $crate::backends::ScrapeId::new(<$name as ScrapeStory>::TYPE, Some(subsource.into().into()), id.into().into()), date, raw_title: raw_title.into().into(), url
+                        id: $crate::backends::ScrapeId::new(<$name as ScrapeStory>::TYPE, Some(subsource.into().into()), id.into().into()), date, raw_title: raw_title.into().into(), url, ingested_at: None
                     },
                     data: $name {
                         $($id),*
@@ -133,7 +207,7 @@ macro_rules! scrape_story {
             pub fn new_with_defaults<'a, S: Clone + Into<std::borrow::Cow<'a, str>>>(id: S, date: $crate::types::StoryDate, raw_title: S, url: $crate::types::StoryUrl) -> GenericScrape<$name> {
                 GenericScrape {
                     shared: $crate::backends::ScrapeShared {
-                        id: $crate::backends::ScrapeId::new(<$name as ScrapeStory>::TYPE, None, id.into().into()), date, raw_title: raw_title.into().into(), url
+                        id: $crate::backends::ScrapeId::new(<$name as ScrapeStory>::TYPE, None, id.into().into()), date, raw_title: raw_title.into().into(), url, ingested_at: None
                     },
                     data: $name {
                         $($id : Default::default() ),*
@@ -145,7 +219,7 @@ macro_rules! scrape_story {
             pub fn new_subsource_with_defaults<'a, S: Clone + Into<std::borrow::Cow<'a, str>>>(id: S, subsource: S, date: $crate::types::StoryDate, raw_title: S, url: $crate::types::StoryUrl) -> GenericScrape<$name> {
                 GenericScrape {
                     shared: $crate::backends::ScrapeShared {
-                        id: $crate::backends::ScrapeId::new(<$name as ScrapeStory>::TYPE, Some(subsource.into().into()), id.into().into()), date, raw_title: raw_title.into().into(), url
+                        id: $crate::backends::ScrapeId::new(<$name as ScrapeStory>::TYPE, Some(subsource.into().into()), id.into().into()), date, raw_title: raw_title.into().into(), url, ingested_at: None
                     },
                     data: $name {
                         $($id : Default::default() ),*
@@ -1,14 +1,23 @@
-use std::borrow::Cow;
+use std::{borrow::Cow, collections::HashMap};
 
 use itertools::Itertools;
+use roxmltree::Document;
 use serde::{Deserialize, Serialize};
+use tl::ParserOptions;
+use url::Url;
 
-use crate::{ScrapeCore, ScrapeSource, TypedScrape};
+use crate::{
+    ScrapeCore, ScrapeError, ScrapeSource, ScrapeWarning, StoryDate, StoryDuration, StoryUrl,
+};
 
 use super::{
-    scrape_story, GenericScrape, ScrapeConfigSource, ScrapeSourceDef, ScrapeStory, Scraper,
+    scrape_story, utils::html::*, utils::strip_site_suffix, GenericScrape, ScrapeConfigSource,
+    ScrapeResult, ScrapeSourceDef, ScrapeStory, Scraper,
 };
 
+/// The subsource for stories from a feed that isn't configured (ie: one scraped by hand).
+const UNKNOWN_SUBSOURCE: &str = "unknown";
+
 pub struct Feed {}
 
 impl ScrapeSourceDef for Feed {
@@ -16,6 +25,8 @@ impl ScrapeSourceDef for Feed {
     type Scrape = FeedStory;
     type Scraper = FeedScraper;
 
+    const SUBSOURCE_IDS: bool = true;
+
     fn comments_url(id: &str, subsource: Option<&str>) -> String {
         "".to_string()
     }
@@ -30,18 +41,408 @@ impl ScrapeSourceDef for Feed {
 }
 
 #[derive(Clone, Default, Serialize, Deserialize)]
-pub struct FeedConfig {}
+pub struct FeedConfig {
+    /// Feeds to poll, keyed by subsource name. Names are part of each story's id, so they must not contain `-`.
+    #[serde(default)]
+    feeds: HashMap<String, FeedSourceConfig>,
+    /// Strip trailing site names (ie: `" - The Verge"`) from story titles.
+    #[serde(default)]
+    strip_title_suffixes: bool,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct FeedSourceConfig {
+    url: String,
+    /// The minimum number of minutes between polls of this feed, regardless of what the feed advertises.
+    #[serde(default)]
+    min_interval_minutes: i64,
+    /// Tags applied to every story from this feed.
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+impl FeedConfig {
+    /// Computes how long we should wait before polling the feed at `url` again, taking the larger of the
+    /// configured minimum interval and the feed's own `<ttl>`/`sy:updatePeriod` hint (if `input` is available).
+    pub fn poll_interval(&self, url: &str, input: Option<&str>) -> StoryDuration {
+        let min_interval = self
+            .feeds
+            .values()
+            .find(|feed| feed.url == url)
+            .map(|feed| feed.min_interval_minutes)
+            .unwrap_or_default();
+        let hint = input
+            .and_then(poll_interval_hint)
+            .unwrap_or(StoryDuration::minutes(0));
+        StoryDuration::minutes(min_interval).max(hint)
+    }
+}
+
+impl FeedConfig {
+    /// The subsource name of the feed at `url`.
+    fn subsource_for_url(&self, url: &str) -> &str {
+        self.feeds
+            .iter()
+            .find(|(_, feed)| feed.url == url)
+            .map(|(name, _)| name.as_str())
+            .unwrap_or(UNKNOWN_SUBSOURCE)
+    }
+
+    /// Adds feeds (ie: from [`parse_opml`]) that aren't already configured, naming each new subsource after the
+    /// feed's host (with any `-` replaced, as names can't contain them). Returns the number of feeds added.
+    pub fn merge_feeds(&mut self, feeds: Vec<(StoryUrl, Vec<String>)>) -> usize {
+        let mut added = 0;
+        for (url, tags) in feeds {
+            if self.feeds.values().any(|feed| feed.url == url.raw()) {
+                continue;
+            }
+            let host = url.host().trim_start_matches("www.").replace('-', "_");
+            let name = (1..)
+                .map(|n| {
+                    if n == 1 {
+                        host.clone()
+                    } else {
+                        format!("{host}_{n}")
+                    }
+                })
+                .find(|name| !self.feeds.contains_key(name))
+                .expect("Expected a free subsource name");
+            self.feeds.insert(
+                name,
+                FeedSourceConfig {
+                    url: url.raw().to_owned(),
+                    min_interval_minutes: 0,
+                    tags,
+                },
+            );
+            added += 1;
+        }
+        added
+    }
+}
+
+/// Parses an OPML subscription list (ie: as exported by an RSS reader) into feed URLs. Any folders a feed is nested
+/// in are flattened into tags for that feed. Entries with invalid feed URLs are skipped.
+pub fn parse_opml(input: &str) -> Result<Vec<(StoryUrl, Vec<String>)>, ScrapeError> {
+    let doc = Document::parse(input)?;
+    let body = doc
+        .root_element()
+        .children()
+        .find(|node| node.has_tag_name("body"))
+        .ok_or_else(|| ScrapeError::StructureError("Missing OPML body".to_owned()))?;
+
+    let mut feeds = vec![];
+    for outline in body
+        .descendants()
+        .filter(|node| node.has_tag_name("outline"))
+    {
+        let Some(url) = outline.attribute("xmlUrl") else {
+            continue;
+        };
+        let Some(url) = StoryUrl::parse(url.trim()) else {
+            continue;
+        };
+        let tags = outline
+            .ancestors()
+            .skip(1)
+            .filter(|node| node.has_tag_name("outline") && node.attribute("xmlUrl").is_none())
+            .filter_map(|node| node.attribute("text").or_else(|| node.attribute("title")))
+            .map(|folder| folder.trim().to_lowercase())
+            .filter(|folder| !folder.is_empty())
+            .collect_vec();
+        // Ancestors are nearest-first, but the outermost folder reads better first
+        feeds.push((url, tags.into_iter().rev().collect()));
+    }
+    Ok(feeds)
+}
 
 impl ScrapeConfigSource for FeedConfig {
     fn provide_urls(&self, subsources: Vec<String>) -> Vec<String> {
-        vec![]
+        subsources
+            .iter()
+            .filter_map(|subsource| self.feeds.get(subsource))
+            .map(|feed| feed.url.clone())
+            .collect()
     }
 
     fn subsources(&self) -> Vec<String> {
-        vec![]
+        self.feeds.keys().cloned().sorted().collect()
+    }
+}
+
+/// Parses the polling hints from an RSS feed: either `<ttl>` (in minutes), or the syndication module's
+/// `sy:updatePeriod` and `sy:updateFrequency`.
+pub fn poll_interval_hint(input: &str) -> Option<StoryDuration> {
+    const SYNDICATION_NS: &str = "http://purl.org/rss/1.0/modules/syndication/";
+    let doc = Document::parse(input).ok()?;
+    let channel = doc
+        .root_element()
+        .children()
+        .find(|node| node.has_tag_name("channel"))?;
+    let text = |name: (&str, &str)| {
+        channel
+            .children()
+            .find(|node| node.has_tag_name(name))
+            .and_then(|node| node.text())
+            .map(str::trim)
+    };
+
+    if let Some(ttl) = channel
+        .children()
+        .find(|node| node.has_tag_name("ttl"))
+        .and_then(|node| node.text())
+    {
+        return ttl.trim().parse().ok().map(StoryDuration::minutes);
+    }
+
+    let period = match text((SYNDICATION_NS, "updatePeriod"))? {
+        "hourly" => StoryDuration::hours(1),
+        "daily" => StoryDuration::days(1),
+        "weekly" => StoryDuration::days(7),
+        "monthly" => StoryDuration::days(30),
+        "yearly" => StoryDuration::days(365),
+        _ => return None,
+    };
+    let frequency = text((SYNDICATION_NS, "updateFrequency"))
+        .and_then(|s| s.parse::<i64>().ok())
+        .filter(|f| *f > 0)
+        .unwrap_or(1);
+    Some(StoryDuration::seconds(period.num_seconds() / frequency))
+}
+
+/// The content types of feeds advertised by `<link rel="alternate">`, in order of preference.
+const FEED_LINK_TYPES: [&str; 2] = ["application/rss+xml", "application/atom+xml"];
+
+/// Is this input a feed, rather than (for example) the HTML page of a blog?
+pub fn is_feed(input: &str) -> bool {
+    Document::parse(input)
+        .map(|doc| matches!(doc.root_element().tag_name().name(), "rss" | "feed" | "RDF"))
+        .unwrap_or_default()
+}
+
+/// Finds the `href` of the preferred feed advertised by an HTML page.
+fn find_feed_link(html: &str) -> Option<String> {
+    let dom = tl::parse(html, ParserOptions::default()).ok()?;
+    let p = dom.parser();
+    let links = html_tag_iterator(p, dom.query_selector("link"))
+        .filter(|link| {
+            get_attribute(p, link, "rel")
+                .map(|rel| {
+                    rel.split_ascii_whitespace()
+                        .any(|rel| rel.eq_ignore_ascii_case("alternate"))
+                })
+                .unwrap_or_default()
+        })
+        .filter_map(|link| {
+            let link_type = get_attribute(p, link, "type")?.trim().to_ascii_lowercase();
+            let href = get_attribute(p, link, "href")?;
+            Some((link_type, unescape_entities(href.trim())))
+        })
+        .collect_vec();
+    FEED_LINK_TYPES.iter().find_map(|feed_type| {
+        links
+            .iter()
+            .find(|(link_type, _)| link_type == feed_type)
+            .map(|(_, href)| href.clone())
+    })
+}
+
+/// Discovers the feed advertised by an HTML page via `<link rel="alternate">`, resolving it against the page's
+/// URL. RSS feeds are preferred over Atom feeds, and earlier links over later ones.
+pub fn discover_feed(html: &str, base_url: &StoryUrl) -> Option<StoryUrl> {
+    let href = find_feed_link(html)?;
+    let url = Url::parse(base_url.raw()).ok()?.join(&href).ok()?;
+    StoryUrl::parse(url.as_str())
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FeedPoll {
+    pub last_poll: StoryDate,
+    pub next_poll: StoryDate,
+}
+
+/// Tracks the last time each feed was polled, keyed by feed URL, so we don't poll feeds more often than
+/// they ask.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct FeedPollState {
+    polls: HashMap<String, FeedPoll>,
+}
+
+impl FeedPollState {
+    /// Is the feed at `url` due to be polled at `now`?
+    pub fn is_due(&self, url: &str, now: StoryDate) -> bool {
+        self.polls
+            .get(url)
+            .map(|poll| now >= poll.next_poll)
+            .unwrap_or(true)
+    }
+
+    /// Record that the feed at `url` was polled at `now`, and should not be polled again until `interval`
+    /// has elapsed.
+    pub fn record_poll(&mut self, url: &str, now: StoryDate, interval: StoryDuration) {
+        let next_poll =
+            StoryDate::from_seconds(now.timestamp() + interval.num_seconds()).unwrap_or(now);
+        self.polls.insert(
+            url.to_owned(),
+            FeedPoll {
+                last_poll: now,
+                next_poll,
+            },
+        );
+    }
+
+    pub fn get(&self, url: &str) -> Option<&FeedPoll> {
+        self.polls.get(url)
+    }
+}
+
+/// Parses the stories of an RSS or Atom document, depending on its root element.
+pub fn parse_feed_items(
+    doc: &Document,
+    subsource: &str,
+) -> (Vec<GenericScrape<FeedStory>>, Vec<ScrapeWarning>) {
+    if doc.root_element().has_tag_name("feed") {
+        parse_atom_entries(doc, subsource)
+    } else {
+        parse_rss_items(doc, subsource)
+    }
+}
+
+/// Builds a story from the fields of a feed item, or a warning if any are missing.
+fn feed_story(
+    subsource: &str,
+    raw_title: Option<String>,
+    id: Option<String>,
+    url: Option<StoryUrl>,
+    date: Option<StoryDate>,
+    tags: Vec<String>,
+) -> Result<GenericScrape<FeedStory>, ScrapeWarning> {
+    let id = id.or_else(|| url.as_ref().map(|url: &StoryUrl| url.raw().to_owned()));
+    if let (Some(raw_title), Some(id), Some(url), Some(date)) = (raw_title, id, url, date) {
+        Ok(FeedStory::new_subsource(
+            id,
+            subsource.to_owned(),
+            date,
+            raw_title,
+            url,
+            tags,
+        ))
+    } else {
+        Err(ScrapeWarning::other(
+            ScrapeSource::Feed,
+            "Story did not contain all required fields",
+        ))
     }
 }
 
+/// Parses the `<item>`s of an RSS document's channels (or, for RSS 1.0, of the document itself) into stories from the
+/// feed named `subsource`. Items without a `<guid>` use their link as their ID, and items missing a title, link or
+/// date are skipped with a warning.
+pub fn parse_rss_items(
+    doc: &Document,
+    subsource: &str,
+) -> (Vec<GenericScrape<FeedStory>>, Vec<ScrapeWarning>) {
+    let mut warnings = vec![];
+    let mut stories = vec![];
+    let root = doc.root_element();
+    for parent in
+        std::iter::once(root).chain(root.children().filter(|node| node.has_tag_name("channel")))
+    {
+        for item in parent.children().filter(|node| node.has_tag_name("item")) {
+            let mut raw_title = None;
+            let mut id = None;
+            let mut url = None;
+            let mut date = None;
+            let mut tags = vec![];
+            for subitem in item.children().filter(|node| node.is_element()) {
+                match subitem.tag_name().name() {
+                    "title" => raw_title = subitem.text().map(|s| s.trim().to_owned()),
+                    "guid" => id = subitem.text().map(|s| s.trim().to_owned()),
+                    "link" => url = subitem.text().and_then(|s| StoryUrl::parse(s.trim())),
+                    "pubDate" => {
+                        date = subitem
+                            .text()
+                            .and_then(|s| StoryDate::parse_from_rfc2822(s.trim()))
+                    }
+                    // RSS 1.0 dates items with `<dc:date>`
+                    "date" => {
+                        date = subitem
+                            .text()
+                            .and_then(|s| StoryDate::parse_from_rfc3339_loose(s.trim()))
+                    }
+                    "category" => {
+                        if let Some(tag) = subitem.text() {
+                            tags.push(tag.trim().to_owned());
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            match feed_story(subsource, raw_title, id, url, date, tags) {
+                Ok(story) => stories.push(story),
+                Err(warning) => warnings.push(warning),
+            }
+        }
+    }
+    (stories, warnings)
+}
+
+/// Parses the `<entry>`s of an Atom document into stories from the feed named `subsource`. Entries are dated by
+/// when they were published, falling back to when they were last updated, and link to their `alternate` link.
+pub fn parse_atom_entries(
+    doc: &Document,
+    subsource: &str,
+) -> (Vec<GenericScrape<FeedStory>>, Vec<ScrapeWarning>) {
+    let mut warnings = vec![];
+    let mut stories = vec![];
+    for entry in doc
+        .root_element()
+        .children()
+        .filter(|node| node.has_tag_name("entry"))
+    {
+        let mut raw_title = None;
+        let mut id = None;
+        let mut url = None;
+        let mut published = None;
+        let mut updated = None;
+        let mut tags = vec![];
+        for subitem in entry.children().filter(|node| node.is_element()) {
+            match subitem.tag_name().name() {
+                "title" => raw_title = subitem.text().map(|s| s.trim().to_owned()),
+                "id" => id = subitem.text().map(|s| s.trim().to_owned()),
+                "link" => {
+                    if matches!(subitem.attribute("rel"), None | Some("alternate")) {
+                        url = subitem
+                            .attribute("href")
+                            .and_then(|s| StoryUrl::parse(s.trim()));
+                    }
+                }
+                "published" => {
+                    published = subitem
+                        .text()
+                        .and_then(|s| StoryDate::parse_from_rfc3339_loose(s.trim()))
+                }
+                "updated" => {
+                    updated = subitem
+                        .text()
+                        .and_then(|s| StoryDate::parse_from_rfc3339_loose(s.trim()))
+                }
+                "category" => {
+                    if let Some(tag) = subitem.attribute("term") {
+                        tags.push(tag.trim().to_owned());
+                    }
+                }
+                _ => {}
+            }
+        }
+        match feed_story(subsource, raw_title, id, url, published.or(updated), tags) {
+            Ok(story) => stories.push(story),
+            Err(warning) => warnings.push(warning),
+        }
+    }
+    (stories, warnings)
+}
+
 scrape_story! {
     FeedStory {
         tags: Vec<String>
@@ -66,27 +467,390 @@ impl Scraper for FeedScraper {
         args: &Self::Config,
         input: &'a super::GenericScrape<Self::Output>,
     ) -> ScrapeCore<'a> {
-        let tags = input
+        let mut tags = input
             .data
             .tags
             .iter()
             .map(|tag| Cow::Borrowed(tag.as_str()))
             .collect_vec();
+        if let Some(feed) = input
+            .shared
+            .id
+            .subsource
+            .as_ref()
+            .and_then(|subsource| args.feeds.get(subsource))
+        {
+            tags.extend(feed.tags.iter().cloned().map(Cow::Owned));
+        }
+        let title = if args.strip_title_suffixes {
+            strip_site_suffix(&input.shared.raw_title, input.shared.url.host())
+        } else {
+            &input.shared.raw_title
+        };
         ScrapeCore {
             source: &input.shared.id,
-            title: Cow::Borrowed(&input.shared.raw_title),
+            title: Cow::Borrowed(title),
             url: &input.shared.url,
             date: input.shared.date,
             rank: None,
+            comments: None,
+            points: None,
+            author: None,
             tags,
         }
     }
 
-    fn scrape(
+    fn scrape(&self, _args: &Self::Config, input: &str) -> ScrapeResult<Self::Output> {
+        self.scrape_feed(UNKNOWN_SUBSOURCE, input)
+    }
+
+    fn scrape_url(
         &self,
         args: &Self::Config,
+        url: &str,
         input: &str,
-    ) -> Result<(Vec<super::GenericScrape<Self::Output>>, Vec<String>), crate::ScrapeError> {
-        unimplemented!()
+    ) -> ScrapeResult<Self::Output> {
+        self.scrape_feed(args.subsource_for_url(url), input)
+    }
+}
+
+impl FeedScraper {
+    fn scrape_feed(&self, subsource: &str, input: &str) -> ScrapeResult<FeedStory> {
+        // Pages are swapped for the feed they link to before scraping, so there's nothing to do with one here
+        if !is_feed(input) {
+            let detail = if find_feed_link(input).is_some() {
+                "Input is an HTML page, not the feed it links to"
+            } else {
+                "Input is not a feed and does not link to one"
+            };
+            return Ok((
+                vec![],
+                vec![ScrapeWarning::other(ScrapeSource::Feed, detail)],
+            ));
+        }
+
+        let doc = Document::parse(input)?;
+        Ok(parse_feed_items(&doc, subsource))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        backends::{scrape_url, ScrapeConfig},
+        ScrapeExtractor, ScrapeId,
+    };
+    use rstest::*;
+
+    const TTL_FEED: &str = r#"<?xml version="1.0"?>
+<rss version="2.0">
+<channel>
+    <title>Example</title>
+    <ttl>60</ttl>
+    <item>
+        <title>A story</title>
+        <link>https://example.com/story</link>
+        <pubDate>Mon, 02 Jan 2023 10:00:00 +0000</pubDate>
+        <category>rust</category>
+    </item>
+</channel>
+</rss>"#;
+
+    const SYNDICATION_FEED: &str = r#"<?xml version="1.0"?>
+<rss version="2.0" xmlns:sy="http://purl.org/rss/1.0/modules/syndication/">
+<channel>
+    <title>Example</title>
+    <sy:updatePeriod>daily</sy:updatePeriod>
+    <sy:updateFrequency>4</sy:updateFrequency>
+</channel>
+</rss>"#;
+
+    fn config(min_interval_minutes: i64) -> FeedConfig {
+        FeedConfig {
+            feeds: HashMap::from_iter([(
+                "example".to_owned(),
+                FeedSourceConfig {
+                    url: "https://example.com/rss".to_owned(),
+                    min_interval_minutes,
+                    tags: vec![],
+                },
+            )]),
+            strip_title_suffixes: false,
+        }
+    }
+
+    #[rstest]
+    #[case(TTL_FEED, Some(60))]
+    #[case(SYNDICATION_FEED, Some(360))]
+    #[case("<rss><channel></channel></rss>", None)]
+    #[case("not a feed", None)]
+    fn test_poll_interval_hint(#[case] input: &str, #[case] minutes: Option<i64>) {
+        assert_eq!(poll_interval_hint(input).map(|d| d.num_minutes()), minutes);
+    }
+
+    #[rstest]
+    #[case(0, TTL_FEED, 60)]
+    #[case(90, TTL_FEED, 90)]
+    #[case(15, "<rss><channel></channel></rss>", 15)]
+    fn test_poll_interval(#[case] min_interval: i64, #[case] input: &str, #[case] minutes: i64) {
+        let config = config(min_interval);
+        let interval = config.poll_interval("https://example.com/rss", Some(input));
+        assert_eq!(interval.num_minutes(), minutes);
+    }
+
+    /// A feed advertising a 60-minute TTL is not re-polled until the TTL elapses, even if we tick every 10 minutes.
+    #[test]
+    fn test_ttl_polling() {
+        let config = config(0);
+        let url = &config.provide_urls(config.subsources())[0];
+        let mut state = FeedPollState::default();
+        let start = StoryDate::year_month_day(2023, 1, 1).expect("Date failed");
+
+        let mut polls = vec![];
+        for tick in 0..18 {
+            let now = StoryDate::from_seconds(start.timestamp() + tick * 10 * 60).expect("Date");
+            if state.is_due(url, now) {
+                polls.push(tick * 10);
+                state.record_poll(url, now, config.poll_interval(url, Some(TTL_FEED)));
+            }
+        }
+        assert_eq!(polls, vec![0, 60, 120]);
+    }
+
+    #[rstest]
+    #[case(include_str!("../../testdata/feed-discovery1.html"), "https://this-week-in-rust.org/", Some("https://this-week-in-rust.org/rss.xml"))]
+    #[case(include_str!("../../testdata/feed-discovery2.html"), "https://blog.example.com/", Some("https://blog.example.com/feeds/all.rss"))]
+    #[case(include_str!("../../testdata/feed-discovery2.html"), "https://blog.example.com/posts/index.html", Some("https://blog.example.com/posts/feeds/all.rss"))]
+    #[case(include_str!("../../testdata/feed-discovery3.html"), "https://example.com/", None)]
+    fn test_discover_feed(#[case] html: &str, #[case] base: &str, #[case] expected: Option<&str>) {
+        let base = StoryUrl::parse(base).expect("URL");
+        let feed = discover_feed(html, &base);
+        assert_eq!(feed.as_ref().map(|url| url.raw()), expected);
+    }
+
+    #[rstest]
+    #[case(include_str!("../../testdata/feed-discovery1.html"))]
+    #[case(include_str!("../../testdata/feed-discovery3.html"))]
+    #[case("not a feed")]
+    fn test_scrape_not_a_feed(#[case] input: &str) {
+        assert!(!is_feed(input));
+        let (stories, warnings) = FeedScraper::default()
+            .scrape(&config(0), input)
+            .expect("Failed to scrape");
+        assert!(stories.is_empty());
+        assert_eq!(warnings.len(), 1);
+    }
+
+    #[test]
+    fn test_scrape() {
+        let (stories, warnings) = FeedScraper::default()
+            .scrape(&config(0), TTL_FEED)
+            .expect("Failed to scrape");
+        assert!(warnings.is_empty());
+        assert_eq!(stories.len(), 1);
+        assert_eq!(stories[0].shared.id.id, "https://example.com/story");
+        assert_eq!(stories[0].data.tags, vec!["rust"]);
+    }
+
+    #[test]
+    fn test_parse_rss_items() {
+        let input = include_str!("../../testdata/feed1.rss");
+        let doc = Document::parse(input).expect("Failed to parse");
+        let (stories, warnings) = parse_rss_items(&doc, "twir");
+        assert!(warnings.is_empty(), "{warnings:?}");
+        let stories = stories
+            .iter()
+            .map(|story| {
+                (
+                    story.shared.id.id.as_str(),
+                    story.shared.raw_title.as_str(),
+                    story.data.tags.join(","),
+                )
+            })
+            .collect_vec();
+        assert_eq!(stories.len(), 4);
+        assert_eq!(
+            stories[0],
+            (
+                "https://this-week-in-rust.org/blog/2022/12/21/this-week-in-rust-474/",
+                "This Week in Rust 474",
+                "Rust".to_owned()
+            )
+        );
+        // No guid, so the link is the ID
+        assert_eq!(
+            stories[2],
+            (
+                "https://this-week-in-rust.org/blog/2022/12/12/rust-and-webassembly-year-in-review/?utm_source=rss&utm_medium=rss",
+                "Rust & WebAssembly: a year in review",
+                "Rust,WebAssembly".to_owned()
+            )
+        );
+
+        // Items missing a required field are skipped with a warning
+        let doc =
+            Document::parse("<rss><channel><item><title>No link</title></item></channel></rss>")
+                .expect("Failed to parse");
+        let (stories, warnings) = parse_rss_items(&doc, "twir");
+        assert!(stories.is_empty());
+        assert_eq!(warnings.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_atom_entries() {
+        let (stories, warnings) = FeedScraper::default()
+            .scrape(&config(0), include_str!("../../testdata/feed2.atom"))
+            .expect("Failed to scrape");
+        assert_eq!(warnings.len(), 1, "{warnings:?}");
+        let stories = stories
+            .iter()
+            .map(|story| {
+                (
+                    story.shared.id.id.as_str(),
+                    story.shared.url.raw(),
+                    story.shared.date.to_rfc3339(),
+                    story.data.tags.join(","),
+                )
+            })
+            .collect_vec();
+        assert_eq!(
+            stories,
+            vec![
+                (
+                    "urn:uuid:1225c695-cfb8-4ebb-aaaa-80da344efa6a",
+                    "https://www.example.org/2023/01/compilers",
+                    "2023-01-02T10:00:00+00:00".to_owned(),
+                    "rust,compilers".to_owned()
+                ),
+                // No published date, so the updated date is used
+                (
+                    "https://www.example.org/2022/12/parsers",
+                    "https://www.example.org/2022/12/parsers",
+                    "2022-12-20T08:30:00+00:00".to_owned(),
+                    "".to_owned()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_rss1_items() {
+        let doc = Document::parse(
+            r#"<rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#" xmlns="http://purl.org/rss/1.0/" xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <channel><title>Example</title></channel>
+    <item>
+        <title>A story</title>
+        <link>https://example.com/story</link>
+        <dc:date>2023-01-02T10:00:00Z</dc:date>
+    </item>
+</rdf:RDF>"#,
+        )
+        .expect("Failed to parse");
+        let (stories, warnings) = parse_feed_items(&doc, "example");
+        assert!(warnings.is_empty(), "{warnings:?}");
+        assert_eq!(stories.len(), 1);
+        assert_eq!(stories[0].shared.url.raw(), "https://example.com/story");
+    }
+
+    #[test]
+    fn test_parse_opml() {
+        let feeds =
+            parse_opml(include_str!("../../testdata/feeds1.opml")).expect("Failed to parse");
+        let feeds = feeds
+            .iter()
+            .map(|(url, tags)| (url.raw(), tags.iter().map(String::as_str).collect_vec()))
+            .collect_vec();
+        assert_eq!(
+            feeds,
+            vec![
+                ("https://this-week-in-rust.org/rss.xml", vec!["rust"]),
+                (
+                    "https://blog.example.com/feeds/all.rss",
+                    vec!["rust", "compilers"]
+                ),
+                ("https://www.example.org/atom.xml", vec![]),
+            ]
+        );
+
+        assert!(parse_opml("not opml").is_err());
+        assert!(parse_opml("<opml></opml>").is_err());
+    }
+
+    /// Imported feeds are added under their host, skipping feeds that are already configured.
+    #[test]
+    fn test_merge_feeds() {
+        let mut config = config(0);
+        let feeds =
+            parse_opml(include_str!("../../testdata/feeds1.opml")).expect("Failed to parse");
+        assert_eq!(config.merge_feeds(feeds.clone()), 3);
+        assert_eq!(
+            config.subsources(),
+            vec![
+                "blog.example.com",
+                "example",
+                "example.org",
+                "this_week_in_rust.org"
+            ]
+        );
+        assert_eq!(
+            config.feeds["blog.example.com"].tags,
+            vec!["rust", "compilers"]
+        );
+        assert_eq!(config.merge_feeds(feeds), 0);
+
+        // A different feed from an existing host gets a new name
+        let url = StoryUrl::parse("https://example.org/other.xml").expect("URL");
+        assert_eq!(config.merge_feeds(vec![(url, vec![])]), 1);
+        assert!(config.feeds.contains_key("example.org_2"));
+    }
+
+    /// Stories scraped from a configured feed pick up the tags configured for that feed, and keep their feed in an
+    /// ID that survives a round trip.
+    #[test]
+    fn test_feed_tags() {
+        let mut config = ScrapeConfig::default();
+        config.feed = self::config(0);
+        config.feed.feeds.get_mut("example").expect("Feed").tags = vec!["blog".to_owned()];
+        let (stories, warnings) = scrape_url(
+            &config,
+            ScrapeSource::Feed,
+            "https://example.com/rss",
+            TTL_FEED,
+        )
+        .expect("Failed to scrape");
+        assert!(warnings.is_empty());
+        assert_eq!(stories.len(), 1);
+        let id = stories[0].id.clone();
+        assert_eq!(id.subsource.as_deref(), Some("example"));
+        assert_eq!(ScrapeId::from_string(id.to_string()), Some(id));
+        let core = ScrapeExtractor::new(&config).extract(&stories[0]);
+        assert_eq!(core.tags, vec!["rust", "blog"]);
+
+        // A feed that isn't configured gets no extra tags
+        let (stories, _) = scrape_url(
+            &config,
+            ScrapeSource::Feed,
+            "https://example.org/rss",
+            TTL_FEED,
+        )
+        .expect("Failed to scrape");
+        let core = ScrapeExtractor::new(&config).extract(&stories[0]);
+        assert_eq!(core.tags, vec!["rust"]);
+    }
+
+    #[rstest]
+    #[case(false, "A story - Example")]
+    #[case(true, "A story")]
+    fn test_strip_title_suffixes(#[case] strip: bool, #[case] expected: &str) {
+        let mut config = config(0);
+        config.strip_title_suffixes = strip;
+        let date = StoryDate::year_month_day(2023, 1, 1).expect("Date failed");
+        let url = StoryUrl::parse("https://example.com/story").expect("URL");
+        let story = FeedStory::new("1", date, "A story - Example", url, vec![]);
+        let core = FeedScraper::default().extract_core(&config, &story);
+        assert_eq!(core.title, expected);
+        // The raw title is kept as-is
+        assert_eq!(story.shared.raw_title, "A story - Example");
     }
 }
@@ -7,8 +7,8 @@ use std::{
 use tl::{HTMLTag, Parser, ParserOptions};
 
 use super::{
-    scrape_story, utils::html::*, GenericScrape, ScrapeConfigSource, ScrapeCore, ScrapeShared,
-    ScrapeSource, ScrapeSourceDef, ScrapeStory, Scraper,
+    scrape_story, utils::html::*, utils::split_host_path, GenericScrape, ScrapeConfigSource,
+    ScrapeCore, ScrapeResult, ScrapeShared, ScrapeSource, ScrapeSourceDef, ScrapeStory, Scraper,
 };
 use crate::types::*;
 
@@ -24,10 +24,12 @@ impl ScrapeSourceDef for HackerNews {
     }
 
     fn id_from_comments_url(url: &str) -> Option<(&str, Option<&str>)> {
-        Some((
-            url.strip_prefix("https://news.ycombinator.com/item?id=")?,
-            None,
-        ))
+        let (host, path) = split_host_path(url);
+        if !Self::is_comments_host(host) {
+            return None;
+        }
+        let id = path.strip_prefix("item?id=")?.split('&').next()?;
+        Some((id, None)).filter(|(id, _)| !id.is_empty())
     }
 
     fn is_comments_host(host: &str) -> bool {
@@ -35,10 +37,48 @@ impl ScrapeSourceDef for HackerNews {
     }
 }
 
+/// Where we scrape Hacker News from.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HackerNewsMode {
+    /// Scrape the HTML pages listed in `pages`.
+    #[default]
+    Html,
+    /// Use the Algolia `search_by_date` JSON API at `algolia_url`. The API returns front page stories newest first
+    /// rather than in front page order, so these stories have no rank.
+    Algolia,
+}
+
 #[derive(Clone, Default, Serialize, Deserialize)]
 pub struct HackerNewsConfig {
     homepage: String,
     pages: Vec<String>,
+    #[serde(default)]
+    mode: HackerNewsMode,
+    #[serde(default)]
+    algolia_url: String,
+    /// Remove prefixes like "Show HN:" from titles, as the story is tagged with them instead.
+    #[serde(default)]
+    strip_title_prefixes: bool,
+}
+
+/// Title prefixes that classify a story, and the tags they map to.
+const TITLE_PREFIXES: &[(&str, &str)] = &[
+    ("Show HN", "showhn"),
+    ("Ask HN", "askhn"),
+    ("Tell HN", "tellhn"),
+];
+
+/// Split a classifying prefix (ie: "Show HN: ") from a title, returning the prefix's tag and the rest of the title.
+fn split_title_prefix(title: &str) -> Option<(&'static str, &str)> {
+    TITLE_PREFIXES.iter().find_map(|(prefix, tag)| {
+        let rest = title.strip_prefix(prefix)?;
+        if rest.is_empty() || rest.starts_with(':') || rest.starts_with(char::is_whitespace) {
+            Some((*tag, rest.trim_start_matches(':').trim_start()))
+        } else {
+            None
+        }
+    })
 }
 
 impl ScrapeConfigSource for HackerNewsConfig {
@@ -47,10 +87,14 @@ impl ScrapeConfigSource for HackerNewsConfig {
     }
 
     fn provide_urls(&self, _: Vec<String>) -> Vec<String> {
-        self.pages
-            .iter()
-            .map(|s| format!("{}{}", self.homepage, s))
-            .collect_vec()
+        match self.mode {
+            HackerNewsMode::Html => self
+                .pages
+                .iter()
+                .map(|s| format!("{}{}", self.homepage, s))
+                .collect_vec(),
+            HackerNewsMode::Algolia => vec![self.algolia_url.clone()],
+        }
     }
 }
 
@@ -59,6 +103,8 @@ scrape_story! {
         points: u32,
         comments: u32,
         position: u32,
+        #[serde(default)]
+        author: String,
     }
 }
 
@@ -68,6 +114,9 @@ impl ScrapeStory for HackerNewsStory {
     fn merge(&mut self, other: HackerNewsStory) {
         self.points = std::cmp::max(self.points, other.points);
         self.comments = std::cmp::max(self.comments, other.comments);
+        if self.author.is_empty() {
+            self.author = other.author;
+        }
     }
 }
 
@@ -87,9 +136,28 @@ struct HackerNewsInfoLine {
     id: String,
     comments: u32,
     points: u32,
+    author: String,
     date: StoryDate,
 }
 
+/// The subset of an Algolia search response that we care about.
+#[derive(Deserialize)]
+struct AlgoliaResponse {
+    hits: Vec<AlgoliaHit>,
+}
+
+#[derive(Deserialize)]
+struct AlgoliaHit {
+    #[serde(rename = "objectID")]
+    object_id: String,
+    title: Option<String>,
+    url: Option<String>,
+    points: Option<u32>,
+    num_comments: Option<u32>,
+    author: Option<String>,
+    created_at: String,
+}
+
 #[derive(Debug)]
 enum HackerNewsNode {
     StoryLine(HackerNewsStoryLine),
@@ -164,10 +232,14 @@ impl HackerNewsScraper {
                 .into();
             let points = extract_number(score_node.inner_text(p).borrow())?;
             let comments = comments.ok_or_else(|| "Missing comment count".to_string())?;
+            let author = find_first(p, node, ".hnuser")
+                .map(|node| node.inner_text(p).trim().to_owned())
+                .unwrap_or_default();
             Ok(HackerNewsNode::InfoLine(HackerNewsInfoLine {
                 id,
                 comments,
                 points,
+                author,
                 date,
             }))
         } else {
@@ -175,6 +247,54 @@ impl HackerNewsScraper {
         };
     }
 
+    fn map_algolia_hit(
+        &self,
+        hit: AlgoliaHit,
+    ) -> Result<GenericScrape<HackerNewsStory>, ScrapeWarning> {
+        let source = ScrapeSource::HackerNews;
+        let title = hit.title.ok_or_else(|| {
+            ScrapeWarning::other(source, format!("Missing title for id {}", hit.object_id))
+        })?;
+        // Text posts have no URL, so they link to their own comments page like the HTML scraper does
+        let url = hit
+            .url
+            .unwrap_or_else(|| HackerNews::comments_url(&hit.object_id, None));
+        let url = StoryUrl::parse(&url).ok_or(ScrapeWarning::UrlParse {
+            scrape_source: source,
+            value: url,
+        })?;
+        let date = StoryDate::parse_from_rfc3339(&hit.created_at).ok_or_else(|| {
+            ScrapeWarning::DateParse {
+                scrape_source: source,
+                value: hit.created_at.clone(),
+            }
+        })?;
+        Ok(HackerNewsStory::new(
+            hit.object_id,
+            date,
+            title,
+            url,
+            hit.points.unwrap_or_default(),
+            hit.num_comments.unwrap_or_default(),
+            // Positions start at one, so this leaves the story unranked
+            0,
+            hit.author.unwrap_or_default(),
+        ))
+    }
+
+    fn scrape_algolia(&self, input: &str) -> ScrapeResult<HackerNewsStory> {
+        let response: AlgoliaResponse = serde_json::from_str(input)?;
+        let mut stories = vec![];
+        let mut errors = vec![];
+        for hit in response.hits {
+            match self.map_algolia_hit(hit) {
+                Ok(story) => stories.push(story),
+                Err(e) => errors.push(e),
+            }
+        }
+        Ok((stories, errors))
+    }
+
     fn tags_from_title(
         &self,
         _args: &<HackerNews as ScrapeSourceDef>::Config,
@@ -182,11 +302,8 @@ impl HackerNewsScraper {
     ) -> Vec<&'static str> {
         let mut tags = vec![];
         // TODO: Strip years [ie: (2005)] from end of title
-        if title.starts_with("Show HN") {
-            tags.push("show");
-        }
-        if title.starts_with("Ask HN") {
-            tags.push("ask");
+        if let Some((tag, _)) = split_title_prefix(title) {
+            tags.push(tag);
         }
         if title.ends_with("[pdf]") {
             tags.push("pdf");
@@ -202,11 +319,12 @@ impl Scraper for HackerNewsScraper {
     type Config = <HackerNews as ScrapeSourceDef>::Config;
     type Output = <HackerNews as ScrapeSourceDef>::Scrape;
 
-    fn scrape(
-        &self,
-        _args: &HackerNewsConfig,
-        input: &str,
-    ) -> Result<(Vec<GenericScrape<Self::Output>>, Vec<String>), ScrapeError> {
+    /// Both the HTML pages and Algolia JSON responses are accepted regardless of the configured mode, so a
+    /// switch of mode never strands already-fetched pages.
+    fn scrape(&self, _args: &HackerNewsConfig, input: &str) -> ScrapeResult<Self::Output> {
+        if input.trim_start().starts_with('{') {
+            return self.scrape_algolia(input);
+        }
         let dom = tl::parse(input, ParserOptions::default())?;
         let p = dom.parser();
         let mut errors = vec![];
@@ -221,7 +339,7 @@ impl Scraper for HackerNewsScraper {
                     story_lines.insert(x.id.clone(), x);
                 }
                 Err(e) => {
-                    errors.push(e);
+                    errors.push(ScrapeWarning::other(ScrapeSource::HackerNews, e));
                 }
             }
         }
@@ -239,14 +357,18 @@ impl Scraper for HackerNewsScraper {
                     date,
                     points,
                     comments,
+                    author,
                     ..
                 } = info;
                 let id = k;
                 stories.push(HackerNewsStory::new(
-                    id, date, raw_title, url, points, comments, position,
+                    id, date, raw_title, url, points, comments, position, author,
                 ));
             } else {
-                errors.push(format!("Unmatched story/info for id {}", k));
+                errors.push(ScrapeWarning::other(
+                    ScrapeSource::HackerNews,
+                    format!("Unmatched story/info for id {}", k),
+                ));
             }
         }
         stories.sort_by_key(|x| x.data.position);
@@ -263,13 +385,152 @@ impl Scraper for HackerNewsScraper {
             .into_iter()
             .map(Cow::Borrowed)
             .collect();
+        let title = match split_title_prefix(&input.shared.raw_title) {
+            Some((_, rest)) if args.strip_title_prefixes && !rest.is_empty() => rest,
+            _ => &input.shared.raw_title,
+        };
         ScrapeCore {
             source: &input.shared.id,
-            title: Cow::Borrowed(&input.shared.raw_title),
+            title: Cow::Borrowed(title),
             url: &input.shared.url,
             date: input.shared.date,
             rank: (input.data.position as usize).checked_sub(1),
+            comments: Some(input.data.comments),
+            points: Some(input.data.points),
+            author: Some(input.data.author.as_str())
+                .filter(|author| !author.is_empty())
+                .map(Cow::Borrowed),
             tags,
         }
     }
 }
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::{backends::scrape, ScrapeConfig, TypedScrape};
+
+    fn scrape_hn(input: &str) -> Vec<GenericScrape<HackerNewsStory>> {
+        let (scrapes, _) = scrape(&ScrapeConfig::default(), ScrapeSource::HackerNews, input)
+            .expect("Failed to scrape");
+        scrapes
+            .into_iter()
+            .map(|scrape| match scrape {
+                TypedScrape::HackerNews(story) => story,
+                _ => panic!("Unexpected scrape type"),
+            })
+            .collect()
+    }
+
+    /// The Algolia API and HTML scrapers produce the same stories for the same front page, though only the HTML
+    /// scraper knows their rank.
+    #[test]
+    fn test_algolia_matches_html() {
+        let html = scrape_hn(include_str!("../../testdata/hn1.html"));
+        let algolia = scrape_hn(include_str!("../../testdata/hn-algolia1.json"));
+        assert_eq!(algolia.len(), 7);
+
+        let config = HackerNewsConfig::default();
+        let scraper = HackerNewsScraper::default();
+        let mut compared = 0;
+        for story in &algolia {
+            let Some(html_story) = html.iter().find(|html| html.shared.id == story.shared.id)
+            else {
+                continue;
+            };
+            let (a, b) = (
+                scraper.extract_core(&config, story),
+                scraper.extract_core(&config, html_story),
+            );
+            assert_eq!(a.source, b.source);
+            assert_eq!(a.title, b.title);
+            assert_eq!(a.url, b.url);
+            assert_eq!(a.date, b.date);
+            assert_eq!(a.tags, b.tags);
+            assert_eq!(a.rank, None);
+            assert!(b.rank.is_some());
+            assert_eq!(a.points, b.points);
+            // The fixtures were captured separately and don't always agree on the submitter, so just make sure we
+            // found one in both
+            assert!(a.author.is_some() && b.author.is_some());
+            assert_eq!(story.data.comments, html_story.data.comments);
+            compared += 1;
+        }
+        assert_eq!(compared, 6);
+        assert_eq!(
+            scraper.extract_core(&config, &html[0]).author,
+            Some(Cow::Borrowed("waddlesplash"))
+        );
+
+        // Text posts link back to their comments page
+        let ask = algolia
+            .iter()
+            .find(|story| story.shared.id.id == "34107581")
+            .expect("Missing story");
+        assert_eq!(
+            ask.shared.url.raw(),
+            "https://news.ycombinator.com/item?id=34107581"
+        );
+        assert_eq!(
+            scraper.extract_core(&config, ask).tags,
+            vec![Cow::Borrowed("askhn")]
+        );
+    }
+
+    #[test]
+    fn test_provide_urls() {
+        let mut config = HackerNewsConfig {
+            homepage: "https://news.ycombinator.com/".into(),
+            pages: vec!["news".into(), "news?p=2".into()],
+            mode: HackerNewsMode::Html,
+            algolia_url: "https://hn.algolia.com/api/v1/search_by_date?tags=front_page".into(),
+            strip_title_prefixes: false,
+        };
+        assert_eq!(
+            config.provide_urls(vec![]),
+            vec![
+                "https://news.ycombinator.com/news",
+                "https://news.ycombinator.com/news?p=2"
+            ]
+        );
+        config.mode = HackerNewsMode::Algolia;
+        assert_eq!(
+            config.provide_urls(vec![]),
+            vec!["https://hn.algolia.com/api/v1/search_by_date?tags=front_page"]
+        );
+    }
+
+    #[test]
+    fn test_title_tags() {
+        let config = HackerNewsConfig::default();
+        let scraper = HackerNewsScraper::default();
+        let tags = |title: &str| scraper.tags_from_title(&config, title);
+        assert_eq!(tags("Show HN: my project"), vec!["showhn"]);
+        assert_eq!(tags("Ask HN: Who is hiring?"), vec!["askhn"]);
+        assert_eq!(tags("Tell HN: Something happened"), vec!["tellhn"]);
+        assert_eq!(tags("A paper about HN [pdf]"), vec!["pdf"]);
+        assert!(tags("Showing HN some love").is_empty());
+        assert!(tags("Show HNews: not a prefix").is_empty());
+        assert!(tags("How to Show HN your project").is_empty());
+    }
+
+    #[test]
+    fn test_strip_title_prefixes() {
+        let date = StoryDate::year_month_day(2020, 1, 1).expect("Date failed");
+        let url = StoryUrl::parse("https://example.com").expect("URL");
+        let scraper = HackerNewsScraper::default();
+        let mut config = HackerNewsConfig::default();
+        let title = |config: &HackerNewsConfig, title: &str| {
+            let story = HackerNewsStory::new_with_defaults("1", date, title, url.clone());
+            scraper.extract_core(config, &story).title.into_owned()
+        };
+
+        assert_eq!(title(&config, "Show HN: My tool"), "Show HN: My tool");
+        config.strip_title_prefixes = true;
+        assert_eq!(title(&config, "Show HN: My tool"), "My tool");
+        assert_eq!(title(&config, "Ask HN: How do you?"), "How do you?");
+        assert_eq!(title(&config, "A normal title"), "A normal title");
+        // A bare prefix is all we have for a title
+        assert_eq!(title(&config, "Ask HN"), "Ask HN");
+    }
+}
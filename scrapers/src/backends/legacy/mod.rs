@@ -1,6 +1,6 @@
 use std::{
     fs::File,
-    io::{BufRead, BufReader},
+    io::{BufRead, BufReader, BufWriter, Write},
     path::Path,
 };
 
@@ -42,3 +42,139 @@ pub fn import_backup(file: &Path) -> Result<Vec<TypedScrape>, LegacyError> {
 
     Ok(out)
 }
+
+/// A record skipped by [`import_backup_lenient`]: the (1-based) line number it started on, and why it was skipped.
+pub type SkippedRecord = (usize, LegacyError);
+
+/// Import a backup-formatted JSON file like [`import_backup`], but skip records that fail to parse rather than
+/// aborting the import. Returns the scrapes that were read, along with the records that were skipped.
+pub fn import_backup_lenient(
+    file: &Path,
+) -> Result<(Vec<TypedScrape>, Vec<SkippedRecord>), LegacyError> {
+    let f = BufReader::new(File::open(file)?);
+    let mut out = vec![];
+    let mut errors = vec![];
+    // Records may span lines, so gather lines until one looks like the end of a record
+    let mut pending: Vec<(usize, Vec<u8>)> = vec![];
+    for (index, line) in f.split(b'\n').enumerate() {
+        let mut line = line?;
+        if pending.is_empty() && line.iter().all(u8::is_ascii_whitespace) {
+            continue;
+        }
+        line.push(b'\n');
+        let complete = line.ends_with("}\n".as_bytes());
+        pending.push((index + 1, line));
+        if complete {
+            parse_pending(&mut pending, &mut out, &mut errors);
+        }
+    }
+    parse_pending(&mut pending, &mut out, &mut errors);
+
+    Ok((out, errors))
+}
+
+/// Parse the gathered lines of a record. If they don't parse, the first line is skipped as a bad record and the
+/// remaining lines are retried, so a truncated record doesn't take the following record down with it.
+fn parse_pending(
+    pending: &mut Vec<(usize, Vec<u8>)>,
+    out: &mut Vec<TypedScrape>,
+    errors: &mut Vec<SkippedRecord>,
+) {
+    let parse = |lines: &[(usize, Vec<u8>)]| -> Result<TypedScrape, LegacyError> {
+        let json = String::from_utf8(lines.iter().flat_map(|(_, line)| line.clone()).collect())?;
+        Ok(serde_json::from_str(&json)?)
+    };
+    while let Some((line, _)) = pending.first() {
+        match parse(pending) {
+            Ok(scrape) => {
+                out.push(scrape);
+                pending.clear();
+            }
+            Err(e) => {
+                errors.push((*line, e));
+                pending.remove(0);
+            }
+        }
+    }
+}
+
+/// Write scrapes in the format read by [`import_backup`], one JSON `TypedScrape` per line. Returns the number of
+/// scrapes written.
+pub fn write_backup<'a, W: Write>(
+    w: &mut W,
+    scrapes: impl IntoIterator<Item = &'a TypedScrape>,
+) -> Result<usize, LegacyError> {
+    let mut count = 0;
+    for scrape in scrapes {
+        serde_json::to_writer(&mut *w, scrape)?;
+        w.write_all(b"\n")?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Export scrapes to a backup-formatted JSON file that can be read back with [`import_backup`].
+pub fn export_backup<'a>(
+    file: &Path,
+    scrapes: impl IntoIterator<Item = &'a TypedScrape>,
+) -> Result<usize, LegacyError> {
+    let mut w = BufWriter::new(File::create(file)?);
+    let count = write_backup(&mut w, scrapes)?;
+    w.flush()?;
+    Ok(count)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{backends::test::load_sample_scrapes, ScrapeConfig};
+    use itertools::Itertools;
+
+    /// Exporting what we imported produces the same scrapes, and the same file.
+    #[test]
+    fn test_round_trip() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempfile::tempdir()?;
+        let (first, second) = (
+            dir.path().join("first.json"),
+            dir.path().join("second.json"),
+        );
+        let samples = load_sample_scrapes(&ScrapeConfig::default());
+        assert_eq!(export_backup(&first, &samples)?, samples.len());
+
+        let imported = import_backup(&first)?;
+        assert_eq!(export_backup(&second, &imported)?, samples.len());
+        let reimported = import_backup(&second)?;
+
+        let json = |scrapes: &[TypedScrape]| serde_json::to_value(scrapes).expect("JSON");
+        assert_eq!(json(&samples), json(&imported));
+        assert_eq!(json(&imported), json(&reimported));
+        assert_eq!(std::fs::read(first)?, std::fs::read(second)?);
+
+        Ok(())
+    }
+
+    /// A truncated record is skipped by the lenient import, without losing the records around it.
+    #[test]
+    fn test_import_lenient() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempfile::tempdir()?;
+        let file = dir.path().join("backup.json");
+        let samples = load_sample_scrapes(&ScrapeConfig::default());
+        let good = samples
+            .iter()
+            .take(2)
+            .map(|scrape| serde_json::to_string(scrape).expect("JSON"))
+            .collect_vec();
+        let truncated = &good[1][..good[1].len() / 2];
+        std::fs::write(&file, format!("{}\n{truncated}\n{}\n", good[0], good[1]))?;
+
+        assert!(import_backup(&file).is_err());
+        let (scrapes, errors) = import_backup_lenient(&file)?;
+        let json = |scrapes: &[TypedScrape]| serde_json::to_value(scrapes).expect("JSON");
+        assert_eq!(json(&scrapes), json(&samples[..2]));
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0, 2);
+        assert!(matches!(errors[0].1, LegacyError::JSONError(_)));
+
+        Ok(())
+    }
+}
@@ -17,8 +17,13 @@ impl ScrapeSourceDef for Lobsters {
     }
 
     fn id_from_comments_url(url: &str) -> Option<(&str, Option<&str>)> {
-        let url = url.trim_end_matches('/');
-        Some((url.strip_prefix("https://lobste.rs/s/")?, None))
+        let (host, path) = utils::split_host_path(url);
+        if !Self::is_comments_host(host) {
+            return None;
+        }
+        // `s/<id>/<optional slug>`
+        let id = path.strip_prefix("s/")?.split('/').next()?;
+        Some((id, None)).filter(|(id, _)| !id.is_empty())
     }
 
     fn is_comments_host(host: &str) -> bool {
@@ -48,6 +53,8 @@ scrape_story! {
         position: u32,
         score: u32,
         tags: Vec<String>,
+        #[serde(default)]
+        author: String,
     }
 }
 
@@ -67,11 +74,7 @@ impl Scraper for LobstersScraper {
     type Config = <Lobsters as ScrapeSourceDef>::Config;
     type Output = <Lobsters as ScrapeSourceDef>::Scrape;
 
-    fn scrape(
-        &self,
-        _args: &Self::Config,
-        input: &str,
-    ) -> Result<(Vec<GenericScrape<Self::Output>>, Vec<String>), ScrapeError> {
+    fn scrape(&self, _args: &Self::Config, input: &str) -> ScrapeResult<Self::Output> {
         let doc = Document::parse(input)?;
         let rss = doc.root_element();
         let mut warnings = vec![];
@@ -88,6 +91,7 @@ impl Scraper for LobstersScraper {
                     let mut url = None;
                     let mut date = None;
                     let mut tags = vec![];
+                    let mut author = String::new();
                     for subitem in item.children() {
                         if !subitem.is_element() {
                             continue;
@@ -100,14 +104,19 @@ impl Scraper for LobstersScraper {
                                 })
                             }
                             "link" => url = subitem.text().and_then(StoryUrl::parse),
-                            "author" => {}
+                            "author" => {
+                                author = subitem.text().map(parse_author).unwrap_or_default()
+                            }
                             "pubDate" => {
                                 date = subitem.text().and_then(StoryDate::parse_from_rfc2822)
                             }
                             "comments" => {}
                             "category" => drop(subitem.text().map(|s| tags.push(s.to_owned()))),
                             "description" => {}
-                            x => warnings.push(format!("Unknown sub-node '{}'", x)),
+                            x => warnings.push(ScrapeWarning::other(
+                                ScrapeSource::Lobsters,
+                                format!("Unknown sub-node '{}'", x),
+                            )),
                         }
                     }
                     if let (Some(raw_title), Some(id), Some(url), Some(date)) =
@@ -125,9 +134,13 @@ impl Scraper for LobstersScraper {
                             position,
                             score,
                             tags,
+                            author,
                         ));
                     } else {
-                        warnings.push("Story did not contain all required fields".to_string());
+                        warnings.push(ScrapeWarning::other(
+                            ScrapeSource::Lobsters,
+                            "Story did not contain all required fields",
+                        ));
                     }
                 }
             }
@@ -155,6 +168,22 @@ impl Scraper for LobstersScraper {
             date: input.shared.date,
             tags,
             rank: (input.data.position as usize).checked_sub(1),
+            // The RSS feed doesn't include comment counts
+            comments: None,
+            points: None,
+            author: Some(input.data.author.as_str())
+                .filter(|author| !author.is_empty())
+                .map(Cow::Borrowed),
         }
     }
 }
+
+/// Authors are formatted as `user@users.lobste.rs (user)`.
+fn parse_author(author: &str) -> String {
+    let author = author.trim();
+    let username = match author.split_once('(') {
+        Some((_, name)) => name.trim_end_matches(')'),
+        None => author.split('@').next().unwrap_or_default(),
+    };
+    username.trim().to_owned()
+}
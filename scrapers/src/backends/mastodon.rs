@@ -0,0 +1,321 @@
+use std::{borrow::Cow, collections::HashMap};
+
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    scrape_story, GenericScrape, ScrapeConfigSource, ScrapeCore, ScrapeResult, ScrapeSource,
+    ScrapeSourceDef, ScrapeStory, Scraper,
+};
+use crate::types::*;
+
+/// The subsource for statuses that don't carry any of the hashtags we follow (ie: from a public timeline).
+const PUBLIC_SUBSOURCE: &str = "public";
+
+pub struct Mastodon {}
+
+impl ScrapeSourceDef for Mastodon {
+    type Config = MastodonConfig;
+    type Scrape = MastodonStory;
+    type Scraper = MastodonScraper;
+
+    const SUBSOURCE_IDS: bool = true;
+
+    /// Status ids are the status URL without its scheme, so the comments page is the status itself.
+    fn comments_url(id: &str, _subsource: Option<&str>) -> String {
+        format!("https://{}", id)
+    }
+
+    fn id_from_comments_url(_url: &str) -> Option<(&str, Option<&str>)> {
+        // Any host may be a fediverse instance, so we can't recognize status URLs
+        None
+    }
+
+    fn is_comments_host(_host: &str) -> bool {
+        false
+    }
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct MastodonConfig {
+    /// The hashtag timeline API, ie: `https://mastodon.social/api/v1/timelines/tag/${tag}`.
+    api: String,
+    limit: usize,
+    /// Hashtags to follow, keyed by tag.
+    #[serde(default)]
+    hashtags: HashMap<String, HashtagConfig>,
+}
+
+impl ScrapeConfigSource for MastodonConfig {
+    fn subsources(&self) -> Vec<String> {
+        self.hashtags.keys().cloned().sorted().collect()
+    }
+
+    fn provide_urls(&self, subsources: Vec<String>) -> Vec<String> {
+        subsources
+            .iter()
+            .map(|tag| self.api.replace("${tag}", tag) + &format!("?limit={}", self.limit))
+            .collect()
+    }
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct HashtagConfig {
+    #[serde(default)]
+    is_tag: bool,
+}
+
+scrape_story! {
+    MastodonStory {
+        reblogs: u32,
+        favourites: u32,
+        replies: u32,
+    }
+}
+
+impl ScrapeStory for MastodonStory {
+    const TYPE: ScrapeSource = ScrapeSource::Mastodon;
+
+    fn merge(&mut self, other: MastodonStory) {
+        self.reblogs = std::cmp::max(self.reblogs, other.reblogs);
+        self.favourites = std::cmp::max(self.favourites, other.favourites);
+        self.replies = std::cmp::max(self.replies, other.replies);
+    }
+}
+
+/// The subset of a Mastodon status that we care about.
+#[derive(Deserialize)]
+struct Status {
+    created_at: String,
+    uri: String,
+    url: Option<String>,
+    /// Boosts wrap the original status.
+    reblog: Option<Box<Status>>,
+    card: Option<Card>,
+    #[serde(default)]
+    tags: Vec<Tag>,
+    #[serde(default)]
+    reblogs_count: u32,
+    #[serde(default)]
+    favourites_count: u32,
+    #[serde(default)]
+    replies_count: u32,
+}
+
+/// The preview card for the first link in a status.
+#[derive(Deserialize)]
+struct Card {
+    url: String,
+    title: String,
+}
+
+#[derive(Deserialize)]
+struct Tag {
+    name: String,
+}
+
+#[derive(Default)]
+pub struct MastodonScraper {}
+
+impl MastodonScraper {
+    fn map_status(
+        &self,
+        args: &MastodonConfig,
+        status: Status,
+    ) -> Result<GenericScrape<<Self as Scraper>::Output>, ScrapeWarning> {
+        let source = ScrapeSource::Mastodon;
+        // A boost of a status is the same story as the status itself
+        let status = match status.reblog {
+            Some(reblog) => *reblog,
+            None => status,
+        };
+
+        // The status URL is `instance/@account/id` on Mastodon, but other servers use their own formats
+        let status_url = status.url.as_deref().unwrap_or(&status.uri);
+        let id = status_url
+            .split_once("://")
+            .map(|(_, id)| id.trim_end_matches('/'))
+            .filter(|id| !id.is_empty())
+            .ok_or_else(|| ScrapeWarning::UrlParse {
+                scrape_source: source,
+                value: status_url.to_owned(),
+            })?
+            .to_owned();
+        let subsource = status
+            .tags
+            .iter()
+            .map(|tag| tag.name.to_ascii_lowercase())
+            .find(|tag| args.hashtags.contains_key(tag))
+            .unwrap_or_else(|| PUBLIC_SUBSOURCE.to_owned());
+
+        let date =
+            StoryDate::parse_from_rfc3339(&status.created_at).ok_or(ScrapeWarning::DateParse {
+                scrape_source: source,
+                value: status.created_at,
+            })?;
+        let card = status.card.ok_or_else(|| {
+            ScrapeWarning::other(source, format!("Status {} has no link card", id))
+        })?;
+        let url = StoryUrl::parse(&card.url).ok_or(ScrapeWarning::UrlParse {
+            scrape_source: source,
+            value: card.url,
+        })?;
+        let title = card.title.trim();
+        if title.is_empty() {
+            return Err(ScrapeWarning::MissingField {
+                scrape_source: source,
+                field: "card.title".to_owned(),
+            });
+        }
+
+        Ok(MastodonStory::new_subsource(
+            id,
+            subsource,
+            date,
+            title.to_owned(),
+            url,
+            status.reblogs_count,
+            status.favourites_count,
+            status.replies_count,
+        ))
+    }
+}
+
+impl Scraper for MastodonScraper {
+    type Config = <Mastodon as ScrapeSourceDef>::Config;
+    type Output = <Mastodon as ScrapeSourceDef>::Scrape;
+
+    fn scrape(&self, args: &MastodonConfig, input: &str) -> ScrapeResult<Self::Output> {
+        let statuses: Vec<Status> = serde_json::from_str(input)?;
+        let mut stories: Vec<GenericScrape<Self::Output>> = vec![];
+        let mut warnings = vec![];
+        for status in statuses {
+            match self.map_status(args, status) {
+                Ok(story) => {
+                    if let Some(existing) = stories.iter_mut().find(|s| s.id == story.id) {
+                        existing.data.merge(story.data);
+                    } else {
+                        stories.push(story);
+                    }
+                }
+                Err(e) => warnings.push(e),
+            }
+        }
+        Ok((stories, warnings))
+    }
+
+    fn extract_core<'a>(
+        &self,
+        args: &Self::Config,
+        input: &'a GenericScrape<Self::Output>,
+    ) -> ScrapeCore<'a> {
+        let mut tags = vec![];
+        if let Some(ref subsource) = input.shared.id.subsource {
+            if let Some(config) = args.hashtags.get(subsource) {
+                if config.is_tag {
+                    tags.push(Cow::Borrowed(subsource.as_str()));
+                }
+            }
+        }
+
+        ScrapeCore {
+            source: &input.shared.id,
+            title: Cow::Borrowed(&input.shared.raw_title),
+            url: &input.shared.url,
+            date: input.shared.date,
+            tags,
+            // Timelines are chronological, so there's no ranking
+            rank: None,
+            comments: Some(input.data.replies),
+            points: None,
+            author: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{backends::scrape, ScrapeConfig, TypedScrape};
+
+    fn config() -> MastodonConfig {
+        MastodonConfig {
+            api: "https://mastodon.social/api/v1/timelines/tag/${tag}".to_owned(),
+            limit: 40,
+            hashtags: HashMap::from_iter([
+                ("rust".to_owned(), HashtagConfig { is_tag: true }),
+                ("programming".to_owned(), HashtagConfig { is_tag: false }),
+            ]),
+        }
+    }
+
+    #[test]
+    fn test_provide_urls() {
+        let config = config();
+        assert_eq!(
+            config.provide_urls(config.subsources()),
+            vec![
+                "https://mastodon.social/api/v1/timelines/tag/programming?limit=40",
+                "https://mastodon.social/api/v1/timelines/tag/rust?limit=40",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scrape() {
+        let config = ScrapeConfig {
+            mastodon: config(),
+            ..Default::default()
+        };
+        let (scrapes, warnings) = scrape(
+            &config,
+            ScrapeSource::Mastodon,
+            include_str!("../../testdata/mastodon-rust1.json"),
+        )
+        .expect("Failed to scrape");
+        let stories = scrapes
+            .iter()
+            .filter_map(TypedScrape::mastodon)
+            .collect_vec();
+
+        // The boost collapses into the original status, and the status without a card is skipped
+        assert_eq!(stories.len(), 3);
+        assert_eq!(warnings.len(), 1);
+        assert!(matches!(warnings[0], ScrapeWarning::Other { .. }));
+
+        let story = stories[0];
+        assert_eq!(
+            story.id,
+            ScrapeSource::Mastodon.subsource_id("rust", "hachyderm.io/@ferris/109662312345678901")
+        );
+        assert_eq!(story.raw_title, "Announcing Rust 1.66.1");
+        assert_eq!(
+            story.url.raw(),
+            "https://blog.rust-lang.org/2023/01/10/Rust-1.66.1.html"
+        );
+        // The boost reported higher counts
+        assert_eq!(story.data.reblogs, 45);
+        assert_eq!(story.data.favourites, 120);
+        assert_eq!(
+            story.id.comments_url(),
+            "https://hachyderm.io/@ferris/109662312345678901"
+        );
+
+        let extractor = crate::ScrapeExtractor::new(&config);
+        let core = extractor.extract(&scrapes[0]);
+        assert_eq!(core.tags, vec!["rust"]);
+        assert_eq!(core.comments, Some(6));
+
+        // A followed tag that isn't a story tag, and an untagged status from the public timeline
+        assert_eq!(stories[1].id.subsource.as_deref(), Some("programming"));
+        assert!(extractor.extract(&scrapes[1]).tags.is_empty());
+        assert_eq!(stories[2].id.subsource.as_deref(), Some("public"));
+    }
+
+    /// Ids survive a trip through their string form, even with hyphens in the instance name.
+    #[test]
+    fn test_id_round_trip() {
+        let id = ScrapeSource::Mastodon.subsource_id("rust", "fosstodon-test.org/@a-b/123");
+        assert_eq!(ScrapeId::from_string(id.to_string()), Some(id));
+    }
+}
@@ -1,8 +1,8 @@
 use serde::{ser::SerializeMap, Deserialize, Serialize};
 use std::{borrow::Cow, fmt::Debug};
 
-pub use self::def::ScrapeCore;
 pub(crate) use self::def::*;
+pub use self::def::{ScrapeCore, ScrapeMergeResult};
 use crate::types::*;
 
 mod def;
@@ -10,6 +10,7 @@ pub mod feed;
 pub mod hacker_news;
 pub mod legacy;
 pub mod lobsters;
+pub mod mastodon;
 pub mod reddit;
 pub mod slashdot;
 mod utils;
@@ -24,7 +25,7 @@ macro_rules! scrapers {
             config: &ScrapeConfig,
             source: ScrapeSource,
             input: &str,
-        ) -> Result<(Vec<TypedScrape>, Vec<String>), ScrapeError> {
+        ) -> Result<(Vec<TypedScrape>, Vec<ScrapeWarning>), ScrapeError> {
             match source {
                 $(
                     ScrapeSource::$name => {
@@ -37,6 +38,25 @@ macro_rules! scrapers {
             }
         }
 
+        /// Like [`scrape`], for input that was fetched from `url`.
+        pub fn scrape_url(
+            config: &ScrapeConfig,
+            source: ScrapeSource,
+            url: &str,
+            input: &str,
+        ) -> Result<(Vec<TypedScrape>, Vec<ScrapeWarning>), ScrapeError> {
+            match source {
+                $(
+                    ScrapeSource::$name => {
+                        let scraper = <$package::$name as ScrapeSourceDef>::Scraper::default();
+                        let (res, warnings) = scraper.scrape_url(&config.$package, url, input)?;
+                        Ok((res.into_iter().map(|x| x.into()).collect(), warnings))
+                    },
+                )*
+                ScrapeSource::Other => unreachable!(),
+            }
+        }
+
         /// Configuration for all scrapers.
         #[derive(Clone, Default, Serialize, Deserialize)]
         pub struct ScrapeConfig {
@@ -83,6 +103,13 @@ macro_rules! scrapers {
                 &[$(Self::$name),*]
             }
 
+            pub fn has_subsource_ids(&self) -> bool {
+                match self {
+                    $(Self::$name => <$package :: $name as ScrapeSourceDef>::SUBSOURCE_IDS,)*
+                    Self::Other => false,
+                }
+            }
+
             pub fn comments_url(&self, id: &str, subsource: Option<&str>) -> String {
                 match self {
                     $(Self::$name => $package :: $name :: comments_url(id, subsource),)*
@@ -96,14 +123,15 @@ macro_rules! scrapers {
                         let (source, subsource) = $package :: $name :: id_from_comments_url(url)?;
                         Some(ScrapeId :: new( *self, subsource.map(|s| s.to_owned()), source.to_owned() ))
                     },)*
-                    _ => unimplemented!()
+                    _ => None
                 }
             }
 
             pub fn is_comments_host(&self, host: &str) -> bool {
                 match self {
                     $(Self::$name => $package :: $name :: is_comments_host(host),)*
-                    _ => unimplemented!()
+                    // Unknown sources have no comments host, so they never produce self-posts
+                    _ => false
                 }
             }
 
@@ -117,18 +145,21 @@ macro_rules! scrapers {
         }
 
         #[derive(Clone, Debug, Deserialize, Serialize)]
+        #[cfg_attr(feature = "json_schema", derive(schemars::JsonSchema))]
         pub enum TypedScrape {
             $( $name (GenericScrape<<$package::$name as ScrapeSourceDef>::Scrape>), )*
         }
 
         impl TypedScrape {
-            pub fn merge(&mut self, b: Self) {
+            /// Merge a re-scrape of this story, returning the shared fields that changed.
+            pub fn merge(&mut self, b: Self) -> std::collections::HashSet<ScrapeMergeResult> {
                 match (self, b) {
                     $( (Self::$name(a), Self::$name(b)) => a.merge_generic(b), )*
                     (_a, _b) => {
                         // tracing::warn!(
                         //     "Unable to merge incompatible scrapes, ignoring",
                         // );
+                        Default::default()
                     }
                 }
             }
@@ -267,6 +298,14 @@ macro_rules! scrapers {
                     other: f(ScrapeSource::Other, self.other)?,
                 })
             }
+
+            /// Combine this map element-wise with `other`, passing each source's pair of values to `f`.
+            pub fn zip_with<W, T>(self, other: TypedScrapeMap<W>, f: impl Fn(ScrapeSource, V, W) -> T) -> TypedScrapeMap<T> {
+                TypedScrapeMap {
+                    $( $package: f(ScrapeSource::$name, self.$package, other.$package), )*
+                    other: f(ScrapeSource::Other, self.other, other.other),
+                }
+            }
         }
 
         const fn one(_: &'static str) -> usize {
@@ -324,6 +363,33 @@ impl From<TypedScrape> for (ScrapeId, TypedScrape) {
     }
 }
 
+#[cfg(feature = "json_schema")]
+impl TypedScrape {
+    /// Generates the JSON schema for a serialized `TypedScrape`, which is the format of each entry in a backup.
+    pub fn json_schema() -> schemars::schema::RootSchema {
+        schemars::schema_for!(TypedScrape)
+    }
+}
+
+impl ScrapeSource {
+    /// How much we trust this source's titles, lowest first. This is also the order in which sources break ties
+    /// between equally-scored stories.
+    pub fn title_priority(&self) -> u8 {
+        match self {
+            // HN is moderated and titles are high quality
+            ScrapeSource::HackerNews => 0,
+            ScrapeSource::Feed => 1,
+            ScrapeSource::Lobsters => 2,
+            ScrapeSource::Slashdot => 3,
+            // User-submitted titles are generally just OK
+            ScrapeSource::Reddit => 4,
+            // Link card titles come from the page, and often carry the site name
+            ScrapeSource::Mastodon => 5,
+            ScrapeSource::Other => 99,
+        }
+    }
+}
+
 impl Serialize for ScrapeSource {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -353,6 +419,7 @@ scrapers! {
     lobsters::Lobsters,
     reddit::Reddit,
     feed::Feed,
+    mastodon::Mastodon,
 }
 
 #[cfg(any(test, feature = "scrape_test"))]
@@ -387,27 +454,36 @@ pub mod test {
         ]
     }
 
+    fn feed_files() -> Vec<&'static str> {
+        stringify_all!["feed1.rss"]
+    }
+
+    fn mastodon_files() -> Vec<&'static str> {
+        stringify_all!["mastodon-rust1.json"]
+    }
+
     pub fn files_by_source(source: ScrapeSource) -> Vec<&'static str> {
         match source {
             ScrapeSource::HackerNews => hacker_news_files(),
             ScrapeSource::Slashdot => slashdot_files(),
             ScrapeSource::Reddit => reddit_files(),
             ScrapeSource::Lobsters => lobsters_files(),
-            ScrapeSource::Feed => vec![],
+            ScrapeSource::Feed => feed_files(),
+            ScrapeSource::Mastodon => mastodon_files(),
             ScrapeSource::Other => vec![],
         }
     }
 
-    /// Loads the various sample stories we've collected.
+    /// Loads the various sample stories we've collected, from every source. Panics if a source has no samples, so
+    /// that new backends can't silently go untested.
     pub fn load_sample_scrapes(config: &ScrapeConfig) -> Vec<TypedScrape> {
         let mut v = vec![];
-        for source in [
-            ScrapeSource::HackerNews,
-            ScrapeSource::Lobsters,
-            ScrapeSource::Reddit,
-            ScrapeSource::Slashdot,
-        ] {
-            for file in files_by_source(source) {
+        for &source in ScrapeSource::all() {
+            let files = files_by_source(source);
+            if files.is_empty() {
+                panic!("No sample files for {:?}", source);
+            }
+            for file in files {
                 let mut res = scrape(config, source, file)
                     .unwrap_or_else(|_| panic!("Scrape of {:?} failed", source));
                 if res.0.is_empty() {
@@ -438,4 +514,56 @@ pub mod test {
             assert!(scrape.date.year() >= 2022 && scrape.date.year() <= 2024);
         }
     }
+
+    #[test]
+    fn test_zip_with() {
+        let mut a = TypedScrapeMap::<usize>::new();
+        a.set(ScrapeSource::HackerNews, 2);
+        a.set(ScrapeSource::Reddit, 3);
+        let mut b = TypedScrapeMap::<usize>::new();
+        b.set(ScrapeSource::Reddit, 4);
+        b.set(ScrapeSource::Lobsters, 5);
+
+        let sum = a.zip_with(b, |_, a, b| a + b);
+        for (source, count) in sum.iter() {
+            let expected = match source {
+                ScrapeSource::HackerNews => 2,
+                ScrapeSource::Reddit => 7,
+                ScrapeSource::Lobsters => 5,
+                _ => 0,
+            };
+            assert_eq!(*count, expected, "{source:?}");
+        }
+
+        // The source is passed along with each pair of values
+        let sources = TypedScrapeMap::new_with_all(())
+            .zip_with(TypedScrapeMap::new_with_all(()), |source, _, _| source);
+        for (source, value) in sources.iter() {
+            assert_eq!(source, *value);
+        }
+    }
+
+    #[cfg(feature = "json_schema")]
+    #[test]
+    fn test_json_schema() {
+        let schema = serde_json::to_value(TypedScrape::json_schema()).expect("Failed to serialize");
+        let schema = jsonschema::JSONSchema::compile(&schema).expect("Invalid schema");
+
+        // A backup line with a raw URL rather than the serialized (url, host, normalized) tuple
+        let line = r#"{"HackerNews":{"id":"hacker_news-1","url":"https://example.com/","raw_title":"Title","date":1672531200,"points":1,"comments":2,"position":3}}"#;
+        let value: serde_json::Value = serde_json::from_str(line).expect("Invalid JSON");
+        assert!(schema.is_valid(&value));
+        serde_json::from_value::<TypedScrape>(value).expect("Failed to deserialize");
+
+        // Backup lines are written with `serde_json::to_string`
+        for scrape in load_sample_scrapes(&ScrapeConfig::default()) {
+            let line = serde_json::to_string(&scrape).expect("Failed to serialize");
+            let value: serde_json::Value = serde_json::from_str(&line).expect("Invalid JSON");
+            assert!(schema.is_valid(&value), "Failed to validate {line}");
+        }
+
+        // Missing fields should fail validation
+        let value = serde_json::json!({"HackerNews": {"id": "hacker_news-1"}});
+        assert!(!schema.is_valid(&value));
+    }
 }
@@ -4,8 +4,9 @@ use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
 use super::{
-    scrape_story, utils::html::unescape_entities, GenericScrape, ScrapeConfigSource, ScrapeCore,
-    ScrapeShared, ScrapeSource, ScrapeSourceDef, ScrapeStory, Scraper,
+    scrape_story, utils::html::unescape_entities, utils::split_host_path, GenericScrape,
+    ScrapeConfigSource, ScrapeCore, ScrapeResult, ScrapeShared, ScrapeSource, ScrapeSourceDef,
+    ScrapeStory, Scraper,
 };
 use crate::{
     datasci::titletrimmer::{remove_tags, trim_title, AWKWARD_LENGTH, IDEAL_LENGTH},
@@ -19,6 +20,8 @@ impl ScrapeSourceDef for Reddit {
     type Scrape = RedditStory;
     type Scraper = RedditScraper;
 
+    const SUBSOURCE_IDS: bool = true;
+
     fn comments_url(id: &str, subsource: Option<&str>) -> String {
         if let Some(subsource) = subsource {
             format!("https://www.reddit.com/r/{}/comments/{}/", subsource, id)
@@ -28,20 +31,16 @@ impl ScrapeSourceDef for Reddit {
     }
 
     fn id_from_comments_url(url: &str) -> Option<(&str, Option<&str>)> {
-        let url = url.trim_end_matches('/');
-        if let Some(url) = url.strip_prefix("https://www.reddit.com/comments/") {
-            Some((url, None))
-        } else {
-            let url = url.strip_prefix("https://www.reddit.com/r/")?;
-            if let Some((subreddit, rest)) = url.split_once('/') {
-                if let Some((_, id)) = rest.split_once('/') {
-                    Some((id, Some(subreddit)))
-                } else {
-                    None
-                }
-            } else {
-                None
-            }
+        let (host, path) = split_host_path(url);
+        if !Self::is_comments_host(host) {
+            return None;
+        }
+        // Either `comments/<id>/...` or `r/<subreddit>/comments/<id>/...`
+        let segments: Vec<&str> = path.split('/').collect();
+        match segments.as_slice() {
+            ["comments", id, ..] if !id.is_empty() => Some((id, None)),
+            ["r", subreddit, "comments", id, ..] if !id.is_empty() => Some((id, Some(subreddit))),
+            _ => None,
         }
     }
 
@@ -95,6 +94,11 @@ scrape_story! {
         num_comments: u32,
         score: u32,
         upvote_ratio: f32,
+        /// Self-posts (and galleries) link back to reddit, so the story URL is the permalink.
+        #[serde(default)]
+        is_self_post: bool,
+        #[serde(default)]
+        author: String,
     }
 }
 
@@ -102,24 +106,36 @@ impl ScrapeStory for RedditStory {
     const TYPE: ScrapeSource = ScrapeSource::Reddit;
 
     fn merge(&mut self, other: RedditStory) {
-        self.position = std::cmp::max(self.position, other.position);
+        // Lower positions are better, and zero means the story wasn't ranked
+        self.position = match (self.position, other.position) {
+            (0, position) | (position, 0) => position,
+            (a, b) => a.min(b),
+        };
         self.upvotes = std::cmp::max(self.upvotes, other.upvotes);
         self.downvotes = std::cmp::max(self.downvotes, other.downvotes);
         self.num_comments = std::cmp::max(self.num_comments, other.num_comments);
         self.score = std::cmp::max(self.score, other.score);
         self.upvote_ratio = f32::max(self.upvote_ratio, other.upvote_ratio);
+        self.is_self_post |= other.is_self_post;
     }
 }
 
+fn other(detail: String) -> ScrapeWarning {
+    ScrapeWarning::other(ScrapeSource::Reddit, detail)
+}
+
 impl RedditScraper {
-    fn require_string(&self, data: &Value, key: &str) -> Result<String, String> {
+    fn require_string(&self, data: &Value, key: &str) -> Result<String, ScrapeWarning> {
         Ok(data[key]
             .as_str()
-            .ok_or(format!("Missing field {:?}", key))?
+            .ok_or_else(|| ScrapeWarning::MissingField {
+                scrape_source: ScrapeSource::Reddit,
+                field: key.to_owned(),
+            })?
             .to_owned())
     }
 
-    fn optional_string(&self, data: &Value, key: &str) -> Result<String, String> {
+    fn optional_string(&self, data: &Value, key: &str) -> Result<String, ScrapeWarning> {
         Ok(data[key].as_str().unwrap_or_default().to_owned())
     }
 
@@ -127,7 +143,7 @@ impl RedditScraper {
         &self,
         data: &Value,
         key: &str,
-    ) -> Result<T, String> {
+    ) -> Result<T, ScrapeWarning> {
         if let Value::Number(n) = &data[key] {
             if let Some(n) = n.as_u64() {
                 if let Ok(n) = n.try_into() {
@@ -145,19 +161,19 @@ impl RedditScraper {
                     return Ok(n);
                 }
             }
-            Err(format!(
+            Err(other(format!(
                 "Failed to parse {} as integer (value was {:?})",
                 key, n
-            ))
+            )))
         } else {
-            Err(format!(
+            Err(other(format!(
                 "Missing or invalid field {:?} (value was {:?})",
                 key, data[key]
-            ))
+            )))
         }
     }
 
-    fn require_float(&self, data: &Value, key: &str) -> Result<f64, String> {
+    fn require_float(&self, data: &Value, key: &str) -> Result<f64, ScrapeWarning> {
         if let Value::Number(n) = &data[key] {
             if let Some(n) = n.as_u64() {
                 return Ok(n as f64);
@@ -168,15 +184,35 @@ impl RedditScraper {
             if let Some(n) = n.as_f64() {
                 return Ok(n);
             }
-            Err(format!(
+            Err(other(format!(
                 "Failed to parse {} as float (value was {:?})",
                 key, n
-            ))
+            )))
         } else {
-            Err(format!(
+            Err(other(format!(
                 "Missing or invalid field {:?} (value was {:?})",
                 key, data[key]
-            ))
+            )))
+        }
+    }
+
+    /// The URL for a self-post, which is always its permalink on `www.reddit.com` so that every scrape of the same
+    /// post normalizes to the same story. The permalink is normally a path, but if it's missing we fall back to the
+    /// post's URL if that is its own comments page (possibly on another reddit host), then to the bare comments URL.
+    fn self_post_url(raw_url: &str, permalink: &str, id: &str, subreddit: &str) -> String {
+        let path = if let Some(path) = permalink.strip_prefix('/') {
+            Some(path)
+        } else {
+            [permalink, raw_url].into_iter().find_map(|url| {
+                match Reddit::id_from_comments_url(url) {
+                    Some((post, _)) if post == id => Some(split_host_path(url).1),
+                    _ => None,
+                }
+            })
+        };
+        match path {
+            Some(path) => format!("https://www.reddit.com/{}", path),
+            None => Reddit::comments_url(id, Some(subreddit)),
         }
     }
 
@@ -184,29 +220,56 @@ impl RedditScraper {
         &self,
         child: &Value,
         positions: &mut HashMap<String, u32>,
-    ) -> Result<GenericScrape<<Self as Scraper>::Output>, String> {
+    ) -> Result<GenericScrape<<Self as Scraper>::Output>, ScrapeWarning> {
         let kind = child["kind"].as_str();
         let data = if kind == Some("t3") {
             &child["data"]
         } else {
-            return Err(format!("Unknown story type: {:?}", kind));
+            return Err(other(format!("Unknown story type: {:?}", kind)));
         };
 
         let id = self.require_string(data, "id")?;
         let subreddit = self.require_string(data, "subreddit")?.to_ascii_lowercase();
         if let Some(true) = data["stickied"].as_bool() {
-            return Err(format!("Ignoring stickied story {}/{}", subreddit, id));
+            return Err(other(format!(
+                "Ignoring stickied story {}/{}",
+                subreddit, id
+            )));
         }
         let position = *positions
             .entry(subreddit.clone())
             .and_modify(|n| *n += 1)
             .or_default()
             + 1;
-        let seconds: i64 = self.require_integer(data, "created_utc")?;
-        let millis = seconds * 1000;
-        let date = StoryDate::from_millis(millis).ok_or_else(|| "Unmappable date".to_string())?;
-        let url = StoryUrl::parse(unescape_entities(&self.require_string(data, "url")?))
-            .ok_or_else(|| "Unmappable URL".to_string())?;
+        let date_error = || ScrapeWarning::DateParse {
+            scrape_source: ScrapeSource::Reddit,
+            value: data["created_utc"].to_string(),
+        };
+        let seconds: i64 = self
+            .require_integer(data, "created_utc")
+            .map_err(|_| date_error())?;
+        let date = seconds
+            .checked_mul(1000)
+            .and_then(StoryDate::from_millis)
+            .ok_or_else(date_error)?;
+        let raw_url = unescape_entities(&self.require_string(data, "url")?);
+        let mut url = StoryUrl::parse(&raw_url).ok_or_else(|| ScrapeWarning::UrlParse {
+            scrape_source: ScrapeSource::Reddit,
+            value: raw_url.clone(),
+        })?;
+        // Self-posts and galleries point back at reddit itself, so we use the permalink as the story URL
+        let is_self_post =
+            data["is_self"].as_bool().unwrap_or_default() || Reddit::is_comments_host(url.host());
+        if is_self_post {
+            let permalink = Self::self_post_url(
+                &raw_url,
+                &self.optional_string(data, "permalink")?,
+                &id,
+                &subreddit,
+            );
+            url = StoryUrl::parse(permalink)
+                .ok_or_else(|| other("Unmappable permalink".to_owned()))?;
+        }
         let raw_title = unescape_entities(&self.require_string(data, "title")?);
         let num_comments = self.require_integer(data, "num_comments")?;
         let score = self.require_integer(data, "score")?;
@@ -214,6 +277,7 @@ impl RedditScraper {
         let upvotes = self.require_integer(data, "ups")?;
         let upvote_ratio = self.require_float(data, "upvote_ratio")? as f32;
         let flair = unescape_entities(&self.optional_string(data, "link_flair_text")?);
+        let author = self.optional_string(data, "author")?;
         let story = RedditStory::new_subsource(
             id,
             subreddit,
@@ -227,6 +291,8 @@ impl RedditScraper {
             num_comments,
             score,
             upvote_ratio,
+            is_self_post,
+            author,
         );
         Ok(story)
     }
@@ -236,11 +302,7 @@ impl Scraper for RedditScraper {
     type Config = <Reddit as ScrapeSourceDef>::Config;
     type Output = <Reddit as ScrapeSourceDef>::Scrape;
 
-    fn scrape(
-        &self,
-        _args: &RedditConfig,
-        input: &str,
-    ) -> Result<(Vec<GenericScrape<Self::Output>>, Vec<String>), ScrapeError> {
+    fn scrape(&self, _args: &RedditConfig, input: &str) -> ScrapeResult<Self::Output> {
         let root: Value = serde_json::from_str(input)?;
         let mut value = &root;
         for path in ["data", "children"] {
@@ -281,8 +343,8 @@ impl Scraper for RedditScraper {
         let mut tags = vec![];
         if let Some(ref subreddit) = input.shared.id.subsource {
             if let Some(config) = args.subreddits.get(subreddit) {
-                if config.flair_is_tag && !input.data.flair.contains(' ') {
-                    tags.push(Cow::Owned(input.data.flair.to_lowercase()));
+                if config.flair_is_tag {
+                    tags.extend(flair_tag(&input.data.flair).map(Cow::Owned));
                 }
                 if config.is_tag {
                     tags.push(Cow::Borrowed(subreddit.as_str()));
@@ -301,7 +363,232 @@ impl Scraper for RedditScraper {
             url: &input.shared.url,
             date: input.shared.date,
             rank: (input.data.position as usize).checked_sub(1),
+            comments: Some(input.data.num_comments),
+            points: None,
+            author: Some(input.data.author.as_str())
+                .filter(|author| !author.is_empty() && *author != "[deleted]")
+                .map(Cow::Borrowed),
             tags,
         }
     }
 }
+
+/// Turn a post's flair into a tag by lowercasing it and joining its words with dashes (ie: "Machine Learning"
+/// becomes `machine-learning`).
+fn flair_tag(flair: &str) -> Option<String> {
+    let tag = flair
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+        .collect::<Vec<_>>()
+        .join("-");
+    if tag.is_empty() {
+        None
+    } else {
+        Some(tag)
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::{backends::scrape, ScrapeConfig, TypedScrape};
+
+    #[test]
+    fn test_self_post() {
+        let (scrapes, _) = scrape(
+            &ScrapeConfig::default(),
+            ScrapeSource::Reddit,
+            include_str!("../../testdata/reddit-prog1.json"),
+        )
+        .expect("Failed to scrape");
+        let find = |id: &str| {
+            scrapes
+                .iter()
+                .find_map(|scrape| match scrape {
+                    TypedScrape::Reddit(story) if story.id.id == id => Some(story.clone()),
+                    _ => None,
+                })
+                .expect("Missing story")
+        };
+
+        let story = find("zsw39q");
+        assert!(story.data.is_self_post);
+        assert_eq!(
+            story.url.raw(),
+            "https://www.reddit.com/r/compsci/comments/zsw39q/proximal_policy_optimization_dive_into_the_unknown/"
+        );
+        assert!(story.id.is_likely_self_post(&story.url));
+
+        assert!(scrapes.iter().any(
+            |scrape| matches!(scrape, TypedScrape::Reddit(story) if !story.data.is_self_post)
+        ));
+    }
+
+    /// A re-scrape keeps the best position the story has been seen at, ignoring unranked scrapes.
+    #[test]
+    fn test_merge_position() {
+        let date = StoryDate::year_month_day(2023, 1, 1).expect("Date failed");
+        let url = StoryUrl::parse("https://example.com/story").expect("URL");
+        let story = |position| {
+            let mut story = RedditStory::new_with_defaults("1", date, "A story", url.clone());
+            story.data.position = position;
+            story
+        };
+        for (a, b, expected) in [(3, 10, 3), (10, 3, 3), (0, 5, 5), (5, 0, 5), (0, 0, 0)] {
+            let mut merged = story(a);
+            merged.merge_generic(story(b));
+            assert_eq!(merged.data.position, expected, "Merging {a} and {b}");
+        }
+    }
+
+    #[test]
+    fn test_self_post_permalinks() {
+        let (scrapes, warnings) = scrape(
+            &ScrapeConfig::default(),
+            ScrapeSource::Reddit,
+            include_str!("../../testdata/reddit-self1.json"),
+        )
+        .expect("Failed to scrape");
+        assert!(warnings.is_empty(), "{warnings:?}");
+        let stories = scrapes
+            .iter()
+            .map(|scrape| match scrape {
+                TypedScrape::Reddit(story) => (
+                    story.id.id.as_str(),
+                    story.data.is_self_post,
+                    story.url.raw(),
+                ),
+                _ => panic!("Unexpected scrape"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            stories,
+            vec![
+                (
+                    "10a1b2c",
+                    true,
+                    "https://www.reddit.com/r/rust/comments/10a1b2c/what_are_you_working_on_this_week/"
+                ),
+                // No permalink, so the post's own URL is moved onto www.reddit.com
+                (
+                    "10a1b2d",
+                    true,
+                    "https://www.reddit.com/r/rust/comments/10a1b2d/crate_ownership_survey_results/"
+                ),
+                // Galleries aren't `is_self`, but link back to reddit so use the permalink
+                (
+                    "10a1b2e",
+                    true,
+                    "https://www.reddit.com/r/rust/comments/10a1b2e/screenshots_of_my_new_editor/"
+                ),
+                (
+                    "10a1b2f",
+                    false,
+                    "https://blog.rust-lang.org/2023/01/10/Rust-1.66.1.html"
+                ),
+            ]
+        );
+        for scrape in &scrapes {
+            assert_eq!(
+                scrape.id.is_likely_self_post(&scrape.url),
+                scrape.url.host().ends_with("reddit.com")
+            );
+        }
+
+        // The same post seen on another host or without a permalink is the same story
+        assert_eq!(
+            RedditScraper::self_post_url(
+                "https://old.reddit.com/r/rust/comments/10a1b2c/what_are_you_working_on_this_week/",
+                "",
+                "10a1b2c",
+                "rust"
+            ),
+            stories[0].2
+        );
+        assert_eq!(
+            RedditScraper::self_post_url("https://i.redd.it/abc.png", "", "10a1b2c", "rust"),
+            "https://www.reddit.com/r/rust/comments/10a1b2c/"
+        );
+    }
+
+    #[test]
+    fn test_date_parse_warning() {
+        let input = r#"{"data": {"children": [{"kind": "t3", "data": {
+            "id": "abc123", "subreddit": "rust", "created_utc": "yesterday",
+            "url": "https://example.com", "title": "A title", "num_comments": 1,
+            "score": 1, "downs": 0, "ups": 1, "upvote_ratio": 1.0
+        }}]}}"#;
+        let (scrapes, warnings) = scrape(&ScrapeConfig::default(), ScrapeSource::Reddit, input)
+            .expect("Failed to scrape");
+        assert!(scrapes.is_empty());
+        assert_eq!(
+            warnings,
+            vec![ScrapeWarning::DateParse {
+                scrape_source: ScrapeSource::Reddit,
+                value: "\"yesterday\"".to_owned(),
+            }]
+        );
+        assert_eq!(warnings[0].to_string(), "Unmappable date");
+    }
+
+    #[test]
+    fn test_flair_tags() {
+        let config = RedditConfig {
+            subreddits: HashMap::from_iter([
+                (
+                    "science".to_owned(),
+                    SubredditConfig {
+                        is_tag: false,
+                        flair_is_tag: true,
+                    },
+                ),
+                (
+                    "rust".to_owned(),
+                    SubredditConfig {
+                        is_tag: true,
+                        flair_is_tag: false,
+                    },
+                ),
+            ]),
+            ..Default::default()
+        };
+        let scraper = RedditScraper::default();
+        let date = StoryDate::year_month_day(2020, 1, 1).expect("Date failed");
+        let tags = |subreddit: &str, flair: &str| {
+            let story = RedditStory::new_subsource(
+                "1",
+                subreddit,
+                date,
+                "A title",
+                StoryUrl::parse("https://example.com").expect("URL"),
+                flair.to_owned(),
+                1,
+                10,
+                0,
+                5,
+                10,
+                1.0,
+                false,
+                "".to_owned(),
+            );
+            scraper
+                .extract_core(&config, &story)
+                .tags
+                .into_iter()
+                .map(Cow::into_owned)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(tags("science", "Physics"), vec!["physics"]);
+        assert_eq!(
+            tags("science", "Computer Science & Math"),
+            vec!["computer-science-math"]
+        );
+        assert!(tags("science", "").is_empty());
+        assert!(tags("science", " / ").is_empty());
+
+        // Flair is only a tag for subreddits that opt in
+        assert_eq!(tags("rust", "Announcement"), vec!["rust"]);
+    }
+}
@@ -13,8 +13,9 @@ use url::Url;
 use crate::types::*;
 
 use super::{
-    scrape_story, utils::html::*, GenericScrape, ScrapeConfigSource, ScrapeCore, ScrapeShared,
-    ScrapeSource, ScrapeSourceDef, ScrapeStory, Scraper,
+    scrape_story, utils::html::*, utils::split_host_path, utils::strip_site_suffix, GenericScrape,
+    ScrapeConfigSource, ScrapeCore, ScrapeResult, ScrapeShared, ScrapeSource, ScrapeSourceDef,
+    ScrapeStory, Scraper,
 };
 
 pub struct Slashdot {}
@@ -29,8 +30,18 @@ impl ScrapeSourceDef for Slashdot {
     }
 
     fn id_from_comments_url(url: &str) -> Option<(&str, Option<&str>)> {
-        let url = url.trim_end_matches('/');
-        Some((url.strip_prefix("https://tech.slashdot.org/story/")?, None))
+        let (host, path) = split_host_path(url);
+        if !Self::is_comments_host(host) {
+            return None;
+        }
+        // Stories live on any section subdomain as `story/<yy>/<mm>/<dd>/<number>/<optional slug>`
+        let path = path.strip_prefix("story/")?.trim_end_matches('/');
+        let end = path
+            .match_indices('/')
+            .nth(3)
+            .map(|(i, _)| i)
+            .unwrap_or(path.len());
+        Some((&path[..end], None)).filter(|(id, _)| !id.is_empty())
     }
 
     fn is_comments_host(host: &str) -> bool {
@@ -42,6 +53,9 @@ impl ScrapeSourceDef for Slashdot {
 pub struct SlashdotConfig {
     homepage: String,
     tag_allowlist: HashSet<String>,
+    /// Strip trailing site names (ie: `" - The Verge"`) from story titles.
+    #[serde(default)]
+    strip_title_suffixes: bool,
 }
 
 impl ScrapeConfigSource for SlashdotConfig {
@@ -188,11 +202,7 @@ impl Scraper for SlashdotScraper {
     type Config = <Slashdot as ScrapeSourceDef>::Config;
     type Output = <Slashdot as ScrapeSourceDef>::Scrape;
 
-    fn scrape(
-        &self,
-        _args: &Self::Config,
-        input: &str,
-    ) -> Result<(Vec<GenericScrape<Self::Output>>, Vec<String>), ScrapeError> {
+    fn scrape(&self, _args: &Self::Config, input: &str) -> ScrapeResult<Self::Output> {
         let dom = tl::parse(input, ParserOptions::default())?;
         let p = dom.parser();
         let mut errors = vec![];
@@ -201,7 +211,7 @@ impl Scraper for SlashdotScraper {
         for article in html_tag_iterator(p, dom.query_selector("article.article")) {
             match Self::map_story(p, article) {
                 Ok(s) => v.push(s),
-                Err(e) => errors.push(e),
+                Err(e) => errors.push(ScrapeWarning::other(ScrapeSource::Slashdot, e)),
             }
         }
 
@@ -220,12 +230,20 @@ impl Scraper for SlashdotScraper {
             }
         }
 
+        let title = if args.strip_title_suffixes {
+            strip_site_suffix(&input.shared.raw_title, input.shared.url.host())
+        } else {
+            &input.shared.raw_title
+        };
         ScrapeCore {
             source: &input.shared.id,
             date: input.shared.date,
-            title: Cow::Borrowed(&input.shared.raw_title),
+            title: Cow::Borrowed(title),
             url: &input.shared.url,
             rank: None,
+            comments: Some(input.data.num_comments),
+            points: None,
+            author: None,
             tags,
         }
     }
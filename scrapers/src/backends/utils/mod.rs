@@ -1 +1,92 @@
 pub mod html;
+
+/// Splits a URL into its host and the rest of the URL after the host (without the leading slash), ignoring
+/// the scheme.
+pub fn split_host_path(url: &str) -> (&str, &str) {
+    let url = url.split_once("://").map(|(_, rest)| rest).unwrap_or(url);
+    url.split_once('/').unwrap_or((url, ""))
+}
+
+/// Strips a trailing site-name suffix (ie: `" - The Verge"` or `" | Ars Technica"`) from a title, but only if the
+/// suffix names the site at `host`. Titles that would be left empty are returned as-is.
+pub fn strip_site_suffix<'a>(title: &'a str, host: &str) -> &'a str {
+    const SEPARATORS: [&str; 6] = [" - ", " | ", " – ", " — ", " :: ", " · "];
+    let Some((index, separator)) = SEPARATORS
+        .iter()
+        .filter_map(|separator| title.rfind(separator).map(|index| (index, separator)))
+        .max_by_key(|(index, _)| *index)
+    else {
+        return title;
+    };
+    let (rest, suffix) = (&title[..index], &title[index + separator.len()..]);
+    if rest.trim().is_empty() {
+        return title;
+    }
+
+    let normalize = |s: &str| {
+        s.chars()
+            .filter(|c| c.is_alphanumeric())
+            .flat_map(char::to_lowercase)
+            .collect::<String>()
+    };
+    let brand = normalize(suffix);
+    let brand = brand.strip_prefix("the").unwrap_or(&brand);
+    if brand.is_empty() {
+        return title;
+    }
+
+    // Compare against each label of the host (minus the TLD and any `www`), as well as all of them together
+    let host = host.to_lowercase();
+    let mut labels = host.split('.').collect::<Vec<_>>();
+    labels.pop();
+    labels.retain(|label| *label != "www");
+    let mut candidates = labels
+        .iter()
+        .map(|label| normalize(label))
+        .collect::<Vec<_>>();
+    candidates.push(normalize(&labels.concat()));
+    if candidates
+        .iter()
+        .any(|candidate| candidate == brand || candidate.strip_prefix("the") == Some(brand))
+    {
+        rest.trim_end()
+    } else {
+        title
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case(
+        "Apple announces new chips - The Verge",
+        "www.theverge.com",
+        "Apple announces new chips"
+    )]
+    #[case(
+        "A look at Rust 2024 | Ars Technica",
+        "arstechnica.com",
+        "A look at Rust 2024"
+    )]
+    #[case(
+        "Linux 6.0 released – The Register",
+        "www.theregister.com",
+        "Linux 6.0 released"
+    )]
+    #[case("Hello, world :: Jane's Blog", "janesblog.example.com", "Hello, world")]
+    #[case("Rust 1.70 - Rust Blog", "blog.rust-lang.org", "Rust 1.70 - Rust Blog")]
+    // The suffix is part of the title, not the site name
+    #[case(
+        "Apple vs Google - Google wins",
+        "www.google.com",
+        "Apple vs Google - Google wins"
+    )]
+    #[case("Why I left - The Verge", "example.com", "Why I left - The Verge")]
+    #[case(" - The Verge", "theverge.com", " - The Verge")]
+    fn test_strip_site_suffix(#[case] title: &str, #[case] host: &str, #[case] expected: &str) {
+        assert_eq!(strip_site_suffix(title, host), expected);
+    }
+}
@@ -1,13 +1,15 @@
 use std::{
     borrow::Cow,
     collections::{hash_map::Entry, HashMap, HashSet},
+    sync::OnceLock,
 };
 
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    backends::ScrapeCore, ScrapeExtractor, ScrapeId, ScrapeSource, StoryDate, StoryUrl, TypedScrape,
+    backends::ScrapeCore, ScrapeExtractor, ScrapeId, ScrapeMergeResult, ScrapeSource, StoryDate,
+    StoryUrl, TypedScrape,
 };
 
 /// Collection of scrapes, which can also extract the best title, etc.
@@ -18,6 +20,70 @@ pub struct ScrapeCollection {
     // TODO: We need to clone the scrape ID because we can't use a reference to the key, and making this a hash set
     // prevents mutation/
     pub scrapes: HashMap<ScrapeId, TypedScrape>,
+
+    /// Memoized result of [`ScrapeCollection::extract`], cleared whenever scrapes are merged in.
+    #[serde(skip)]
+    extracted: OnceLock<ExtractionCache>,
+}
+
+/// Owned extraction results for a single scrape.
+#[derive(Debug)]
+struct CachedScrapeCore {
+    title: String,
+    date: StoryDate,
+    tags: Vec<String>,
+    rank: Option<usize>,
+    comments: Option<u32>,
+    points: Option<u32>,
+    author: Option<String>,
+}
+
+/// Owned extraction results for a whole collection, keyed by the extractor generation and scrape ids they were
+/// computed from.
+#[derive(Debug)]
+struct ExtractionCache {
+    generation: u64,
+    title_story: ScrapeId,
+    cores: HashMap<ScrapeId, CachedScrapeCore>,
+}
+
+impl ExtractionCache {
+    fn new(generation: u64, extracted: &ExtractedScrapeCollection) -> Self {
+        let cores = extracted
+            .scrapes
+            .iter()
+            .map(|(id, (core, _))| {
+                (
+                    (*id).clone(),
+                    CachedScrapeCore {
+                        title: core.title.to_string(),
+                        date: core.date,
+                        tags: core.tags.iter().map(|tag| tag.to_string()).collect(),
+                        rank: core.rank,
+                        comments: core.comments,
+                        points: core.points,
+                        author: core.author.as_ref().map(|author| author.to_string()),
+                    },
+                )
+            })
+            .collect();
+        Self {
+            generation,
+            title_story: extracted.title_story.clone(),
+            cores,
+        }
+    }
+
+    /// Was this cache computed by the given extractor from exactly the given set of scrapes?
+    fn matches(
+        &self,
+        extractor: &ScrapeExtractor,
+        scrapes: &HashMap<ScrapeId, TypedScrape>,
+    ) -> bool {
+        self.generation == extractor.generation()
+            && self.cores.len() == scrapes.len()
+            && scrapes.keys().all(|id| self.cores.contains_key(id))
+    }
 }
 
 impl ScrapeCollection {
@@ -25,6 +91,7 @@ impl ScrapeCollection {
         Self {
             earliest: scrape.date,
             scrapes: HashMap::from_iter([(scrape.id.clone(), scrape)]),
+            extracted: OnceLock::new(),
         }
     }
 
@@ -35,18 +102,23 @@ impl ScrapeCollection {
             .map(|x| x.date)
             .min()
             .expect("Requires at least one TypedScrape");
-        Self { earliest, scrapes }
+        Self {
+            earliest,
+            scrapes,
+            extracted: OnceLock::new(),
+        }
     }
 
-    /// Takes and merges another `TypedScrape`.
-    pub fn merge(&mut self, scrape: TypedScrape) {
+    /// Takes and merges another `TypedScrape`. If this is a re-scrape of a scrape we already have, returns the
+    /// fields of that scrape that changed, otherwise returns an empty set.
+    pub fn merge(&mut self, scrape: TypedScrape) -> HashSet<ScrapeMergeResult> {
+        self.extracted.take();
         self.earliest = self.earliest.min(scrape.date);
         match self.scrapes.entry(scrape.id.clone()) {
-            Entry::Occupied(mut x) => {
-                x.get_mut().merge(scrape);
-            }
+            Entry::Occupied(mut x) => x.get_mut().merge(scrape),
             Entry::Vacant(x) => {
                 x.insert(scrape);
+                HashSet::new()
             }
         }
     }
@@ -54,10 +126,34 @@ impl ScrapeCollection {
     /// Takes and merges all the `TypedScrape`s from the other `ScrapeCollection`.
     pub fn merge_all(&mut self, other: Self) {
         for scrape in other.scrapes.into_values() {
-            self.merge(scrape)
+            self.merge(scrape);
         }
     }
 
+    /// The date of the story, which is the date of its earliest scrape. If `outlier_days` is provided, scrapes
+    /// dated more than that many days before the median scrape date are assumed to have been mis-dated by their
+    /// source and are ignored. The lower median is used, so that an outlier needs to be outvoted by at least two
+    /// other scrapes.
+    pub fn story_date(&self, outlier_days: Option<u32>) -> StoryDate {
+        let Some(outlier_days) = outlier_days else {
+            return self.earliest;
+        };
+        let dates = self
+            .scrapes
+            .values()
+            .map(|scrape| scrape.date)
+            .sorted()
+            .collect_vec();
+        let median = dates[(dates.len() - 1) / 2];
+        let cutoff = median
+            .checked_sub_days(outlier_days as u64)
+            .unwrap_or(StoryDate::MIN);
+        dates
+            .into_iter()
+            .find(|date| *date >= cutoff)
+            .unwrap_or(self.earliest)
+    }
+
     pub fn url(&self) -> &StoryUrl {
         &self
             .scrapes
@@ -67,20 +163,56 @@ impl ScrapeCollection {
             .url
     }
 
+    /// Extract the scrape data for this collection. The result is memoized, so repeated extractions of the same
+    /// collection with the same `extractor` are cheap.
     pub fn extract<'a>(&'a self, extractor: &ScrapeExtractor) -> ExtractedScrapeCollection<'a> {
-        let title_score = |source: &ScrapeSource| {
-            match source {
-                // HN is moderated and titles are high quality
-                ScrapeSource::HackerNews => 0,
-                ScrapeSource::Feed => 1,
-                ScrapeSource::Lobsters => 2,
-                ScrapeSource::Slashdot => 3,
-                // User-submitted titles are generally just OK
-                ScrapeSource::Reddit => 4,
-                ScrapeSource::Other => 99,
-            }
-        };
+        let cache = self.extracted.get_or_init(|| {
+            ExtractionCache::new(extractor.generation(), &self.extract_uncached(extractor))
+        });
+        if !cache.matches(extractor, &self.scrapes) {
+            // The scrapes were modified directly or the extractor was reconfigured, so the cache is stale
+            return self.extract_uncached(extractor);
+        }
+
+        let scrapes = self
+            .scrapes
+            .iter()
+            .map(|(id, scrape)| {
+                let core = &cache.cores[id];
+                let core = ScrapeCore {
+                    source: &scrape.id,
+                    title: Cow::Borrowed(core.title.as_str()),
+                    url: &scrape.url,
+                    date: core.date,
+                    tags: core
+                        .tags
+                        .iter()
+                        .map(|tag| Cow::Borrowed(tag.as_str()))
+                        .collect(),
+                    rank: core.rank,
+                    comments: core.comments,
+                    points: core.points,
+                    author: core.author.as_deref().map(Cow::Borrowed),
+                };
+                (id, (core, scrape))
+            })
+            .collect();
+        let (title_story, _) = self
+            .scrapes
+            .get_key_value(&cache.title_story)
+            .expect("Expected the title story to be in the scrape collection");
+
+        ExtractedScrapeCollection {
+            earliest: self.earliest,
+            title_story,
+            scrapes,
+        }
+    }
 
+    fn extract_uncached<'a>(
+        &'a self,
+        extractor: &ScrapeExtractor,
+    ) -> ExtractedScrapeCollection<'a> {
         let iter = self
             .scrapes
             .iter()
@@ -91,9 +223,9 @@ impl ScrapeCollection {
             .next()
             .expect("Expected at least one scrape")
             .0;
-        let mut max_title_score = i32::MAX;
+        let mut max_title_score = u8::MAX;
         for (id, (_, _)) in &scrapes {
-            let this_score = title_score(&id.source);
+            let this_score = id.source.title_priority();
             if this_score < max_title_score {
                 max_title_score = this_score;
                 title_story = *id;
@@ -135,6 +267,17 @@ impl<'a> ExtractedScrapeCollection<'a> {
             .url
     }
 
+    /// The distinct subsources (ie: subreddits) that this story was scraped from for the given source, in order.
+    pub fn subsources(&self, source: ScrapeSource) -> Vec<&'a str> {
+        self.scrapes
+            .keys()
+            .filter(|id| id.source == source)
+            .filter_map(|id| id.subsource.as_deref())
+            .sorted()
+            .dedup()
+            .collect_vec()
+    }
+
     pub fn tags<'b>(&'b self) -> Vec<Cow<'a, str>> {
         let mut tags = HashSet::new();
         for (scrape, _) in self.scrapes.values() {
@@ -172,3 +315,198 @@ impl<'a> ExtractedScrapeCollection<'a> {
     //     (*best_title.1, best_title.2)
     // }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{hacker_news::HackerNewsStory, reddit::RedditStory, ScrapeConfig};
+
+    fn collection() -> ScrapeCollection {
+        let date = StoryDate::year_month_day(2020, 1, 1).expect("Date failed");
+        let url = StoryUrl::parse("https://example.com/story").expect("URL");
+        ScrapeCollection::new_from_one(
+            RedditStory::new_with_defaults("1", date, "Reddit title", url).into(),
+        )
+    }
+
+    /// Two extracts of the same unchanged collection reuse the cached extraction.
+    #[test]
+    fn test_extract_cached() {
+        let extractor = ScrapeExtractor::new(&ScrapeConfig::default());
+        let collection = collection();
+        let a = collection.extract(&extractor);
+        let b = collection.extract(&extractor);
+        assert_eq!(a.title(), "Reddit title");
+        assert!(std::ptr::eq(a.title(), b.title()));
+    }
+
+    /// Merging a new scrape invalidates the cached extraction.
+    #[test]
+    fn test_extract_invalidated() {
+        let extractor = ScrapeExtractor::new(&ScrapeConfig::default());
+        let mut collection = collection();
+        assert_eq!(collection.extract(&extractor).title(), "Reddit title");
+
+        let scrape = HackerNewsStory::new_with_defaults(
+            "2",
+            collection.earliest,
+            "HN title",
+            collection.url().clone(),
+        );
+        collection.merge(scrape.into());
+        let extracted = collection.extract(&extractor);
+        assert_eq!(extracted.scrapes.len(), 2);
+        assert_eq!(extracted.title(), "HN title");
+
+        // Direct modification of the scrapes is detected as well
+        collection
+            .scrapes
+            .retain(|id, _| id.source == ScrapeSource::Reddit);
+        assert_eq!(collection.extract(&extractor).title(), "Reddit title");
+    }
+
+    /// A cached extraction isn't reused by an extractor with a different configuration.
+    #[test]
+    fn test_extract_reconfigured() {
+        let date = StoryDate::year_month_day(2020, 1, 1).expect("Date failed");
+        let url = StoryUrl::parse("https://example.com/story").expect("URL");
+        let collection = ScrapeCollection::new_from_one(
+            RedditStory::new_subsource_with_defaults("1", "rust", date, "Reddit title", url).into(),
+        );
+        let tags = |extractor: &ScrapeExtractor| {
+            collection
+                .extract(extractor)
+                .scrapes
+                .values()
+                .flat_map(|(core, _)| core.tags.iter().map(|tag| tag.to_string()))
+                .collect_vec()
+        };
+
+        let extractor = ScrapeExtractor::new(&ScrapeConfig::default());
+        assert!(tags(&extractor).is_empty());
+
+        let config = ScrapeConfig {
+            reddit: serde_json::from_value(serde_json::json!({
+                "api": "", "subreddit_batch": 1, "limit": 1,
+                "subreddits": { "rust": { "is_tag": true } }
+            }))
+            .expect("Failed to parse config"),
+            ..Default::default()
+        };
+        let extractor = ScrapeExtractor::new(&config);
+        assert_eq!(tags(&extractor), vec!["rust"]);
+    }
+
+    #[test]
+    fn test_merge_unchanged() {
+        let mut collection = collection();
+        let url = collection.url().clone();
+        let rescrape =
+            RedditStory::new_with_defaults("1", collection.earliest, "Reddit title", url);
+        assert!(collection.merge(rescrape.into()).is_empty());
+
+        // A scrape from another source isn't a re-scrape
+        let url = collection.url().clone();
+        let other = HackerNewsStory::new_with_defaults("2", collection.earliest, "HN", url);
+        assert!(collection.merge(other.into()).is_empty());
+    }
+
+    #[test]
+    fn test_merge_date() {
+        let mut collection = collection();
+        let url = collection.url().clone();
+        let earlier = StoryDate::year_month_day(2019, 12, 31).expect("Date failed");
+        let rescrape = RedditStory::new_with_defaults("1", earlier, "Reddit title", url.clone());
+        assert_eq!(
+            collection.merge(rescrape.into()),
+            HashSet::from([ScrapeMergeResult::Date])
+        );
+        assert_eq!(collection.earliest, earlier);
+
+        // A later re-scrape doesn't move the date
+        let later = StoryDate::year_month_day(2020, 1, 2).expect("Date failed");
+        let rescrape = RedditStory::new_with_defaults("1", later, "Reddit title", url);
+        assert!(collection.merge(rescrape.into()).is_empty());
+        assert_eq!(
+            collection.scrapes.values().next().expect("Scrape").date,
+            earlier
+        );
+    }
+
+    #[test]
+    fn test_merge_title() {
+        let mut collection = collection();
+        let url = collection.url().clone();
+        let rescrape =
+            RedditStory::new_with_defaults("1", collection.earliest, "Title", url.clone());
+        assert_eq!(
+            collection.merge(rescrape.into()),
+            HashSet::from([ScrapeMergeResult::Title])
+        );
+        let extractor = ScrapeExtractor::new(&ScrapeConfig::default());
+        assert_eq!(collection.extract(&extractor).title(), "Title");
+
+        // Longer or empty titles don't replace the current one
+        for title in ["A much longer title", " "] {
+            let rescrape =
+                RedditStory::new_with_defaults("1", collection.earliest, title, url.clone());
+            assert!(collection.merge(rescrape.into()).is_empty());
+        }
+        assert_eq!(collection.extract(&extractor).title(), "Title");
+    }
+
+    /// Cross-posts of the same URL to different subreddits are separate scrapes, so both subreddits are retained.
+    #[test]
+    fn test_merge_subreddits() {
+        let date = StoryDate::year_month_day(2020, 1, 1).expect("Date failed");
+        let url = StoryUrl::parse("https://example.com/story").expect("URL");
+        let mut collection = ScrapeCollection::new_from_one(
+            RedditStory::new_subsource_with_defaults("1", "rust", date, "Title", url.clone())
+                .into(),
+        );
+        let crosspost =
+            RedditStory::new_subsource_with_defaults("2", "programming", date, "Title", url);
+        assert!(collection.merge(crosspost.into()).is_empty());
+        assert_eq!(collection.scrapes.len(), 2);
+
+        let extractor = ScrapeExtractor::new(&ScrapeConfig::default());
+        assert_eq!(
+            collection
+                .extract(&extractor)
+                .subsources(ScrapeSource::Reddit),
+            vec!["programming", "rust"]
+        );
+    }
+
+    #[test]
+    fn test_story_date_outliers() {
+        let date = |year, month, day| StoryDate::year_month_day(year, month, day).expect("Date");
+        let url = StoryUrl::parse("https://example.com/story").expect("URL");
+        // With two scrapes, there's no telling which one is mis-dated
+        let pair = ScrapeCollection::new_from_iter(
+            [
+                HackerNewsStory::new_with_defaults("1", date(2022, 12, 20), "A", url.clone()),
+                HackerNewsStory::new_with_defaults("2", date(2000, 1, 1), "A", url.clone()),
+            ]
+            .into_iter()
+            .map(TypedScrape::from),
+        );
+        assert_eq!(pair.story_date(Some(30)), date(2000, 1, 1));
+
+        let mut collection = ScrapeCollection::new_from_iter(
+            [
+                HackerNewsStory::new_with_defaults("1", date(2022, 12, 20), "A", url.clone()),
+                HackerNewsStory::new_with_defaults("2", date(2022, 12, 22), "A", url.clone()),
+            ]
+            .into_iter()
+            .map(TypedScrape::from),
+        );
+        assert_eq!(collection.story_date(Some(30)), date(2022, 12, 20));
+
+        collection
+            .merge(RedditStory::new_with_defaults("3", date(2000, 1, 1), "A", url.clone()).into());
+        assert_eq!(collection.story_date(None), date(2000, 1, 1));
+        assert_eq!(collection.story_date(Some(30)), date(2022, 12, 20));
+        assert_eq!(collection.story_date(Some(10000)), date(2000, 1, 1));
+    }
+}
@@ -1,16 +1,29 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
 use crate::backends::{ScrapeConfig, ScrapeCore, TypedScrape};
 
+/// Source of unique generations for [`ScrapeExtractor`]s.
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
 pub struct ScrapeExtractor {
     config: ScrapeConfig,
+    generation: u64,
 }
 
 impl ScrapeExtractor {
     pub fn new(config: &ScrapeConfig) -> Self {
         Self {
             config: config.clone(),
+            generation: GENERATION.fetch_add(1, Ordering::Relaxed),
         }
     }
 
+    /// Uniquely identifies the configuration of this extractor, so that cached extractions made with a different
+    /// (ie: since reconfigured) extractor can be detected.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
     pub fn extract<'a>(&self, scrape: &'a TypedScrape) -> ScrapeCore<'a> {
         scrape.extract(&self.config)
     }
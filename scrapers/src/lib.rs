@@ -6,8 +6,12 @@ mod scrapers;
 mod types;
 
 pub use backends::export::*;
-pub use backends::legacy::{import_backup, LegacyError};
-pub use backends::{ScrapeConfig, ScrapeCore, ScrapeSource, TypedScrape, TypedScrapeMap};
+pub use backends::legacy::{
+    export_backup, import_backup, import_backup_lenient, write_backup, LegacyError, SkippedRecord,
+};
+pub use backends::{
+    ScrapeConfig, ScrapeCore, ScrapeMergeResult, ScrapeSource, TypedScrape, TypedScrapeMap,
+};
 pub use collections::{ExtractedScrapeCollection, ScrapeCollection};
 pub use extractor::*;
 pub use scrapers::*;
@@ -1,9 +1,11 @@
 //! Public interface for the collection of scrapers.
 use std::collections::HashMap;
 
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
-use crate::{backends::scrape, ScrapeConfig, ScrapeSource, TypedScrape};
+use crate::{
+    backends::scrape_url, ScrapeConfig, ScrapeSource, ScrapeWarning, StoryDuration, TypedScrape,
+};
 
 /// Accumulates the URLs required to scrape for all the services.
 #[derive(Serialize)]
@@ -14,13 +16,52 @@ pub struct ScraperPossibilities {
 #[derive(Serialize)]
 pub enum ScraperHttpResponseInput {
     HTTPError(u16, String),
+    /// The server told us (via a `304`) that nothing has changed since our last fetch.
+    NotModified,
     Ok(String),
 }
 
 #[derive(Serialize)]
 pub enum ScraperHttpResult {
     Err(ScraperHttpResponseInput, String),
-    Ok(String, Vec<TypedScrape>),
+    /// The raw input, the stories scraped from it, and the stories that were skipped.
+    Ok(String, Vec<TypedScrape>, Vec<ScrapeWarning>),
+}
+
+/// The cache validators a server sent along with a URL's content, which are sent back on the next fetch
+/// so that the server can reply with `304 Not Modified` if nothing has changed.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HttpCacheValidators {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+impl HttpCacheValidators {
+    pub fn is_empty(&self) -> bool {
+        self.etag.is_none() && self.last_modified.is_none()
+    }
+}
+
+/// Tracks the cache validators for each scraped URL, keyed by URL.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct HttpCacheState {
+    validators: HashMap<String, HttpCacheValidators>,
+}
+
+impl HttpCacheState {
+    pub fn get(&self, url: &str) -> Option<&HttpCacheValidators> {
+        self.validators.get(url)
+    }
+
+    /// Record the validators from a successful fetch of `url`. If the server didn't send any, we forget
+    /// whatever we had so stale validators aren't sent again.
+    pub fn record(&mut self, url: &str, validators: HttpCacheValidators) {
+        if validators.is_empty() {
+            self.validators.remove(url);
+        } else {
+            self.validators.insert(url.to_owned(), validators);
+        }
+    }
 }
 
 pub struct Scrapers {
@@ -69,22 +110,108 @@ impl Scrapers {
         }
     }
 
-    /// Given the result of fetching a URL, returns the scraped stories.
+    /// How long to wait before polling the feed at `url` again, given the (optional) body of the last poll.
+    pub fn feed_poll_interval(&self, url: &str, input: Option<&str>) -> StoryDuration {
+        self.config.feed.poll_interval(url, input)
+    }
+
+    /// Given the result of fetching `url`, returns the scraped stories.
     pub fn scrape_http_result(
         &self,
         source: ScrapeSource,
+        url: &str,
         input: ScraperHttpResponseInput,
     ) -> ScraperHttpResult {
         match input {
-            ScraperHttpResponseInput::Ok(s) => match scrape(&self.config, source, &s) {
-                Ok((scrapes, _warnings)) => ScraperHttpResult::Ok(s, scrapes),
+            ScraperHttpResponseInput::Ok(s) => match scrape_url(&self.config, source, url, &s) {
+                Ok((scrapes, warnings)) => ScraperHttpResult::Ok(s, scrapes, warnings),
                 Err(e) => {
                     ScraperHttpResult::Err(ScraperHttpResponseInput::Ok(s), format!("{:?}", e))
                 }
             },
+            ScraperHttpResponseInput::NotModified => {
+                ScraperHttpResult::Ok(String::new(), vec![], vec![])
+            }
             error @ ScraperHttpResponseInput::HTTPError(..) => {
                 ScraperHttpResult::Err(error, "HTTP Error".to_string())
             }
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_not_modified() {
+        let scrapers = Scrapers::new(&ScrapeConfig::default());
+        for source in ScrapeSource::all() {
+            match scrapers.scrape_http_result(*source, "", ScraperHttpResponseInput::NotModified) {
+                ScraperHttpResult::Ok(_, scrapes, warnings) => {
+                    assert!(scrapes.is_empty());
+                    assert!(warnings.is_empty());
+                }
+                ScraperHttpResult::Err(_, e) => panic!("Unexpected error for {source:?}: {e}"),
+            }
+        }
+    }
+
+    /// Stories skipped from malformed input come back as structured warnings alongside the scrapes.
+    #[test]
+    fn test_scrape_warnings() {
+        let scrapers = Scrapers::new(&ScrapeConfig::default());
+        let input = r#"{"data": {"children": [
+            {"kind": "t3", "data": {"id": "a", "subreddit": "rust", "created_utc": "yesterday",
+                "url": "https://example.com", "title": "A", "num_comments": 1, "score": 1, "downs": 0,
+                "ups": 1, "upvote_ratio": 1.0}},
+            {"kind": "t3", "data": {"id": "b", "subreddit": "rust", "created_utc": 1.0e9,
+                "url": "https://example.com", "num_comments": 1, "score": 1, "downs": 0, "ups": 1,
+                "upvote_ratio": 1.0}}
+        ]}}"#;
+        let result = scrapers.scrape_http_result(
+            ScrapeSource::Reddit,
+            "https://reddit.com/r/rust.json",
+            ScraperHttpResponseInput::Ok(input.to_owned()),
+        );
+        let ScraperHttpResult::Ok(_, scrapes, warnings) = result else {
+            panic!("Scrape failed");
+        };
+        assert!(scrapes.is_empty());
+        assert_eq!(
+            warnings,
+            vec![
+                ScrapeWarning::DateParse {
+                    scrape_source: ScrapeSource::Reddit,
+                    value: "\"yesterday\"".to_owned(),
+                },
+                ScrapeWarning::MissingField {
+                    scrape_source: ScrapeSource::Reddit,
+                    field: "title".to_owned(),
+                },
+            ]
+        );
+        assert_eq!(
+            serde_json::to_value(&warnings[1]).expect("Serialize failed"),
+            serde_json::json!({"kind": "missing_field", "source": "reddit", "field": "title"})
+        );
+    }
+
+    #[test]
+    fn test_http_cache_state() {
+        let mut state = HttpCacheState::default();
+        let url = "https://example.com/feed.xml";
+        assert_eq!(state.get(url), None);
+
+        let validators = HttpCacheValidators {
+            etag: Some("\"abc\"".into()),
+            last_modified: Some("Wed, 21 Oct 2015 07:28:00 GMT".into()),
+        };
+        state.record(url, validators.clone());
+        assert_eq!(state.get(url), Some(&validators));
+
+        // A response without validators clears the old ones
+        state.record(url, HttpCacheValidators::default());
+        assert_eq!(state.get(url), None);
+    }
+}
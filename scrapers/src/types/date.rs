@@ -140,6 +140,18 @@ impl Serialize for StoryDate {
     }
 }
 
+#[cfg(feature = "json_schema")]
+impl schemars::JsonSchema for StoryDate {
+    fn schema_name() -> String {
+        "StoryDate".to_owned()
+    }
+
+    /// Serialized as seconds since the epoch.
+    fn json_schema(gen: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        i64::json_schema(gen)
+    }
+}
+
 impl<'de> Deserialize<'de> for StoryDate {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
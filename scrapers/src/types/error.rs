@@ -1,5 +1,8 @@
+use serde::Serialize;
 use thiserror::Error;
 
+use crate::ScrapeSource;
+
 #[derive(Error, Debug)]
 pub enum ScrapeError {
     #[error("I/O error")]
@@ -13,3 +16,73 @@ pub enum ScrapeError {
     #[error("Structure error")]
     StructureError(String),
 }
+
+/// A problem with a single story that was skipped, while the rest of the scrape succeeded. Serialized with a `kind`
+/// tag so that warnings can be grouped by what went wrong.
+#[derive(Clone, Debug, Error, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ScrapeWarning {
+    /// A required field was missing.
+    #[error("Missing field {field:?}")]
+    MissingField {
+        #[serde(rename = "source")]
+        scrape_source: ScrapeSource,
+        field: String,
+    },
+    /// The story's date couldn't be parsed.
+    #[error("{}", date_parse_message(*.scrape_source, .value))]
+    DateParse {
+        #[serde(rename = "source")]
+        scrape_source: ScrapeSource,
+        value: String,
+    },
+    /// The story's URL couldn't be parsed.
+    #[error("{}", url_parse_message(*.scrape_source, .value))]
+    UrlParse {
+        #[serde(rename = "source")]
+        scrape_source: ScrapeSource,
+        value: String,
+    },
+    /// Any other reason we skipped the story.
+    #[error("{detail}")]
+    Other {
+        #[serde(rename = "source")]
+        scrape_source: ScrapeSource,
+        detail: String,
+    },
+}
+
+impl ScrapeWarning {
+    pub fn other(source: ScrapeSource, detail: impl Into<String>) -> Self {
+        Self::Other {
+            scrape_source: source,
+            detail: detail.into(),
+        }
+    }
+
+    /// The source that produced this warning.
+    pub fn source(&self) -> ScrapeSource {
+        match self {
+            Self::MissingField { scrape_source, .. }
+            | Self::DateParse { scrape_source, .. }
+            | Self::UrlParse { scrape_source, .. }
+            | Self::Other { scrape_source, .. } => *scrape_source,
+        }
+    }
+}
+
+/// The message each scraper has always logged for an unparseable date.
+fn date_parse_message(source: ScrapeSource, value: &str) -> String {
+    match source {
+        ScrapeSource::HackerNews => format!("Failed to map date: {value}"),
+        _ => "Unmappable date".to_owned(),
+    }
+}
+
+/// The message each scraper has always logged for an unparseable URL.
+fn url_parse_message(source: ScrapeSource, value: &str) -> String {
+    match source {
+        ScrapeSource::HackerNews => format!("Failed to parse URL {value}"),
+        _ => "Unmappable URL".to_owned(),
+    }
+}
@@ -25,11 +25,16 @@ impl ScrapeId {
         }
     }
 
-    /// Given a URL, determines if that URL would make this story a self-post. The current heuristic for
-    /// this is whether the url's host looks like a comments host, and the url itself contains the scrape's
-    /// ID. The latter heuristic isn't perfect, but the failure modes are pretty harmless.
+    /// Given a URL, determines if that URL would make this story a self-post: that is, the URL is this scrape's
+    /// own comments page on its source (an Ask HN item, a reddit text post, a lobste.rs text story, etc). Each
+    /// source parses its comments URLs in `id_from_comments_url`, so a link to a _different_ discussion on the
+    /// same site is not a self-post.
     pub fn is_likely_self_post(&self, url: &StoryUrl) -> bool {
-        self.source.is_comments_host(url.host()) && url.raw().contains(&self.id)
+        self.source.is_comments_host(url.host())
+            && self
+                .source
+                .id_from_comments_url(url.raw())
+                .is_some_and(|id| id.id == self.id)
     }
 
     /// Generate a comments URL for this scrape.
@@ -41,10 +46,11 @@ impl ScrapeId {
     pub fn from_string<S: AsRef<str>>(s: S) -> Option<Self> {
         if let Some((head, rest)) = s.as_ref().split_once('-') {
             if let Some(source) = ScrapeSource::try_from_str(head) {
-                if let Some((subsource, id)) = rest.split_once('-') {
-                    Some(source.subsource_id(subsource, id))
-                } else {
-                    Some(source.id(rest))
+                match rest.split_once('-') {
+                    Some((subsource, id)) if source.has_subsource_ids() => {
+                        Some(source.subsource_id(subsource, id))
+                    }
+                    _ => Some(source.id(rest)),
                 }
             } else {
                 None
@@ -90,6 +96,18 @@ impl Serialize for ScrapeId {
     }
 }
 
+#[cfg(feature = "json_schema")]
+impl schemars::JsonSchema for ScrapeId {
+    fn schema_name() -> String {
+        "ScrapeId".to_owned()
+    }
+
+    /// Serialized as `source-id` or `source-subsource-id`.
+    fn json_schema(gen: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        String::json_schema(gen)
+    }
+}
+
 impl<'de> Deserialize<'de> for ScrapeId {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
@@ -99,13 +117,217 @@ impl<'de> Deserialize<'de> for ScrapeId {
         if let Some((head, rest)) = s.split_once('-') {
             let source = ScrapeSource::try_from_str(head)
                 .ok_or(serde::de::Error::custom("Invalid source"))?;
-            if let Some((subsource, id)) = rest.split_once('-') {
-                Ok(source.subsource_id(subsource, id))
-            } else {
-                Ok(source.id(rest))
+            match rest.split_once('-') {
+                Some((subsource, id)) if source.has_subsource_ids() => {
+                    Ok(source.subsource_id(subsource, id))
+                }
+                _ => Ok(source.id(rest)),
             }
         } else {
             Err(serde::de::Error::custom("Invalid format"))
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    // Ask/Show HN items link to their own comments page
+    #[case(
+        ScrapeSource::HackerNews,
+        None,
+        "34107581",
+        "https://news.ycombinator.com/item?id=34107581",
+        true
+    )]
+    #[case(
+        ScrapeSource::HackerNews,
+        None,
+        "34107581",
+        "http://news.ycombinator.com/item?id=34107581&p=2",
+        true
+    )]
+    #[case(
+        ScrapeSource::HackerNews,
+        None,
+        "34107581",
+        "https://example.com/item?id=34107581",
+        false
+    )]
+    // A link to a different HN discussion, even one whose id contains ours
+    #[case(
+        ScrapeSource::HackerNews,
+        None,
+        "3410758",
+        "https://news.ycombinator.com/item?id=34107581",
+        false
+    )]
+    #[case(
+        ScrapeSource::HackerNews,
+        None,
+        "34107581",
+        "https://example.com/",
+        false
+    )]
+    // Reddit text posts link to their comments page, on any reddit host
+    #[case(
+        ScrapeSource::Reddit,
+        Some("compsci"),
+        "zsw39q",
+        "https://www.reddit.com/r/compsci/comments/zsw39q/proximal_policy/",
+        true
+    )]
+    #[case(
+        ScrapeSource::Reddit,
+        Some("rust"),
+        "abc123",
+        "https://old.reddit.com/r/rust/comments/abc123/",
+        true
+    )]
+    #[case(
+        ScrapeSource::Reddit,
+        None,
+        "abc123",
+        "https://www.reddit.com/comments/abc123/",
+        true
+    )]
+    #[case(
+        ScrapeSource::Reddit,
+        Some("rust"),
+        "abc",
+        "https://www.reddit.com/r/rust/comments/xyz789/abc_is_great/",
+        false
+    )]
+    #[case(
+        ScrapeSource::Reddit,
+        Some("rust"),
+        "abc123",
+        "https://i.redd.it/abc123.png",
+        false
+    )]
+    #[case(
+        ScrapeSource::Reddit,
+        Some("rust"),
+        "abc123",
+        "https://example.com/abc123",
+        false
+    )]
+    // Lobsters text stories link to the story page, with or without the slug
+    #[case(
+        ScrapeSource::Lobsters,
+        None,
+        "xf162v",
+        "https://lobste.rs/s/xf162v/haiku_r1_beta4_has_been_released",
+        true
+    )]
+    #[case(
+        ScrapeSource::Lobsters,
+        None,
+        "xf162v",
+        "https://lobste.rs/s/xf162v",
+        true
+    )]
+    #[case(
+        ScrapeSource::Lobsters,
+        None,
+        "xf162",
+        "https://lobste.rs/s/xf162v/haiku",
+        false
+    )]
+    #[case(
+        ScrapeSource::Lobsters,
+        None,
+        "xf162v",
+        "https://example.com/xf162v",
+        false
+    )]
+    // Slashdot stories live on section subdomains
+    #[case(
+        ScrapeSource::Slashdot,
+        None,
+        "22/12/23/1234567",
+        "https://tech.slashdot.org/story/22/12/23/1234567/a-story",
+        true
+    )]
+    #[case(
+        ScrapeSource::Slashdot,
+        None,
+        "22/12/23/1234567",
+        "https://science.slashdot.org/story/22/12/23/1234567/",
+        true
+    )]
+    #[case(
+        ScrapeSource::Slashdot,
+        None,
+        "22/12/23/123456",
+        "https://tech.slashdot.org/story/22/12/23/1234567/",
+        false
+    )]
+    #[case(
+        ScrapeSource::Slashdot,
+        None,
+        "22/12/23/1234567",
+        "https://example.com/story/22/12/23/1234567",
+        false
+    )]
+    // Feeds and unknown sources never have self-posts
+    #[case(
+        ScrapeSource::Feed,
+        None,
+        "https://example.com/",
+        "https://example.com/",
+        false
+    )]
+    #[case(ScrapeSource::Other, None, "1", "https://example.com/1", false)]
+    fn test_is_likely_self_post(
+        #[case] source: ScrapeSource,
+        #[case] subsource: Option<&str>,
+        #[case] id: &str,
+        #[case] url: &str,
+        #[case] expected: bool,
+    ) {
+        let scrape_id = ScrapeId::new(source, subsource.map(str::to_owned), id.to_owned());
+        let url = StoryUrl::parse(url).expect("Failed to parse URL");
+        assert_eq!(scrape_id.is_likely_self_post(&url), expected);
+    }
+
+    /// Every source's comments URL parses back to the same id.
+    #[rstest]
+    #[case(ScrapeSource::HackerNews, None, "34107581")]
+    #[case(ScrapeSource::Reddit, Some("rust"), "abc123")]
+    #[case(ScrapeSource::Reddit, None, "abc123")]
+    #[case(ScrapeSource::Lobsters, None, "xf162v")]
+    #[case(ScrapeSource::Slashdot, None, "22/12/23/1234567")]
+    fn test_comments_url_round_trip(
+        #[case] source: ScrapeSource,
+        #[case] subsource: Option<&str>,
+        #[case] id: &str,
+    ) {
+        let scrape_id = ScrapeId::new(source, subsource.map(str::to_owned), id.to_owned());
+        let url = scrape_id.comments_url();
+        assert_eq!(source.id_from_comments_url(&url), Some(scrape_id.clone()));
+        let url = StoryUrl::parse(url).expect("Failed to parse URL");
+        assert!(scrape_id.is_likely_self_post(&url));
+    }
+
+    /// Ids survive a trip through their string form, including ids with hyphens.
+    #[rstest]
+    #[case(ScrapeSource::HackerNews, None, "34107581")]
+    #[case(ScrapeSource::Reddit, Some("rust"), "abc123")]
+    #[case(ScrapeSource::Feed, Some("example"), "https://example.com/a-story")]
+    fn test_string_round_trip(
+        #[case] source: ScrapeSource,
+        #[case] subsource: Option<&str>,
+        #[case] id: &str,
+    ) {
+        let scrape_id = ScrapeId::new(source, subsource.map(str::to_owned), id.to_owned());
+        let s = scrape_id.to_string();
+        assert_eq!(ScrapeId::from_string(&s), Some(scrape_id.clone()));
+        let json = serde_json::to_string(&scrape_id).expect("Failed to serialize");
+        let parsed: ScrapeId = serde_json::from_str(&json).expect("Failed to deserialize");
+        assert_eq!(parsed, scrape_id);
+    }
+}
@@ -1,15 +1,208 @@
 use std::{
-    collections::hash_map::DefaultHasher,
+    collections::{hash_map::DefaultHasher, HashMap},
     fmt::Display,
     hash::{Hash, Hasher},
+    sync::RwLock,
 };
 
+use percent_encoding::percent_decode_str;
 use serde::{Deserialize, Serialize};
+use thiserror::Error;
 use url::Url;
-use urlnorm::UrlNormalizer;
+use urlnorm::{Options, UrlNormalizer};
 
 lazy_static::lazy_static! {
-    static ref URL_NORMALIZER: UrlNormalizer = UrlNormalizer::default();
+    static ref URL_NORMALIZER: RwLock<StoryUrlNormalizer> = RwLock::new(
+        UrlNormalizationConfig::default()
+            .compile()
+            .expect("Default URL normalization failed to compile")
+    );
+}
+
+/// Query parameters that only record where a link was shared from (ie: UTM campaigns and ad click ids).
+const DEFAULT_TRACKING_PARAMS: &[&str] = &[
+    "utm_[a-z]+",
+    "gclid",
+    "dclid",
+    "fbclid",
+    "msclkid",
+    "igshid",
+    "_ga",
+    "_gl",
+    "mc_cid",
+    "mc_eid",
+    "[Ww][Tt]\\.mc_(id|ev)",
+    "__[a-z]+",
+];
+
+#[derive(Debug, Error)]
+pub enum UrlNormalizationConfigError {
+    #[error("Invalid tracking parameter pattern: {0}")]
+    TrackingParam(#[from] regex::Error),
+}
+
+/// Configures how story URLs are normalized for deduplication. Stories are filed under their normalized URL, so
+/// changing any of this splits new scrapes from the stories already indexed under the old normalization until the
+/// index is rebuilt.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct UrlNormalizationConfig {
+    /// Query parameters that are dropped from a URL's normalization, but kept in the raw URL. Each is a regular
+    /// expression that must match the whole parameter name. Adding or removing a parameter changes the identity of
+    /// stories already indexed from URLs with it, so they'll no longer merge with new scrapes until reindexed.
+    #[serde(default = "UrlNormalizationConfig::default_tracking_params")]
+    pub tracking_params: Vec<String>,
+    /// Treat AMP versions of a page as the page itself: a trailing `amp` path segment and an `amp=1` query parameter
+    /// are dropped from the normalization, but kept in the raw URL. Off by default, as enabling it changes the
+    /// identity of stories already indexed from AMP URLs.
+    #[serde(default)]
+    pub strip_amp: bool,
+    /// Hosts where every path segment identifies the content (ie: a GitHub repository named `amp`), so AMP stripping
+    /// never touches their URLs. Hosts are matched after normalization, like `significant_query_params`.
+    #[serde(default = "UrlNormalizationConfig::default_significant_path_hosts")]
+    pub significant_path_hosts: Vec<String>,
+    /// For hosts that carry meaning in their query string, the query parameters that identify the content. All
+    /// other query parameters for these hosts are ignored for normalization (ie: YouTube's `t=` timestamp or `list=`
+    /// playlist). Hosts are matched after normalization, so `www.` and `m.` prefixes are already gone.
+    #[serde(default = "UrlNormalizationConfig::default_significant_query_params")]
+    pub significant_query_params: HashMap<String, Vec<String>>,
+}
+
+impl Default for UrlNormalizationConfig {
+    fn default() -> Self {
+        Self {
+            tracking_params: Self::default_tracking_params(),
+            strip_amp: false,
+            significant_path_hosts: Self::default_significant_path_hosts(),
+            significant_query_params: Self::default_significant_query_params(),
+        }
+    }
+}
+
+impl UrlNormalizationConfig {
+    fn default_tracking_params() -> Vec<String> {
+        DEFAULT_TRACKING_PARAMS
+            .iter()
+            .map(|param| param.to_string())
+            .collect()
+    }
+
+    fn default_significant_path_hosts() -> Vec<String> {
+        DEFAULT_SIGNIFICANT_PATH_HOSTS
+            .iter()
+            .map(|host| host.to_string())
+            .collect()
+    }
+
+    fn default_significant_query_params() -> HashMap<String, Vec<String>> {
+        DEFAULT_SIGNIFICANT_QUERY_PARAMS
+            .iter()
+            .map(|(host, params)| {
+                (
+                    host.to_string(),
+                    params.iter().map(|param| param.to_string()).collect(),
+                )
+            })
+            .collect()
+    }
+
+    fn compile(&self) -> Result<StoryUrlNormalizer, UrlNormalizationConfigError> {
+        let normalizer = Options::default()
+            .with_ignored_query_params(self.tracking_params.clone())
+            .compile()?;
+        Ok(StoryUrlNormalizer {
+            normalizer,
+            strip_amp: self.strip_amp,
+            significant_path_hosts: self.significant_path_hosts.clone(),
+            significant_query_params: self.significant_query_params.clone(),
+        })
+    }
+
+    /// Reject configurations that can't be compiled (ie: an invalid tracking parameter pattern).
+    pub fn validate(&self) -> Result<(), UrlNormalizationConfigError> {
+        self.compile().map(drop)
+    }
+
+    /// Use this configuration for all URLs parsed from now on. As this changes the normalization of URLs that
+    /// are already indexed, it should be applied once at startup before any URLs are parsed.
+    pub fn apply(&self) -> Result<(), UrlNormalizationConfigError> {
+        let normalizer = self.compile()?;
+        *URL_NORMALIZER.write().expect("Poisoned URL normalizer") = normalizer;
+        Ok(())
+    }
+}
+
+/// A compiled [`UrlNormalizationConfig`].
+struct StoryUrlNormalizer {
+    normalizer: UrlNormalizer,
+    strip_amp: bool,
+    significant_path_hosts: Vec<String>,
+    significant_query_params: HashMap<String, Vec<String>>,
+}
+
+/// Rewrites an AMP URL to the URL of the page it mirrors (ie: `example.com/post/amp?amp=1` to `example.com/post`). A
+/// URL that would be left without a path is returned unchanged.
+fn strip_amp(url: &Url) -> Option<Url> {
+    let mut stripped = url.clone();
+    let mut changed = false;
+    let mut segments = url
+        .path_segments()
+        .into_iter()
+        .flatten()
+        .filter(|segment| !segment.is_empty())
+        .collect::<Vec<_>>();
+    if segments.len() > 1 && segments.last() == Some(&"amp") {
+        segments.pop();
+        stripped.set_path(&segments.join("/"));
+        changed = true;
+    }
+    if url.query_pairs().any(|(k, v)| k == "amp" && v == "1") {
+        let query = url
+            .query_pairs()
+            .filter(|(k, v)| !(k == "amp" && v == "1"))
+            .map(|(k, v)| (k.into_owned(), v.into_owned()))
+            .collect::<Vec<_>>();
+        if query.is_empty() {
+            stripped.set_query(None);
+        } else {
+            stripped.query_pairs_mut().clear().extend_pairs(query);
+        }
+        changed = true;
+    }
+    changed.then_some(stripped)
+}
+
+/// Default hosts where every path segment identifies the content.
+const DEFAULT_SIGNIFICANT_PATH_HOSTS: &[&str] = &["github.com", "gitlab.com", "codeberg.org"];
+
+/// Default hosts whose query string identifies the content, with the query parameters that do so.
+const DEFAULT_SIGNIFICANT_QUERY_PARAMS: &[(&str, &[&str])] = &[
+    ("youtube.com", &["v"]),
+    ("arxiv.org", &["id"]),
+    ("news.ycombinator.com", &["id"]),
+];
+
+impl StoryUrlNormalizer {
+    /// Computes the normalization string for a URL, keeping only the significant query parameters for hosts that
+    /// have them.
+    fn compute_normalization_string(&self, url: &Url, host: &str) -> String {
+        if let Some(params) = self.significant_query_params.get(host) {
+            if url.query().is_some() {
+                let mut url = url.clone();
+                let query = url
+                    .query_pairs()
+                    .filter(|(k, _)| params.iter().any(|param| param == k))
+                    .map(|(k, v)| (k.into_owned(), v.into_owned()))
+                    .collect::<Vec<_>>();
+                if query.is_empty() {
+                    url.set_query(None);
+                } else {
+                    url.query_pairs_mut().clear().extend_pairs(query);
+                }
+                return self.normalizer.compute_normalization_string(&url);
+            }
+        }
+        self.normalizer.compute_normalization_string(url)
+    }
 }
 
 /// Story-specific URL that caches the normalization information and other important parts of the URL.
@@ -30,6 +223,25 @@ impl Serialize for StoryUrl {
     }
 }
 
+#[cfg(feature = "json_schema")]
+impl schemars::JsonSchema for StoryUrl {
+    fn schema_name() -> String {
+        "StoryUrl".to_owned()
+    }
+
+    fn json_schema(gen: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        // Matches what we accept in deserialization: a raw URL, or the (url, host, normalized) tuple
+        #[derive(schemars::JsonSchema)]
+        #[schemars(untagged)]
+        #[allow(dead_code)]
+        enum StoryUrlSchema {
+            Raw(String),
+            Bits((String, String, String)),
+        }
+        StoryUrlSchema::json_schema(gen)
+    }
+}
+
 impl<'de> Deserialize<'de> for StoryUrl {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
@@ -67,11 +279,23 @@ impl Display for StoryUrl {
 
 impl StoryUrl {
     pub fn parse<S: AsRef<str>>(s: S) -> Option<Self> {
+        Self::parse_with(&URL_NORMALIZER.read().expect("Poisoned URL normalizer"), s)
+    }
+
+    fn parse_with<S: AsRef<str>>(normalizer: &StoryUrlNormalizer, s: S) -> Option<Self> {
         if let Ok(url) = Url::parse(s.as_ref()) {
-            if let Some(host) = URL_NORMALIZER.normalize_host(&url) {
+            // The raw URL is kept for linking, but the canonical page is normalized
+            let canonical = normalizer
+                .strip_amp
+                .then(|| normalizer.normalizer.normalize_host(&url))
+                .flatten()
+                .filter(|host| !normalizer.significant_path_hosts.iter().any(|h| h == host))
+                .and_then(|_| strip_amp(&url));
+            let canonical = canonical.as_ref().unwrap_or(&url);
+            if let Some(host) = normalizer.normalizer.normalize_host(canonical) {
                 let host = host.to_owned();
                 let norm_str = StoryUrlNorm {
-                    norm: URL_NORMALIZER.compute_normalization_string(&url),
+                    norm: normalizer.compute_normalization_string(canonical, &host),
                 };
                 let url = url.into();
                 return Some(Self {
@@ -95,6 +319,25 @@ impl StoryUrl {
     pub fn normalization(&self) -> &StoryUrlNorm {
         &self.norm_str
     }
+
+    /// The non-empty segments of this URL's path, percent-decoded.
+    pub fn path_segments(&self) -> Vec<String> {
+        let Ok(url) = Url::parse(&self.url) else {
+            return vec![];
+        };
+        url.path_segments()
+            .into_iter()
+            .flatten()
+            .filter(|segment| !segment.is_empty())
+            .map(|segment| percent_decode_str(segment).decode_utf8_lossy().into_owned())
+            .collect()
+    }
+
+    /// Do these URLs point at the same story? Unlike `==`, which compares the raw URLs, this compares normalizations
+    /// the same way the index does (ie: `http://www.example.com/` and `https://example.com/` are the same story).
+    pub fn same_story(&self, other: &StoryUrl) -> bool {
+        self.norm_str.hash() == other.norm_str.hash()
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -138,3 +381,165 @@ impl StoryUrlNorm {
         &self.norm
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case("https://github.com/rust-lang/rust", &["rust-lang", "rust"])]
+    #[case("https://example.com/a//b/?q=1#c", &["a", "b"])]
+    #[case("https://example.com/c%2B%2B%20tips.html", &["c++ tips.html"])]
+    #[case("https://example.com/", &[])]
+    fn test_path_segments(#[case] url: &str, #[case] segments: &[&str]) {
+        let url = StoryUrl::parse(url).expect("URL");
+        assert_eq!(url.path_segments(), segments);
+    }
+
+    #[rstest]
+    #[case(
+        "http://youtube.com/watch?v=123",
+        "https://www.youtube.com/watch?v=123&t=30s"
+    )]
+    #[case(
+        "http://youtube.com/watch?v=123",
+        "https://m.youtube.com/watch?list=abc&v=123"
+    )]
+    #[case(
+        "https://arxiv.org/abs?id=1234",
+        "https://arxiv.org/abs?id=1234&context=cs"
+    )]
+    #[case("https://example.com/?a=1&b=2", "https://example.com/?b=2&a=1")]
+    #[case(
+        "https://example.com/post?utm_source=twitter&utm_medium=social",
+        "https://example.com/post?utm_campaign=launch&utm_id=123"
+    )]
+    #[case("https://example.com/post", "https://example.com/post?fbclid=abc")]
+    #[case("https://example.com/?a=1", "https://example.com/?gclid=abc&a=1")]
+    fn test_same_norm(#[case] a: &str, #[case] b: &str) {
+        let a = StoryUrl::parse(a).expect("Failed to parse");
+        let b = StoryUrl::parse(b).expect("Failed to parse");
+        assert_eq!(a.normalization(), b.normalization());
+        assert!(a.same_story(&b));
+        assert_ne!(a, b);
+    }
+
+    #[rstest]
+    #[case("http://youtube.com/watch?v=123", "http://youtube.com/watch?v=456")]
+    #[case("https://arxiv.org/abs?id=1234", "https://arxiv.org/abs?id=5678")]
+    #[case("https://example.com/?a=1", "https://example.com/?a=1&b=2")]
+    #[case("https://example.com/?utm=1", "https://example.com/?utm=2")]
+    fn test_different_norm(#[case] a: &str, #[case] b: &str) {
+        let a = StoryUrl::parse(a).expect("Failed to parse");
+        let b = StoryUrl::parse(b).expect("Failed to parse");
+        assert_ne!(a.normalization(), b.normalization());
+        assert!(!a.same_story(&b));
+    }
+
+    /// Tracking parameters stay in the raw URL, and can be replaced through configuration.
+    #[test]
+    fn test_tracking_params() {
+        let url = StoryUrl::parse("https://example.com/post?utm_source=rss&ref=abc").expect("URL");
+        assert_eq!(url.raw(), "https://example.com/post?utm_source=rss&ref=abc");
+
+        let normalizer = UrlNormalizationConfig {
+            tracking_params: vec!["ref".to_owned()],
+            ..Default::default()
+        }
+        .compile()
+        .expect("Failed to compile");
+        let parse = |s| StoryUrl::parse_with(&normalizer, s).expect("URL");
+        assert!(parse("https://example.com/post?ref=abc")
+            .same_story(&parse("https://example.com/post")));
+        assert!(!parse("https://example.com/post?utm_source=rss")
+            .same_story(&parse("https://example.com/post")));
+    }
+
+    /// Invalid tracking parameter patterns are rejected up front.
+    #[test]
+    fn test_invalid_tracking_params() {
+        let config = UrlNormalizationConfig {
+            tracking_params: vec!["utm_(".to_owned()],
+            ..Default::default()
+        };
+        assert!(matches!(
+            config.validate(),
+            Err(UrlNormalizationConfigError::TrackingParam(_))
+        ));
+        assert!(UrlNormalizationConfig::default().validate().is_ok());
+    }
+
+    /// Significant query parameters are configured per host.
+    #[test]
+    fn test_significant_query_params() {
+        let normalizer = UrlNormalizationConfig {
+            significant_query_params: HashMap::from_iter([(
+                "example.com".to_owned(),
+                vec!["p".to_owned()],
+            )]),
+            ..Default::default()
+        }
+        .compile()
+        .expect("Failed to compile");
+        let parse = |s| StoryUrl::parse_with(&normalizer, s).expect("URL");
+        assert!(parse("https://example.com/?p=1&ref=abc")
+            .same_story(&parse("https://example.com/?p=1")));
+        assert!(!parse("https://example.com/?p=1").same_story(&parse("https://example.com/?p=2")));
+        assert!(!parse("https://youtube.com/watch?v=1&t=30s")
+            .same_story(&parse("https://youtube.com/watch?v=1")));
+    }
+
+    /// AMP and mobile versions of a page normalize to the page itself when AMP stripping is enabled.
+    #[rstest]
+    #[case("https://example.com/foo")]
+    #[case("https://m.example.com/foo")]
+    #[case("https://example.com/foo/amp")]
+    #[case("https://example.com/foo/amp/")]
+    #[case("https://example.com/foo?amp=1")]
+    #[case("https://www.example.com/foo/amp?amp=1")]
+    fn test_amp_norm(#[case] url: &str) {
+        let normalizer = UrlNormalizationConfig {
+            strip_amp: true,
+            ..Default::default()
+        }
+        .compile()
+        .expect("Failed to compile");
+        let canonical = StoryUrl::parse_with(&normalizer, "https://example.com/foo").expect("URL");
+        let amp = StoryUrl::parse_with(&normalizer, url).expect("URL");
+        assert_eq!(amp.raw(), Url::parse(url).expect("URL").as_str());
+        assert_eq!(amp.normalization().hash(), canonical.normalization().hash());
+        assert_eq!(amp.host(), "example.com");
+    }
+
+    /// AMP stripping is disabled by default.
+    #[test]
+    fn test_amp_disabled() {
+        let parse = |s| StoryUrl::parse(s).expect("URL");
+        let canonical = parse("https://example.com/foo");
+        assert!(!parse("https://example.com/foo/amp").same_story(&canonical));
+        assert!(!parse("https://example.com/foo?amp=1").same_story(&canonical));
+    }
+
+    /// Only a whole trailing `amp` segment and `amp=1` are stripped, hosts with meaningful paths are left alone, and a
+    /// genuinely different path stays distinct.
+    #[rstest]
+    #[case("https://example.com/amp/foo", "https://example.com/foo")]
+    #[case("https://amp.example.com/foo", "https://example.com/foo")]
+    #[case("https://example.com/foo", "https://example.com/foo/amplify")]
+    #[case("https://example.com/amp", "https://example.com/")]
+    #[case("https://example.com/foo?amp=0", "https://example.com/foo")]
+    #[case("https://github.com/example/amp", "https://github.com/example")]
+    #[case("https://www.github.com/example/amp", "https://github.com/example")]
+    fn test_amp_different_norm(#[case] a: &str, #[case] b: &str) {
+        let normalizer = UrlNormalizationConfig {
+            strip_amp: true,
+            ..Default::default()
+        }
+        .compile()
+        .expect("Failed to compile");
+        let a = StoryUrl::parse_with(&normalizer, a).expect("Failed to parse");
+        let b = StoryUrl::parse_with(&normalizer, b).expect("Failed to parse");
+        assert_ne!(a.normalization().hash(), b.normalization().hash());
+    }
+}
@@ -1,6 +1,77 @@
+use std::{collections::HashMap, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+/// The role granted to an authorized admin user.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    /// May view admin pages, but may not trigger any mutating action (cron, backup, scrape, etc).
+    ReadOnly,
+    /// Full access to the admin interface.
+    Admin,
+}
+
+impl Role {
+    /// Can this role perform actions that mutate state?
+    pub fn can_mutate(&self) -> bool {
+        matches!(self, Role::Admin)
+    }
+}
+
+impl FromStr for Role {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "read-only" | "readonly" | "read_only" => Ok(Role::ReadOnly),
+            "admin" => Ok(Role::Admin),
+            _ => Err(format!("Unknown role '{s}'")),
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub enum Auth {
     None,
     Fixed(String),
     FromHeader(String),
+    /// Extract the user from a header, allowing only the users in the map with the given roles.
+    FromHeaderWithUsers(String, HashMap<String, Role>),
+}
+
+impl Auth {
+    /// Parse a list of `user=role` pairs into a user map.
+    pub fn parse_users(users: &[String]) -> Result<HashMap<String, Role>, String> {
+        let mut map = HashMap::new();
+        for user in users {
+            let Some((user, role)) = user.rsplit_once('=') else {
+                return Err(format!("Expected user=role, got '{user}'"));
+            };
+            map.insert(user.to_owned(), role.parse()?);
+        }
+        Ok(map)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case("a@example.com=admin", "a@example.com", Role::Admin)]
+    #[case("b@example.com=read-only", "b@example.com", Role::ReadOnly)]
+    #[case("c=d=readonly", "c=d", Role::ReadOnly)]
+    fn test_parse_users(#[case] input: &str, #[case] user: &str, #[case] role: Role) {
+        let users = Auth::parse_users(&[input.to_owned()]).expect("Failed to parse");
+        assert_eq!(users.get(user), Some(&role));
+    }
+
+    #[rstest]
+    #[case("a@example.com")]
+    #[case("a@example.com=superuser")]
+    fn test_parse_users_invalid(#[case] input: &str) {
+        assert!(Auth::parse_users(&[input.to_owned()]).is_err());
+    }
 }
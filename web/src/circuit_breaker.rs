@@ -0,0 +1,140 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use progscrape_scrapers::ScrapeSource;
+use serde::{Deserialize, Serialize};
+
+use crate::cron::CronInterval;
+
+/// Stops cron from scraping a source that keeps failing, until a cooldown has passed.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CircuitBreakerConfig {
+    /// The number of consecutive failed scrapes that opens the breaker for a source. Zero disables the breaker.
+    pub failure_threshold: u32,
+    /// How long the breaker stays open before the source is scraped again.
+    pub cooldown: (usize, CronInterval),
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 0,
+            cooldown: (1, CronInterval::Hour),
+        }
+    }
+}
+
+#[derive(Default)]
+struct BreakerState {
+    failures: u32,
+    open_until: Option<Instant>,
+}
+
+/// Per-source circuit breaker state.
+pub struct CircuitBreakers {
+    failure_threshold: u32,
+    cooldown: Duration,
+    sources: HashMap<ScrapeSource, BreakerState>,
+}
+
+impl CircuitBreakers {
+    pub fn new(config: &CircuitBreakerConfig) -> Self {
+        Self {
+            failure_threshold: config.failure_threshold,
+            cooldown: config.cooldown.1.as_duration(config.cooldown.0),
+            sources: HashMap::new(),
+        }
+    }
+
+    /// Should we scrape this source now? Closes the breaker again once its cooldown has elapsed.
+    pub fn allow(&mut self, source: ScrapeSource, now: Instant) -> bool {
+        let Some(state) = self.sources.get_mut(&source) else {
+            return true;
+        };
+        match state.open_until {
+            Some(until) if now < until => false,
+            Some(_) => {
+                tracing::info!("Circuit breaker for {source:?} closed after cooldown");
+                *state = BreakerState::default();
+                true
+            }
+            None => true,
+        }
+    }
+
+    /// Record the outcome of a scrape. A success resets the failure count, while enough consecutive failures
+    /// open the breaker.
+    pub fn record(&mut self, source: ScrapeSource, success: bool, now: Instant) {
+        if success {
+            self.sources.remove(&source);
+            return;
+        }
+        let state = self.sources.entry(source).or_default();
+        state.failures += 1;
+        if self.failure_threshold > 0
+            && state.failures >= self.failure_threshold
+            && state.open_until.is_none()
+        {
+            tracing::warn!(
+                "Circuit breaker for {source:?} opened after {} consecutive failures",
+                state.failures
+            );
+            state.open_until = Some(now + self.cooldown);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_circuit_breaker() {
+        let mut breakers = CircuitBreakers::new(&CircuitBreakerConfig {
+            failure_threshold: 3,
+            cooldown: (10, CronInterval::Minute),
+        });
+        let mut now = Instant::now();
+        let reddit = ScrapeSource::Reddit;
+        let hn = ScrapeSource::HackerNews;
+
+        // Failures below the threshold keep the breaker closed
+        for _ in 0..2 {
+            assert!(breakers.allow(reddit, now));
+            breakers.record(reddit, false, now);
+        }
+        assert!(breakers.allow(reddit, now));
+        breakers.record(reddit, false, now);
+
+        // Subsequent ticks skip the source until the cooldown elapses, but other sources are unaffected
+        for _ in 0..9 {
+            now += Duration::from_secs(60);
+            assert!(!breakers.allow(reddit, now));
+            assert!(breakers.allow(hn, now));
+        }
+        now += Duration::from_secs(61);
+        assert!(breakers.allow(reddit, now));
+
+        // The breaker starts from scratch after the cooldown
+        breakers.record(reddit, false, now);
+        assert!(breakers.allow(reddit, now));
+
+        // A success resets the failure count
+        breakers.record(reddit, false, now);
+        breakers.record(reddit, true, now);
+        breakers.record(reddit, false, now);
+        assert!(breakers.allow(reddit, now));
+    }
+
+    #[test]
+    fn test_circuit_breaker_disabled() {
+        let mut breakers = CircuitBreakers::new(&CircuitBreakerConfig::default());
+        let now = Instant::now();
+        for _ in 0..100 {
+            breakers.record(ScrapeSource::Reddit, false, now);
+        }
+        assert!(breakers.allow(ScrapeSource::Reddit, now));
+    }
+}
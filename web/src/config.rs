@@ -9,4 +9,8 @@ pub struct Config {
     pub scrape: progscrape_scrapers::ScrapeConfig,
     pub cron: crate::cron::CronConfig,
     pub rate_limits: crate::rate_limits::RateLimitsConfig,
+    #[serde(default)]
+    pub fetch: crate::fetch::FetchConfig,
+    #[serde(default)]
+    pub host: crate::web::HostConfig,
 }
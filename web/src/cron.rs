@@ -61,6 +61,9 @@ pub struct CronConfig {
     pub jitter: (i8, i8),
     pub history_age: (usize, CronInterval),
     pub history_count: usize,
+    /// Temporarily stop scraping sources that keep failing
+    #[serde(default)]
+    pub circuit_breaker: crate::circuit_breaker::CircuitBreakerConfig,
 }
 
 impl Default for CronConfig {
@@ -70,6 +73,7 @@ impl Default for CronConfig {
             jitter: (0, 0),
             history_age: (1, CronInterval::Minute),
             history_count: 10,
+            circuit_breaker: Default::default(),
         }
     }
 }
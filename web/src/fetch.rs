@@ -0,0 +1,226 @@
+use std::{collections::HashMap, future::Future, sync::Arc, time::Duration};
+
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    sync::{Mutex, Semaphore},
+    time::Instant,
+};
+
+/// Limits how hard we hit any single origin when scraping.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct FetchConfig {
+    /// The maximum number of requests in flight overall.
+    pub max_concurrency: usize,
+    /// The maximum number of requests in flight to a single host.
+    pub per_host_concurrency: usize,
+    /// The minimum delay between the starts of two requests to the same host, in milliseconds.
+    pub per_host_delay_ms: u64,
+    /// How many times to retry a request that was throttled or hit a server error.
+    pub max_retries: u32,
+    /// The delay before the first retry, in milliseconds. Each further retry waits twice as long as the last.
+    pub retry_base_delay_ms: u64,
+}
+
+impl Default for FetchConfig {
+    fn default() -> Self {
+        Self {
+            max_concurrency: 8,
+            per_host_concurrency: 1,
+            per_host_delay_ms: 0,
+            max_retries: 3,
+            retry_base_delay_ms: 1000,
+        }
+    }
+}
+
+/// Per-host politeness state.
+struct HostState {
+    semaphore: Semaphore,
+    /// The earliest time the next request to this host may start.
+    next: Mutex<Instant>,
+}
+
+fn host_for_url(url: &str) -> String {
+    reqwest::Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_owned))
+        .unwrap_or_default()
+}
+
+/// Fetch all of the given URLs with `fetch`, running requests to different hosts in parallel while limiting
+/// concurrency and spacing out requests to any single host as configured. Results are returned in completion
+/// order.
+pub async fn fetch_politely<F, Fut, T>(
+    config: &FetchConfig,
+    urls: Vec<String>,
+    fetch: F,
+) -> Vec<(String, T)>
+where
+    F: Fn(String) -> Fut,
+    Fut: Future<Output = T>,
+{
+    let now = Instant::now();
+    let mut hosts = HashMap::new();
+    for url in &urls {
+        hosts.entry(host_for_url(url)).or_insert_with(|| {
+            Arc::new(HostState {
+                semaphore: Semaphore::new(config.per_host_concurrency.max(1)),
+                next: Mutex::new(now),
+            })
+        });
+    }
+    let global = Semaphore::new(config.max_concurrency.max(1));
+    let delay = Duration::from_millis(config.per_host_delay_ms);
+    let count = urls.len().max(1);
+
+    futures::stream::iter(urls)
+        .map(|url| {
+            let host = hosts[&host_for_url(&url)].clone();
+            let global = &global;
+            let fetch = &fetch;
+            async move {
+                let _host_permit = host.semaphore.acquire().await;
+                // Reserve the next slot for this host, then wait for it
+                let start = {
+                    let mut next = host.next.lock().await;
+                    let start = (*next).max(Instant::now());
+                    *next = start + delay;
+                    start
+                };
+                tokio::time::sleep_until(start).await;
+                let _permit = global.acquire().await;
+                tracing::debug!("Fetching {url}");
+                let res = fetch(url.clone()).await;
+                (url, res)
+            }
+        })
+        .buffer_unordered(count)
+        .collect()
+        .await
+}
+
+/// Run `fetch` until `should_retry` rejects its result or we run out of retries, backing off exponentially between
+/// attempts. Returns the final result and the number of retries it took.
+pub async fn fetch_with_backoff<F, Fut, T>(
+    config: &FetchConfig,
+    should_retry: impl Fn(&T) -> bool,
+    fetch: F,
+) -> (T, u32)
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = T>,
+{
+    let mut retries = 0;
+    loop {
+        let res = fetch().await;
+        if retries >= config.max_retries || !should_retry(&res) {
+            return (res, retries);
+        }
+        let delay = config
+            .retry_base_delay_ms
+            .saturating_mul(1 << retries.min(16));
+        tokio::time::sleep(Duration::from_millis(delay)).await;
+        retries += 1;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Requests to the same host are spaced by the configured delay, while other hosts proceed in parallel.
+    #[tokio::test(start_paused = true)]
+    async fn test_politeness() {
+        let config = FetchConfig {
+            max_concurrency: 8,
+            per_host_concurrency: 1,
+            per_host_delay_ms: 1000,
+            ..Default::default()
+        };
+        let urls = vec![
+            "https://www.reddit.com/r/rust.json".to_owned(),
+            "https://www.reddit.com/r/golang.json".to_owned(),
+            "https://www.reddit.com/r/python.json".to_owned(),
+            "https://news.ycombinator.com/".to_owned(),
+            "https://lobste.rs/hottest.json".to_owned(),
+        ];
+        let start = Instant::now();
+        let starts = Arc::new(std::sync::Mutex::new(vec![]));
+        let res = fetch_politely(&config, urls.clone(), |url| {
+            let starts = starts.clone();
+            async move {
+                starts.lock().expect("Poisoned").push((url, Instant::now()));
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        })
+        .await;
+        assert_eq!(res.len(), urls.len());
+
+        let starts = starts.lock().expect("Poisoned");
+        let offset = |prefix: &str| {
+            starts
+                .iter()
+                .filter(|(url, _)| url.starts_with(prefix))
+                .map(|(_, time)| (*time - start).as_millis())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(offset("https://www.reddit.com/"), vec![0, 1000, 2000]);
+        assert_eq!(offset("https://news.ycombinator.com/"), vec![0]);
+        assert_eq!(offset("https://lobste.rs/"), vec![0]);
+    }
+
+    /// Overall concurrency is limited across hosts.
+    #[tokio::test(start_paused = true)]
+    async fn test_max_concurrency() {
+        let config = FetchConfig {
+            max_concurrency: 1,
+            per_host_concurrency: 1,
+            per_host_delay_ms: 0,
+            ..Default::default()
+        };
+        let urls = vec![
+            "https://a.example.com/".to_owned(),
+            "https://b.example.com/".to_owned(),
+        ];
+        let start = Instant::now();
+        let res = fetch_politely(&config, urls, |_| async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            Instant::now()
+        })
+        .await;
+        let mut finished = res
+            .into_iter()
+            .map(|(_, time)| (time - start).as_millis())
+            .collect::<Vec<_>>();
+        finished.sort();
+        assert_eq!(finished, vec![100, 200]);
+    }
+
+    /// Retries back off exponentially and stop once the result is acceptable or we run out.
+    #[tokio::test(start_paused = true)]
+    async fn test_backoff() {
+        let config = FetchConfig {
+            max_retries: 3,
+            retry_base_delay_ms: 100,
+            ..Default::default()
+        };
+        let start = Instant::now();
+        let attempts = std::sync::Mutex::new(vec![]);
+        let fetch = || {
+            let mut attempts = attempts.lock().expect("Poisoned");
+            attempts.push((Instant::now() - start).as_millis());
+            let attempt = attempts.len();
+            async move { attempt }
+        };
+        let (res, retries) = fetch_with_backoff(&config, |attempt| *attempt < 3, fetch).await;
+        assert_eq!((res, retries), (3, 2));
+        assert_eq!(*attempts.lock().expect("Poisoned"), vec![0, 100, 300]);
+
+        // Give up after the configured number of retries
+        attempts.lock().expect("Poisoned").clear();
+        let (res, retries) = fetch_with_backoff(&config, |_| true, fetch).await;
+        assert_eq!((res, retries), (4, 3));
+    }
+}
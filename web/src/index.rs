@@ -11,23 +11,170 @@ use crate::{
 use itertools::Itertools;
 use keepcalm::{Shared, SharedMut};
 use progscrape_application::{
-    BackerUpper, BackupResult, IntoStoryQuery, PersistError, PersistLocation, ScrapePersistResult,
-    SearchSummary, Shard, Storage, StorageFetch, StorageSummary, StorageWriter, Story,
-    StoryEvaluator, StoryIdentifier, StoryIndex, StoryQuery, StoryRender, StoryScrapePayload,
+    BackerUpper, BackupResult, DomainSearchConfig, EmptyTitlePolicy, FuzzySearchConfig,
+    HostBlocklistConfig, IntoStoryQuery, PersistError, PersistLocation, ScoreSnapshot,
+    ScoreSnapshotConfig, ScrapePersistResult, SearchRankingConfig, SearchSummary, Shard,
+    ShardOrder, ShardScanLimits, Storage, StorageFetch, StorageSummary, StorageWriter, Story,
+    StoryCursor, StoryEvaluator, StoryIdentifier, StoryIndex, StoryQuery, StoryRender,
+    StoryScrapePayload, TieBreakConfig, UrlChangePolicy,
 };
-use progscrape_scrapers::{StoryDate, StoryUrl, TypedScrape};
+use progscrape_scrapers::{StoryDate, StoryUrl, TypedScrape, UrlNormalizationConfig};
 use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
 use tracing::Level;
 
+/// How many front page updates a slow live subscriber may fall behind before it starts missing them.
+const FRONT_PAGE_EVENT_CAPACITY: usize = 16;
+
+/// How many pages of stories a single cursor request may scan for filter matches before it returns what it has.
+const MAX_CURSOR_SCAN_PAGES: usize = 10;
+
+/// Post-filters applied to fetched stories.
+#[derive(Clone, Debug, Default)]
+pub struct StoryFilter {
+    /// Only stories at or after this date.
+    pub after: Option<StoryDate>,
+    /// Only stories before this date.
+    pub before: Option<StoryDate>,
+    /// Only stories seen on at least this many distinct sources.
+    pub min_sources: usize,
+    /// Skip stories with any of these tags.
+    pub hidden_tags: Vec<String>,
+    /// Skip stories from any of these hosts, or their subdomains.
+    pub hidden_hosts: Vec<String>,
+}
+
+impl StoryFilter {
+    /// Hide the stories matching a comma-separated list of tags and domains (ie: `crypto,twitter.com`). Anything
+    /// with a dot in it is treated as a domain.
+    pub fn hide(mut self, hide: &str) -> Self {
+        for item in hide.split(',') {
+            let item = item.trim().to_lowercase();
+            if item.is_empty() {
+                continue;
+            }
+            if item.contains('.') {
+                let host = item.trim_start_matches("www.").to_owned();
+                self.hidden_hosts.push(host);
+            } else {
+                self.hidden_tags.push(item);
+            }
+        }
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.after.is_none()
+            && self.before.is_none()
+            && self.min_sources <= 1
+            && self.hidden_tags.is_empty()
+            && self.hidden_hosts.is_empty()
+    }
+
+    pub fn matches<S>(&self, story: &Story<S>) -> bool {
+        self.after.map_or(true, |after| story.date >= after)
+            && self.before.map_or(true, |before| story.date < before)
+            && story.source_count() >= self.min_sources
+            && !self.hidden_tags.iter().any(|tag| story.tags.contains(tag))
+            && !self.is_hidden_host(story.url.host())
+    }
+
+    fn is_hidden_host(&self, host: &str) -> bool {
+        let host = host.to_lowercase();
+        self.hidden_hosts.iter().any(|hidden| {
+            host == *hidden
+                || host
+                    .strip_suffix(hidden.as_str())
+                    .map_or(false, |prefix| prefix.ends_with('.'))
+        })
+    }
+}
+
 pub struct HotSet {
     stories: Vec<Story<Shard>>,
     top_tags: Vec<(String, usize)>,
+    /// Has this been computed from the index, or is it the empty placeholder we start with?
+    refreshed: bool,
+}
+
+/// A cheap summary of the index's state, for load balancer health checks.
+#[derive(Serialize)]
+pub struct IndexHealth {
+    pub most_recent_story: StoryDate,
+    pub story_count: usize,
+    pub oldest_shard: Option<String>,
+    pub newest_shard: Option<String>,
+    pub hot_set_refreshed: bool,
 }
 
 #[derive(Serialize, Deserialize, Default, Clone)]
 pub struct IndexConfig {
     pub hot_set: HotSetConfig,
     pub max_count: usize,
+    /// How many of the newest shards each query type may scan
+    #[serde(default)]
+    pub max_shards: ShardScanLimits,
+    /// If specified, hot set scores are recorded on every re-index
+    #[serde(default)]
+    pub score_snapshots: Option<ScoreSnapshotConfig>,
+    /// How to handle scrapes with empty titles
+    #[serde(default)]
+    pub empty_titles: EmptyTitlePolicy,
+    /// Hosts whose scrapes are dropped at insert time
+    #[serde(default)]
+    pub blocklist: HostBlocklistConfig,
+    /// Keep penalized (ie: image) hosts off the front page entirely
+    #[serde(default)]
+    pub exclude_penalized_hosts: bool,
+    /// Keep self-posts from every source off the front page (they remain searchable)
+    #[serde(default)]
+    pub exclude_self_posts: bool,
+    /// Skip shards that fail to open instead of failing queries that span them
+    #[serde(default)]
+    pub skip_degraded_shards: bool,
+    /// Search recency tuning
+    #[serde(default)]
+    pub search_ranking: SearchRankingConfig,
+    /// Host prefixes to ignore in domain searches
+    #[serde(default)]
+    pub domain_search: DomainSearchConfig,
+    /// Inexact matching for tag and domain searches that find nothing
+    #[serde(default)]
+    pub fuzzy_search: FuzzySearchConfig,
+    /// What to do when a source re-scrapes a story under a corrected URL
+    #[serde(default)]
+    pub url_changes: UrlChangePolicy,
+    /// Ignore scrape dates more than this many days before the rest of a story's scrapes when dating it, once at least
+    /// two other scrapes agree
+    #[serde(default)]
+    pub date_outlier_days: Option<u32>,
+    /// Clamp story dates more than this many hours in the future to the time they were indexed
+    #[serde(default)]
+    pub future_date_tolerance_hours: Option<u32>,
+    /// How story URLs are normalized for deduplication (tracking and significant query parameters, AMP pages). This
+    /// is process-wide and applied once at startup, so changes take effect on restart. Changing it splits new scrapes
+    /// from stories already indexed under the old normalization, so rebuild the index with `reindex` afterwards.
+    #[serde(default)]
+    pub url_normalization: UrlNormalizationConfig,
+}
+
+impl IndexConfig {
+    /// Apply the settings that the storage layer needs to a freshly-opened index.
+    pub fn apply(&self, index: &mut StoryIndex) {
+        index.set_scan_limits(self.max_shards.clone());
+        index.set_score_snapshots(self.score_snapshots.clone());
+        index.set_empty_title_policy(self.empty_titles);
+        index.set_blocklist(self.blocklist.clone());
+        index.set_exclude_penalized_hosts(self.exclude_penalized_hosts);
+        index.set_exclude_self_posts(self.exclude_self_posts);
+        index.set_skip_degraded_shards(self.skip_degraded_shards);
+        index.set_search_ranking(self.search_ranking.clone());
+        index.set_domain_search(self.domain_search.clone());
+        index.set_fuzzy_search(self.fuzzy_search.clone());
+        index.set_url_change_policy(self.url_changes);
+        index.set_date_outlier_days(self.date_outlier_days);
+        index.set_future_date_tolerance_hours(self.future_date_tolerance_hours);
+    }
 }
 
 #[derive(Serialize, Deserialize, Default, Clone)]
@@ -36,6 +183,12 @@ pub struct HotSetConfig {
     pub size: usize,
     /// The max amount of jitter we may add to each story's score when fetching the hot set
     pub jitter: f32,
+    /// How we order stories with effectively equal scores
+    #[serde(default)]
+    pub tie_break: TieBreakConfig,
+    /// How many top stories are pushed to live front page subscribers when they change (zero disables live updates)
+    #[serde(default)]
+    pub live_count: usize,
 }
 
 pub struct Index<S: StorageWriter> {
@@ -45,6 +198,7 @@ pub struct Index<S: StorageWriter> {
     eval: Shared<StoryEvaluator>,
     blog: Shared<Vec<BlogPost>>,
     config: Shared<IndexConfig>,
+    front_page_events: broadcast::Sender<Vec<Story<Shard>>>,
 }
 
 impl<S: StorageWriter> Clone for Index<S> {
@@ -56,6 +210,7 @@ impl<S: StorageWriter> Clone for Index<S> {
             eval: self.eval.clone(),
             blog: self.blog.clone(),
             config: self.config.clone(),
+            front_page_events: self.front_page_events.clone(),
         }
     }
 }
@@ -93,17 +248,24 @@ impl Index<StoryIndex> {
         blog: Shared<Vec<BlogPost>>,
         config: Shared<IndexConfig>,
     ) -> Result<Index<StoryIndex>, WebError> {
-        let index = StoryIndex::new(PersistLocation::Path(path.as_ref().to_owned()))?;
+        let mut index = StoryIndex::new(PersistLocation::Path(path.as_ref().to_owned()))?;
+        config.read().apply(&mut index);
+        index.check_url_normalization(&config.read().url_normalization)?;
+        // Rebuild any shards left behind by a schema change before we serve from them
+        index.migrate_shards(&eval.read())?;
+        index.warm(config.read().max_shards.front_page)?;
         Ok(Index {
             storage: SharedMut::new(index),
             hot_set: SharedMut::new(HotSet {
                 stories: vec![],
                 top_tags: vec![],
+                refreshed: false,
             }),
             pinned_story: SharedMut::new(None),
             blog,
             eval,
             config,
+            front_page_events: broadcast::channel(FRONT_PAGE_EVENT_CAPACITY).0,
         })
     }
 
@@ -112,12 +274,12 @@ impl Index<StoryIndex> {
         let scorer = &self.eval.read().scorer;
         let lock = self.pinned_story.read();
         let pinned = (*lock).as_ref();
-        let (mut pinned, mut stories) = stories
+        let (mut pinned, stories) = stories
             .into_iter()
             .partition::<Vec<Story<Shard>>, _>(|s| Some(&s.url) == pinned);
         pinned.truncate(1);
-        stories
-            .sort_by_cached_key(|x| ((x.score + scorer.score_age(now - x.date)) * -1000.0) as i32);
+        let tie_break = &self.config.read().hot_set.tie_break;
+        let mut stories = tie_break.sort(stories, |x| x.score + scorer.score_age(now - x.date));
 
         // Count each item
         let mut tag_counts = HashMap::new();
@@ -148,6 +310,7 @@ impl Index<StoryIndex> {
         HotSet {
             stories: pinned,
             top_tags,
+            refreshed: true,
         }
     }
 
@@ -163,9 +326,9 @@ impl Index<StoryIndex> {
         let results = storage.with_scrapes(|scrapes| backup.backup_range(scrapes, shard_range));
         for (shard, result) in &results {
             match result {
-                Ok(res) => tracing::info!("Backed up shard {}: {:?}", shard.to_string(), res),
+                Ok(res) => tracing::info!("Backed up shard {shard}: {res:?}"),
                 Err(e) => {
-                    tracing::error!("Backed up shard {}: FAILED {:?}", shard.to_string(), e)
+                    tracing::error!("Backed up shard {shard}: FAILED {e:?}")
                 }
             }
         }
@@ -177,18 +340,66 @@ impl Index<StoryIndex> {
 
         // Fetch
         let max = self.config.read().hot_set.size;
+        let scorer = self.eval.read().scorer.clone();
         let v = async_run!(self.storage, |storage: &StoryIndex| {
-            storage.fetch::<Shard>(&StoryQuery::FrontPage, max)
+            storage.fetch_front_page_with_floor(&scorer, max)
         })?;
 
         // TODO: We should only add this if it doesn't exist
         // for pinned in self.pinned_story.read().iter() {
         //     v.append(&mut self.fetch(StoryQuery::UrlSearch(pinned.clone()), 1).await?);
         // }
-        *self.hot_set.write() = self.compute_hot_set(v, now);
+        let hot_set = self.compute_hot_set(v, now);
+
+        // Notify live subscribers if the top of the front page changed
+        let live_count = self.config.read().hot_set.live_count;
+        let changed = live_count > 0
+            && !self
+                .hot_set
+                .read()
+                .stories
+                .iter()
+                .take(live_count)
+                .map(|story| &story.id)
+                .eq(hot_set
+                    .stories
+                    .iter()
+                    .take(live_count)
+                    .map(|story| &story.id));
+        let top = changed.then(|| {
+            hot_set
+                .stories
+                .iter()
+                .take(live_count)
+                .cloned()
+                .collect_vec()
+        });
+        *self.hot_set.write() = hot_set;
+        if let Some(top) = top {
+            // An error here only means that nobody is listening
+            _ = self.front_page_events.send(top);
+        }
         Ok(())
     }
 
+    /// Subscribe to changes in the top stories of the front page, or `None` if live updates are disabled.
+    pub fn subscribe_front_page(&self) -> Option<broadcast::Receiver<Vec<Story<Shard>>>> {
+        if self.config.read().hot_set.live_count > 0 {
+            Some(self.front_page_events.subscribe())
+        } else {
+            None
+        }
+    }
+
+    /// Render stories previously received from [`Index::subscribe_front_page`].
+    pub fn render_stories<S: From<StoryRender>>(
+        &self,
+        host: &HostParams,
+        stories: &[Story<Shard>],
+    ) -> Vec<S> {
+        self.filter_and_render(host, stories.iter(), 0, stories.len())
+    }
+
     /// Borrows the hot set
     fn with_hot_set<T>(
         &self,
@@ -216,6 +427,22 @@ impl Index<StoryIndex> {
         Ok(res)
     }
 
+    /// Re-score the hot set without re-tagging it, for when only the score configuration has changed.
+    pub async fn rescore_hot_set(&self) -> Result<Vec<ScrapePersistResult>, PersistError> {
+        let story_ids = self.with_hot_set(|hot_set| {
+            Ok(hot_set.iter().map(|story| story.id.clone()).collect_vec())
+        })?;
+
+        let eval_clone = self.eval.clone();
+        let res = async_run_write!(self.storage, |storage: &mut StoryIndex| {
+            storage.rescore_stories(&eval_clone.read(), story_ids)
+        })?;
+
+        self.refresh_hot_set().await?;
+
+        Ok(res)
+    }
+
     fn filter_and_render<'a, S: From<StoryRender>>(
         &self,
         host: &HostParams,
@@ -285,9 +512,24 @@ impl Index<StoryIndex> {
         query: StoryQuery,
         offset: usize,
         count: usize,
+    ) -> Result<Vec<S>, PersistError> {
+        self.stories_filtered(host, query, &StoryFilter::default(), offset, count)
+            .await
+    }
+
+    /// Like [`Index::stories`], but only includes stories matching `filter`.
+    pub async fn stories_filtered<S: From<StoryRender>>(
+        &self,
+        host: &HostParams,
+        query: StoryQuery,
+        filter: &StoryFilter,
+        offset: usize,
+        count: usize,
     ) -> Result<Vec<S>, PersistError> {
         let stories = if let StoryQuery::FrontPage = query {
-            self.filter_and_render(host, self.hot_set.read().stories.iter(), offset, count)
+            let hot_set = self.hot_set.read();
+            let stories = hot_set.stories.iter().filter(|story| filter.matches(story));
+            self.filter_and_render(host, stories, offset, count)
         } else {
             let start = Instant::now();
             let (query_log, query_text) = if tracing::enabled!(Level::INFO) {
@@ -305,12 +547,126 @@ impl Index<StoryIndex> {
                 query_text.unwrap_or_default(),
                 query_log.unwrap_or_default()
             );
-            self.filter_and_render(host, stories.iter(), offset, count)
+            let stories = stories.iter().filter(|story| filter.matches(story));
+            self.filter_and_render(host, stories, offset, count)
         };
 
         Ok(stories)
     }
 
+    /// Fetch a page of the stories dated within `[start, end)`, newest first. The page size is clamped to the
+    /// configured `max_count`, but the offset is not, so the whole range can be paged through.
+    pub async fn archive_stories<S: From<StoryRender>>(
+        &self,
+        host: &HostParams,
+        start: StoryDate,
+        end: StoryDate,
+        offset: usize,
+        count: usize,
+    ) -> Result<Vec<S>, PersistError> {
+        let count = self.config.read().max_count.min(count);
+        let stories = async_run!(self.storage, |storage: &StoryIndex| {
+            storage.fetch_date_range_page(start, end, offset, count)
+        })?;
+        Ok(self.filter_and_render(host, stories.iter(), 0, count))
+    }
+
+    /// The total number of stories matching the query. For the front page, this is the size of the hot set.
+    pub async fn search_count_total(&self, query: &StoryQuery) -> Result<usize, PersistError> {
+        if let StoryQuery::FrontPage = query {
+            return Ok(self.hot_set.read().stories.len());
+        }
+        Ok(self.stories_by_shard(query.clone()).await?.total)
+    }
+
+    /// Fetch a page of stories matching `filter`, along with the total number of matching stories and whether that
+    /// total is exact. When a search is filtered, only the first `max_count` search results are considered, so if
+    /// that many were found the total only counts the matches among them and is a lower bound.
+    pub async fn stories_page<S: From<StoryRender>>(
+        &self,
+        host: &HostParams,
+        query: StoryQuery,
+        filter: &StoryFilter,
+        offset: usize,
+        count: usize,
+    ) -> Result<(Vec<S>, usize, bool), PersistError> {
+        let in_range = |story: &&Story<Shard>| filter.matches(story);
+        if let StoryQuery::FrontPage = query {
+            let hot_set = self.hot_set.read();
+            let stories = hot_set.stories.iter().filter(in_range).collect_vec();
+            let total = stories.len();
+            return Ok((
+                self.filter_and_render(host, stories.into_iter(), offset, count),
+                total,
+                true,
+            ));
+        }
+
+        if filter.is_empty() {
+            let total = self.search_count_total(&query).await?;
+            let stories = self
+                .fetch::<Shard>(query, offset.saturating_add(count))
+                .await?;
+            Ok((
+                self.filter_and_render(host, stories.iter(), offset, count),
+                total,
+                true,
+            ))
+        } else {
+            let stories = self.fetch::<Shard>(query, usize::MAX).await?;
+            let exact = stories.len() < self.config.read().max_count;
+            let stories = stories.iter().filter(in_range).collect_vec();
+            let total = stories.len();
+            Ok((
+                self.filter_and_render(host, stories.into_iter(), offset, count),
+                total,
+                exact,
+            ))
+        }
+    }
+
+    /// Fetch a page of stories matching `filter`, newest first, resuming after `cursor` if one is given. Also
+    /// returns the cursor for the next page, or `None` if there are no more stories. A filter that matches few
+    /// stories could otherwise scan the whole index, so at most [`MAX_CURSOR_SCAN_PAGES`] pages are scanned and the
+    /// page may come back short (or empty) with a cursor to continue from.
+    pub async fn stories_after<S: From<StoryRender>>(
+        &self,
+        host: &HostParams,
+        query: StoryQuery,
+        filter: &StoryFilter,
+        mut cursor: Option<StoryCursor>,
+        count: usize,
+    ) -> Result<(Vec<S>, Option<StoryCursor>), PersistError> {
+        let count = self.config.read().max_count.min(count).max(1);
+        let mut stories = vec![];
+        for _ in 0..MAX_CURSOR_SCAN_PAGES {
+            let query = query.clone();
+            let page = async_run!(self.storage, |storage: &StoryIndex| {
+                storage.fetch_after(&query, cursor.as_ref(), count)
+            })?;
+            let exhausted = page.len() < count;
+            for story in page {
+                cursor = Some(StoryCursor::after(&story));
+                if filter.matches(&story) {
+                    stories.push(story);
+                    if stories.len() == count {
+                        return Ok((
+                            self.filter_and_render(host, stories.iter(), 0, count),
+                            cursor,
+                        ));
+                    }
+                }
+            }
+            if exhausted {
+                return Ok((self.filter_and_render(host, stories.iter(), 0, count), None));
+            }
+        }
+        Ok((
+            self.filter_and_render(host, stories.iter(), 0, count),
+            cursor,
+        ))
+    }
+
     pub fn top_tags(&self, limit: usize) -> Result<Vec<(String, usize)>, PersistError> {
         let top_tags = &self.hot_set.read().top_tags;
         let tagger = &self.eval.read().tagger;
@@ -321,6 +677,25 @@ impl Index<StoryIndex> {
             .collect_vec())
     }
 
+    /// The tags that most often appear alongside stories matching `query`, most frequent first.
+    pub async fn related_tags(
+        &self,
+        query: StoryQuery,
+        limit: usize,
+    ) -> Result<Vec<(String, usize)>, PersistError> {
+        let related = async_run!(self.storage, |storage: &StoryIndex| {
+            storage.related_tags(&query, limit)
+        })?;
+        let tagger = &self.eval.read().tagger;
+        Ok(related
+            .into_iter()
+            .map(|(s, count)| (tagger.make_display_tag(&s), count))
+            .collect_vec())
+    }
+
+    /// Insert scrapes from any task. Each call holds the exclusive storage lock for its whole batch, so concurrent
+    /// callers (cron, blog updates, submissions) are serialized and only one of them ever owns the shard writers.
+    /// Reads wait for an in-progress insert to finish rather than seeing a partially-written batch.
     pub async fn insert_scrapes<I: IntoIterator<Item = TypedScrape> + Send + 'static>(
         &self,
         scrapes: I,
@@ -331,12 +706,56 @@ impl Index<StoryIndex> {
         })
     }
 
+    /// Predict what inserting these scrapes would do, without inserting them.
+    pub async fn evaluate_scrapes<I: IntoIterator<Item = TypedScrape> + Send + 'static>(
+        &self,
+        scrapes: I,
+    ) -> Result<Vec<ScrapePersistResult>, PersistError> {
+        async_run!(self.storage, move |storage: &StoryIndex| {
+            storage.evaluate_scrapes(scrapes)
+        })
+    }
+
+    /// Merge one story into another by id, then refresh the hot set so the removed story drops out of it.
+    pub async fn merge_stories(
+        &self,
+        keep: StoryIdentifier,
+        merge: StoryIdentifier,
+    ) -> Result<ScrapePersistResult, PersistError> {
+        let eval = self.eval.clone();
+        let res = async_run_write!(self.storage, move |storage: &mut StoryIndex| {
+            storage.merge_stories(&eval.read(), &keep, &merge)
+        })?;
+        self.refresh_hot_set().await?;
+        Ok(res)
+    }
+
     pub async fn most_recent_story(&self) -> Result<StoryDate, PersistError> {
         async_run!(self.storage, |storage: &StoryIndex| {
             storage.most_recent_story()
         })
     }
 
+    pub async fn health(&self) -> Result<IndexHealth, PersistError> {
+        let hot_set_refreshed = self.hot_set.read().refreshed;
+        async_run!(self.storage, |storage: &StoryIndex| {
+            let shards = storage.shard_range()?;
+            Ok(IndexHealth {
+                most_recent_story: storage.most_recent_story()?,
+                story_count: storage.total_story_count()?,
+                oldest_shard: shards
+                    .iterate(ShardOrder::OldestFirst)
+                    .next()
+                    .map(|shard| shard.to_string()),
+                newest_shard: shards
+                    .iterate(ShardOrder::NewestFirst)
+                    .next()
+                    .map(|shard| shard.to_string()),
+                hot_set_refreshed,
+            })
+        })
+    }
+
     pub async fn story_count(&self) -> Result<StorageSummary, PersistError> {
         async_run!(self.storage, |storage: &StoryIndex| {
             storage.story_count()
@@ -370,12 +789,33 @@ impl Index<StoryIndex> {
         })
     }
 
-    pub async fn fetch_detail_one(
+    pub async fn fetch_many_by_id<S: StoryScrapePayload + 'static>(
+        &self,
+        ids: Vec<StoryIdentifier>,
+    ) -> Result<HashMap<StoryIdentifier, Story<S>>, PersistError>
+    where
+        StoryIndex: StorageFetch<S>,
+    {
+        async_run!(self.storage, |storage: &StoryIndex| {
+            storage.fetch_many_by_id::<S>(&ids)
+        })
+    }
+
+    pub async fn fetch_one_with_doc(
+        &self,
+        id: StoryIdentifier,
+    ) -> Result<Option<(Story<TypedScrape>, HashMap<String, Vec<String>>)>, PersistError> {
+        async_run!(self.storage, |storage: &StoryIndex| {
+            storage.fetch_one_with_doc(&StoryQuery::ById(id))
+        })
+    }
+
+    pub async fn fetch_score_snapshots(
         &self,
         id: StoryIdentifier,
-    ) -> Result<Option<HashMap<String, Vec<String>>>, PersistError> {
+    ) -> Result<Vec<ScoreSnapshot>, PersistError> {
         async_run!(self.storage, |storage: &StoryIndex| {
-            storage.fetch_detail_one(&StoryQuery::ById(id))
+            storage.fetch_score_snapshots(&id)
         })
     }
 }
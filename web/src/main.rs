@@ -1,17 +1,19 @@
 use std::collections::{HashMap, HashSet};
 use std::fs::File;
-use std::io::BufReader;
+use std::io::{BufWriter, Write};
 use std::net::SocketAddr;
 use std::path::PathBuf;
 use std::time::Instant;
 
 use clap::{Parser, Subcommand};
-use config::Config;
 use index::{HotSetConfig, IndexConfig};
+use itertools::Itertools;
 use keepcalm::Shared;
 use progscrape_application::{
-    MemIndex, PersistLocation, Storage, StorageWriter, StoryEvaluator, StoryIndex,
+    BackerUpper, MemIndex, PersistError, PersistLocation, ShardOrder, Storage, StorageForEach,
+    StorageWriter, Story, StoryEvaluator, StoryIndex,
 };
+use progscrape_scrapers::{feed, StoryDate, TypedScrape};
 use tracing_subscriber::filter::LevelFilter;
 use tracing_subscriber::EnvFilter;
 use web::WebError;
@@ -21,12 +23,16 @@ use crate::index::Index;
 use crate::resource::Resources;
 
 mod auth;
+mod circuit_breaker;
 mod config;
 mod cron;
+mod fetch;
 mod filters;
 mod index;
+mod query;
 mod rate_limits;
 mod resource;
+mod schema;
 mod serve_static_files;
 mod smoketest;
 mod static_files;
@@ -87,6 +93,13 @@ pub enum Command {
         )]
         fixed_auth_value: Option<String>,
 
+        #[arg(
+            long,
+            value_name = "USER=ROLE",
+            help = "Allowed user and role (admin or read-only) when using --auth-header (may be repeated)"
+        )]
+        auth_user: Vec<String>,
+
         #[arg(
             long,
             value_name = "HEADER",
@@ -115,6 +128,67 @@ pub enum Command {
 
         input: Vec<PathBuf>,
     },
+    Archive {
+        #[arg(long, value_name = "DIR", value_hint = clap::ValueHint::DirPath, help = "Persistence path")]
+        persist_path: PathBuf,
+
+        #[arg(long, value_name = "DIR", value_hint = clap::ValueHint::DirPath, help = "Back up shards here before archiving them")]
+        backup_path: Option<PathBuf>,
+
+        #[arg(
+            long,
+            value_name = "MONTHS",
+            help = "Number of months of shards to keep, including the current month"
+        )]
+        keep_months: u32,
+    },
+    Migrate {
+        #[arg(long, value_name = "DIR", value_hint = clap::ValueHint::DirPath, help = "Persistence path")]
+        persist_path: PathBuf,
+
+        #[arg(long, value_name = "DIR", value_hint = clap::ValueHint::DirPath, help = "Root path")]
+        root: Option<PathBuf>,
+    },
+    Reindex {
+        #[arg(long, value_name = "DIR", value_hint = clap::ValueHint::DirPath, help = "Persistence path")]
+        persist_path: PathBuf,
+
+        #[arg(long, value_name = "DIR", value_hint = clap::ValueHint::DirPath, help = "New persistence path, which must not exist")]
+        output_path: PathBuf,
+
+        #[arg(long, value_name = "DIR", value_hint = clap::ValueHint::DirPath, help = "Root path")]
+        root: Option<PathBuf>,
+    },
+    Export {
+        #[arg(long, value_name = "DIR", value_hint = clap::ValueHint::DirPath, help = "Persistence path")]
+        persist_path: PathBuf,
+
+        #[arg(long, value_name = "FILE", value_hint = clap::ValueHint::FilePath, help = "Output file, in the format read by load and initialize")]
+        output: PathBuf,
+    },
+    Query {
+        #[arg(long, value_name = "DIR", value_hint = clap::ValueHint::DirPath, help = "Persistence path")]
+        persist_path: PathBuf,
+
+        #[arg(long, value_name = "DIR", value_hint = clap::ValueHint::DirPath, help = "Root path")]
+        root: Option<PathBuf>,
+
+        #[arg(
+            long,
+            default_value_t = 30,
+            help = "Maximum number of stories to print"
+        )]
+        count: usize,
+
+        search: String,
+    },
+    ImportOpml {
+        #[arg(long, value_name = "DIR", value_hint = clap::ValueHint::DirPath, help = "Root path")]
+        root: Option<PathBuf>,
+
+        #[arg(value_name = "FILE", value_hint = clap::ValueHint::FilePath, help = "OPML file exported from an RSS reader")]
+        input: PathBuf,
+    },
 }
 
 /// Our entry point.
@@ -159,15 +233,33 @@ async fn go() -> Result<(), WebError> {
             tracing::info!("Root path: {}", root_path.to_string_lossy());
             let resource_path = root_path.join("resource");
             let resources = Resources::get_resources(resource_path)?;
+            let url_normalization = resources.config.read().index.url_normalization.clone();
+            url_normalization.apply()?;
             let index = Index::initialize_with_persistence(
                 persist_path,
                 resources.story_evaluator.clone(),
                 resources.blog_posts.clone(),
                 Shared::new(IndexConfig {
                     max_count: 300,
+                    max_shards: Default::default(),
+                    score_snapshots: None,
+                    empty_titles: Default::default(),
+                    blocklist: Default::default(),
+                    exclude_penalized_hosts: false,
+                    exclude_self_posts: false,
+                    skip_degraded_shards: false,
+                    search_ranking: Default::default(),
+                    domain_search: Default::default(),
+                    fuzzy_search: Default::default(),
+                    url_changes: Default::default(),
+                    date_outlier_days: None,
+                    future_date_tolerance_hours: None,
+                    url_normalization,
                     hot_set: HotSetConfig {
                         size: 500,
                         jitter: 0.0,
+                        tie_break: Default::default(),
+                        live_count: 0,
                     },
                 }),
             )?;
@@ -178,6 +270,7 @@ async fn go() -> Result<(), WebError> {
             persist_path,
             auth_header,
             fixed_auth_value,
+            auth_user,
             metrics_auth_bearer_token,
             listen_port,
             backup_path,
@@ -188,6 +281,7 @@ async fn go() -> Result<(), WebError> {
             let resource_path = root_path.join("resource");
 
             let resources = Resources::start_watcher(resource_path).await?;
+            resources.config.read().index.url_normalization.apply()?;
 
             let persist_path = persist_path
                 .unwrap_or("target/index".into())
@@ -204,6 +298,15 @@ async fn go() -> Result<(), WebError> {
                 .unwrap_or(SocketAddr::from(([127, 0, 0, 1], 3000)));
 
             let auth = match (auth_header, fixed_auth_value) {
+                (Some(auth_header), None) if !auth_user.is_empty() => Auth::FromHeaderWithUsers(
+                    auth_header,
+                    Auth::parse_users(&auth_user).map_err(WebError::ArgumentsInvalid)?,
+                ),
+                (_, _) if !auth_user.is_empty() => {
+                    return Err(WebError::ArgumentsInvalid(
+                        "--auth-user requires --auth-header".into(),
+                    ));
+                }
                 (Some(auth_header), None) => Auth::FromHeader(auth_header),
                 (None, Some(fixed_auth_value)) => Auth::Fixed(fixed_auth_value),
                 (None, None) => Auth::None,
@@ -236,8 +339,8 @@ async fn go() -> Result<(), WebError> {
             };
             std::fs::create_dir_all(&persist_path)?;
             let resource_path = root.unwrap_or(".".into()).canonicalize()?.join("resource");
-            let reader = BufReader::new(File::open(resource_path.join("config/config.json"))?);
-            let config: Config = serde_json::from_reader(reader)?;
+            let config = resource::create_config(&resource_path)?;
+            config.index.url_normalization.apply()?;
             let eval = StoryEvaluator::new(&config.tagger, &config.score, &config.scrape);
 
             let start = Instant::now();
@@ -253,13 +356,23 @@ async fn go() -> Result<(), WebError> {
                 tracing::info!("Importing from {}...", input.to_string_lossy());
                 let scrapes = progscrape_scrapers::import_backup(&input)?;
                 memindex.insert_scrapes(scrapes)?;
+                tracing::info!("{} stories in memory", memindex.len());
             }
             let memindex_time = memindex_start.elapsed();
 
             // Now, import those stories
             let story_start = Instant::now();
             let mut index = StoryIndex::new(PersistLocation::Path(persist_path))?;
-            index.insert_scrape_collections(&eval, memindex.get_all_stories())?;
+            config.index.apply(&mut index);
+            index.check_url_normalization(&config.index.url_normalization)?;
+            let total = memindex.len();
+            let stories = memindex.get_all_stories().enumerate().map(|(i, story)| {
+                if i > 0 && i % 10000 == 0 {
+                    tracing::info!("Imported {i} of {total} stories");
+                }
+                story
+            });
+            index.insert_scrape_collections(&eval, stories)?;
             let story_index_time = story_start.elapsed();
 
             let count = index.story_count()?;
@@ -283,10 +396,13 @@ async fn go() -> Result<(), WebError> {
             year,
         } => {
             let resource_path = root.unwrap_or(".".into()).canonicalize()?.join("resource");
-            let reader = BufReader::new(File::open(resource_path.join("config/config.json"))?);
-            let config: Config = serde_json::from_reader(reader)?;
+            let config = resource::create_config(&resource_path)?;
+            config.index.url_normalization.apply()?;
             let eval = StoryEvaluator::new(&config.tagger, &config.score, &config.scrape);
             let mut index = StoryIndex::new(PersistLocation::Path(persist_path))?;
+            config.index.apply(&mut index);
+            index.check_url_normalization(&config.index.url_normalization)?;
+            index.migrate_shards(&eval)?;
             let years: HashSet<usize> = HashSet::from_iter(year);
 
             for input in input {
@@ -308,6 +424,131 @@ async fn go() -> Result<(), WebError> {
                 tracing::info!("Results: total={} {:?}", res.len(), result_count);
             }
         }
+        Command::Archive {
+            persist_path,
+            backup_path,
+            keep_months,
+        } => {
+            let before = StoryDate::now()
+                .checked_sub_months(keep_months.saturating_sub(1))
+                .ok_or_else(|| {
+                    WebError::ArgumentsInvalid(format!("Invalid --keep-months {keep_months}"))
+                })?;
+            let backup = backup_path.map(BackerUpper::new);
+            let mut index = StoryIndex::new(PersistLocation::Path(persist_path))?;
+            let archived = index.archive_shards_before(before, backup.as_ref())?;
+            tracing::info!("Archived {} shard(s): {archived:?}", archived.len());
+        }
+        Command::Migrate { persist_path, root } => {
+            let resource_path = root.unwrap_or(".".into()).canonicalize()?.join("resource");
+            let config = resource::create_config(&resource_path)?;
+            config.index.url_normalization.apply()?;
+            let eval = StoryEvaluator::new(&config.tagger, &config.score, &config.scrape);
+            let mut index = StoryIndex::new(PersistLocation::Path(persist_path))?;
+            config.index.apply(&mut index);
+            index.check_url_normalization(&config.index.url_normalization)?;
+            let migrated = index.migrate_shards(&eval)?;
+            tracing::info!("Migrated {} shard(s): {migrated:?}", migrated.len());
+        }
+        Command::Reindex {
+            persist_path,
+            output_path,
+            root,
+        } => {
+            if output_path.exists() {
+                return Err(WebError::ArgumentsInvalid(format!(
+                    "Path {} must not exist",
+                    output_path.to_string_lossy()
+                )));
+            };
+            let resource_path = root.unwrap_or(".".into()).canonicalize()?.join("resource");
+            let config = resource::create_config(&resource_path)?;
+            config.index.url_normalization.apply()?;
+            let eval = StoryEvaluator::new(&config.tagger, &config.score, &config.scrape);
+            let mut index = StoryIndex::new(PersistLocation::Path(persist_path))?;
+            config.index.apply(&mut index);
+            index.migrate_shards(&eval)?;
+            std::fs::create_dir_all(&output_path)?;
+            let mut output = StoryIndex::new(PersistLocation::Path(output_path))?;
+            config.index.apply(&mut output);
+            output.check_url_normalization(&config.index.url_normalization)?;
+            let total = index.reindex_into(&eval, &mut output)?;
+            tracing::info!("Reindexed {total} scrape(s)");
+        }
+        Command::Export {
+            persist_path,
+            output,
+        } => {
+            if !persist_path.exists() {
+                return Err(WebError::ArgumentsInvalid(format!(
+                    "Path {} does not exist",
+                    persist_path.to_string_lossy()
+                )));
+            };
+            let index = StoryIndex::new(PersistLocation::Path(persist_path))?;
+            let mut w = BufWriter::new(File::create(&output)?);
+            let mut total = 0;
+            for shard in index.shards().iterate(ShardOrder::OldestFirst) {
+                // Stream each story's scrapes out so that large shards aren't loaded all at once
+                let mut count = 0;
+                index.for_each_story_in_shard(shard, |story: Story<TypedScrape>| {
+                    let scrapes = story.scrapes.iter().sorted_by_key(|(id, _)| *id);
+                    count += progscrape_scrapers::write_backup(&mut w, scrapes.map(|(_, s)| s))
+                        .map_err(|e| PersistError::UnexpectedError(e.to_string()))?;
+                    Ok(())
+                })?;
+                tracing::info!("Exported {count} scrape(s) from {shard}");
+                total += count;
+            }
+            w.flush()?;
+            tracing::info!("Exported {total} scrape(s) to {}", output.to_string_lossy());
+        }
+        Command::Query {
+            persist_path,
+            root,
+            count,
+            search,
+        } => {
+            if !persist_path.exists() {
+                return Err(WebError::ArgumentsInvalid(format!(
+                    "Path {} does not exist",
+                    persist_path.to_string_lossy()
+                )));
+            };
+            let resource_path = root.unwrap_or(".".into()).canonicalize()?.join("resource");
+            let config = resource::create_config(&resource_path)?;
+            config.index.url_normalization.apply()?;
+            let eval = StoryEvaluator::new(&config.tagger, &config.score, &config.scrape);
+            let mut index = StoryIndex::new(PersistLocation::Path(persist_path))?;
+            config.index.apply(&mut index);
+            index.check_url_normalization(&config.index.url_normalization)?;
+            // Migrating rewrites shards in place, which a read-only query mustn't do while a server may be running
+            let shards = index.shards_needing_migration();
+            if !shards.is_empty() {
+                return Err(WebError::ArgumentsInvalid(format!(
+                    "Shards {} must be migrated to the current schema first: run `serve` or `load` on this index",
+                    shards.iter().join(", ")
+                )));
+            }
+            let found =
+                query::query_stories(&index, &eval, &search, count, &mut std::io::stdout())?;
+            tracing::info!("Found {found} stories");
+        }
+        Command::ImportOpml { root, input } => {
+            let resource_path = root.unwrap_or(".".into()).canonicalize()?.join("resource");
+            let mut config = resource::create_config(&resource_path)?;
+            config.index.url_normalization.apply()?;
+            let feeds = feed::parse_opml(&std::fs::read_to_string(&input)?)?;
+            let total = feeds.len();
+            let added = config.scrape.feed.merge_feeds(feeds);
+            tracing::info!(
+                "Added {added} of {total} feed(s) from {}",
+                input.to_string_lossy()
+            );
+            // Print the merged feed configuration to be pasted into config.json
+            serde_json::to_writer_pretty(std::io::stdout(), &config.scrape.feed)?;
+            println!();
+        }
     };
     Ok(())
 }
@@ -0,0 +1,70 @@
+use std::io::Write;
+
+use itertools::Itertools;
+use progscrape_application::{Shard, Storage, StoryEvaluator, StoryIndex, StoryQuery};
+
+use crate::web::WebError;
+
+/// Run a search against the index the same way the web frontend parses it, and write the matching stories to
+/// `out`. Returns the number of stories written.
+pub fn query_stories(
+    index: &StoryIndex,
+    eval: &StoryEvaluator,
+    search: &str,
+    count: usize,
+    out: &mut impl Write,
+) -> Result<usize, WebError> {
+    let query = StoryQuery::from_search(&eval.tagger, search);
+    tracing::info!("Query: {query:?}");
+    let stories = if let StoryQuery::FrontPage = query {
+        index.fetch_front_page_with_floor(&eval.scorer, count)?
+    } else {
+        index.fetch::<Shard>(&query, count)?
+    };
+    for (order, story) in stories.iter().enumerate() {
+        let render = story.render(eval, order);
+        writeln!(out, "{:>8.2} {}", render.score, render.title)?;
+        writeln!(out, "         {}", render.url)?;
+        writeln!(out, "         [{}]", render.tags.iter().join(", "))?;
+    }
+    Ok(stories.len())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::resource::Resources;
+    use progscrape_application::{PersistLocation, StorageWriter};
+    use progscrape_scrapers::{hacker_news::HackerNewsStory, StoryDate, StoryUrl};
+
+    #[test]
+    fn test_query_stories() -> Result<(), Box<dyn std::error::Error>> {
+        let resources = Resources::get_resources("../resource/")?;
+        let eval = resources.story_evaluator.read();
+        let tempdir = tempfile::tempdir()?;
+        let mut index = StoryIndex::new(PersistLocation::Path(tempdir.path().to_owned()))?;
+        let date = StoryDate::year_month_day(2020, 1, 1).expect("Date failed");
+        let url = StoryUrl::parse("https://example.com/rust-story").expect("URL");
+        let other = StoryUrl::parse("https://example.com/other-story").expect("URL");
+        index.insert_scrapes(
+            &eval,
+            [
+                HackerNewsStory::new("1", date, "Why I love Rust", url, 10, 10, 1, String::new())
+                    .into(),
+                HackerNewsStory::new("2", date, "Gardening tips", other, 10, 10, 2, String::new())
+                    .into(),
+            ],
+        )?;
+
+        let mut out = vec![];
+        let count = query_stories(&index, &eval, "rust", 10, &mut out)?;
+        let out = String::from_utf8(out)?;
+        assert_eq!(count, 1, "{out}");
+        assert!(out.contains("Why I love Rust"), "{out}");
+        assert!(out.contains("https://example.com/rust-story"), "{out}");
+        assert!(out.contains("[example.com, rust]"), "{out}");
+        assert!(!out.contains("Gardening"), "{out}");
+
+        Ok(())
+    }
+}
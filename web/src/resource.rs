@@ -10,13 +10,14 @@ use std::fs::File;
 use std::io::BufReader;
 
 use keepcalm::{Shared, SharedMut};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 use tera::Tera;
 use tokio::sync::watch;
 
 use progscrape_application::StoryEvaluator;
 
+use crate::circuit_breaker::CircuitBreakers;
 use crate::config::Config;
 use crate::filters::*;
 use crate::rate_limits::RateLimits;
@@ -32,6 +33,7 @@ struct ResourceHolder {
     story_evaluator: StoryEvaluator,
     scrapers: Scrapers,
     rate_limits: RateLimits,
+    circuit_breakers: CircuitBreakers,
 }
 
 #[derive(Clone)]
@@ -44,6 +46,7 @@ pub struct Resources {
     pub story_evaluator: Shared<StoryEvaluator>,
     pub scrapers: Shared<Scrapers>,
     pub rate_limits: SharedMut<RateLimits>,
+    pub circuit_breakers: SharedMut<CircuitBreakers>,
 }
 
 impl Resources {
@@ -57,6 +60,7 @@ impl Resources {
             story_evaluator: r.shared_copy().project_fn(|x| &x.story_evaluator),
             scrapers: r.shared_copy().project_fn(|x| &x.scrapers),
             rate_limits: r.project_fn(|x| &x.rate_limits, |x| &mut x.rate_limits),
+            circuit_breakers: r.project_fn(|x| &x.circuit_breakers, |x| &mut x.circuit_breakers),
         }
     }
 }
@@ -153,6 +157,28 @@ fn blog_posts(resource_path: &Path) -> Result<Vec<BlogPost>, WebError> {
     Ok(posts)
 }
 
+/// Collects every file under `dir` as a `(path, template name)` pair, where the name is the path relative to
+/// `root` with `/` separators (matching the names `Tera::new` would give them).
+fn collect_template_files(
+    root: &Path,
+    dir: &Path,
+    files: &mut Vec<(PathBuf, Option<String>)>,
+) -> Result<(), WebError> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_template_files(root, &path, files)?;
+        } else if let Ok(relative) = path.strip_prefix(root) {
+            let name = relative
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .join("/");
+            files.push((path, Some(name)));
+        }
+    }
+    Ok(())
+}
+
 fn create_templates(
     resource_path: &Path,
     static_files: StaticFileRegistry,
@@ -164,6 +190,15 @@ fn create_templates(
             .borrow(),
     )?;
 
+    // Templates in the overrides directory shadow the base templates with the same name
+    let overrides = resource_path.join("templates-override");
+    if overrides.is_dir() {
+        let mut files = vec![];
+        collect_template_files(&overrides, &overrides, &mut files)?;
+        tracing::info!("Loading {} template override(s)", files.len());
+        tera.add_template_files(files)?;
+    }
+
     tera.register_filter("comma", CommaFilter::default());
 
     tera.register_filter("rfc_3339", RFC3339Filter::default());
@@ -196,9 +231,13 @@ fn create_admin_css(resource_path: &Path) -> Result<String, WebError> {
     Ok(out)
 }
 
-fn create_config(resource_path: &Path) -> Result<Config, WebError> {
+/// Load and validate `config/config.json` from the resource path.
+pub fn create_config(resource_path: &Path) -> Result<Config, WebError> {
     let reader = BufReader::new(File::open(resource_path.join("config/config.json"))?);
-    Ok(serde_json::from_reader(reader)?)
+    let config: Config = serde_json::from_reader(reader)?;
+    config.score.validate()?;
+    config.index.url_normalization.validate()?;
+    Ok(config)
 }
 
 fn generate<T: AsRef<Path>>(resource_path: T) -> Result<ResourceHolder, WebError> {
@@ -225,6 +264,7 @@ fn generate<T: AsRef<Path>>(resource_path: T) -> Result<ResourceHolder, WebError
     let scrapers = Scrapers::new(&config.scrape);
     let blog_posts = blog_posts(resource_path)?;
     let rate_limits = RateLimits::new(&config.rate_limits);
+    let circuit_breakers = CircuitBreakers::new(&config.cron.circuit_breaker);
     Ok(ResourceHolder {
         templates,
         static_files,
@@ -234,6 +274,7 @@ fn generate<T: AsRef<Path>>(resource_path: T) -> Result<ResourceHolder, WebError
         scrapers,
         blog_posts,
         rate_limits,
+        circuit_breakers,
     })
 }
 
@@ -284,3 +325,54 @@ impl Resources {
         Ok(Resources::new(r))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_template_overrides() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempfile::tempdir()?;
+        let templates = dir.path().join("templates");
+        std::fs::create_dir_all(templates.join("admin"))?;
+        std::fs::write(
+            templates.join("base.html"),
+            "<base>{% block content %}{% endblock %}</base>",
+        )?;
+        std::fs::write(
+            templates.join("index.html"),
+            r#"{% extends "base.html" %}{% block content %}base index{% endblock %}"#,
+        )?;
+        std::fs::write(templates.join("admin/status.html"), "base status")?;
+
+        // Without overrides, we get the base templates
+        let tera = create_templates(dir.path(), StaticFileRegistry::default())?;
+        let context = tera::Context::new();
+        assert_eq!(
+            tera.render("index.html", &context)?,
+            "<base>base index</base>"
+        );
+
+        let overrides = dir.path().join("templates-override");
+        std::fs::create_dir_all(overrides.join("admin"))?;
+        std::fs::write(
+            overrides.join("index.html"),
+            r#"{% extends "base.html" %}{% block content %}override index{% endblock %}"#,
+        )?;
+        std::fs::write(overrides.join("admin/status.html"), "override status")?;
+
+        let tera = create_templates(dir.path(), StaticFileRegistry::default())?;
+        assert_eq!(
+            tera.render("index.html", &context)?,
+            "<base>override index</base>"
+        );
+        assert_eq!(
+            tera.render("admin/status.html", &context)?,
+            "override status"
+        );
+        // Templates without an override are untouched
+        assert_eq!(tera.render("base.html", &context)?, "<base></base>");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,105 @@
+use schemars::{gen::SchemaSettings, JsonSchema};
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use progscrape_application::SearchSummary;
+
+use crate::story::FeedStory;
+
+/// The response for `/feed.json`.
+#[derive(Serialize, JsonSchema)]
+pub struct FeedResponse {
+    /// The format version, currently 1.
+    pub v: u32,
+    /// The most popular tags on the front page.
+    pub tags: Vec<String>,
+    pub stories: Vec<FeedStory>,
+    /// Only present when paging with `cursor`. Pass this back as `cursor` to get the next page, or stop if it is null.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<Option<String>>,
+}
+
+/// The response for `/zeitgeist.json`.
+#[derive(Serialize, JsonSchema)]
+pub struct ZeitgeistResponse {
+    /// The format version, currently 1.
+    pub v: u32,
+    pub stories: SearchSummary,
+}
+
+fn query_param(name: &str, description: &str, schema: Value) -> Value {
+    json!({
+        "name": name,
+        "in": "query",
+        "required": false,
+        "description": description,
+        "schema": schema,
+    })
+}
+
+fn json_response(description: &str, schema: Value) -> Value {
+    json!({
+        "200": {
+            "description": description,
+            "content": { "application/json": { "schema": schema } }
+        }
+    })
+}
+
+/// An OpenAPI 3 document describing the JSON feed endpoints. The response schemas are generated from the types
+/// that the endpoints serialize, so they can't drift.
+pub fn openapi_document() -> Value {
+    let mut gen = SchemaSettings::openapi3().into_generator();
+    let feed = gen.subschema_for::<FeedResponse>();
+    let zeitgeist = gen.subschema_for::<ZeitgeistResponse>();
+    let search = query_param(
+        "search",
+        "A search query, tag or domain to restrict the stories to.",
+        json!({ "type": "string" }),
+    );
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "progscrape",
+            "version": "1",
+            "description": "JSON feeds of the stories on progscrape.com.",
+        },
+        "paths": {
+            "/feed.json": {
+                "get": {
+                    "summary": "The front page, or the stories matching a search.",
+                    "parameters": [
+                        search,
+                        query_param(
+                            "count",
+                            "The number of stories to return (default 150).",
+                            json!({ "type": "integer", "minimum": 1 }),
+                        ),
+                        query_param(
+                            "min_sources",
+                            "Only return stories seen on at least this many sources.",
+                            json!({ "type": "integer", "minimum": 0 }),
+                        ),
+                        query_param(
+                            "cursor",
+                            "Page through stories newest first. Pass an empty cursor for the first page, then each response's `next_cursor`.",
+                            json!({ "type": "string" }),
+                        ),
+                    ],
+                    "responses": json_response("A page of stories.", json!(feed)),
+                }
+            },
+            "/zeitgeist.json": {
+                "get": {
+                    "summary": "The number of stories matching a search in each month.",
+                    "parameters": [search],
+                    "responses": json_response("Story counts by month.", json!(zeitgeist)),
+                }
+            },
+        },
+        "components": {
+            "schemas": gen.take_definitions(),
+        },
+    })
+}
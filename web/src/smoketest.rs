@@ -3,10 +3,12 @@ mod test {
     use std::cmp::Ordering;
 
     use axum::{http::HeaderValue, routing::IntoMakeService, Router};
-    use hyper::{header::CONTENT_TYPE, Method};
+    use hyper::{header::CONTENT_TYPE, Method, StatusCode};
     use keepcalm::Shared;
     use progscrape_application::StoryIndex;
-    use progscrape_scrapers::{hacker_news::HackerNewsStory, StoryUrl};
+    use progscrape_scrapers::{
+        hacker_news::HackerNewsStory, reddit::RedditStory, StoryDate, StoryUrl,
+    };
     use serde::Deserialize;
     use tower::Service;
     use tracing_subscriber::EnvFilter;
@@ -14,13 +16,13 @@ mod test {
     use crate::{
         index::{HotSetConfig, Index, IndexConfig},
         resource::Resources,
-        story::FeedStory,
+        story::{ApiStory, FeedStory},
         web::create_feeds,
     };
 
     fn create_request(
-        path: &'static str,
-        query: &'static str,
+        path: &str,
+        query: &str,
     ) -> Result<axum::extract::Request, Box<dyn std::error::Error>> {
         let uri = format!("http://localhost{}{}", path, query).parse()?;
         let mut req = axum::extract::Request::default();
@@ -54,21 +56,10 @@ mod test {
         Ok(body)
     }
 
-    #[derive(Deserialize)]
-    struct Feed {
-        v: i32,
-        tags: Vec<String>,
-        stories: Vec<FeedStory>,
-    }
-
-    /// A test that tests the whole stack: populating an index from scraped data, fetching the homepage,
-    /// and rendering various feeds (HTML, JSON, XML).
-    #[tokio::test]
-    async fn smoke_test() -> Result<(), Box<dyn std::error::Error>> {
-        tracing_subscriber::fmt()
-            .with_env_filter(EnvFilter::from_default_env())
-            .init();
-
+    /// Creates an index populated from the sample scrapes, along with one scrape we can use for testing search
+    /// (returning its date).
+    async fn populated_index(
+    ) -> Result<(Index<StoryIndex>, Resources, StoryDate), Box<dyn std::error::Error>> {
         // Load web resources and configuration
         let resources = Resources::get_resources("../resource/")?;
 
@@ -92,14 +83,49 @@ mod test {
             resources.blog_posts.clone(),
             Shared::new(IndexConfig {
                 max_count: 300,
+                max_shards: Default::default(),
+                score_snapshots: None,
+                empty_titles: Default::default(),
+                blocklist: Default::default(),
+                exclude_penalized_hosts: false,
+                exclude_self_posts: false,
+                skip_degraded_shards: false,
+                search_ranking: Default::default(),
+                domain_search: Default::default(),
+                fuzzy_search: Default::default(),
+                url_changes: Default::default(),
+                date_outlier_days: None,
+                future_date_tolerance_hours: None,
+                url_normalization: Default::default(),
                 hot_set: HotSetConfig {
                     size: 500,
                     jitter: 0.0,
+                    tie_break: Default::default(),
+                    live_count: 0,
                 },
             }),
         )?;
         index.insert_scrapes(scrapes).await?;
         index.refresh_hot_set().await?;
+        Ok((index, resources, date))
+    }
+
+    #[derive(Deserialize)]
+    struct Feed {
+        v: i32,
+        tags: Vec<String>,
+        stories: Vec<FeedStory>,
+    }
+
+    /// A test that tests the whole stack: populating an index from scraped data, fetching the homepage,
+    /// and rendering various feeds (HTML, JSON, XML).
+    #[tokio::test]
+    async fn smoke_test() -> Result<(), Box<dyn std::error::Error>> {
+        tracing_subscriber::fmt()
+            .with_env_filter(EnvFilter::from_default_env())
+            .init();
+
+        let (index, resources, _) = populated_index().await?;
 
         // Create a router that we can send mock requests to
         let router = create_feeds::<()>(index, resources);
@@ -125,6 +151,8 @@ mod test {
             ("?search=rust", Ordering::Greater, 2),
             ("?search=cobsteme", Ordering::Equal, 1),
             ("?search=Cobsteme", Ordering::Equal, 1),
+            // Unbalanced quotes fall back to a literal search
+            ("?search=%22rust", Ordering::Greater, 2),
         ] {
             // Test the front page
             let s = assert_response(&mut router, "/", query, "text/html; charset=utf-8").await?;
@@ -149,4 +177,169 @@ mod test {
 
         Ok(())
     }
+
+    #[derive(Deserialize)]
+    struct ApiStories {
+        stories: Vec<ApiStory>,
+        offset: usize,
+        limit: usize,
+        total: usize,
+        total_exact: bool,
+    }
+
+    /// Send a request to `/api/v1/stories`, returning the status and parsed body for successful requests.
+    async fn api_request(
+        router: &mut IntoMakeService<Router>,
+        query: &str,
+    ) -> Result<(StatusCode, Option<ApiStories>), Box<dyn std::error::Error>> {
+        let mut router = router.call(()).await?;
+        let resp = router
+            .call(create_request("/api/v1/stories", query)?)
+            .await?;
+        let status = resp.status();
+        if !status.is_success() {
+            return Ok((status, None));
+        }
+        let body = axum::body::to_bytes(resp.into_body(), 1_000_000)
+            .await
+            .expect("No body");
+        Ok((status, Some(serde_json::from_slice(&body)?)))
+    }
+
+    #[tokio::test]
+    async fn api_test() -> Result<(), Box<dyn std::error::Error>> {
+        let (index, resources, date) = populated_index().await?;
+        let router = create_feeds::<()>(index, resources);
+        let mut router = router.into_make_service();
+
+        // Dates are passed as YYYY-MM-DD (midnight UTC)
+        let day =
+            |date: Option<StoryDate>| date.expect("Invalid date").to_rfc3339()[..10].to_owned();
+        let day_before = day(date.checked_sub_days(1));
+        let day_after = day(date.checked_add_days(1));
+
+        // No parameters is the front page
+        let (_, res) = api_request(&mut router, "").await?;
+        let res = res.expect("Expected a response");
+        assert_eq!((res.offset, res.limit, res.stories.len()), (0, 30, 30));
+        assert!(res.total > 30);
+
+        // Tag search, paginated
+        let (_, res) = api_request(&mut router, "?tag=rust&offset=1&limit=2").await?;
+        let res = res.expect("Expected a response");
+        assert_eq!((res.offset, res.limit, res.stories.len()), (1, 2, 2));
+        assert!(res.total > 2);
+        let (_, all) = api_request(&mut router, "?tag=rust").await?;
+        let all = all.expect("Expected a response");
+        assert_eq!(all.total, res.total);
+        assert_eq!(all.stories[1].id, res.stories[0].id);
+
+        // Domain search
+        let (_, res) = api_request(&mut router, "?domain=buwheal.example.com").await?;
+        let res = res.expect("Expected a response");
+        assert_eq!(res.total, 1);
+        assert_eq!(res.stories[0].title, "Cobsteme whooperchia");
+
+        // Date ranges, alone and combined with searches
+        for (query, expected) in [
+            (format!("?domain=buwheal.example.com&after={day_before}"), 1),
+            (
+                format!("?domain=buwheal.example.com&before={day_before}"),
+                0,
+            ),
+            (format!("?domain=buwheal.example.com&after={day_after}"), 0),
+            (
+                format!("?q=cobsteme&after={day_before}&before={day_after}"),
+                1,
+            ),
+            (format!("?q=cobsteme&after={day_before}T00:00:00Z"), 1),
+            (format!("?tag=rust&after={day_after}"), 0),
+            ("?before=1970-01-02".to_owned(), 0),
+        ] {
+            let (_, res) = api_request(&mut router, &query).await?;
+            let res = res.expect("Expected a response");
+            assert_eq!(res.total, expected, "Unexpected total for {query}");
+            assert_eq!(res.stories.len(), expected, "Unexpected count for {query}");
+        }
+        let (_, res) = api_request(&mut router, "?tag=rust&after=1970-01-02").await?;
+        let res = res.expect("Expected a response");
+        assert!(res.total > 0);
+        assert!(res.total <= all.total);
+        assert!(res.total_exact);
+
+        // An offset past the end is just an empty page
+        let (_, res) =
+            api_request(&mut router, &format!("?tag=rust&offset={}", usize::MAX)).await?;
+        let res = res.expect("Expected a response");
+        assert!(res.stories.is_empty());
+        assert_eq!(res.total, all.total);
+
+        // Invalid parameter combinations
+        for query in [
+            "?tag=rust&domain=buwheal.example.com",
+            "?q=rust&tag=rust",
+            "?after=yesterday",
+            "?offset=-1",
+        ] {
+            let (status, _) = api_request(&mut router, query).await?;
+            assert_eq!(
+                status,
+                StatusCode::BAD_REQUEST,
+                "Expected error for {query}"
+            );
+        }
+
+        Ok(())
+    }
+
+    /// `min_sources` excludes single-source stories from the JSON feeds.
+    #[tokio::test]
+    async fn min_sources_test() -> Result<(), Box<dyn std::error::Error>> {
+        let (index, resources, date) = populated_index().await?;
+        // Make sure at least one story has been seen on two sources
+        index
+            .insert_scrapes([progscrape_scrapers::TypedScrape::Reddit(
+                RedditStory::new_with_defaults(
+                    "1",
+                    date,
+                    "Cobsteme whooperchia",
+                    StoryUrl::parse("https://buwheal.example.com/saskimplaid").expect("url"),
+                ),
+            )])
+            .await?;
+        index.refresh_hot_set().await?;
+        let router = create_feeds::<()>(index, resources);
+        let mut router = router.into_make_service();
+
+        let source_count = |story: &FeedStory| {
+            story
+                .comment_urls()
+                .values()
+                .filter(|s| s.is_some())
+                .count()
+        };
+        let s = assert_response(&mut router, "/feed.json", "", "application/json").await?;
+        let all: Feed = serde_json::from_str(&s)?;
+        assert!(all.stories.iter().any(|story| source_count(story) == 1));
+        let s = assert_response(
+            &mut router,
+            "/feed.json",
+            "?min_sources=2",
+            "application/json",
+        )
+        .await?;
+        let feed: Feed = serde_json::from_str(&s)?;
+        assert!(!feed.stories.is_empty());
+        assert!(feed.stories.iter().all(|story| source_count(story) >= 2));
+
+        let (_, all) = api_request(&mut router, "").await?;
+        let all = all.expect("Expected a response");
+        let (_, res) = api_request(&mut router, "?min_sources=2").await?;
+        let res = res.expect("Expected a response");
+        assert!(res.total > 0);
+        assert!(res.total < all.total);
+        assert!(res.stories.iter().all(|story| story.comments.len() >= 2));
+
+        Ok(())
+    }
 }
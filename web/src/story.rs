@@ -1,15 +1,21 @@
+use std::collections::BTreeMap;
+
 use progscrape_application::StoryRender;
 use progscrape_scrapers::{StoryDate, StoryUrl, TypedScrapeMap};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 /// The older-style feed.json story. This will be replaced by a more modern
 /// data model in the future.
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct FeedStory {
+    /// When the story was first seen, in RFC 3339 format.
     date: String,
+    /// The story's URL.
     href: String,
     title: String,
     tags: Vec<String>,
+    /// Comment page URLs for each source the story was seen on.
     #[serde(skip_serializing_if = "Option::is_none")]
     reddit: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -29,6 +35,7 @@ impl FeedStory {
             lobsters: self.lobsters.as_deref(),
             reddit: self.reddit.as_deref(),
             feed: None,
+            mastodon: None,
             other: None,
         }
     }
@@ -52,6 +59,46 @@ impl From<StoryRender> for FeedStory {
     }
 }
 
+/// A story as returned by the `/api/v1/` endpoints.
+#[derive(Serialize, Deserialize)]
+pub struct ApiStory {
+    pub id: String,
+    pub date: String,
+    pub url: String,
+    pub domain: String,
+    pub title: String,
+    pub score: f32,
+    pub tags: Vec<String>,
+    /// Has this story been seen again recently?
+    pub updated: bool,
+    /// Comment URLs, keyed by scrape source.
+    pub comments: BTreeMap<String, String>,
+}
+
+impl From<StoryRender> for ApiStory {
+    fn from(story: StoryRender) -> Self {
+        let comments = story
+            .sources
+            .iter()
+            .filter_map(|(source, id)| {
+                id.as_ref()
+                    .map(|id| (source.into_str().to_owned(), id.comments_url()))
+            })
+            .collect();
+        ApiStory {
+            id: story.id,
+            date: story.date.to_rfc3339(),
+            url: story.url,
+            domain: story.domain,
+            title: story.title,
+            score: story.score,
+            tags: story.tags,
+            updated: story.updated,
+            comments,
+        }
+    }
+}
+
 impl TryInto<StoryRender> for FeedStory {
     type Error = String;
     fn try_into(self) -> Result<StoryRender, Self::Error> {
@@ -67,8 +114,13 @@ impl TryInto<StoryRender> for FeedStory {
             }
         })?;
         let url = StoryUrl::parse(self.href).ok_or("Invalid url")?;
+        let date = StoryDate::parse_from_rfc3339(&self.date).ok_or("Invalid date")?;
+        let self_post = sources
+            .values()
+            .flatten()
+            .any(|id| id.is_likely_self_post(&url));
         Ok(StoryRender {
-            date: StoryDate::parse_from_rfc3339(&self.date).ok_or("Invalid date")?,
+            date,
             url: url.to_string(),
             title: self.title,
             tags: self.tags,
@@ -78,6 +130,12 @@ impl TryInto<StoryRender> for FeedStory {
             score: 0.0,
             html: "".to_owned(),
             sources,
+            subreddits: vec![],
+            last_activity: date,
+            ingested_at: None,
+            comments: Default::default(),
+            updated: false,
+            self_post,
         })
     }
 }
@@ -101,17 +159,24 @@ mod tests {
         sources.lobsters = Some(ScrapeId::new(ScrapeSource::Lobsters, None, "3".to_string()));
         sources.slashdot = Some(ScrapeId::new(ScrapeSource::Slashdot, None, "4".to_string()));
         let url = StoryUrl::parse("http://example.com").unwrap();
+        let date = StoryDate::year_month_day(2024, 1, 1).unwrap();
         let story = StoryRender {
             id: "".to_string(),
-            date: StoryDate::year_month_day(2024, 1, 1).unwrap(),
+            date,
             domain: "example.com".to_string(),
             order: 0,
             score: 0.0,
             sources,
+            subreddits: vec![],
             tags: vec!["a".to_string()],
             title: "Title".to_string(),
             url: url.to_string(),
             html: "".to_string(),
+            last_activity: date,
+            ingested_at: None,
+            comments: Default::default(),
+            updated: false,
+            self_post: false,
         };
 
         let feed_story: FeedStory = story.clone().into();
@@ -1,47 +1,58 @@
 use std::{
     collections::HashMap,
-    net::SocketAddr,
+    net::{IpAddr, SocketAddr},
     sync::OnceLock,
     time::{Duration, Instant},
 };
 
 use axum::{
+    async_trait,
     body::Body,
-    extract::{Host, OriginalUri, Path, Query, Request, State},
+    extract::{FromRef, FromRequestParts, Host, OriginalUri, Path, Query, Request, State},
     http::{HeaderName, HeaderValue},
     middleware::{self, Next},
-    response::{Html, IntoResponse, Redirect, Response},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        Html, IntoResponse, Redirect, Response,
+    },
     routing::{get, post},
     Extension, Json, Router,
 };
-use hyper::{header, HeaderMap, Method, StatusCode};
+use futures::{stream, Stream};
+use hyper::{header, http::request::Parts, HeaderMap, Method, StatusCode};
 use itertools::Itertools;
 use keepcalm::SharedMut;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use tera::Context;
 use thiserror::Error;
-use tokio::{net::TcpListener, sync::Semaphore};
+use tokio::{
+    net::TcpListener,
+    sync::{broadcast::error::RecvError, Semaphore},
+};
 use tower::Service;
 use unwrap_infallible::UnwrapInfallible;
 
 use crate::{
-    auth::Auth,
+    auth::{Auth, Role},
     cron::{Cron, CronHistory},
-    index::Index,
+    fetch::{fetch_politely, fetch_with_backoff, FetchConfig},
+    index::{Index, StoryFilter},
     rate_limits::LimitState,
     resource::Resources,
+    schema::{self, FeedResponse, ZeitgeistResponse},
     serve_static_files,
-    story::FeedStory,
+    story::{ApiStory, FeedStory},
 };
 use progscrape_application::{
-    IntoStoryQuery, PersistError, ScrapePersistResultSummarizer, ScrapePersistResultSummary, Shard,
-    Story, StoryEvaluator, StoryIdentifier, StoryIndex, StoryQuery, StoryRender, StoryScore,
-    TagSet,
+    IntoStoryQuery, PersistError, ScrapePersistResult, ScrapePersistResultSummarizer,
+    ScrapePersistResultSummary, Shard, Story, StoryCursor, StoryEvaluator, StoryIdentifier,
+    StoryIndex, StoryQuery, StoryRender, StoryScore, TagSet,
 };
 use progscrape_scrapers::{
-    ScrapeCollection, ScrapeSource, ScraperHttpResponseInput, ScraperHttpResult, StoryDate,
-    TypedScrape,
+    feed::{self, FeedPollState},
+    HttpCacheState, HttpCacheValidators, ScrapeCollection, ScrapeSource, ScraperHttpResponseInput,
+    ScraperHttpResult, StoryDate, StoryUrl, TypedScrape,
 };
 
 pub const BLOG_SEARCH: &str = "progscrape blog";
@@ -72,6 +83,10 @@ pub enum WebError {
     CBORError(#[from] serde_cbor::Error),
     #[error("JSON error")]
     JSONError(#[from] serde_json::Error),
+    #[error("Invalid score configuration: {0}")]
+    ScoreConfigError(#[from] progscrape_application::StoryScoreConfigError),
+    #[error("Invalid URL normalization configuration: {0}")]
+    UrlNormalizationConfigError(#[from] progscrape_scrapers::UrlNormalizationConfigError),
     #[error("Reqwest error")]
     ReqwestError(#[from] reqwest::Error),
     #[error("Log setup error")]
@@ -86,6 +101,8 @@ pub enum WebError {
     NotFound,
     #[error("Authentication failed")]
     AuthError,
+    #[error("Bad request: {0}")]
+    BadRequest(String),
     #[error("Wrong URL, redirecting")]
     WrongUrl(String),
     #[error("Invalid command-line arguments")]
@@ -102,6 +119,8 @@ impl IntoResponse for WebError {
             Self::AuthError => StatusCode::UNAUTHORIZED,
             Self::NotFound => StatusCode::NOT_FOUND,
             Self::InvalidHeader(_) => StatusCode::BAD_REQUEST,
+            Self::BadRequest(_) => StatusCode::BAD_REQUEST,
+            Self::PersistError(PersistError::TantivyQueryError(_)) => StatusCode::BAD_REQUEST,
             Self::ServerTooBusy => StatusCode::REQUEST_TIMEOUT,
             _ => StatusCode::INTERNAL_SERVER_ERROR,
         };
@@ -115,12 +134,15 @@ struct AdminState {
     index: Index<StoryIndex>,
     cron: SharedMut<Cron>,
     cron_history: SharedMut<CronHistory>,
+    feed_polls: SharedMut<FeedPollState>,
+    http_cache: SharedMut<HttpCacheState>,
     backup_path: Option<std::path::PathBuf>,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
 struct CurrentUser {
     user: String,
+    role: Role,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -135,18 +157,25 @@ async fn authorize(
     if req.extensions().get::<CronMarker>().is_some() {
         req.extensions_mut().insert(CurrentUser {
             user: "cron".into(),
+            role: Role::Admin,
         });
         return Ok(next.run(req).await);
     }
 
     tracing::info!("Attempting authorization against auth = {:?}", auth);
+    fn header_user(headers: &HeaderMap, header: &str) -> Option<String> {
+        headers
+            .get(header)
+            .and_then(|header| header.to_str().ok().map(|s| s.to_string()))
+    }
     let user = match auth {
         Auth::None => None,
-        Auth::Fixed(fixed) => Some(fixed),
-        Auth::FromHeader(header) => req
-            .headers()
-            .get(header)
-            .and_then(|header| header.to_str().ok().map(|s| s.to_string())),
+        Auth::Fixed(fixed) => Some((fixed, Role::Admin)),
+        Auth::FromHeader(header) => {
+            header_user(req.headers(), &header).map(|user| (user, Role::Admin))
+        }
+        Auth::FromHeaderWithUsers(header, users) => header_user(req.headers(), &header)
+            .and_then(|user| users.get(&user).map(|role| (user, *role))),
     };
 
     match user {
@@ -154,8 +183,17 @@ async fn authorize(
             tracing::error!("No user authorized for this path!");
             Ok((StatusCode::UNAUTHORIZED, ">progscrape: 403 ▒").into_response())
         }
-        Some(user) => {
-            req.extensions_mut().insert(CurrentUser { user });
+        Some((user, role)) => {
+            // Read-only users may only make safe requests
+            if !role.can_mutate() && !matches!(*req.method(), Method::GET | Method::HEAD) {
+                tracing::error!(
+                    "User {user} with role {role:?} not allowed to {} {}",
+                    req.method(),
+                    req.uri()
+                );
+                return Ok((StatusCode::FORBIDDEN, ">progscrape: 403 ▒").into_response());
+            }
+            req.extensions_mut().insert(CurrentUser { user, role });
             Ok(next.run(req).await)
         }
     }
@@ -267,6 +305,7 @@ pub fn admin_routes<S: Clone + Send + Sync + 'static>(
         .route("/cron/backup", post(admin_cron_backup))
         .route("/cron/refresh", post(admin_cron_refresh))
         .route("/cron/reindex", post(admin_cron_reindex))
+        .route("/cron/rescore", post(admin_cron_rescore))
         .route("/cron/scrape/:service", post(admin_cron_scrape))
         .route("/headers/", get(admin_headers))
         .route("/scrape/", get(admin_scrape))
@@ -279,12 +318,15 @@ pub fn admin_routes<S: Clone + Send + Sync + 'static>(
         )
         .route("/index/shard/:shard/", get(admin_status_shard))
         .route("/index/story/:story/", get(admin_status_story))
+        .route("/index/merge", post(admin_index_merge))
         .fallback(handle_404_admin)
         .with_state(AdminState {
             resources,
             index,
             cron,
             cron_history,
+            feed_polls: SharedMut::new(FeedPollState::default()),
+            http_cache: SharedMut::new(HttpCacheState::default()),
             backup_path,
         })
         .route_layer(middleware::from_fn_with_state(auth, authorize))
@@ -363,6 +405,11 @@ pub fn create_feeds<S: Clone + Send + Sync + 'static>(
         .route("/feed.json", get(root_feed_json))
         .route("/feed.txt", get(root_feed_text))
         .route("/feed", get(root_feed_xml))
+        .route("/api/v1/stories", get(api_v1_stories))
+        .route("/api/schema.json", get(api_schema_json))
+        .route("/archive/:year/:month", get(archive))
+        .route("/archive/:year/:month/", get(archive))
+        .route("/events/frontpage", get(front_page_events))
         .route("/blog", get(blog_posts))
         .route("/blog/", get(blog_posts))
         .route("/blog/:date", get(blog_post))
@@ -404,6 +451,7 @@ pub async fn start_server<P2: Into<std::path::PathBuf>>(
     let app = create_feeds(index.clone(), resources.clone())
         .route("/metrics/opentelemetry.txt", get(root_metrics_txt))
         .with_state((index.clone(), resources.clone(), metrics_auth_bearer_token))
+        .merge(create_health(index.clone()))
         .route("/state", get(state_tracker))
         .nest(
             "/admin/",
@@ -528,14 +576,13 @@ fn render_admin(
 
 async fn blog_posts(
     OriginalUri(original_uri): OriginalUri,
-    Host(host): Host,
+    host: HostParams,
     State((index, resources)): State<(Index<StoryIndex>, Resources)>,
 ) -> Result<impl IntoResponse, WebError> {
     // TODO: This should be middleware
     if original_uri.path() == "/blog" {
         return Err(WebError::WrongUrl("/blog/".to_string()));
     }
-    let host = HostParams::new(host);
     let posts = &*resources.blog_posts.read();
     let now = now(&index).await?;
     let top_tags = index.top_tags(20)?;
@@ -561,7 +608,7 @@ struct BlogPath {
 
 async fn blog_post(
     OriginalUri(original_uri): OriginalUri,
-    Host(host): Host,
+    host: HostParams,
     State((index, resources)): State<(Index<StoryIndex>, Resources)>,
     Path(path): Path<BlogPath>,
 ) -> Result<impl IntoResponse, WebError> {
@@ -572,7 +619,6 @@ async fn blog_post(
         .filter(|s| s.id == path.date)
         .cloned()
         .collect_vec();
-    let host = HostParams::new(host);
     if posts.is_empty() {
         return Err(WebError::NotFound);
     }
@@ -606,6 +652,8 @@ struct SearchParams {
     r#type: &'static str,
     offset: usize,
     count: usize,
+    /// The tags and domains hidden from the results, as passed in `?hide=`
+    hide: String,
 }
 
 impl SearchParams {
@@ -624,12 +672,41 @@ impl SearchParams {
                 r#type,
                 offset,
                 count,
+                hide: String::new(),
             },
             query,
         ))
     }
 }
 
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Protocol {
+    Http,
+    Https,
+}
+
+impl Protocol {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Http => "http",
+            Self::Https => "https",
+        }
+    }
+}
+
+/// How we decide the protocol used for absolute links back to this site.
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+pub struct HostConfig {
+    /// Always use this protocol, regardless of the request.
+    #[serde(default)]
+    pub protocol: Option<Protocol>,
+    /// Use the `X-Forwarded-Proto` header set by a reverse proxy, if present. Off by default, as a client can send
+    /// the header itself: only enable this behind a proxy that overwrites it.
+    #[serde(default)]
+    pub trust_forwarded_proto: bool,
+}
+
 #[derive(Serialize)]
 pub struct HostParams {
     pub host: String,
@@ -637,25 +714,112 @@ pub struct HostParams {
 }
 
 impl HostParams {
-    pub fn new(host: String) -> Self {
-        let protocol = if host.starts_with("localhost") {
-            "http"
-        } else {
-            "https"
+    pub fn new(host: String, headers: &HeaderMap, config: &HostConfig) -> Self {
+        let forwarded = || {
+            headers
+                .get("x-forwarded-proto")
+                .and_then(|proto| proto.to_str().ok())
+                .and_then(|proto| match proto.trim().to_ascii_lowercase().as_str() {
+                    "http" => Some(Protocol::Http),
+                    "https" => Some(Protocol::Https),
+                    _ => None,
+                })
+        };
+        let protocol = config
+            .protocol
+            .or_else(|| config.trust_forwarded_proto.then(forwarded).flatten())
+            .unwrap_or_else(|| {
+                if Self::is_local(&host) {
+                    Protocol::Http
+                } else {
+                    Protocol::Https
+                }
+            });
+        Self {
+            host,
+            protocol: protocol.as_str(),
+        }
+    }
+
+    /// The absolute URL of `path` on this host, with `search` (if not empty) as its `?search=` parameter.
+    pub fn search_url(&self, path: &str, search: &str) -> String {
+        let base = format!("{}://{}{path}", self.protocol, self.host);
+        if search.is_empty() {
+            return base;
+        }
+        match reqwest::Url::parse(&base) {
+            Ok(mut url) => {
+                url.query_pairs_mut().append_pair("search", search);
+                url.into()
+            }
+            Err(_) => base,
+        }
+    }
+
+    /// Hosts that are unlikely to be served over TLS: loopback and private addresses, `localhost`, mDNS names and
+    /// bare (ie: container) hostnames.
+    fn is_local(host: &str) -> bool {
+        let host = match host.strip_prefix('[') {
+            Some(ipv6) => ipv6.split(']').next().unwrap_or_default(),
+            None => host.rsplit_once(':').map_or(host, |(host, _port)| host),
         };
-        Self { host, protocol }
+        if let Ok(ip) = host.parse::<IpAddr>() {
+            return match ip {
+                IpAddr::V4(ip) => ip.is_loopback() || ip.is_private() || ip.is_unspecified(),
+                IpAddr::V6(ip) => ip.is_loopback() || ip.is_unspecified(),
+            };
+        }
+        host == "localhost"
+            || host.ends_with(".localhost")
+            || host.ends_with(".local")
+            || !host.contains('.')
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for HostParams
+where
+    Resources: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = <Host as FromRequestParts<S>>::Rejection;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Host(host) = Host::from_request_parts(parts, state).await?;
+        let resources = Resources::from_ref(state);
+        let config = resources.config.read();
+        Ok(Self::new(host, &parts.headers, &config.host))
+    }
+}
+
+impl FromRef<(Index<StoryIndex>, Resources)> for Resources {
+    fn from_ref((_, resources): &(Index<StoryIndex>, Resources)) -> Self {
+        resources.clone()
+    }
+}
+
+impl FromRef<(Index<StoryIndex>, Resources, Option<String>)> for Resources {
+    fn from_ref((_, resources, _): &(Index<StoryIndex>, Resources, Option<String>)) -> Self {
+        resources.clone()
+    }
+}
+
+impl FromRef<AdminState> for Resources {
+    fn from_ref(state: &AdminState) -> Self {
+        state.resources.clone()
     }
 }
 
 async fn root(
     OriginalUri(original_uri): OriginalUri,
-    Host(host): Host,
+    host: HostParams,
     State((index, resources)): State<(Index<StoryIndex>, Resources)>,
     query: Query<HashMap<String, String>>,
 ) -> Result<impl IntoResponse, WebError> {
     let now = now(&index).await?;
-    let host = HostParams::new(host);
-    let (search, query) = SearchParams::new(
+    // Readers can hide tags and domains they aren't interested in, ie: `?hide=crypto,twitter.com`
+    let hide = query.get("hide").cloned().unwrap_or_default();
+    let (mut search, query) = SearchParams::new(
         &index,
         query.get("search"),
         query
@@ -674,8 +838,15 @@ async fn root(
     if let StoryQuery::UrlSearch(url) = query {
         return Err(WebError::WrongUrl(format!("/s/{url}")));
     }
+    let related_tags = if let StoryQuery::TagSearch(..) = query {
+        index.related_tags(query.clone(), 10).await?
+    } else {
+        vec![]
+    };
+    let filter = StoryFilter::default().hide(&hide);
+    search.hide = hide;
     let stories = index
-        .stories::<StoryRender>(&host, query, search.offset, search.count)
+        .stories_filtered::<StoryRender>(&host, query, &filter, search.offset, search.count)
         .await?;
     let top_tags = index.top_tags(20)?;
     let path = original_uri
@@ -688,16 +859,67 @@ async fn root(
             "public, max-age=300, s-max-age=300, stale-while-revalidate=60, stale-if-error=86400",
         ),
     )],
-    render(&resources, "index.html", context!(top_tags, stories, now, search, host, path))))
+    render(&resources, "index.html", context!(top_tags, related_tags, stories, now, search, host, path))))
+}
+
+/// Browse the stories from a single month, newest first.
+async fn archive(
+    host: HostParams,
+    State((index, resources)): State<(Index<StoryIndex>, Resources)>,
+    Path((year, month)): Path<(i32, u32)>,
+    params: Query<HashMap<String, String>>,
+) -> Result<impl IntoResponse, WebError> {
+    let now = now(&index).await?;
+    let start = StoryDate::year_month_day(year, month, 1).ok_or(WebError::NotFound)?;
+    let end = start.checked_add_months(1).ok_or(WebError::NotFound)?;
+    let query = StoryQuery::DateRange(start, end);
+    let search = SearchParams {
+        text: "".to_owned(),
+        r#type: query.query_type(),
+        offset: params
+            .get("offset")
+            .map(|x| x.parse().unwrap_or_default())
+            .unwrap_or_default(),
+        count: params
+            .get("count")
+            .map(|x| x.parse::<usize>().unwrap_or_default())
+            .unwrap_or(30)
+            .max(1),
+        hide: String::new(),
+    };
+    let related_tags: Vec<(String, usize)> = vec![];
+    let stories = index
+        .archive_stories::<StoryRender>(&host, start, end, search.offset, search.count)
+        .await?;
+    let top_tags = index.top_tags(20)?;
+    let path = format!("/archive/{year}/{month}/");
+    Ok(([(
+        header::CACHE_CONTROL,
+        HeaderValue::from_static(
+            "public, max-age=300, s-max-age=300, stale-while-revalidate=60, stale-if-error=86400",
+        ),
+    )],
+    render(&resources, "index.html", context!(top_tags, related_tags, stories, now, search, host, path))))
+}
+
+/// Remove the story itself from its list of related stories, including copies under a normalized-equivalent URL.
+fn filter_related(story: &StoryRender, related: Vec<StoryRender>) -> Vec<StoryRender> {
+    let same_story = |a: &str, b: &str| match (StoryUrl::parse(a), StoryUrl::parse(b)) {
+        (Some(a), Some(b)) => a.same_story(&b),
+        _ => a == b,
+    };
+    related
+        .into_iter()
+        .filter(|related| !(related.date == story.date && same_story(&related.url, &story.url)))
+        .collect()
 }
 
 async fn story(
     OriginalUri(original_uri): OriginalUri,
-    Host(host): Host,
+    host: HostParams,
     State((index, resources)): State<(Index<StoryIndex>, Resources)>,
 ) -> Result<impl IntoResponse, WebError> {
     let now = now(&index).await?;
-    let host = HostParams::new(host);
     let mut search = original_uri
         .path_and_query()
         .map(|p| p.as_str())
@@ -730,15 +952,12 @@ async fn story(
     let mut related = vec![];
     if let Some(story) = stories.first() {
         let related_query = StoryQuery::RelatedSearch(story.title.clone(), story.tags.clone());
-        for story in index
-            .stories::<StoryRender>(&host, related_query, offset, 30)
-            .await?
-        {
-            if story.url == stories[0].url && story.date == stories[0].date {
-                continue;
-            }
-            related.push(story);
-        }
+        related = filter_related(
+            story,
+            index
+                .stories::<StoryRender>(&host, related_query, offset, 30)
+                .await?,
+        );
     } else {
         // No URL matching this in the index, so just run a domain search
         let related_query = StoryQuery::DomainSearch(url.host().to_string());
@@ -748,16 +967,15 @@ async fn story(
                 .await?,
         );
     }
+    let ids = stories
+        .iter()
+        .map(|story| StoryIdentifier::from_base64(story.id.clone()).ok_or(WebError::NotFound))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut stories_raw = index.fetch_many_by_id::<TypedScrape>(ids.clone()).await?;
     let mut stories_with_scrapes = vec![];
-    for story in stories {
-        let story_raw = index
-            .fetch_one::<TypedScrape>(StoryQuery::ById(
-                StoryIdentifier::from_base64(story.id.clone()).ok_or(WebError::NotFound)?,
-            ))
-            .await?
-            .ok_or(WebError::NotFound)?;
-        let scrapes = story_raw.scrapes;
-        stories_with_scrapes.push((story, scrapes));
+    for (story, id) in stories.into_iter().zip(ids) {
+        let story_raw = stories_raw.remove(&id).ok_or(WebError::NotFound)?;
+        stories_with_scrapes.push((story, story_raw.scrapes));
     }
     let top_tags = index.top_tags(20)?;
     let path = original_uri
@@ -796,19 +1014,15 @@ async fn zeitgeist_json(
                 "public, max-age=3600, s-max-age=3600, stale-while-revalidate=3600, stale-if-error=86400",
             ),
         )],
-        Json(json!({
-            "v": 1,
-            "stories": stories
-        })),
+        Json(ZeitgeistResponse { v: 1, stories }),
     ))
 }
 
 async fn root_feed_json(
-    Host(host): Host,
+    host: HostParams,
     State((index, _resources)): State<(Index<StoryIndex>, Resources)>,
     query: Query<HashMap<String, String>>,
 ) -> Result<impl IntoResponse, WebError> {
-    let host = HostParams::new(host);
     // Allow consumers to request a story count from feed.json
     let count = query
         .get("count")
@@ -816,16 +1030,168 @@ async fn root_feed_json(
         .unwrap_or(150)
         .max(1);
 
-    let (search, query) = SearchParams::new(&index, query.get("search"), 0, count)?;
-    let stories = index
-        .stories::<FeedStory>(&host, query, search.offset, search.count)
-        .await?;
+    // Optionally restrict the feed to stories seen on multiple sources
+    let filter = StoryFilter {
+        min_sources: query
+            .get("min_sources")
+            .map(|x| x.parse::<usize>().unwrap_or_default())
+            .unwrap_or_default(),
+        ..Default::default()
+    };
+
+    // Passing a `cursor` (empty for the first page) pages through stories newest first, rather than by score
+    let cursor = query
+        .get("cursor")
+        .map(|cursor| {
+            if cursor.is_empty() {
+                Ok(None)
+            } else {
+                StoryCursor::from_base64(cursor)
+                    .map(Some)
+                    .ok_or_else(|| WebError::BadRequest(format!("Invalid cursor: {cursor}")))
+            }
+        })
+        .transpose()?;
+
+    let (search, story_query) = SearchParams::new(&index, query.get("search"), 0, count)?;
+    let (stories, next_cursor) = if let Some(cursor) = cursor {
+        let (stories, next_cursor) = index
+            .stories_after::<FeedStory>(&host, story_query, &filter, cursor, search.count)
+            .await?;
+        (stories, Some(next_cursor.map(|cursor| cursor.to_base64())))
+    } else {
+        let stories = index
+            .stories_filtered::<FeedStory>(&host, story_query, &filter, search.offset, search.count)
+            .await?;
+        (stories, None)
+    };
     let top_tags: Vec<_> = index
         .top_tags(usize::MAX)?
         .into_iter()
         .map(|s| s.0)
         .collect();
 
+    // A null cursor means that there are no more stories
+    let feed = FeedResponse {
+        v: 1,
+        tags: top_tags,
+        stories,
+        next_cursor,
+    };
+
+    Ok((
+        [(
+            header::CACHE_CONTROL,
+            HeaderValue::from_static(
+                "public, max-age=300, s-max-age=300, stale-while-revalidate=60, stale-if-error=86400",
+            ),
+        )],
+        Json(feed),
+    ))
+}
+
+/// An OpenAPI description of the JSON feeds, for generating clients.
+async fn api_schema_json() -> impl IntoResponse {
+    static SCHEMA: OnceLock<serde_json::Value> = OnceLock::new();
+    (
+        [(
+            header::CACHE_CONTROL,
+            HeaderValue::from_static("public, max-age=86400"),
+        )],
+        Json(SCHEMA.get_or_init(schema::openapi_document).clone()),
+    )
+}
+
+/// The maximum page size for `/api/v1/stories`.
+const API_MAX_LIMIT: usize = 100;
+
+#[derive(Deserialize)]
+struct ApiStoriesParams {
+    q: Option<String>,
+    tag: Option<String>,
+    domain: Option<String>,
+    after: Option<String>,
+    before: Option<String>,
+    #[serde(default)]
+    offset: usize,
+    limit: Option<usize>,
+    #[serde(default)]
+    min_sources: usize,
+}
+
+/// Parses an API date parameter, either as `YYYY-MM-DD` (midnight UTC) or RFC3339.
+fn parse_api_date(name: &str, date: &Option<String>) -> Result<Option<StoryDate>, WebError> {
+    let Some(date) = date else {
+        return Ok(None);
+    };
+    let parsed = if date.len() == 10 {
+        StoryDate::parse_from_rfc3339(&format!("{date}T00:00:00Z"))
+    } else {
+        StoryDate::parse_from_rfc3339(date)
+    };
+    parsed
+        .map(Some)
+        .ok_or_else(|| WebError::BadRequest(format!("Invalid date for '{name}': {date}")))
+}
+
+/// Server-sent events carrying the top front page stories whenever they change.
+async fn front_page_events(
+    host: HostParams,
+    State((index, _resources)): State<(Index<StoryIndex>, Resources)>,
+) -> Result<Sse<impl Stream<Item = Result<Event, axum::Error>>>, WebError> {
+    let receiver = index.subscribe_front_page().ok_or(WebError::NotFound)?;
+    let events = stream::unfold(
+        (index, host, receiver),
+        |(index, host, mut receiver)| async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(stories) => {
+                        let stories = index.render_stories::<StoryRender>(&host, &stories);
+                        let event = Event::default().event("frontpage").json_data(stories);
+                        return Some((event, (index, host, receiver)));
+                    }
+                    Err(RecvError::Lagged(skipped)) => {
+                        tracing::info!("Live front page subscriber skipped {skipped} update(s)");
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        },
+    );
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+async fn api_v1_stories(
+    host: HostParams,
+    State((index, _resources)): State<(Index<StoryIndex>, Resources)>,
+    Query(params): Query<ApiStoriesParams>,
+) -> Result<impl IntoResponse, WebError> {
+    let limit = params.limit.unwrap_or(30).clamp(1, API_MAX_LIMIT);
+    let filter = StoryFilter {
+        after: parse_api_date("after", &params.after)?,
+        before: parse_api_date("before", &params.before)?,
+        min_sources: params.min_sources,
+        ..Default::default()
+    };
+
+    let query = match (&params.q, &params.tag, &params.domain) {
+        (None, None, None) => StoryQuery::FrontPage,
+        (Some(q), None, None) => index.parse_query(q)?,
+        (None, Some(tag), None) => match index.parse_query(tag)? {
+            query @ StoryQuery::TagSearch(..) => query,
+            _ => StoryQuery::TagSearch(tag.trim().to_lowercase(), None),
+        },
+        (None, None, Some(domain)) => StoryQuery::DomainSearch(domain.trim().to_lowercase()),
+        _ => {
+            return Err(WebError::BadRequest(
+                "Only one of 'q', 'tag' or 'domain' may be specified".to_owned(),
+            ))
+        }
+    };
+    let (stories, total, total_exact) = index
+        .stories_page::<ApiStory>(&host, query, &filter, params.offset, limit)
+        .await?;
+
     Ok((
         [(
             header::CACHE_CONTROL,
@@ -834,27 +1200,32 @@ async fn root_feed_json(
             ),
         )],
         Json(json!({
-            "v": 1,
-            "tags": top_tags,
-            "stories": stories
+            "stories": stories,
+            "offset": params.offset,
+            "limit": limit,
+            "total": total,
+            "total_exact": total_exact,
         })),
     ))
 }
 
 async fn root_feed_xml(
-    Host(host): Host,
+    host: HostParams,
     State((index, resources)): State<(Index<StoryIndex>, Resources)>,
     query: Query<HashMap<String, String>>,
 ) -> Result<impl IntoResponse, WebError> {
     let now = now(&index).await?;
-    let host = HostParams::new(host);
-    let (search, query) = SearchParams::new(&index, query.get("search"), 0, 30)?;
+    let query = index.parse_query(query.get("search"))?;
+    // Links are built from the parsed query so that equivalent searches share a feed id
+    let search = query.query_text().into_owned();
+    let self_url = host.search_url("/feed", &search);
+    let alternate_url = host.search_url("/", &search);
     let stories = index.stories::<StoryRender>(&host, query, 0, 30).await?;
 
-    let xml = resources
-        .templates
-        .read()
-        .render("feed.xml", &context!(stories, now, host))?;
+    let xml = resources.templates.read().render(
+        "feed.xml",
+        &context!(stories, now, host, search, self_url, alternate_url),
+    )?;
     Ok((
         [(
             header::CONTENT_TYPE,
@@ -873,12 +1244,11 @@ async fn root_feed_xml(
 }
 
 async fn root_feed_text(
-    Host(host): Host,
+    host: HostParams,
     State((index, resources)): State<(Index<StoryIndex>, Resources)>,
     query: Query<HashMap<String, String>>,
 ) -> Result<impl IntoResponse, WebError> {
     let now = now(&index).await?;
-    let host = HostParams::new(host);
     let (search, query) = SearchParams::new(&index, query.get("search"), 0, 100)?;
     let stories = index
         .stories::<StoryRender>(&host, query, search.offset, search.count)
@@ -958,10 +1328,32 @@ async fn state_tracker(
     ))
 }
 
+/// Create the router for the unauthenticated health check used by load balancers.
+pub fn create_health<S: Clone + Send + Sync + 'static>(index: Index<StoryIndex>) -> Router<S> {
+    Router::new()
+        .route("/healthz", get(healthz))
+        .with_state(index)
+}
+
+/// Return a summary of the index as JSON. This is a 503 until the hot set has been computed for the first time.
+async fn healthz(State(index): State<Index<StoryIndex>>) -> Result<impl IntoResponse, WebError> {
+    let health = index.health().await?;
+    let status = if health.hot_set_refreshed {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    Ok((
+        status,
+        [(header::CACHE_CONTROL, HeaderValue::from_static("no-store"))],
+        Json(health),
+    ))
+}
+
 /// Return the current metrics in Prometheus-compatible format.
 async fn root_metrics_txt(
     headers_in: HeaderMap,
-    Host(host): Host,
+    host: HostParams,
     State((index, resources, metrics_auth_bearer_token)): State<(
         Index<StoryIndex>,
         Resources,
@@ -987,7 +1379,6 @@ async fn root_metrics_txt(
             return Err(WebError::AuthError);
         }
     }
-    let host = HostParams::new(host);
     let stories = index
         .stories::<StoryRender>(&host, StoryQuery::FrontPage, 0, usize::MAX)
         .await?;
@@ -1128,6 +1519,24 @@ async fn admin_cron_reindex(
     )
 }
 
+async fn admin_cron_rescore(
+    State(AdminState {
+        resources, index, ..
+    }): State<AdminState>,
+) -> Result<impl IntoResponse, WebError> {
+    let start = Instant::now();
+    let results = index.rescore_hot_set().await?;
+    let elapsed_ms = start.elapsed().as_millis();
+    let summary = results.summary();
+    tracing::info!("Hotset rescore: time={elapsed_ms}ms result={summary:?}");
+    render_admin(
+        None,
+        &resources,
+        "admin/cron_reindex.html",
+        context!(results, elapsed_ms, summary),
+    )
+}
+
 async fn admin_update_blog(
     State(AdminState {
         resources, index, ..
@@ -1148,42 +1557,207 @@ async fn admin_update_blog(
     render_admin(None, &resources, "admin/cron_blog.html", context!())
 }
 
+/// A fetched scrape input, along with the cache validators to record once it has been scraped and stored.
+struct FetchedInput {
+    input: ScraperHttpResponseInput,
+    /// The URL the validators belong to, which is the discovered feed for a page that links to one.
+    url: String,
+    validators: Option<HttpCacheValidators>,
+}
+
+/// Fetch `url`, sending back any cache validators we have for it so an unchanged page comes back as
+/// [`ScraperHttpResponseInput::NotModified`] rather than a full body. The validators of a full response are
+/// returned rather than recorded, as a page must not be skipped next time if we fail to store what we scraped.
+async fn fetch_scrape_input(
+    client: &reqwest::Client,
+    http_cache: &SharedMut<HttpCacheState>,
+    url: &str,
+) -> Result<FetchedInput, WebError> {
+    use reqwest::header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+    let mut req = client.get(url).header("User-Agent", "progscrape");
+    if let Some(validators) = http_cache.read().get(url) {
+        if let Some(etag) = &validators.etag {
+            req = req.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &validators.last_modified {
+            req = req.header(IF_MODIFIED_SINCE, last_modified);
+        }
+    }
+    let resp = req.send().await?;
+    let status = resp.status();
+    let mut validators = None;
+    let input = if status == StatusCode::OK {
+        let header = |name| {
+            resp.headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_owned)
+        };
+        validators = Some(HttpCacheValidators {
+            etag: header(ETAG),
+            last_modified: header(LAST_MODIFIED),
+        });
+        ScraperHttpResponseInput::Ok(resp.text().await?)
+    } else if status == StatusCode::NOT_MODIFIED {
+        ScraperHttpResponseInput::NotModified
+    } else {
+        ScraperHttpResponseInput::HTTPError(status.as_u16(), status.as_str().to_owned())
+    };
+    Ok(FetchedInput {
+        input,
+        url: url.to_owned(),
+        validators,
+    })
+}
+
+/// Fetch a URL for scraping, retrying throttled requests and server errors with a backoff. Returns the final
+/// response along with the number of retries it took.
+async fn fetch_scrape_input_with_backoff(
+    client: &reqwest::Client,
+    http_cache: &SharedMut<HttpCacheState>,
+    config: &FetchConfig,
+    url: &str,
+) -> (Result<FetchedInput, WebError>, u32) {
+    let should_retry = |fetched: &Result<FetchedInput, _>| {
+        matches!(fetched, Ok(FetchedInput { input: ScraperHttpResponseInput::HTTPError(code, _), .. })
+            if *code == StatusCode::TOO_MANY_REQUESTS.as_u16() || (500..600).contains(code))
+    };
+    fetch_with_backoff(config, should_retry, || {
+        fetch_scrape_input(client, http_cache, url)
+    })
+    .await
+}
+
+/// If a configured feed URL is actually an HTML page (ie: a blog's homepage), fetch the feed it links to instead.
+/// Otherwise the input is returned as-is, and the feed scraper will warn about it. We never keep validators for
+/// a page that isn't a feed, so that the page is always fetched in full and its feed is checked on every poll.
+async fn discover_feed_input(
+    client: &reqwest::Client,
+    http_cache: &SharedMut<HttpCacheState>,
+    url: &str,
+    mut fetched: FetchedInput,
+) -> FetchedInput {
+    let ScraperHttpResponseInput::Ok(body) = &fetched.input else {
+        return fetched;
+    };
+    if feed::is_feed(body) {
+        return fetched;
+    }
+    fetched.validators = None;
+    let Some(feed_url) = StoryUrl::parse(url).and_then(|url| feed::discover_feed(body, &url))
+    else {
+        tracing::info!("No feed found for {url}");
+        return fetched;
+    };
+    tracing::info!("Discovered feed {feed_url} for {url}");
+    match fetch_scrape_input(client, http_cache, feed_url.raw()).await {
+        Ok(feed_input) => feed_input,
+        Err(e) => {
+            tracing::error!("Failed to fetch discovered feed {feed_url}: {e:?}");
+            fetched
+        }
+    }
+}
+
 async fn admin_cron_scrape(
     State(AdminState {
-        resources, index, ..
+        resources,
+        index,
+        feed_polls,
+        http_cache,
+        ..
     }): State<AdminState>,
     Path(source): Path<ScrapeSource>,
-) -> Result<impl IntoResponse, WebError> {
+    cron: Option<Extension<CronMarker>>,
+) -> Result<Response, WebError> {
+    // Only scheduled runs are held back by the circuit breaker, so a manual run can still be used to test a source
+    if cron.is_some()
+        && !resources
+            .circuit_breakers
+            .write()
+            .allow(source, Instant::now())
+    {
+        tracing::info!("Skipping scrape of {source:?}, circuit breaker is open");
+        return Ok((
+            StatusCode::SERVICE_UNAVAILABLE,
+            format!("Circuit breaker open for {source:?}"),
+        )
+            .into_response());
+    }
+
     let start = Instant::now();
     let subsources = resources.scrapers.read().compute_scrape_subsources(source);
-    let urls = resources
+    let mut urls = resources
         .scrapers
         .read()
         .compute_scrape_url_demands(source, subsources);
+    let now = StoryDate::now();
+    if source == ScrapeSource::Feed {
+        // Feeds are only polled as often as they (and our config) allow
+        urls.retain(|url| feed_polls.read().is_due(url, now));
+    }
+    let fetch_config = resources.config.read().fetch.clone();
+    let client = reqwest::Client::new();
+    let responses = fetch_politely(&fetch_config, urls, |url| {
+        let client = client.clone();
+        let http_cache = http_cache.clone();
+        let fetch_config = &fetch_config;
+        async move {
+            let (input, retries) =
+                fetch_scrape_input_with_backoff(&client, &http_cache, fetch_config, &url).await;
+            let input = match input {
+                Ok(fetched) if source == ScrapeSource::Feed => {
+                    Ok(discover_feed_input(&client, &http_cache, &url, fetched).await)
+                }
+                input => input,
+            };
+            (input, retries)
+        }
+    })
+    .await;
     let mut map = HashMap::new();
-    for url in urls {
-        let resp = reqwest::Client::new()
-            .get(&url)
-            .header("User-Agent", "progscrape")
-            .send()
-            .await?;
-        let status = resp.status();
-        if status == StatusCode::OK {
-            map.insert(url, ScraperHttpResponseInput::Ok(resp.text().await?));
-        } else {
-            map.insert(
-                url,
-                ScraperHttpResponseInput::HTTPError(status.as_u16(), status.as_str().to_owned()),
-            );
+    let mut retries = HashMap::new();
+    let mut validators = HashMap::new();
+    let attempted = !responses.is_empty();
+    for (url, (input, retry_count)) in responses {
+        if retry_count > 0 {
+            retries.insert(url.clone(), retry_count);
         }
+        let input = match input {
+            Ok(FetchedInput {
+                input,
+                url: fetched_url,
+                validators: Some(fetched_validators),
+            }) => {
+                validators.insert(url.clone(), (fetched_url, fetched_validators));
+                input
+            }
+            Ok(fetched) => fetched.input,
+            Err(e) => {
+                resources
+                    .circuit_breakers
+                    .write()
+                    .record(source, false, Instant::now());
+                return Err(e);
+            }
+        };
+        if source == ScrapeSource::Feed {
+            let body = match &input {
+                ScraperHttpResponseInput::Ok(body) => Some(body.as_str()),
+                _ => None,
+            };
+            let interval = resources.scrapers.read().feed_poll_interval(&url, body);
+            feed_polls.write().record_poll(&url, now, interval);
+        }
+        map.insert(url, input);
     }
     let fetch_ms = start.elapsed().as_millis();
 
     let start = Instant::now();
-    let scrapes = HashMap::from_iter(
-        map.into_iter()
-            .map(|(k, v)| (k, resources.scrapers.read().scrape_http_result(source, v))),
-    );
+    let scrapes = HashMap::from_iter(map.into_iter().map(|(k, v)| {
+        let result = resources.scrapers.read().scrape_http_result(source, &k, v);
+        (k, result)
+    }));
     let process_ms = start.elapsed().as_millis();
 
     let start = Instant::now();
@@ -1191,7 +1765,7 @@ async fn admin_cron_scrape(
     let mut errors = 0;
     for result in scrapes.values() {
         match result {
-            ScraperHttpResult::Ok(_, scrapes) => {
+            ScraperHttpResult::Ok(_, scrapes, _) => {
                 let res = index.insert_scrapes(scrapes.clone()).await?;
                 summary += res.summary();
             }
@@ -1202,14 +1776,32 @@ async fn admin_cron_scrape(
     }
     let insert_ms = start.elapsed().as_millis();
 
-    tracing::info!("Scrape source={source:?} fetch_time={fetch_ms}ms process_time={process_ms}ms insert_time={insert_ms}ms errors={errors} result={summary:?}");
+    // Only now that everything is stored can we skip unchanged pages on the next run
+    for (url, result) in &scrapes {
+        if let (ScraperHttpResult::Ok(..), Some((fetched_url, validators))) =
+            (result, validators.remove(url))
+        {
+            http_cache.write().record(&fetched_url, validators);
+        }
+    }
+
+    tracing::info!("Scrape source={source:?} fetch_time={fetch_ms}ms process_time={process_ms}ms insert_time={insert_ms}ms errors={errors} retries={retries:?} result={summary:?}");
 
-    render_admin(
+    // A run only counts as a failure if every URL we fetched failed
+    if attempted {
+        resources
+            .circuit_breakers
+            .write()
+            .record(source, errors < scrapes.len(), Instant::now());
+    }
+
+    Ok(render_admin(
         None,
         &resources,
         "admin/cron_scrape_run.html",
-        context!(source, scrapes: HashMap<String, ScraperHttpResult>, summary, fetch_ms, process_ms, insert_ms,),
-    )
+        context!(source, scrapes: HashMap<String, ScraperHttpResult>, summary, retries: HashMap<String, u32>, fetch_ms, process_ms, insert_ms,),
+    )?
+    .into_response())
 }
 
 async fn admin_headers(
@@ -1259,7 +1851,9 @@ struct AdminScrapeTestParams {
 
 async fn admin_scrape_test(
     Extension(user): Extension<CurrentUser>,
-    State(AdminState { resources, .. }): State<AdminState>,
+    State(AdminState {
+        index, resources, ..
+    }): State<AdminState>,
     Json(params): Json<AdminScrapeTestParams>,
 ) -> Result<impl IntoResponse, WebError> {
     let urls = resources
@@ -1285,20 +1879,33 @@ async fn admin_scrape_test(
     }
 
     let scrapes = HashMap::from_iter(map.into_iter().map(|(k, v)| {
-        (
-            k,
-            resources
-                .scrapers
-                .read()
-                .scrape_http_result(params.source, v),
-        )
+        let result = resources
+            .scrapers
+            .read()
+            .scrape_http_result(params.source, &k, v);
+        (k, result)
     }));
 
+    // Preview what ingesting these scrapes would do to the index
+    let all_scrapes = scrapes
+        .values()
+        .filter_map(|result| match result {
+            ScraperHttpResult::Ok(_, scrapes, _) => Some(scrapes.clone()),
+            ScraperHttpResult::Err(..) => None,
+        })
+        .flatten()
+        .collect_vec();
+    let impact = index.evaluate_scrapes(all_scrapes).await?.summary();
+
     render_admin(
         Some(&user),
         &resources,
         "admin/scrape_test.html",
-        context!(user, scrapes: HashMap<String, ScraperHttpResult>),
+        context!(
+            user,
+            scrapes: HashMap<String, ScraperHttpResult>,
+            impact: ScrapePersistResultSummary
+        ),
     )
 }
 
@@ -1318,7 +1925,7 @@ async fn admin_index_status(
 
 async fn admin_status_frontpage(
     Extension(user): Extension<CurrentUser>,
-    Host(host): Host,
+    host: HostParams,
     State(AdminState {
         index, resources, ..
     }): State<AdminState>,
@@ -1326,7 +1933,6 @@ async fn admin_status_frontpage(
 ) -> Result<impl IntoResponse, WebError> {
     let now = now(&index).await?;
     let sort = sort.get("sort").cloned().unwrap_or_default();
-    let host = HostParams::new(host);
     let stories = index
         .stories::<StoryRender>(&host, StoryQuery::FrontPage, 0, usize::MAX)
         .await?;
@@ -1359,7 +1965,7 @@ async fn admin_index_frontpage_scoretuner(
     for mut story in stories {
         let scrapes = ScrapeCollection::new_from_iter(story.scrapes.values().cloned());
         let extracted = scrapes.extract(&eval.extractor);
-        story.score = eval.scorer.score(&extracted) + eval.scorer.score_age(now - story.date);
+        story.score = eval.scorer.score(&extracted, now) + eval.scorer.score_age(now - story.date);
         let mut tags = TagSet::from_iter(extracted.tags());
         eval.tagger.tag(extracted.title(), &mut tags);
         story.tags = tags;
@@ -1389,7 +1995,7 @@ async fn admin_status_shard(
     sort: Query<HashMap<String, String>>,
 ) -> Result<impl IntoResponse, WebError> {
     let sort = sort.get("sort").cloned().unwrap_or_default();
-    let shard = Shard::from_string(&shard).expect("Failed to parse shard");
+    let shard: Shard = shard.parse().map_err(|_| WebError::NotFound)?;
     let stories = index
         .fetch::<Shard>(StoryQuery::ByShard(shard), usize::MAX)
         .await?;
@@ -1412,8 +2018,8 @@ async fn admin_status_story(
     let id = StoryIdentifier::from_base64(id).ok_or(WebError::NotFound)?;
     let now = now(&index).await?;
     tracing::info!("Loading story = {:?}", id);
-    let story = index
-        .fetch_one(StoryQuery::ById(id.clone()))
+    let (story, doc) = index
+        .fetch_one_with_doc(id.clone())
         .await?
         .ok_or(WebError::NotFound)?;
     let scrapes = ScrapeCollection::new_from_iter(story.scrapes.clone().into_values());
@@ -1421,8 +2027,13 @@ async fn admin_status_story(
     let eval = resources.story_evaluator.clone();
     let extract = scrapes.extract(&eval.read().extractor);
     let score_details = eval.read().scorer.score_detail(&extract, now);
-    let tags = Default::default(); // _details = resources.story_evaluator.tagger.tag_detail(&story);
-    let doc = index.fetch_detail_one(id).await?.unwrap_or_default();
+    let tags = HashMap::<_, _>::from_iter(
+        story
+            .tags_with_source()
+            .into_iter()
+            .map(|(tag, sources)| (tag, sources.iter().map(|s| s.to_string()).collect_vec())),
+    );
+    let snapshots = index.fetch_score_snapshots(id).await?;
     let story = story.render(&eval.read(), 0);
 
     render_admin(
@@ -1436,11 +2047,41 @@ async fn admin_status_story(
             tags: HashMap<String, Vec<String>>,
             score = score_details,
             doc,
+            snapshots,
             story,
         ),
     )
 }
 
+#[derive(Deserialize)]
+struct AdminIndexMergeParams {
+    /// The story that survives the merge
+    keep: String,
+    /// The story whose scrapes are folded into `keep`, and which is then removed
+    merge: String,
+}
+
+async fn admin_index_merge(
+    State(AdminState { index, .. }): State<AdminState>,
+    Json(params): Json<AdminIndexMergeParams>,
+) -> Result<Json<ScrapePersistResult>, WebError> {
+    let parse = |id: &str| {
+        StoryIdentifier::from_base64(id).ok_or(WebError::BadRequest(format!("Invalid id: {id}")))
+    };
+    let keep = parse(&params.keep)?;
+    let merge = parse(&params.merge)?;
+    if keep == merge {
+        return Err(WebError::BadRequest(
+            "Cannot merge a story into itself".into(),
+        ));
+    }
+    tracing::info!("Merging story {merge:?} into {keep:?}");
+    match index.merge_stories(keep, merge).await? {
+        ScrapePersistResult::NotFound => Err(WebError::NotFound),
+        res => Ok(Json(res)),
+    }
+}
+
 pub async fn serve_static_files_immutable(
     headers_in: HeaderMap,
     Path(key): Path<String>,
@@ -1456,3 +2097,865 @@ pub async fn serve_static_files_well_known(
 ) -> Result<impl IntoResponse, WebError> {
     serve_static_files::well_known(headers_in, file, &resources.static_files_root.read())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::index::{HotSetConfig, IndexConfig};
+    use futures::StreamExt;
+    use keepcalm::Shared;
+    use progscrape_scrapers::hacker_news::HackerNewsStory;
+    use rstest::rstest;
+
+    fn admin_router(auth: Auth) -> Result<Router, Box<dyn std::error::Error>> {
+        Ok(admin_router_with_index(auth)?.0)
+    }
+
+    fn test_index(resources: &Resources) -> Result<Index<StoryIndex>, Box<dyn std::error::Error>> {
+        let tempdir = tempfile::tempdir()?;
+        let index = Index::<StoryIndex>::initialize_with_persistence(
+            tempdir,
+            resources.story_evaluator.clone(),
+            resources.blog_posts.clone(),
+            Shared::new(IndexConfig {
+                max_count: 300,
+                max_shards: Default::default(),
+                score_snapshots: None,
+                empty_titles: Default::default(),
+                blocklist: Default::default(),
+                exclude_penalized_hosts: false,
+                exclude_self_posts: false,
+                skip_degraded_shards: false,
+                search_ranking: Default::default(),
+                domain_search: Default::default(),
+                fuzzy_search: Default::default(),
+                url_changes: Default::default(),
+                date_outlier_days: None,
+                future_date_tolerance_hours: None,
+                url_normalization: Default::default(),
+                hot_set: HotSetConfig {
+                    size: 500,
+                    jitter: 0.0,
+                    tie_break: Default::default(),
+                    live_count: 30,
+                },
+            }),
+        )?;
+        Ok(index)
+    }
+
+    fn admin_router_with_index(
+        auth: Auth,
+    ) -> Result<(Router, Index<StoryIndex>), Box<dyn std::error::Error>> {
+        let resources = Resources::get_resources("../resource/")?;
+        let index = test_index(&resources)?;
+        let router = Router::new().nest(
+            "/admin/",
+            admin_routes(
+                resources,
+                index.clone(),
+                SharedMut::new(Cron::new()),
+                SharedMut::new(CronHistory::default()),
+                None,
+                auth,
+            ),
+        );
+        Ok((router, index))
+    }
+
+    async fn admin_request(
+        router: &mut Router,
+        method: Method,
+        path: &str,
+        user: &str,
+    ) -> Result<StatusCode, Box<dyn std::error::Error>> {
+        let mut req = Request::<Body>::default();
+        *req.method_mut() = method;
+        *req.uri_mut() = format!("http://localhost{path}").parse()?;
+        req.headers_mut()
+            .insert("x-user", HeaderValue::from_str(user)?);
+        Ok(router.call(req).await.unwrap_infallible().status())
+    }
+
+    #[tokio::test]
+    async fn test_auth_roles() -> Result<(), Box<dyn std::error::Error>> {
+        let users = Auth::parse_users(&["reader=read-only".into(), "writer=admin".into()])?;
+        let mut router = admin_router(Auth::FromHeaderWithUsers("x-user".into(), users))?;
+
+        // Read-only users can view, but not mutate
+        let status = admin_request(&mut router, Method::GET, "/admin/index/", "reader").await?;
+        assert_eq!(status, StatusCode::OK);
+        let status =
+            admin_request(&mut router, Method::POST, "/admin/cron/backup", "reader").await?;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        // Admins can do both
+        let status = admin_request(&mut router, Method::GET, "/admin/index/", "writer").await?;
+        assert_eq!(status, StatusCode::OK);
+        let status =
+            admin_request(&mut router, Method::POST, "/admin/cron/backup", "writer").await?;
+        assert_eq!(status, StatusCode::OK);
+
+        // Unknown users are rejected outright
+        let status = admin_request(&mut router, Method::GET, "/admin/index/", "nobody").await?;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        Ok(())
+    }
+
+    /// The scrape test page groups and counts the warnings for stories that were skipped.
+    #[test]
+    fn test_scrape_test_warnings() -> Result<(), Box<dyn std::error::Error>> {
+        let resources = Resources::get_resources("../resource/")?;
+        let input = r#"{"data": {"children": [
+            {"kind": "t3", "data": {"id": "a", "subreddit": "rust", "created_utc": "yesterday",
+                "url": "https://example.com", "title": "A", "num_comments": 1, "score": 1, "downs": 0,
+                "ups": 1, "upvote_ratio": 1.0}},
+            {"kind": "t3", "data": {"id": "b", "subreddit": "rust", "created_utc": "today",
+                "url": "https://example.com", "title": "B", "num_comments": 1, "score": 1, "downs": 0,
+                "ups": 1, "upvote_ratio": 1.0}}
+        ]}}"#;
+        let result = resources.scrapers.read().scrape_http_result(
+            ScrapeSource::Reddit,
+            "https://example.com/r/rust.json",
+            ScraperHttpResponseInput::Ok(input.to_owned()),
+        );
+        let scrapes = HashMap::from_iter([("https://example.com/r/rust.json".to_owned(), result)]);
+        let impact = ScrapePersistResultSummary::default();
+        let html = resources.templates.read().render(
+            "admin/scrape_test.html",
+            &context!(scrapes: HashMap<String, ScraperHttpResult>, impact: ScrapePersistResultSummary),
+        )?;
+        assert!(html.contains("Warnings (2 skipped)"), "{html}");
+        assert!(html.contains("date parse: 2"), "{html}");
+
+        Ok(())
+    }
+
+    /// Each tag's Atom feed links to itself and has an id derived from its query, while the unfiltered feed keeps its
+    /// original id.
+    #[tokio::test]
+    async fn test_feed_self_links() -> Result<(), Box<dyn std::error::Error>> {
+        let resources = Resources::get_resources("../resource/")?;
+        let index = test_index(&resources)?;
+        let mut router: Router = create_feeds(index, resources);
+
+        let mut ids = std::collections::HashSet::new();
+        for tag in ["rust", "python"] {
+            let mut req = Request::<Body>::default();
+            *req.uri_mut() = format!("http://localhost/feed?search=%20{tag}%20").parse()?;
+            let resp = router.call(req).await.unwrap_infallible();
+            assert_eq!(resp.status(), StatusCode::OK);
+            let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await?;
+            let xml = String::from_utf8(body.to_vec())?;
+
+            let self_url = tera::escape_html(&format!("http://localhost/feed?search={tag}"));
+            assert!(xml.contains(&format!("<id>{self_url}</id>")), "{xml}");
+            assert!(xml.contains(&format!(r#"<link rel="self" href="{self_url}" />"#)));
+            ids.insert(self_url);
+        }
+        assert_eq!(ids.len(), 2);
+
+        let mut req = Request::<Body>::default();
+        *req.uri_mut() = "http://localhost/feed".parse()?;
+        let resp = router.call(req).await.unwrap_infallible();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await?;
+        let xml = String::from_utf8(body.to_vec())?;
+        assert!(xml.contains("<id>http://localhost/</id>"), "{xml}");
+        let self_url = tera::escape_html("http://localhost/feed");
+        assert!(xml.contains(&format!(r#"<link rel="self" href="{self_url}" />"#)));
+
+        Ok(())
+    }
+
+    /// The OpenAPI document describes the feeds, with every schema reference resolving to a component.
+    #[tokio::test]
+    async fn test_api_schema() -> Result<(), Box<dyn std::error::Error>> {
+        let resources = Resources::get_resources("../resource/")?;
+        let index = test_index(&resources)?;
+        let mut router: Router = create_feeds(index, resources);
+        let mut req = Request::<Body>::default();
+        *req.uri_mut() = "http://localhost/api/schema.json".parse()?;
+        let resp = router.call(req).await.unwrap_infallible();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await?;
+        let doc: serde_json::Value = serde_json::from_slice(&body)?;
+
+        assert_eq!(doc["openapi"], "3.0.3");
+        let params = doc["paths"]["/feed.json"]["get"]["parameters"]
+            .as_array()
+            .expect("Missing parameters")
+            .iter()
+            .map(|param| param["name"].as_str().expect("Missing name"))
+            .collect_vec();
+        assert_eq!(params, vec!["search", "count", "min_sources", "cursor"]);
+
+        let schemas = &doc["components"]["schemas"];
+        let fields = |name: &str| {
+            schemas[name]["properties"]
+                .as_object()
+                .unwrap_or_else(|| panic!("Missing schema {name}"))
+                .keys()
+                .cloned()
+                .sorted()
+                .collect_vec()
+        };
+        assert_eq!(
+            fields("FeedResponse"),
+            vec!["next_cursor", "stories", "tags", "v"]
+        );
+        assert_eq!(
+            fields("FeedStory"),
+            vec!["date", "hnews", "href", "lobsters", "reddit", "slashdot", "tags", "title"]
+        );
+        assert_eq!(fields("ZeitgeistResponse"), vec!["stories", "v"]);
+
+        let text = doc.to_string();
+        for reference in text.split(r##""$ref":"#/components/schemas/"##).skip(1) {
+            let name = reference.split('"').next().unwrap_or_default();
+            assert!(schemas.get(name).is_some(), "Missing schema {name}");
+        }
+
+        Ok(())
+    }
+
+    /// Malformed shards and story ids in admin paths are a clean 404 rather than a panic.
+    #[tokio::test]
+    async fn test_admin_bad_paths() -> Result<(), Box<dyn std::error::Error>> {
+        let mut router = admin_router(Auth::Fixed("admin".into()))?;
+        for path in [
+            "/admin/index/shard/not-a-shard/",
+            "/admin/index/shard/2020-13/",
+            "/admin/index/story/not-a-story/",
+        ] {
+            let status = admin_request(&mut router, Method::GET, path, "admin").await?;
+            assert_eq!(status, StatusCode::NOT_FOUND, "{path}");
+        }
+        let status = admin_request(
+            &mut router,
+            Method::GET,
+            "/admin/index/shard/2020-01/",
+            "admin",
+        )
+        .await?;
+        assert_eq!(status, StatusCode::OK);
+
+        Ok(())
+    }
+
+    /// Concurrent inserts from several tasks are serialized by the index, so every scrape lands.
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_insert_scrapes() -> Result<(), Box<dyn std::error::Error>> {
+        const TASKS: usize = 8;
+        const STORIES: usize = 10;
+        let resources = Resources::get_resources("../resource/")?;
+        let index = test_index(&resources)?;
+        let date = StoryDate::year_month_day(2020, 1, 1).expect("Date");
+        let shared_url = StoryUrl::parse("https://example.com/shared").expect("URL");
+
+        let tasks = (0..TASKS).map(|task| {
+            let index = index.clone();
+            let shared_url = shared_url.clone();
+            tokio::spawn(async move {
+                let mut scrapes = vec![HackerNewsStory::new(
+                    format!("shared{task}"),
+                    date,
+                    "A shared story".to_owned(),
+                    shared_url,
+                    10,
+                    10,
+                    1,
+                    String::new(),
+                )
+                .into()];
+                for story in 0..STORIES {
+                    let url = StoryUrl::parse(format!("https://example.com/{task}/{story}"))
+                        .expect("URL");
+                    let id = format!("{task}-{story}");
+                    scrapes.push(
+                        HackerNewsStory::new(
+                            id,
+                            date,
+                            "A story".to_owned(),
+                            url,
+                            10,
+                            10,
+                            1,
+                            String::new(),
+                        )
+                        .into(),
+                    );
+                }
+                index.insert_scrapes(scrapes).await
+            })
+        });
+        for task in tasks.collect_vec() {
+            task.await??;
+        }
+
+        let counts = index.story_count().await?;
+        assert_eq!(counts.total.story_count, TASKS * STORIES + 1);
+        let shared = index
+            .fetch_one::<TypedScrape>(StoryQuery::UrlSearch(shared_url))
+            .await?
+            .expect("Missing shared story");
+        assert_eq!(shared.scrapes.len(), TASKS);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_admin_index_merge() -> Result<(), Box<dyn std::error::Error>> {
+        let (mut router, index) = admin_router_with_index(Auth::Fixed("admin".into()))?;
+        let date = StoryDate::year_month_day(2020, 1, 1).expect("Date failed");
+        let keep_url = StoryUrl::parse("https://example.com/story").expect("URL");
+        let merge_url = StoryUrl::parse("https://example.com/story-mirror").expect("URL");
+        index
+            .insert_scrapes([
+                HackerNewsStory::new(
+                    "1",
+                    date,
+                    "A story",
+                    keep_url.clone(),
+                    10,
+                    10,
+                    1,
+                    String::new(),
+                )
+                .into(),
+                HackerNewsStory::new(
+                    "2",
+                    date,
+                    "A story",
+                    merge_url.clone(),
+                    10,
+                    10,
+                    2,
+                    String::new(),
+                )
+                .into(),
+            ])
+            .await?;
+        let keep = StoryIdentifier::new(date, keep_url.normalization());
+        let merge = StoryIdentifier::new(date, merge_url.normalization());
+
+        let merge_request = |router: &mut Router| {
+            let mut req = Request::<Body>::new(Body::from(
+                json!({"keep": keep.to_base64(), "merge": merge.to_base64()}).to_string(),
+            ));
+            *req.method_mut() = Method::POST;
+            *req.uri_mut() = "http://localhost/admin/index/merge".parse().expect("URI");
+            req.headers_mut().insert(
+                header::CONTENT_TYPE,
+                HeaderValue::from_static("application/json"),
+            );
+            router.call(req)
+        };
+        let status = merge_request(&mut router)
+            .await
+            .unwrap_infallible()
+            .status();
+        assert_eq!(status, StatusCode::OK);
+
+        let (story, _) = index
+            .fetch_one_with_doc(keep.clone())
+            .await?
+            .expect("Expected kept story");
+        assert_eq!(story.scrapes.len(), 2);
+        assert!(index.fetch_one_with_doc(merge.clone()).await?.is_none());
+
+        // Merging again fails because the merged story is gone
+        let status = merge_request(&mut router)
+            .await
+            .unwrap_infallible()
+            .status();
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_front_page_events() -> Result<(), Box<dyn std::error::Error>> {
+        let resources = Resources::get_resources("../resource/")?;
+        let index = test_index(&resources)?;
+        let mut router: Router = create_feeds(index.clone(), resources);
+        let mut req = Request::<Body>::default();
+        *req.uri_mut() = "http://localhost/events/frontpage".parse()?;
+        req.headers_mut()
+            .insert(header::HOST, HeaderValue::from_static("localhost"));
+        let response = router.call(req).await.unwrap_infallible();
+        assert_eq!(response.status(), StatusCode::OK);
+        let mut body = response.into_body().into_data_stream();
+
+        let date = StoryDate::year_month_day(2020, 1, 1).expect("Date failed");
+        let url = StoryUrl::parse("https://example.com/story").expect("URL");
+        index
+            .insert_scrapes([HackerNewsStory::new(
+                "1",
+                date,
+                "A story",
+                url,
+                10,
+                10,
+                1,
+                String::new(),
+            )
+            .into()])
+            .await?;
+        index.refresh_hot_set().await?;
+
+        let chunk = tokio::time::timeout(Duration::from_secs(5), body.next())
+            .await?
+            .expect("Expected an event")?;
+        let chunk = String::from_utf8(chunk.to_vec())?;
+        assert!(chunk.starts_with("event: frontpage\n"), "{chunk}");
+        let data = chunk
+            .lines()
+            .find_map(|line| line.strip_prefix("data: "))
+            .expect("Expected event data");
+        let stories: Vec<StoryRender> = serde_json::from_str(data)?;
+        assert_eq!(
+            stories.iter().map(|s| s.title.as_str()).collect_vec(),
+            vec!["A story"]
+        );
+
+        // Refreshing without any change to the front page is silent
+        index.refresh_hot_set().await?;
+        assert!(
+            tokio::time::timeout(Duration::from_millis(100), body.next())
+                .await
+                .is_err()
+        );
+
+        Ok(())
+    }
+
+    /// `/feed.json` pages with a cursor without duplicates or gaps, even when a story arrives mid-paging.
+    #[tokio::test]
+    async fn test_feed_json_cursor() -> Result<(), Box<dyn std::error::Error>> {
+        let resources = Resources::get_resources("../resource/")?;
+        let index = test_index(&resources)?;
+        let mut router: Router = create_feeds(index.clone(), resources);
+        let start = StoryDate::year_month_day(2020, 1, 1).expect("Date failed");
+        let story = move |n: usize| -> TypedScrape {
+            let date = StoryDate::from_seconds(start.timestamp() + n as i64 * 60).expect("Date");
+            let url = StoryUrl::parse(format!("https://example.com/{n}")).expect("URL");
+            HackerNewsStory::new(
+                n.to_string(),
+                date,
+                format!("Story {n}"),
+                url,
+                10,
+                10,
+                1,
+                String::new(),
+            )
+            .into()
+        };
+        index.insert_scrapes((0..5).map(story)).await?;
+
+        let mut titles = vec![];
+        let mut cursor = Some("".to_owned());
+        while let Some(next) = cursor {
+            let mut req = Request::<Body>::default();
+            *req.uri_mut() = format!("http://localhost/feed.json?count=2&cursor={next}").parse()?;
+            req.headers_mut()
+                .insert(header::HOST, HeaderValue::from_static("localhost"));
+            let response = router.call(req).await.unwrap_infallible();
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX).await?;
+            let feed: serde_json::Value = serde_json::from_slice(&body)?;
+            for story in feed["stories"].as_array().expect("Expected stories") {
+                titles.push(story["title"].as_str().expect("Expected title").to_owned());
+            }
+            cursor = feed["next_cursor"].as_str().map(str::to_owned);
+            if titles.len() == 2 {
+                index.insert_scrapes([story(5)]).await?;
+            }
+        }
+
+        // The newer story sorts before the cursor, so it doesn't show up
+        assert_eq!(
+            titles,
+            (0..5).rev().map(|n| format!("Story {n}")).collect_vec()
+        );
+
+        Ok(())
+    }
+
+    /// A cursor request whose filter matches nothing stops scanning after a bounded number of stories, returning a
+    /// short page and a cursor to continue from.
+    #[tokio::test]
+    async fn test_feed_json_cursor_scan_limit() -> Result<(), Box<dyn std::error::Error>> {
+        let resources = Resources::get_resources("../resource/")?;
+        let index = test_index(&resources)?;
+        let mut router: Router = create_feeds(index.clone(), resources);
+        let start = StoryDate::year_month_day(2020, 1, 1).expect("Date failed");
+        let story = move |n: usize| -> TypedScrape {
+            let date = StoryDate::from_seconds(start.timestamp() + n as i64 * 60).expect("Date");
+            let url = StoryUrl::parse(format!("https://example.com/{n}")).expect("URL");
+            HackerNewsStory::new(
+                n.to_string(),
+                date,
+                format!("Story {n}"),
+                url,
+                10,
+                10,
+                1,
+                String::new(),
+            )
+            .into()
+        };
+        index.insert_scrapes((0..25).map(story)).await?;
+
+        // Every story has a single source, so none match and each request scans its limit of pages
+        let mut requests = 0;
+        let mut cursor = Some("".to_owned());
+        while let Some(next) = cursor {
+            let mut req = Request::<Body>::default();
+            *req.uri_mut() =
+                format!("http://localhost/feed.json?count=1&min_sources=2&cursor={next}")
+                    .parse()?;
+            req.headers_mut()
+                .insert(header::HOST, HeaderValue::from_static("localhost"));
+            let response = router.call(req).await.unwrap_infallible();
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX).await?;
+            let feed: serde_json::Value = serde_json::from_slice(&body)?;
+            assert_eq!(feed["stories"].as_array().map(Vec::len), Some(0));
+            cursor = feed["next_cursor"].as_str().map(str::to_owned);
+            requests += 1;
+        }
+        assert_eq!(requests, 3);
+
+        Ok(())
+    }
+
+    /// `/archive/:year/:month` lists the stories from that month only.
+    #[tokio::test]
+    async fn test_archive() -> Result<(), Box<dyn std::error::Error>> {
+        let resources = Resources::get_resources("../resource/")?;
+        let index = test_index(&resources)?;
+        let mut router: Router = create_feeds(index.clone(), resources);
+        let story = |month: u32, day: u32| -> TypedScrape {
+            let date = StoryDate::year_month_day(2020, month, day).expect("Date failed");
+            let url = StoryUrl::parse(format!("https://example.com/{month}/{day}")).expect("URL");
+            let title = format!("Story from {month} {day}");
+            HackerNewsStory::new(
+                format!("{month}-{day}"),
+                date,
+                title,
+                url,
+                10,
+                10,
+                1,
+                String::new(),
+            )
+            .into()
+        };
+        index
+            .insert_scrapes([story(1, 31), story(2, 1), story(2, 29), story(3, 1)])
+            .await?;
+
+        let mut req = Request::<Body>::default();
+        *req.uri_mut() = "http://localhost/archive/2020/2/".parse()?;
+        req.headers_mut()
+            .insert(header::HOST, HeaderValue::from_static("localhost"));
+        let response = router.call(req).await.unwrap_infallible();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await?;
+        let body = String::from_utf8(body.to_vec())?;
+        let first = body.find("Story from 2 29").expect("Missing 2/29");
+        let second = body.find("Story from 2 1").expect("Missing 2/1");
+        assert!(first < second);
+        assert!(!body.contains("Story from 1 31"));
+        assert!(!body.contains("Story from 3 1"));
+
+        let mut req = Request::<Body>::default();
+        *req.uri_mut() = "http://localhost/archive/2020/13/".parse()?;
+        req.headers_mut()
+            .insert(header::HOST, HeaderValue::from_static("localhost"));
+        let response = router.call(req).await.unwrap_infallible();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        // A busy month can be paged past the first hundred stories
+        index
+            .insert_scrapes((1..=28).flat_map(|day| (0..5).map(move |n| (day, n))).map(
+                |(day, n)| {
+                    let date = StoryDate::year_month_day(2020, 4, day).expect("Date failed");
+                    let url =
+                        StoryUrl::parse(format!("https://example.com/4/{day}/{n}")).expect("URL");
+                    HackerNewsStory::new(
+                        format!("4-{day}-{n}"),
+                        date,
+                        format!("April story {day}-{n}"),
+                        url,
+                        10,
+                        10,
+                        1,
+                        String::new(),
+                    )
+                    .into()
+                },
+            ))
+            .await?;
+        let mut req = Request::<Body>::default();
+        *req.uri_mut() = "http://localhost/archive/2020/4/?offset=130&count=10".parse()?;
+        req.headers_mut()
+            .insert(header::HOST, HeaderValue::from_static("localhost"));
+        let response = router.call(req).await.unwrap_infallible();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await?;
+        let body = String::from_utf8(body.to_vec())?;
+        assert!(body.contains("April story 1-"));
+        assert!(!body.contains("April story 28-"));
+
+        Ok(())
+    }
+
+    /// `?hide=` keeps stories with the given tags or from the given domains (and their subdomains) off the front page.
+    #[tokio::test]
+    async fn test_front_page_hide() -> Result<(), Box<dyn std::error::Error>> {
+        let resources = Resources::get_resources("../resource/")?;
+        let index = test_index(&resources)?;
+        let mut router: Router = create_feeds(index.clone(), resources);
+        let date = StoryDate::year_month_day(2020, 2, 1).expect("Date failed");
+        let story = |id: &str, title: &str, url: &str| -> TypedScrape {
+            let url = StoryUrl::parse(url).expect("URL");
+            HackerNewsStory::new(id, date, title, url, 10, 10, 1, String::new()).into()
+        };
+        index
+            .insert_scrapes([
+                story("1", "Why I love Rust", "https://example.com/rust"),
+                story("2", "A tweet", "https://twitter.com/someone/status/1"),
+                story(
+                    "3",
+                    "A mobile tweet",
+                    "https://mobile.twitter.com/someone/status/2",
+                ),
+                story("4", "Something else entirely", "https://example.org/other"),
+                story("5", "Not twitter", "https://nottwitter.com/post"),
+            ])
+            .await?;
+        index.refresh_hot_set().await?;
+
+        let fetch = |uri: &'static str| {
+            let mut req = Request::<Body>::default();
+            *req.uri_mut() = uri.parse().expect("URI");
+            req.headers_mut()
+                .insert(header::HOST, HeaderValue::from_static("localhost"));
+            req
+        };
+        let response = router
+            .call(fetch("http://localhost/?hide=rust,www.twitter.com"))
+            .await
+            .unwrap_infallible();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await?;
+        let body = String::from_utf8(body.to_vec())?;
+        assert!(body.contains("Something else entirely"));
+        assert!(body.contains("Not twitter"));
+        assert!(!body.contains("Why I love Rust"));
+        assert!(!body.contains("A tweet"));
+        assert!(!body.contains("A mobile tweet"));
+
+        // Hidden stories don't count towards a page, and the next page keeps them hidden
+        let response = router
+            .call(fetch("http://localhost/?hide=rust,twitter.com&count=1"))
+            .await
+            .unwrap_infallible();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await?;
+        let body = String::from_utf8(body.to_vec())?;
+        assert_eq!(body.matches("class=\"story\"").count(), 1);
+        assert!(body.contains("hide=rust%2Ctwitter%2Ecom"));
+
+        Ok(())
+    }
+
+    /// `/healthz` is unavailable until the hot set is first refreshed, then reports on the index.
+    #[tokio::test]
+    async fn test_healthz() -> Result<(), Box<dyn std::error::Error>> {
+        let resources = Resources::get_resources("../resource/")?;
+        let index = test_index(&resources)?;
+        let mut router: Router = create_health(index.clone());
+        let date = StoryDate::year_month_day(2020, 2, 1).expect("Date failed");
+        let url = StoryUrl::parse("https://example.com/story").expect("URL");
+        index
+            .insert_scrapes([HackerNewsStory::new(
+                "1",
+                date,
+                "A story",
+                url,
+                10,
+                10,
+                1,
+                String::new(),
+            )
+            .into()])
+            .await?;
+
+        let get = || {
+            let mut req = Request::<Body>::default();
+            *req.uri_mut() = "http://localhost/healthz".parse().expect("URI");
+            req
+        };
+        let response = router.call(get()).await.unwrap_infallible();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        index.refresh_hot_set().await?;
+        let response = router.call(get()).await.unwrap_infallible();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await?;
+        let health: serde_json::Value = serde_json::from_slice(&body)?;
+        assert_eq!(health["story_count"], 1);
+        assert_eq!(health["hot_set_refreshed"], true);
+        assert_eq!(health["newest_shard"], "2020-02");
+        assert_eq!(health["most_recent_story"], serde_json::to_value(date)?);
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case("localhost:3000", "http")]
+    #[case("127.0.0.1:3000", "http")]
+    #[case("127.0.0.1", "http")]
+    #[case("192.168.1.10:8080", "http")]
+    #[case("[::1]:3000", "http")]
+    #[case("progscrape.local", "http")]
+    #[case("web:3000", "http")]
+    #[case("progscrape.com", "https")]
+    #[case("8.8.8.8", "https")]
+    fn test_host_protocol(#[case] host: &str, #[case] protocol: &str) {
+        let params = HostParams::new(host.into(), &HeaderMap::new(), &HostConfig::default());
+        assert_eq!(params.protocol, protocol);
+    }
+
+    #[test]
+    fn test_host_protocol_config() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-proto", HeaderValue::from_static("https"));
+        let protocol = |host: &str, config: &HostConfig| {
+            HostParams::new(host.into(), &headers, config).protocol
+        };
+
+        // The forwarded protocol is ignored unless we trust it
+        let mut config = HostConfig::default();
+        assert_eq!(protocol("127.0.0.1:3000", &config), "http");
+        config.trust_forwarded_proto = true;
+        assert_eq!(protocol("127.0.0.1:3000", &config), "https");
+
+        // An explicit protocol always wins
+        config.protocol = Some(Protocol::Http);
+        assert_eq!(protocol("progscrape.com", &config), "http");
+    }
+
+    #[test]
+    fn test_filter_related() {
+        let date = StoryDate::year_month_day(2020, 1, 1).expect("Date failed");
+        let story = |url: &str, date: StoryDate| StoryRender {
+            url: url.into(),
+            date,
+            ..Default::default()
+        };
+        let first = story("https://www.example.com/story?utm_source=foo", date);
+        let related = vec![
+            story("http://example.com/story", date),
+            story(
+                "https://example.com/story",
+                date.checked_add_days(30).expect("Date"),
+            ),
+            story("https://example.com/other", date),
+        ];
+        let related = filter_related(&first, related);
+        assert_eq!(
+            related.iter().map(|s| s.url.as_str()).collect_vec(),
+            vec!["https://example.com/story", "https://example.com/other"]
+        );
+    }
+
+    /// A throttled scrape is retried after a backoff and succeeds once the server recovers.
+    #[tokio::test]
+    async fn test_fetch_retry() -> Result<(), Box<dyn std::error::Error>> {
+        use std::sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        };
+
+        let hits = Arc::new(AtomicUsize::new(0));
+        let server: Router = Router::new().route(
+            "/",
+            get({
+                let hits = hits.clone();
+                move || async move {
+                    if hits.fetch_add(1, Ordering::SeqCst) == 0 {
+                        (StatusCode::TOO_MANY_REQUESTS, "slow down")
+                    } else {
+                        (StatusCode::OK, "ok")
+                    }
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("http://{}/", listener.local_addr()?);
+        tokio::spawn(async move { axum::serve(listener, server).await });
+
+        let config = FetchConfig {
+            retry_base_delay_ms: 1,
+            ..Default::default()
+        };
+        let http_cache = SharedMut::new(HttpCacheState::default());
+        let (input, retries) =
+            fetch_scrape_input_with_backoff(&reqwest::Client::new(), &http_cache, &config, &url)
+                .await;
+        assert!(matches!(input?.input, ScraperHttpResponseInput::Ok(body) if body == "ok"));
+        assert_eq!(retries, 1);
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+
+        Ok(())
+    }
+
+    /// A page that links to its feed is always fetched in full, and only the feed's validators are kept, and only
+    /// once the caller records them.
+    #[tokio::test]
+    async fn test_discover_feed_validators() -> Result<(), Box<dyn std::error::Error>> {
+        use reqwest::header::ETAG;
+
+        let server: Router = Router::new()
+            .route(
+                "/",
+                get(|| async {
+                    (
+                        [(ETAG, "\"page\"")],
+                        r#"<html><head><link rel="alternate" type="application/rss+xml" href="/feed.xml"></head></html>"#,
+                    )
+                }),
+            )
+            .route(
+                "/feed.xml",
+                get(|| async { ([(ETAG, "\"feed\"")], r#"<rss version="2.0"><channel></channel></rss>"#) }),
+            );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("http://{}/", listener.local_addr()?);
+        tokio::spawn(async move { axum::serve(listener, server).await });
+
+        let client = reqwest::Client::new();
+        let http_cache = SharedMut::new(HttpCacheState::default());
+        let page = fetch_scrape_input(&client, &http_cache, &url).await?;
+        assert_eq!(
+            page.validators.as_ref().and_then(|v| v.etag.as_deref()),
+            Some("\"page\"")
+        );
+        let fetched = discover_feed_input(&client, &http_cache, &url, page).await;
+        assert!(matches!(fetched.input, ScraperHttpResponseInput::Ok(_)));
+        assert_eq!(fetched.url, format!("{url}feed.xml"));
+        assert_eq!(
+            fetched.validators.as_ref().and_then(|v| v.etag.as_deref()),
+            Some("\"feed\"")
+        );
+        assert!(http_cache.read().get(&url).is_none());
+        assert!(http_cache.read().get(&fetched.url).is_none());
+
+        Ok(())
+    }
+}